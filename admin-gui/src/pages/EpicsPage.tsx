@@ -23,6 +23,7 @@ const STATUS_BADGE: Record<string, string> = {
   ready: 'badge-success',
   done: 'badge-success',
   needs_technical_shaping: 'badge-warning',
+  awaiting_approval: 'badge-warning',
   blocked: 'badge-error',
 };
 
@@ -0,0 +1,73 @@
+//! Which agents propose their results for a user's approval instead of
+//! applying them. The server-wide list is `[approval]` in project.toml; a
+//! project replaces it with `<project>/.nocodo/approval.toml`:
+//!
+//! ```toml
+//! agents = ["db_engineer", "ui_designer"]
+//! ```
+//!
+//! A run of a listed agent stores its schema, form layout or code as a
+//! proposal and leaves the task `awaiting_approval`. Approving applies it;
+//! rejecting it or asking for changes runs the agent again with the comment.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::error::AgentError;
+
+/// Where a project keeps its approval settings, relative to its root.
+pub const PROJECT_APPROVAL_FILE: &str = ".nocodo/approval.toml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ApprovalPolicy {
+    /// Agent types whose results need approval, e.g. "db_engineer".
+    #[serde(default)]
+    pub agents: Vec<String>,
+}
+
+impl ApprovalPolicy {
+    pub fn parse(source: &str) -> Result<Self, AgentError> {
+        toml::from_str(source)
+            .map_err(|e| AgentError::Config(format!("invalid approval settings: {}", e)))
+    }
+
+    /// The project's `.nocodo/approval.toml`, or this policy when it has
+    /// none.
+    pub fn for_project(&self, project_path: impl AsRef<Path>) -> Result<Self, AgentError> {
+        let path = project_path.as_ref().join(PROJECT_APPROVAL_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(source) => {
+                log::info!("[Approval] using {}", path.display());
+                Self::parse(&source)
+            }
+            Err(_) => Ok(self.clone()),
+        }
+    }
+
+    pub fn requires_approval(&self, agent_type: &str) -> bool {
+        self.agents.iter().any(|a| a == agent_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_agents_that_need_approval() {
+        let policy = ApprovalPolicy::parse(r#"agents = ["db_engineer"]"#).unwrap();
+        assert!(policy.requires_approval("db_engineer"));
+        assert!(!policy.requires_approval("ui_designer"));
+        assert!(!ApprovalPolicy::default().requires_approval("db_engineer"));
+    }
+
+    #[test]
+    fn project_without_settings_keeps_the_default() {
+        let default = ApprovalPolicy {
+            agents: vec!["ui_designer".to_string()],
+        };
+        let policy = default.for_project("/nonexistent/project").unwrap();
+        assert!(policy.requires_approval("ui_designer"));
+    }
+}
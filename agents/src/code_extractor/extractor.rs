@@ -14,6 +14,9 @@ pub struct CodeBlock {
     pub file: PathBuf,
     pub start_line: u32,
     pub end_line: u32,
+    /// Byte range of `source` within `file`, for splicing edits back in place.
+    pub start_byte: usize,
+    pub end_byte: usize,
     pub source: String,
 }
 
@@ -118,6 +121,8 @@ pub fn extract_struct(path: &Path, name: &str) -> Result<Option<CodeBlock>, Stri
                     file: path.to_path_buf(),
                     start_line,
                     end_line,
+                    start_byte: it.start_byte(),
+                    end_byte: it.end_byte(),
                     source: node_source(it, &src),
                 }));
             }
@@ -150,6 +155,8 @@ pub fn extract_enum(path: &Path, name: &str) -> Result<Option<CodeBlock>, String
                     file: path.to_path_buf(),
                     start_line,
                     end_line,
+                    start_byte: it.start_byte(),
+                    end_byte: it.end_byte(),
                     source: node_source(it, &src),
                 }));
             }
@@ -187,8 +194,9 @@ const KNOWN_TYPES: &[&str] = &[
     "Uuid",
 ];
 
-/// Parse the struct source for field types that are NOT standard Rust/chrono types.
-/// Returns unique type names that need to be resolved (e.g. "ContactType").
+/// Parse the source for field, parameter and generic argument types that are NOT
+/// standard Rust/chrono types. Returns unique type names that need to be resolved
+/// (e.g. "ContactType" from a struct field, "NewContact" from `web::Json<NewContact>`).
 fn collect_custom_type_names(struct_code: &str) -> Vec<String> {
     let mut types = Vec::new();
     let mut parser = make_parser().ok();
@@ -200,10 +208,15 @@ fn collect_custom_type_names(struct_code: &str) -> Vec<String> {
     };
 
     let lang = tree_sitter_rust::LANGUAGE.into();
-    // Match any type_identifier that appears as a field type in a struct.
+    // Match any type_identifier that appears as a struct field type, a function
+    // parameter type, or a generic argument (`Option<T>`, `web::Json<T>`).
     let type_query = Query::new(
         &lang,
-        "(field_declaration type: (type_identifier) @type_name)",
+        "[
+            (field_declaration type: (type_identifier) @type_name)
+            (parameter type: (type_identifier) @type_name)
+            (type_arguments (type_identifier) @type_name)
+        ]",
     )
     .ok();
     let Some(type_query) = type_query else {
@@ -223,8 +236,9 @@ fn collect_custom_type_names(struct_code: &str) -> Vec<String> {
     types
 }
 
-/// For each custom type referenced in the struct, try to find and extract its
-/// definition. Searches the same file first, then all Rust files under `root`.
+/// For each custom type referenced in the code, try to find and extract its
+/// definition — enums first, then structs (request/response DTOs). Searches the
+/// same file first, then all Rust files under `root`.
 pub fn find_dependent_types(
     root: &Path,
     struct_file: &Path,
@@ -235,7 +249,7 @@ pub fn find_dependent_types(
 
     for type_name in type_names {
         // Try the struct's own file first (enums are often co-located).
-        if let Some(block) = extract_enum(struct_file, &type_name)? {
+        if let Some(block) = extract_type_def(struct_file, &type_name)? {
             result.push(block);
             continue;
         }
@@ -245,7 +259,7 @@ pub fn find_dependent_types(
             if path == struct_file {
                 continue;
             }
-            if let Some(block) = extract_enum(&path, &type_name)? {
+            if let Some(block) = extract_type_def(&path, &type_name)? {
                 result.push(block);
                 break;
             }
@@ -255,6 +269,13 @@ pub fn find_dependent_types(
     Ok(result)
 }
 
+fn extract_type_def(path: &Path, name: &str) -> Result<Option<CodeBlock>, String> {
+    match extract_enum(path, name)? {
+        Some(block) => Ok(Some(block)),
+        None => extract_struct(path, name),
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Extraction: free function (top-level, not inside impl/trait)
 // ---------------------------------------------------------------------------
@@ -279,6 +300,8 @@ pub fn extract_free_fn(path: &Path, name: &str) -> Result<Option<CodeBlock>, Str
                     file: path.to_path_buf(),
                     start_line,
                     end_line,
+                    start_byte: it.start_byte(),
+                    end_byte: it.end_byte(),
                     source: node_source(it, &src),
                 }));
             }
@@ -327,6 +350,8 @@ pub fn extract_impl_fn(
                             file: path.to_path_buf(),
                             start_line,
                             end_line,
+                            start_byte: fn_it.start_byte(),
+                            end_byte: fn_it.end_byte(),
                             source: node_source(fn_it, &src),
                         }));
                     }
@@ -372,6 +397,178 @@ pub fn find_impl_fn_file(
     Ok(None)
 }
 
+// ---------------------------------------------------------------------------
+// Extraction: Actix route handlers (free fns with #[get]/#[post]/… attributes)
+// ---------------------------------------------------------------------------
+
+/// Actix route macros recognised as handler attributes.
+pub const ROUTE_MACROS: &[&str] = &["get", "post", "put", "patch", "delete"];
+
+/// Return the route macro (`get`, `post`, …) if `attr` is an Actix route attribute
+/// such as `#[get("/users")]` or `#[actix_web::post("/users")]`.
+pub fn route_macro_name(attr: &str) -> Option<&'static str> {
+    let inner = attr.trim().strip_prefix("#[")?.trim_start();
+    let path = inner
+        .split(|c: char| c == '(' || c == ']' || c.is_whitespace())
+        .next()?;
+    let name = path.rsplit("::").next()?;
+    ROUTE_MACROS.iter().copied().find(|m| *m == name)
+}
+
+/// List all top-level route handlers in a file. The returned source includes the
+/// handler's attributes and doc comments so it can be used verbatim as an example.
+pub fn list_route_handlers(path: &Path) -> Result<Vec<CodeBlock>, String> {
+    list_attributed_fns(path, true, |attr| route_macro_name(attr).is_some())
+}
+
+/// List functions (at any nesting depth unless `top_level_only`) that carry an
+/// attribute matching `is_match`. The returned source starts at the first
+/// preceding attribute or doc comment.
+fn list_attributed_fns(
+    path: &Path,
+    top_level_only: bool,
+    is_match: impl Fn(&str) -> bool,
+) -> Result<Vec<CodeBlock>, String> {
+    let src = std::fs::read_to_string(path).map_err(|e| format!("read {path:?}: {e}"))?;
+    let mut parser = make_parser()?;
+    let tree = parser
+        .parse(src.as_bytes(), None)
+        .ok_or_else(|| format!("parse failed for {path:?}"))?;
+
+    let query = Query::new(&tree_sitter_rust::LANGUAGE.into(), queries::ANY_FN)
+        .map_err(|e| format!("query compile: {e}"))?;
+
+    let mut result = Vec::new();
+    for caps in run_query(&query, tree.root_node(), src.as_bytes()) {
+        let Some(it) = find_capture(&caps, &query, "item") else {
+            continue;
+        };
+        if top_level_only && !is_top_level(it) {
+            continue;
+        }
+
+        // Attributes and doc comments are preceding siblings, not children.
+        let mut first = it;
+        let mut matched = false;
+        let mut prev = it.prev_named_sibling();
+        while let Some(p) = prev {
+            match p.kind() {
+                "attribute_item" => {
+                    if is_match(&src[p.byte_range()]) {
+                        matched = true;
+                    }
+                }
+                "line_comment" | "block_comment" => {}
+                _ => break,
+            }
+            first = p;
+            prev = p.prev_named_sibling();
+        }
+        if !matched {
+            continue;
+        }
+
+        result.push(CodeBlock {
+            file: path.to_path_buf(),
+            start_line: first.start_position().row as u32 + 1,
+            end_line: it.end_position().row as u32 + 1,
+            start_byte: first.start_byte(),
+            end_byte: it.end_byte(),
+            source: src[first.start_byte()..it.end_byte()].to_string(),
+        });
+    }
+    Ok(result)
+}
+
+/// Scan all files under `root` for Actix route handlers.
+pub fn find_route_handlers(root: &Path) -> Result<Vec<CodeBlock>, String> {
+    let mut result = Vec::new();
+    for path in rust_sources(root) {
+        result.extend(list_route_handlers(&path)?);
+    }
+    Ok(result)
+}
+
+// ---------------------------------------------------------------------------
+// Extraction: test functions (#[test], #[tokio::test], #[actix_web::test], …)
+// ---------------------------------------------------------------------------
+
+/// True for `#[test]` and async runtime variants such as `#[tokio::test]`.
+pub fn is_test_attribute(attr: &str) -> bool {
+    let Some(inner) = attr.trim().strip_prefix("#[") else {
+        return false;
+    };
+    let path = inner
+        .split(|c: char| c == '(' || c == ']' || c.is_whitespace())
+        .next()
+        .unwrap_or_default();
+    path.rsplit("::").next() == Some("test")
+}
+
+/// List all test functions in a file, including those inside `mod tests`.
+pub fn list_test_fns(path: &Path) -> Result<Vec<CodeBlock>, String> {
+    list_attributed_fns(path, false, is_test_attribute)
+}
+
+/// Scan all files under `root` for test functions.
+pub fn find_test_fns(root: &Path) -> Result<Vec<CodeBlock>, String> {
+    let mut result = Vec::new();
+    for path in rust_sources(root) {
+        result.extend(list_test_fns(&path)?);
+    }
+    Ok(result)
+}
+
+// ---------------------------------------------------------------------------
+// Extraction: trait impls (e.g. actix `Transform`, `FromRequest`)
+// ---------------------------------------------------------------------------
+
+/// List all top-level `impl <trait_name> for …` blocks in a file. Matches on the
+/// last path segment, so `actix_web::FromRequest` and `FromRequest<S>` both match.
+pub fn list_trait_impls(path: &Path, trait_name: &str) -> Result<Vec<CodeBlock>, String> {
+    let src = std::fs::read_to_string(path).map_err(|e| format!("read {path:?}: {e}"))?;
+    let mut parser = make_parser()?;
+    let tree = parser
+        .parse(src.as_bytes(), None)
+        .ok_or_else(|| format!("parse failed for {path:?}"))?;
+
+    let query = Query::new(&tree_sitter_rust::LANGUAGE.into(), queries::TRAIT_IMPL)
+        .map_err(|e| format!("query compile: {e}"))?;
+
+    let mut result = Vec::new();
+    for caps in run_query(&query, tree.root_node(), src.as_bytes()) {
+        let Some(name) = find_capture(&caps, &query, "trait_name") else {
+            continue;
+        };
+        if &src[name.byte_range()] != trait_name {
+            continue;
+        }
+        if let Some(it) = find_capture(&caps, &query, "item") {
+            if is_top_level(it) {
+                let (start_line, end_line) = line_range(it);
+                result.push(CodeBlock {
+                    file: path.to_path_buf(),
+                    start_line,
+                    end_line,
+                    start_byte: it.start_byte(),
+                    end_byte: it.end_byte(),
+                    source: node_source(it, &src),
+                });
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Scan all files under `root` for implementations of `trait_name`.
+pub fn find_trait_impls(root: &Path, trait_name: &str) -> Result<Vec<CodeBlock>, String> {
+    let mut result = Vec::new();
+    for path in rust_sources(root) {
+        result.extend(list_trait_impls(&path, trait_name)?);
+    }
+    Ok(result)
+}
+
 // ---------------------------------------------------------------------------
 // Extraction: all impl methods for a struct (for use as examples)
 // ---------------------------------------------------------------------------
@@ -406,6 +603,8 @@ pub fn list_impl_fns(path: &Path, struct_name: &str) -> Result<Vec<CodeBlock>, S
                         file: path.to_path_buf(),
                         start_line,
                         end_line,
+                        start_byte: fn_it.start_byte(),
+                        end_byte: fn_it.end_byte(),
                         source: node_source(fn_it, &src),
                     });
                 }
@@ -537,6 +736,127 @@ mod tests {
         assert!(path.to_string_lossy().contains("db.rs"));
     }
 
+    #[test]
+    fn test_route_macro_name() {
+        assert_eq!(route_macro_name("#[get(\"/users\")]"), Some("get"));
+        assert_eq!(
+            route_macro_name("#[actix_web::post(\"/users\")]"),
+            Some("post")
+        );
+        assert_eq!(route_macro_name("#[derive(Debug)]"), None);
+        assert_eq!(route_macro_name("#[test]"), None);
+    }
+
+    #[test]
+    fn test_list_route_handlers() {
+        let dir =
+            std::env::temp_dir().join(format!("nocodo_route_handlers_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("handlers.rs");
+        std::fs::write(
+            &path,
+            r#"use actix_web::{get, post, web, HttpResponse, Responder};
+
+/// List all contacts.
+#[get("/contacts")]
+pub async fn list_contacts() -> impl Responder {
+    HttpResponse::Ok().finish()
+}
+
+#[post("/contacts")]
+pub async fn create_contact(body: web::Json<NewContact>) -> impl Responder {
+    HttpResponse::Created().finish()
+}
+
+fn helper() {}
+"#,
+        )
+        .unwrap();
+
+        let handlers = list_route_handlers(&path).expect("parse ok");
+        let src = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(handlers.len(), 2);
+        assert!(handlers[0].source.starts_with("/// List all contacts."));
+        assert!(handlers[0].source.contains("#[get(\"/contacts\")]"));
+        assert!(handlers[1].source.starts_with("#[post(\"/contacts\")]"));
+        assert!(handlers[1].source.contains("fn create_contact"));
+        for h in &handlers {
+            assert_eq!(&src[h.start_byte..h.end_byte], h.source);
+        }
+    }
+
+    #[test]
+    fn test_list_test_fns() {
+        let dir = std::env::temp_dir().join(format!("nocodo_test_fns_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("contact.rs");
+        std::fs::write(
+            &path,
+            r#"pub fn count() -> i32 { 1 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_one() {
+        assert_eq!(count(), 1);
+    }
+
+    #[tokio::test]
+    async fn counts_async() {}
+
+    fn helper() {}
+}
+"#,
+        )
+        .unwrap();
+
+        let tests = list_test_fns(&path).expect("parse ok");
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(tests.len(), 2);
+        assert!(tests[0].source.starts_with("#[test]"));
+        assert!(tests[1].source.contains("async fn counts_async"));
+        assert!(!is_test_attribute("#[cfg(test)]"));
+    }
+
+    #[test]
+    fn test_list_trait_impls() {
+        let dir = std::env::temp_dir().join(format!("nocodo_trait_impls_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("middleware.rs");
+        std::fs::write(
+            &path,
+            r#"pub struct RequireAuth;
+
+impl<S, B> Transform<S, ServiceRequest> for RequireAuth {
+    type Response = ServiceResponse<B>;
+}
+
+impl actix_web::FromRequest for CurrentUser {
+    type Error = Error;
+}
+
+impl RequireAuth {
+    fn helper() {}
+}
+"#,
+        )
+        .unwrap();
+
+        let transforms = list_trait_impls(&path, "Transform").expect("parse ok");
+        let extractors = list_trait_impls(&path, "FromRequest").expect("parse ok");
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(transforms.len(), 1);
+        assert!(transforms[0].source.contains("for RequireAuth"));
+        assert_eq!(extractors.len(), 1);
+        assert!(extractors[0].source.contains("for CurrentUser"));
+    }
+
     #[test]
     fn test_find_impl_fn_file() {
         let src = rustysolid_backend();
@@ -8,12 +8,24 @@ use super::extractor::{self, CodeBlock};
 // Schema
 // ---------------------------------------------------------------------------
 
+/// Bumped whenever the tables below change; stored in `PRAGMA user_version`.
+/// Version 2 added `start_byte`/`end_byte`.
+const SCHEMA_VERSION: i64 = 2;
+
+const DROP_SCHEMA: &str = "
+DROP TABLE IF EXISTS code_index_structs;
+DROP TABLE IF EXISTS code_index_free_fns;
+DROP TABLE IF EXISTS code_index_impl_fns;
+";
+
 const SCHEMA: &str = "
 CREATE TABLE IF NOT EXISTS code_index_structs (
     name TEXT PRIMARY KEY,
     file TEXT NOT NULL,
     start_line INTEGER NOT NULL,
     end_line INTEGER NOT NULL,
+    start_byte INTEGER NOT NULL,
+    end_byte INTEGER NOT NULL,
     source TEXT NOT NULL
 );
 
@@ -22,6 +34,8 @@ CREATE TABLE IF NOT EXISTS code_index_free_fns (
     file TEXT NOT NULL,
     start_line INTEGER NOT NULL,
     end_line INTEGER NOT NULL,
+    start_byte INTEGER NOT NULL,
+    end_byte INTEGER NOT NULL,
     source TEXT NOT NULL
 );
 
@@ -31,6 +45,8 @@ CREATE TABLE IF NOT EXISTS code_index_impl_fns (
     file TEXT NOT NULL,
     start_line INTEGER NOT NULL,
     end_line INTEGER NOT NULL,
+    start_byte INTEGER NOT NULL,
+    end_byte INTEGER NOT NULL,
     source TEXT NOT NULL,
     PRIMARY KEY (struct_name, fn_name)
 );
@@ -42,14 +58,33 @@ CREATE TABLE IF NOT EXISTS code_index_impl_fns (
 
 pub struct CodeIndex {
     conn: Connection,
+    reset: bool,
 }
 
 impl CodeIndex {
-    /// Open (or create) a code index backed by a SQLite file.
+    /// Open (or create) a code index backed by a SQLite file. An index
+    /// written with an older schema is dropped and recreated empty; see
+    /// `was_reset`.
     pub fn open(path: &str) -> Result<Self, String> {
         let conn = Connection::open(path).map_err(|e| e.to_string())?;
-        conn.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
-        Ok(Self { conn })
+        let version: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        let reset = version != SCHEMA_VERSION;
+        if reset {
+            conn.execute_batch(&format!(
+                "BEGIN;{DROP_SCHEMA}{SCHEMA}PRAGMA user_version = {SCHEMA_VERSION};COMMIT;"
+            ))
+            .map_err(|e| e.to_string())?;
+        }
+        Ok(Self { conn, reset })
+    }
+
+    /// Whether `open` found no index, or one with an older schema, and
+    /// created empty tables — the index needs a `build` before queries find
+    /// anything.
+    pub fn was_reset(&self) -> bool {
+        self.reset
     }
 
     /// Open an in-memory index (useful for testing).
@@ -119,7 +154,7 @@ impl CodeIndex {
     pub fn get_struct(&self, name: &str) -> Result<Option<CodeBlock>, String> {
         self.conn
             .query_row(
-                "SELECT file, start_line, end_line, source FROM code_index_structs WHERE name = ?1",
+                "SELECT file, start_line, end_line, start_byte, end_byte, source FROM code_index_structs WHERE name = ?1",
                 params![name],
                 |row| row_to_block(row),
             )
@@ -130,7 +165,7 @@ impl CodeIndex {
     pub fn get_free_fn(&self, name: &str) -> Result<Option<CodeBlock>, String> {
         self.conn
             .query_row(
-                "SELECT file, start_line, end_line, source FROM code_index_free_fns WHERE name = ?1",
+                "SELECT file, start_line, end_line, start_byte, end_byte, source FROM code_index_free_fns WHERE name = ?1",
                 params![name],
                 |row| row_to_block(row),
             )
@@ -145,7 +180,7 @@ impl CodeIndex {
     ) -> Result<Option<CodeBlock>, String> {
         self.conn
             .query_row(
-                "SELECT file, start_line, end_line, source FROM code_index_impl_fns WHERE struct_name = ?1 AND fn_name = ?2",
+                "SELECT file, start_line, end_line, start_byte, end_byte, source FROM code_index_impl_fns WHERE struct_name = ?1 AND fn_name = ?2",
                 params![struct_name, fn_name],
                 |row| row_to_block(row),
             )
@@ -208,7 +243,9 @@ fn row_to_block(row: &rusqlite::Row<'_>) -> rusqlite::Result<CodeBlock> {
         file: PathBuf::from(file),
         start_line: row.get(1)?,
         end_line: row.get(2)?,
-        source: row.get(3)?,
+        start_byte: row.get::<_, i64>(3)? as usize,
+        end_byte: row.get::<_, i64>(4)? as usize,
+        source: row.get(5)?,
     })
 }
 
@@ -271,8 +308,8 @@ fn scan_file(
             let end = it.end_position().row as u32 + 1;
             let source = &src[it.byte_range()];
             tx.execute(
-                "INSERT OR REPLACE INTO code_index_structs (name, file, start_line, end_line, source) VALUES (?1, ?2, ?3, ?4, ?5)",
-                params![name, rel_str, start, end, source],
+                "INSERT OR REPLACE INTO code_index_structs (name, file, start_line, end_line, start_byte, end_byte, source) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![name, rel_str, start, end, it.start_byte() as i64, it.end_byte() as i64, source],
             ).map_err(|e| e.to_string())?;
             stats.structs += 1;
         }
@@ -293,8 +330,8 @@ fn scan_file(
                 let end = it.end_position().row as u32 + 1;
                 let source = &src[it.byte_range()];
                 tx.execute(
-                    "INSERT OR REPLACE INTO code_index_free_fns (name, file, start_line, end_line, source) VALUES (?1, ?2, ?3, ?4, ?5)",
-                    params![name, rel_str, start, end, source],
+                    "INSERT OR REPLACE INTO code_index_free_fns (name, file, start_line, end_line, start_byte, end_byte, source) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![name, rel_str, start, end, it.start_byte() as i64, it.end_byte() as i64, source],
                 ).map_err(|e| e.to_string())?;
                 stats.free_fns += 1;
             }
@@ -330,8 +367,8 @@ fn scan_file(
                     let end = fn_it.end_position().row as u32 + 1;
                     let source = &src[fn_it.byte_range()];
                     tx.execute(
-                        "INSERT OR REPLACE INTO code_index_impl_fns (struct_name, fn_name, file, start_line, end_line, source) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-                        params![struct_name, fn_name, rel_str, start, end, source],
+                        "INSERT OR REPLACE INTO code_index_impl_fns (struct_name, fn_name, file, start_line, end_line, start_byte, end_byte, source) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                        params![struct_name, fn_name, rel_str, start, end, fn_it.start_byte() as i64, fn_it.end_byte() as i64, source],
                     ).map_err(|e| e.to_string())?;
                     stats.impl_fns += 1;
                 }
@@ -361,6 +398,52 @@ mod tests {
             .join("src")
     }
 
+    #[test]
+    fn test_open_recreates_an_index_with_the_old_schema() {
+        let path =
+            std::env::temp_dir().join(format!("nocodo_code_index_v1_{}.db", std::process::id()));
+        let path_str = path.to_string_lossy().into_owned();
+        std::fs::remove_file(&path).ok();
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE code_index_structs (
+                     name TEXT PRIMARY KEY, file TEXT NOT NULL, start_line INTEGER NOT NULL,
+                     end_line INTEGER NOT NULL, source TEXT NOT NULL);
+                 INSERT INTO code_index_structs VALUES ('Old', 'a.rs', 1, 2, 'struct Old;');",
+            )
+            .unwrap();
+        }
+
+        let root =
+            std::env::temp_dir().join(format!("nocodo_code_index_src_{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join("lib.rs"),
+            "pub struct Contact {\n    pub id: i64,\n}\n",
+        )
+        .unwrap();
+
+        let mut idx = CodeIndex::open(&path_str).expect("open old index");
+        assert!(idx.was_reset());
+        assert!(idx.get_struct("Old").expect("query ok").is_none());
+        idx.build(&root).expect("build ok");
+        let block = idx
+            .get_struct("Contact")
+            .expect("query ok")
+            .expect("struct found");
+        let file = std::fs::read_to_string(root.join("lib.rs")).unwrap();
+        assert_eq!(&file[block.start_byte..block.end_byte], block.source);
+        drop(idx);
+
+        let reopened = CodeIndex::open(&path_str).expect("reopen");
+        assert!(!reopened.was_reset());
+        assert!(reopened.get_struct("Contact").expect("query ok").is_some());
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir_all(&root).ok();
+    }
+
     #[test]
     fn test_build_and_query_struct() {
         let src = rustysolid_backend();
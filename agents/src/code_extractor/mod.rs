@@ -4,6 +4,8 @@ mod queries;
 
 pub use extractor::{
     extract_enum, extract_free_fn, extract_impl_fn, extract_struct, find_dependent_types,
-    find_free_fn_file, find_impl_fn_file, find_struct_file, list_impl_fns, rust_sources, CodeBlock,
+    find_free_fn_file, find_impl_fn_file, find_route_handlers, find_struct_file, find_test_fns,
+    find_trait_impls, is_test_attribute, list_impl_fns, list_route_handlers, list_test_fns,
+    list_trait_impls, route_macro_name, rust_sources, CodeBlock, ROUTE_MACROS,
 };
 pub use index::{BuildStats, CodeIndex};
@@ -54,3 +54,19 @@ pub const ENUM_DEF: &str = r#"
     (enum_item
       name: (type_identifier) @name) @item
 "#;
+
+// ---------------------------------------------------------------------------
+// Trait impls
+// ---------------------------------------------------------------------------
+
+/// Matches `impl Trait for Foo`, `impl<S> Trait<S> for Foo`, `impl path::Trait for Foo`.
+/// Captures: @trait_name (type_identifier, last path segment), @item (the full impl_item).
+pub const TRAIT_IMPL: &str = r#"
+    (impl_item
+      trait: [
+        (type_identifier) @trait_name
+        (scoped_type_identifier name: (type_identifier) @trait_name)
+        (generic_type type: (type_identifier) @trait_name)
+        (generic_type type: (scoped_type_identifier name: (type_identifier) @trait_name))
+      ]) @item
+"#;
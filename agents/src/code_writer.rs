@@ -1,6 +1,6 @@
 use std::path::Path;
 
-use crate::error::AgentError;
+use crate::{code_extractor::CodeBlock, error::AgentError};
 
 /// Write a Diesel model struct (generated by the LLM) to disk via schema-codegen.
 ///
@@ -17,3 +17,64 @@ pub fn write_diesel_schema(project_path: &Path, code: &str) -> Result<String, Ag
     schema_codegen::append_table_to_schema(project_path, code)
         .map_err(|e| AgentError::Other(e.to_string()))
 }
+
+/// Write an Actix route handler into `handler_file` (relative to the project root)
+/// and register it in the module's `configure` function.
+///
+/// Returns the relative file path that was written.
+pub fn write_actix_handler(
+    project_path: &Path,
+    handler_file: &str,
+    code: &str,
+) -> Result<String, AgentError> {
+    schema_codegen::write_handler(project_path, handler_file, code)
+        .map_err(|e| AgentError::Other(e.to_string()))
+}
+
+/// Write an Actix middleware or extractor module to `backend/src/middleware/`,
+/// declare it, and optionally wrap the `App` builder with it.
+///
+/// Returns the relative file path that was written.
+pub fn write_actix_middleware(
+    project_path: &Path,
+    name: &str,
+    code: &str,
+    wrap_app: bool,
+) -> Result<String, AgentError> {
+    schema_codegen::write_middleware_file(project_path, name, code, wrap_app)
+        .map_err(|e| AgentError::Other(e.to_string()))
+}
+
+/// Add a generated `#[test]` fn to the `#[cfg(test)]` module of `model_file`
+/// (relative to the project root), creating the module and its in-memory
+/// `test_pool()` helper if needed.
+///
+/// Returns the relative file path that was written.
+pub fn write_model_test(
+    project_path: &Path,
+    model_file: &str,
+    code: &str,
+) -> Result<String, AgentError> {
+    schema_codegen::write_model_test(project_path, model_file, code)
+        .map_err(|e| AgentError::Other(e.to_string()))
+}
+
+/// Replace `block` in its file with `code`, splicing at the block's byte range.
+/// Fails if the file changed since the block was extracted.
+///
+/// Returns the relative file path that was written.
+pub fn replace_code_block(
+    project_path: &Path,
+    block: &CodeBlock,
+    code: &str,
+) -> Result<String, AgentError> {
+    let rel = block.file.strip_prefix(project_path).unwrap_or(&block.file);
+    schema_codegen::replace_byte_range(
+        project_path,
+        &rel.to_string_lossy(),
+        block.start_byte..block.end_byte,
+        &block.source,
+        code,
+    )
+    .map_err(|e| AgentError::Other(e.to_string()))
+}
@@ -1,11 +1,30 @@
 use std::{fs, path::Path, path::PathBuf};
 
-use crate::error::AgentError;
+use crate::{
+    error::AgentError,
+    llm_fixture::{FixtureConfig, FixtureMode},
+    text_tools::ToolProtocol,
+};
 
 /// Provider identifier constants — mirror llm_sdk::providers
 pub const PROVIDER_OPENAI: &str = "openai";
 pub const PROVIDER_ANTHROPIC: &str = "anthropic";
 pub const PROVIDER_GROQ: &str = "groq";
+/// A local llama.cpp server (`llama-server`); needs no API key.
+pub const PROVIDER_LLAMA_CPP: &str = "llama_cpp";
+/// Ollama or any other OpenAI-compatible local server; needs no API key.
+pub const PROVIDER_OLLAMA: &str = "ollama";
+
+/// Default model for the llama.cpp provider, also used by the Rust engineer.
+pub const DEFAULT_LLAMA_CPP_MODEL: &str = "unsloth/Qwen3.5-0.8B-GGUF:UD-Q4_K_XL";
+pub const DEFAULT_OLLAMA_MODEL: &str = "qwen3:1.7b";
+pub const DEFAULT_LLAMA_CPP_BASE_URL: &str = "http://localhost:8080";
+pub const DEFAULT_OLLAMA_BASE_URL: &str = "http://localhost:11434/v1";
+
+/// History budget, in estimated tokens, for hosted models.
+pub const DEFAULT_CONTEXT_BUDGET: usize = 100_000;
+/// History budget for local models, which usually run with a small context.
+pub const DEFAULT_LOCAL_CONTEXT_BUDGET: usize = 6_000;
 
 /// Runtime configuration for an agent: which LLM to use and how to authenticate.
 #[derive(Debug, Clone)]
@@ -13,6 +32,28 @@ pub struct AgentConfig {
     pub provider: String,
     pub model: String,
     pub api_key: String,
+    /// Override the provider's API base URL, e.g. to point an OpenAI-compatible
+    /// client at a local server. `None` uses the provider default; local
+    /// providers always have one.
+    pub base_url: Option<String>,
+    /// Record or replay LLM traffic through fixture files instead of (or on
+    /// top of) the provider. `None` talks to the provider directly.
+    pub fixture: Option<FixtureConfig>,
+    /// Models to try, in order, when this one fails a run (see
+    /// `run_with_fallback`). Loaded from `{prefix}_FALLBACK_MODELS`, a
+    /// comma-separated list of `provider:model` entries.
+    pub fallbacks: Vec<AgentConfig>,
+    /// Estimated tokens of history sent per turn before older turns are
+    /// summarized (see `ContextWindow`). Loaded from `{prefix}_CONTEXT_BUDGET`.
+    pub context_budget: usize,
+    /// Model that writes those summaries, on the same provider. Loaded from
+    /// `{prefix}_SUMMARY_MODEL`; defaults to `model`.
+    pub summary_model: String,
+    /// How tools are offered to the model. Loaded from
+    /// `{prefix}_TOOL_PROTOCOL` (`native` / `text`); otherwise `text` for
+    /// models listed in `TEXT_TOOL_MODELS`, a comma-separated list of model
+    /// names, and `native` for the rest.
+    pub tool_protocol: ToolProtocol,
 }
 
 impl AgentConfig {
@@ -31,6 +72,11 @@ impl AgentConfig {
         Self::load_with_agent_fallback("UI_AGENT")
     }
 
+    /// Load Engineering Manager config: EM_AGENT_* with fallback to AGENT_*.
+    pub fn load_engineering_manager() -> Result<Self, AgentError> {
+        Self::load_with_agent_fallback("EM_AGENT")
+    }
+
     /// Load PM agent config: PM_AGENT_* with fallback to AGENT_*.
     pub fn load_pm() -> Result<Self, AgentError> {
         Self::load_with_agent_fallback("PM_AGENT")
@@ -41,52 +87,102 @@ impl AgentConfig {
         Self::load_with_agent_fallback("CONTEXT_AGENT")
     }
 
+    /// Load stack reviewer config: STACK_REVIEWER_AGENT_* with fallback to AGENT_*.
+    pub fn load_stack_reviewer() -> Result<Self, AgentError> {
+        Self::load_with_agent_fallback("STACK_REVIEWER_AGENT")
+    }
+
+    /// Load rust-engineer config: the llama.cpp model in RUST_ENGINEER_MODEL
+    /// on the server at LLAMA_CPP_BASE_URL, without fallbacks.
+    pub fn load_rust_engineer() -> Result<Self, AgentError> {
+        let model = std::env::var("RUST_ENGINEER_MODEL")
+            .unwrap_or_else(|_| DEFAULT_LLAMA_CPP_MODEL.to_string());
+        Self::finish(
+            PROVIDER_LLAMA_CPP.to_string(),
+            model,
+            local_base_url(PROVIDER_LLAMA_CPP),
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
     /// Try `{prefix}_PROVIDER` / `{prefix}_MODEL` first; fall back to AGENT_* defaults.
+    /// `AGENT_BASE_URL` is only inherited by agents whose provider also comes
+    /// from `AGENT_PROVIDER`; it points at that provider's server.
     fn load_with_agent_fallback(prefix: &str) -> Result<Self, AgentError> {
         let provider_key = format!("{}_PROVIDER", prefix);
         let model_key = format!("{}_MODEL", prefix);
 
-        let provider = std::env::var(&provider_key)
+        let own_provider = std::env::var(&provider_key)
             .ok()
-            .or_else(|| read_project_conf(&provider_key))
+            .or_else(|| read_project_conf(&provider_key));
+        let inherits_provider = own_provider.is_none();
+        let provider = own_provider
             .or_else(|| std::env::var("AGENT_PROVIDER").ok())
             .or_else(|| read_project_conf("AGENT_PROVIDER"))
             .unwrap_or_else(|| PROVIDER_OPENAI.to_string());
 
-        let default_model = match provider.as_str() {
-            PROVIDER_ANTHROPIC => llm_sdk::models::claude::SONNET_4_5_ID.to_string(),
-            PROVIDER_GROQ => llm_sdk::models::groq::GPT_OSS_120B_ID.to_string(),
-            _ => llm_sdk::models::openai::GPT_5_MINI_ID.to_string(),
-        };
-
         let model = std::env::var(&model_key)
             .ok()
             .or_else(|| read_project_conf(&model_key))
             .or_else(|| std::env::var("AGENT_MODEL").ok())
             .or_else(|| read_project_conf("AGENT_MODEL"))
-            .unwrap_or(default_model);
+            .unwrap_or_else(|| default_model(&provider));
 
-        let key_name = match provider.as_str() {
-            PROVIDER_ANTHROPIC => "ANTHROPIC_API_KEY",
-            PROVIDER_GROQ => "GROQ_API_KEY",
-            _ => "OPENAI_API_KEY",
-        };
+        let base_url_key = format!("{}_BASE_URL", prefix);
+        let base_url = std::env::var(&base_url_key)
+            .ok()
+            .or_else(|| read_project_conf(&base_url_key))
+            .or_else(|| {
+                inherits_provider
+                    .then(|| {
+                        std::env::var("AGENT_BASE_URL")
+                            .ok()
+                            .or_else(|| read_project_conf("AGENT_BASE_URL"))
+                    })
+                    .flatten()
+            })
+            .or_else(|| local_base_url(&provider));
 
-        let api_key = std::env::var(key_name)
+        let fallbacks_key = format!("{}_FALLBACK_MODELS", prefix);
+        let fallbacks = std::env::var(&fallbacks_key)
             .ok()
-            .or_else(|| read_project_conf(key_name))
-            .ok_or_else(|| {
-                AgentError::Config(format!(
-                    "{} not set — add it to environment or project.conf",
-                    key_name
-                ))
-            })?;
+            .or_else(|| read_project_conf(&fallbacks_key))
+            .or_else(|| std::env::var("AGENT_FALLBACK_MODELS").ok())
+            .or_else(|| read_project_conf("AGENT_FALLBACK_MODELS"));
 
-        Ok(AgentConfig {
+        let budget_key = format!("{}_CONTEXT_BUDGET", prefix);
+        let context_budget = std::env::var(&budget_key)
+            .ok()
+            .or_else(|| read_project_conf(&budget_key))
+            .or_else(|| std::env::var("AGENT_CONTEXT_BUDGET").ok())
+            .or_else(|| read_project_conf("AGENT_CONTEXT_BUDGET"));
+
+        let summary_model_key = format!("{}_SUMMARY_MODEL", prefix);
+        let summary_model = std::env::var(&summary_model_key)
+            .ok()
+            .or_else(|| read_project_conf(&summary_model_key))
+            .or_else(|| std::env::var("AGENT_SUMMARY_MODEL").ok())
+            .or_else(|| read_project_conf("AGENT_SUMMARY_MODEL"));
+
+        let protocol_key = format!("{}_TOOL_PROTOCOL", prefix);
+        let tool_protocol = std::env::var(&protocol_key)
+            .ok()
+            .or_else(|| read_project_conf(&protocol_key))
+            .or_else(|| std::env::var("AGENT_TOOL_PROTOCOL").ok())
+            .or_else(|| read_project_conf("AGENT_TOOL_PROTOCOL"));
+
+        Self::finish(
             provider,
             model,
-            api_key,
-        })
+            base_url,
+            fallbacks.as_deref(),
+            context_budget.as_deref(),
+            summary_model,
+            tool_protocol.as_deref(),
+        )
     }
 
     fn load_from_prefix(prefix: &str) -> Result<Self, AgentError> {
@@ -98,39 +194,258 @@ impl AgentConfig {
             .or_else(|| read_project_conf(&provider_key))
             .unwrap_or_else(|| PROVIDER_OPENAI.to_string());
 
-        let default_model = match provider.as_str() {
-            PROVIDER_ANTHROPIC => llm_sdk::models::claude::SONNET_4_5_ID.to_string(),
-            PROVIDER_GROQ => llm_sdk::models::groq::GPT_OSS_120B_ID.to_string(),
-            _ => llm_sdk::models::openai::GPT_5_MINI_ID.to_string(),
-        };
-
         let model = std::env::var(&model_key)
             .ok()
             .or_else(|| read_project_conf(&model_key))
-            .unwrap_or(default_model);
+            .unwrap_or_else(|| default_model(&provider));
 
-        let key_name = match provider.as_str() {
-            PROVIDER_ANTHROPIC => "ANTHROPIC_API_KEY",
-            PROVIDER_GROQ => "GROQ_API_KEY",
-            _ => "OPENAI_API_KEY",
-        };
+        let base_url_key = format!("{}_BASE_URL", prefix);
+        let base_url = std::env::var(&base_url_key)
+            .ok()
+            .or_else(|| read_project_conf(&base_url_key))
+            .or_else(|| local_base_url(&provider));
+
+        let fallbacks_key = format!("{}_FALLBACK_MODELS", prefix);
+        let fallbacks = std::env::var(&fallbacks_key)
+            .ok()
+            .or_else(|| read_project_conf(&fallbacks_key));
 
-        let api_key = std::env::var(key_name)
+        let budget_key = format!("{}_CONTEXT_BUDGET", prefix);
+        let context_budget = std::env::var(&budget_key)
             .ok()
-            .or_else(|| read_project_conf(key_name))
-            .ok_or_else(|| {
+            .or_else(|| read_project_conf(&budget_key));
+
+        let summary_model_key = format!("{}_SUMMARY_MODEL", prefix);
+        let summary_model = std::env::var(&summary_model_key)
+            .ok()
+            .or_else(|| read_project_conf(&summary_model_key));
+
+        let protocol_key = format!("{}_TOOL_PROTOCOL", prefix);
+        let tool_protocol = std::env::var(&protocol_key)
+            .ok()
+            .or_else(|| read_project_conf(&protocol_key));
+
+        Self::finish(
+            provider,
+            model,
+            base_url,
+            fallbacks.as_deref(),
+            context_budget.as_deref(),
+            summary_model,
+            tool_protocol.as_deref(),
+        )
+    }
+
+    /// Resolve the API key, fixture settings, context budget, tool protocol
+    /// and fallback chain shared by every loader.
+    fn finish(
+        provider: String,
+        model: String,
+        base_url: Option<String>,
+        fallbacks: Option<&str>,
+        context_budget: Option<&str>,
+        summary_model: Option<String>,
+        tool_protocol: Option<&str>,
+    ) -> Result<Self, AgentError> {
+        if base_url.is_some() && !takes_base_url(&provider) {
+            return Err(AgentError::Config(format!(
+                "provider {} always uses its own API; unset the *_BASE_URL setting for it \
+                 (AGENT_BASE_URL included) or use an OpenAI-compatible provider",
+                provider
+            )));
+        }
+        let fixture = load_fixture_config()?;
+        let text_tool_models = std::env::var("TEXT_TOOL_MODELS")
+            .ok()
+            .or_else(|| read_project_conf("TEXT_TOOL_MODELS"))
+            .unwrap_or_default();
+        let protocol_for = |model: &str| {
+            if text_tool_models.split(',').any(|m| m.trim() == model) {
+                ToolProtocol::Text
+            } else {
+                ToolProtocol::Native
+            }
+        };
+        let tool_protocol = match tool_protocol {
+            Some(p) => ToolProtocol::parse(p).ok_or_else(|| {
+                AgentError::Config(format!(
+                    "tool protocol must be \"native\" or \"text\", got \"{}\"",
+                    p
+                ))
+            })?,
+            None => protocol_for(&model),
+        };
+        let context_budget = match context_budget {
+            Some(budget) => budget.trim().parse().map_err(|_| {
                 AgentError::Config(format!(
-                    "{} not set — add it to environment or project.conf",
-                    key_name
+                    "context budget must be a number of tokens, got \"{}\"",
+                    budget
+                ))
+            })?,
+            None => default_context_budget(&provider),
+        };
+        let replaying = matches!(&fixture, Some(f) if f.mode == FixtureMode::Replay);
+        let api_key = load_api_key(&provider, replaying)?;
+
+        let mut chain = Vec::new();
+        for entry in fallbacks
+            .unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        {
+            // Ollama tags contain ':' too, so only the first one separates.
+            let (fb_provider, fb_model) = entry.split_once(':').ok_or_else(|| {
+                AgentError::Config(format!(
+                    "fallback model \"{}\" must be provider:model",
+                    entry
                 ))
             })?;
+            let fb_model = match fb_model.trim() {
+                "" => default_model(fb_provider),
+                m => m.to_string(),
+            };
+            // A fallback on the primary's provider shares its server.
+            let fb_base_url = if fb_provider == provider {
+                base_url.clone()
+            } else {
+                local_base_url(fb_provider)
+            };
+            // A fallback without a key is dropped rather than failing the
+            // primary, which may well be configured correctly.
+            let fb_api_key = match load_api_key(fb_provider, replaying) {
+                Ok(key) => key,
+                Err(e) => {
+                    log::warn!("[Config] skipping fallback {}: {}", entry, e);
+                    continue;
+                }
+            };
+            chain.push(AgentConfig {
+                provider: fb_provider.to_string(),
+                api_key: fb_api_key,
+                base_url: fb_base_url,
+                fixture: fixture.clone(),
+                fallbacks: Vec::new(),
+                // Never give a fallback more history than its provider handles by default.
+                context_budget: context_budget.min(default_context_budget(fb_provider)),
+                summary_model: fb_model.clone(),
+                tool_protocol: protocol_for(&fb_model),
+                model: fb_model,
+            });
+        }
 
         Ok(AgentConfig {
+            summary_model: summary_model.unwrap_or_else(|| model.clone()),
             provider,
             model,
             api_key,
+            base_url,
+            fixture,
+            fallbacks: chain,
+            context_budget,
+            tool_protocol,
         })
     }
+
+    /// `provider:model`, as recorded on sessions and in logs.
+    pub fn label(&self) -> String {
+        format!("{}:{}", self.provider, self.model)
+    }
+}
+
+/// Local servers and replays never need a key.
+fn load_api_key(provider: &str, replaying: bool) -> Result<String, AgentError> {
+    let Some(key_name) = api_key_name(provider) else {
+        return Ok(String::new());
+    };
+    match std::env::var(key_name)
+        .ok()
+        .or_else(|| read_project_conf(key_name))
+    {
+        Some(key) => Ok(key),
+        None if replaying => Ok(String::new()),
+        None => Err(AgentError::Config(format!(
+            "{} not set — add it to environment or project.conf",
+            key_name
+        ))),
+    }
+}
+
+fn default_model(provider: &str) -> String {
+    match provider {
+        PROVIDER_ANTHROPIC => llm_sdk::models::claude::SONNET_4_5_ID.to_string(),
+        PROVIDER_GROQ => llm_sdk::models::groq::GPT_OSS_120B_ID.to_string(),
+        PROVIDER_LLAMA_CPP => DEFAULT_LLAMA_CPP_MODEL.to_string(),
+        PROVIDER_OLLAMA => DEFAULT_OLLAMA_MODEL.to_string(),
+        _ => llm_sdk::models::openai::GPT_5_MINI_ID.to_string(),
+    }
+}
+
+fn default_context_budget(provider: &str) -> usize {
+    match provider {
+        PROVIDER_LLAMA_CPP | PROVIDER_OLLAMA => DEFAULT_LOCAL_CONTEXT_BUDGET,
+        _ => DEFAULT_CONTEXT_BUDGET,
+    }
+}
+
+/// The env / project.conf key holding the provider's API key; `None` for
+/// local providers.
+fn api_key_name(provider: &str) -> Option<&'static str> {
+    match provider {
+        PROVIDER_ANTHROPIC => Some("ANTHROPIC_API_KEY"),
+        PROVIDER_GROQ => Some("GROQ_API_KEY"),
+        PROVIDER_LLAMA_CPP | PROVIDER_OLLAMA => None,
+        _ => Some("OPENAI_API_KEY"),
+    }
+}
+
+/// Whether the provider's client can be pointed at another server. The
+/// Anthropic and Groq clients have no base URL to set.
+fn takes_base_url(provider: &str) -> bool {
+    !matches!(provider, PROVIDER_ANTHROPIC | PROVIDER_GROQ)
+}
+
+/// Base URL for a local provider when no `*_BASE_URL` key is set:
+/// `LLAMA_CPP_BASE_URL` / `OLLAMA_BASE_URL`, then the server's default port.
+fn local_base_url(provider: &str) -> Option<String> {
+    let (key, default) = match provider {
+        PROVIDER_LLAMA_CPP => ("LLAMA_CPP_BASE_URL", DEFAULT_LLAMA_CPP_BASE_URL),
+        PROVIDER_OLLAMA => ("OLLAMA_BASE_URL", DEFAULT_OLLAMA_BASE_URL),
+        _ => return None,
+    };
+    Some(
+        std::env::var(key)
+            .ok()
+            .or_else(|| read_project_conf(key))
+            .unwrap_or_else(|| default.to_string()),
+    )
+}
+
+/// `LLM_FIXTURE_MODE` (`record` or `replay`) and `LLM_FIXTURE_DIR`, from the
+/// environment or project.conf. Unset mode means no fixtures.
+fn load_fixture_config() -> Result<Option<FixtureConfig>, AgentError> {
+    let Some(mode) = std::env::var("LLM_FIXTURE_MODE")
+        .ok()
+        .or_else(|| read_project_conf("LLM_FIXTURE_MODE"))
+    else {
+        return Ok(None);
+    };
+    let mode = FixtureMode::parse(&mode).ok_or_else(|| {
+        AgentError::Config(format!(
+            "LLM_FIXTURE_MODE must be \"record\" or \"replay\", got \"{}\"",
+            mode
+        ))
+    })?;
+    let dir = std::env::var("LLM_FIXTURE_DIR")
+        .ok()
+        .or_else(|| read_project_conf("LLM_FIXTURE_DIR"))
+        .ok_or_else(|| {
+            AgentError::Config("LLM_FIXTURE_DIR must be set with LLM_FIXTURE_MODE".to_string())
+        })?;
+    Ok(Some(FixtureConfig {
+        mode,
+        dir: PathBuf::from(dir),
+    }))
 }
 
 // ---------------------------------------------------------------------------
@@ -193,3 +508,57 @@ fn read_project_conf(key: &str) -> Option<String> {
 
     candidates.iter().find_map(|p| read_conf_file(p, key))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_url_rejected_for_providers_without_one() {
+        for provider in [PROVIDER_ANTHROPIC, PROVIDER_GROQ] {
+            let err = AgentConfig::finish(
+                provider.to_string(),
+                default_model(provider),
+                Some("http://localhost:8080/v1".to_string()),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap_err();
+            assert!(err.to_string().contains("_BASE_URL"), "{err}");
+        }
+        assert!(takes_base_url(PROVIDER_OPENAI));
+        assert!(takes_base_url(PROVIDER_OLLAMA));
+    }
+
+    #[test]
+    fn test_agent_base_url_only_follows_agent_provider() {
+        std::env::set_var("AGENT_PROVIDER", PROVIDER_OLLAMA);
+        std::env::set_var("AGENT_BASE_URL", "http://localhost:11434");
+        std::env::set_var("ANTHROPIC_API_KEY", "test-key");
+        std::env::set_var("CONFIG_TEST_PM_PROVIDER", PROVIDER_ANTHROPIC);
+
+        let overridden = AgentConfig::load_with_agent_fallback("CONFIG_TEST_PM");
+        let inherited = AgentConfig::load_with_agent_fallback("CONFIG_TEST_EM");
+
+        for key in [
+            "AGENT_PROVIDER",
+            "AGENT_BASE_URL",
+            "ANTHROPIC_API_KEY",
+            "CONFIG_TEST_PM_PROVIDER",
+        ] {
+            std::env::remove_var(key);
+        }
+
+        let overridden = overridden.expect("anthropic config");
+        assert_eq!(overridden.provider, PROVIDER_ANTHROPIC);
+        assert_eq!(overridden.base_url, None);
+        let inherited = inherited.expect("ollama config");
+        assert_eq!(inherited.provider, PROVIDER_OLLAMA);
+        assert_eq!(
+            inherited.base_url.as_deref(),
+            Some("http://localhost:11434")
+        );
+    }
+}
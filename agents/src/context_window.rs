@@ -0,0 +1,481 @@
+//! Keeps the history an agent sends each turn inside its model's context
+//! window. When a session's history outgrows the budget, the oldest turns are
+//! replaced by a summary written by a cheap model call; the summary is stored
+//! as a message row so later turns (and fallback models) reuse it.
+//!
+//! Cuts only ever fall on turn boundaries, so an assistant tool-call row is
+//! never separated from its tool-result rows.
+
+use std::sync::Arc;
+
+use llm_sdk::{
+    client::LlmClient,
+    types::{CompletionRequest, CompletionResponse, ContentBlock, Message, Role},
+};
+
+use crate::{
+    error::AgentError,
+    storage::{
+        AgentStorage, AgentType, ChatMessage, LlmUsage, MessageContent, UserChatMessageRow,
+        UserChatStorage,
+    },
+};
+
+/// Fixed per-message cost (role, separators) on top of the text itself.
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+const SUMMARY_MAX_TOKENS: u32 = 1024;
+/// Leading text of the summary as the model sees it.
+pub const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:\n";
+
+const SUMMARY_SYSTEM_PROMPT: &str = "You compress the history of a conversation \
+between a user and an AI agent so the agent can continue it. Write a concise plain-text \
+summary that keeps every requirement, decision, name, identifier and open question, and \
+the outcome of every tool call. Leave out greetings and small talk. Do not add a preamble.";
+
+/// Rough token count of one message: ~4 characters per token plus overhead.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4) + MESSAGE_OVERHEAD_TOKENS
+}
+
+/// Per-model history budget plus the client that writes summaries.
+#[derive(Clone)]
+pub struct ContextWindow {
+    client: Arc<dyn LlmClient>,
+    summary_model: String,
+    budget_tokens: usize,
+}
+
+impl ContextWindow {
+    pub fn new(
+        client: Arc<dyn LlmClient>,
+        summary_model: impl Into<String>,
+        budget_tokens: usize,
+    ) -> Self {
+        Self {
+            client,
+            summary_model: summary_model.into(),
+            budget_tokens,
+        }
+    }
+
+    /// History of an agent session, ready to map to LLM messages: the latest
+    /// summary (as a "user" row prefixed with `SUMMARY_PREFIX`) followed by the
+    /// rows it does not cover. Summarizes first when that is over budget.
+    pub async fn session_history(
+        &self,
+        storage: &dyn AgentStorage,
+        project_id: i64,
+        agent_type: AgentType,
+        task_id: i64,
+        session_id: i64,
+    ) -> Result<Vec<ChatMessage>, AgentError> {
+        let summary = storage.latest_summary(session_id).await?;
+        let mut summary_text = summary.as_ref().map(|s| s.content.clone());
+        let covered = summary.map_or(0, |s| s.covers_until_id);
+        let mut rows: Vec<ChatMessage> = storage
+            .get_messages(session_id)
+            .await?
+            .into_iter()
+            .filter(|m| m.id.unwrap_or(0) >= covered)
+            .collect();
+
+        let tokens: Vec<usize> = rows.iter().map(|m| estimate_tokens(&m.content)).collect();
+        let boundaries: Vec<bool> = (0..rows.len())
+            .map(|i| rows[i].role != "tool" && (i == 0 || rows[i].turn_id != rows[i - 1].turn_id))
+            .collect();
+        if let Some(cut) = self.plan_cut(summary_text.as_deref(), &tokens, &boundaries) {
+            let transcript: Vec<(String, String)> = rows[..cut]
+                .iter()
+                .map(|m| (transcript_role(m), m.content.clone()))
+                .collect();
+            let (text, response) = self.summarize(summary_text.as_deref(), &transcript).await?;
+            let covers_until_id = rows[cut].id.unwrap_or(0);
+            storage
+                .create_summary(session_id, agent_type.as_str(), &text, covers_until_id)
+                .await?;
+            storage
+                .record_usage(LlmUsage {
+                    task_id: Some(task_id),
+                    session_id: Some(session_id),
+                    ..self.usage(project_id, agent_type, &response)
+                })
+                .await?;
+            log::info!(
+                "[Context] session={} summarized {} rows up to id {}",
+                session_id,
+                cut,
+                covers_until_id
+            );
+            rows.drain(..cut);
+            summary_text = Some(text);
+        }
+
+        let Some(text) = summary_text else {
+            return Ok(rows);
+        };
+        let mut history = Vec::with_capacity(rows.len() + 1);
+        history.push(ChatMessage {
+            id: None,
+            session_id,
+            role: "user".to_string(),
+            agent_type: Some(agent_type.as_str().to_string()),
+            content: format!("{}{}", SUMMARY_PREFIX, text),
+            tool_call_id: None,
+            tool_name: None,
+            turn_id: None,
+            created_at: 0,
+        });
+        history.extend(rows);
+        Ok(history)
+    }
+
+    /// User-chat counterpart of `session_history`: returns the rendered summary
+    /// (with `SUMMARY_PREFIX`), if any, and the rows it does not cover. `rows`
+    /// come from `UserChatStorage::get_messages`. Turns start at user rows.
+    pub async fn user_chat_history(
+        &self,
+        chat_storage: &dyn UserChatStorage,
+        usage_storage: &dyn AgentStorage,
+        project_id: i64,
+        agent_type: AgentType,
+        session_id: i64,
+        rows: Vec<UserChatMessageRow>,
+    ) -> Result<(Option<String>, Vec<UserChatMessageRow>), AgentError> {
+        let summary = chat_storage.latest_summary(session_id).await?;
+        let mut summary_text = summary.as_ref().map(|s| s.content.clone());
+        let covered = summary.map_or(0, |s| s.covers_until_id);
+        let mut rows: Vec<UserChatMessageRow> =
+            rows.into_iter().filter(|m| m.id >= covered).collect();
+
+        let texts: Vec<String> = rows
+            .iter()
+            .map(|m| MessageContent::from_row(&m.content_type, &m.content).to_llm_text())
+            .collect();
+        let tokens: Vec<usize> = texts.iter().map(|t| estimate_tokens(t)).collect();
+        let boundaries: Vec<bool> = rows
+            .iter()
+            .enumerate()
+            .map(|(i, m)| i == 0 || m.author_type == "user")
+            .collect();
+        if let Some(cut) = self.plan_cut(summary_text.as_deref(), &tokens, &boundaries) {
+            let transcript: Vec<(String, String)> = rows[..cut]
+                .iter()
+                .zip(&texts)
+                .map(|(m, text)| {
+                    let role = match (m.author_type.as_str(), m.agent_type.as_deref()) {
+                        ("user", _) => "user".to_string(),
+                        (_, Some(agent)) => agent.to_string(),
+                        (author, None) => author.to_string(),
+                    };
+                    (role, text.clone())
+                })
+                .collect();
+            let (text, response) = self.summarize(summary_text.as_deref(), &transcript).await?;
+            let covers_until_id = rows[cut].id;
+            chat_storage
+                .append_summary(session_id, agent_type, &text, covers_until_id)
+                .await?;
+            usage_storage
+                .record_usage(LlmUsage {
+                    session_id: Some(session_id),
+                    ..self.usage(project_id, agent_type, &response)
+                })
+                .await?;
+            log::info!(
+                "[Context] user chat session={} summarized {} rows up to id {}",
+                session_id,
+                cut,
+                covers_until_id
+            );
+            rows.drain(..cut);
+            summary_text = Some(text);
+        }
+
+        Ok((
+            summary_text.map(|text| format!("{}{}", SUMMARY_PREFIX, text)),
+            rows,
+        ))
+    }
+
+    /// Index of the first row to keep when the history is over budget, or
+    /// `None` when it fits (or there is no earlier turn to summarize).
+    fn plan_cut(
+        &self,
+        summary: Option<&str>,
+        tokens: &[usize],
+        boundaries: &[bool],
+    ) -> Option<usize> {
+        let summary_tokens = summary.map(estimate_tokens).unwrap_or(0);
+        let total: usize = summary_tokens + tokens.iter().sum::<usize>();
+        if total <= self.budget_tokens {
+            return None;
+        }
+        // Keep the newest turns within half the budget, leaving room for the
+        // summary and the reply; always keep at least the last turn.
+        plan_cut(tokens, boundaries, self.budget_tokens / 2)
+    }
+
+    fn usage(
+        &self,
+        project_id: i64,
+        agent_type: AgentType,
+        response: &CompletionResponse,
+    ) -> LlmUsage {
+        LlmUsage::from_response(
+            project_id,
+            agent_type,
+            self.client.as_ref(),
+            &self.summary_model,
+            response,
+        )
+    }
+
+    async fn summarize(
+        &self,
+        previous: Option<&str>,
+        transcript: &[(String, String)],
+    ) -> Result<(String, CompletionResponse), AgentError> {
+        let mut input = String::new();
+        if let Some(previous) = previous {
+            input.push_str("Summary so far:\n");
+            input.push_str(previous);
+            input.push_str("\n\n");
+        }
+        input.push_str("Conversation to add to the summary:\n");
+        for (role, text) in transcript {
+            input.push_str(&format!("[{}] {}\n", role, text));
+        }
+
+        let request = CompletionRequest {
+            messages: vec![Message {
+                role: Role::User,
+                content: vec![ContentBlock::Text { text: input }],
+                tool_call_id: None,
+                tool_name: None,
+            }],
+            max_tokens: SUMMARY_MAX_TOKENS,
+            model: self.summary_model.clone(),
+            system: Some(SUMMARY_SYSTEM_PROMPT.to_string()),
+            temperature: Some(0.0),
+            top_p: None,
+            stop_sequences: None,
+            tools: None,
+            tool_choice: None,
+            response_format: None,
+        };
+        let response = self.client.complete(request).await?;
+        let text = response
+            .content
+            .iter()
+            .filter_map(|b| match b {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("")
+            .trim()
+            .to_string();
+        if text.is_empty() {
+            return Err(AgentError::Other("summary model returned no text".into()));
+        }
+        Ok((text, response))
+    }
+}
+
+fn transcript_role(m: &ChatMessage) -> String {
+    match (m.role.as_str(), &m.tool_name) {
+        ("assistant", Some(tool)) => format!("assistant called {}", tool),
+        ("tool", Some(tool)) => format!("{} result", tool),
+        (role, _) => role.to_string(),
+    }
+}
+
+/// The latest boundary from which the remaining rows fit in `keep_tokens`,
+/// falling back to the last boundary. `None` when that is the first row.
+fn plan_cut(tokens: &[usize], boundaries: &[bool], keep_tokens: usize) -> Option<usize> {
+    let mut cut = None;
+    let mut kept = 0;
+    for i in (0..tokens.len()).rev() {
+        kept += tokens[i];
+        if !boundaries[i] {
+            continue;
+        }
+        if cut.is_some() && kept > keep_tokens {
+            break;
+        }
+        cut = Some(i);
+    }
+    cut.filter(|&i| i > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+    use llm_sdk::error::LlmError;
+
+    use super::*;
+    use crate::storage::{sqlite::SqliteAgentStorage, test_db};
+
+    /// Answers "summary 1", "summary 2", ... and keeps every request's text.
+    #[derive(Default)]
+    struct SummaryClient {
+        inputs: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl LlmClient for SummaryClient {
+        async fn complete(
+            &self,
+            request: CompletionRequest,
+        ) -> Result<CompletionResponse, LlmError> {
+            let mut inputs = self.inputs.lock().unwrap();
+            if let ContentBlock::Text { text } = &request.messages[0].content[0] {
+                inputs.push(text.clone());
+            }
+            Ok(serde_json::from_value(serde_json::json!({
+                "content": [{ "type": "text", "text": format!("summary {}", inputs.len()) }],
+                "role": "assistant",
+                "stop_reason": "end_turn",
+                "tool_calls": null,
+                "usage": { "input_tokens": 100, "output_tokens": 10 }
+            }))
+            .unwrap())
+        }
+
+        fn provider_name(&self) -> &str {
+            "stub"
+        }
+
+        fn model_name(&self) -> &str {
+            "summary-model"
+        }
+    }
+
+    fn message(session_id: i64, role: &str, tool: Option<&str>, content: String) -> ChatMessage {
+        ChatMessage {
+            id: None,
+            session_id,
+            role: role.to_string(),
+            agent_type: Some("db_engineer".to_string()),
+            content,
+            tool_call_id: tool.map(|_| "call".to_string()),
+            tool_name: tool.map(str::to_string),
+            turn_id: None,
+            created_at: 0,
+        }
+    }
+
+    /// A single-row turn; ~104 tokens when `big`, ~8 otherwise.
+    async fn say(storage: &SqliteAgentStorage, session_id: i64, text: &str, big: bool) -> i64 {
+        let content = format!("{:<width$}", text, width = if big { 400 } else { 16 });
+        storage
+            .create_message(message(session_id, "user", None, content))
+            .await
+            .unwrap()
+    }
+
+    /// A big assistant tool call and its small result, as one turn.
+    async fn call_tool(storage: &SqliteAgentStorage, session_id: i64, text: &str) {
+        storage
+            .create_turn(vec![
+                message(
+                    session_id,
+                    "assistant",
+                    Some("run_sql"),
+                    format!("{:<400}", text),
+                ),
+                message(session_id, "tool", Some("run_sql"), "ok".to_string()),
+            ])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_session_history_summarizes_whole_turns_and_extends_the_summary() {
+        let storage = SqliteAgentStorage::new(test_db::with_tasks(1));
+        let session_id = storage
+            .create_task_session(1, 1, "db_engineer")
+            .await
+            .unwrap()
+            .id
+            .unwrap();
+        let client = Arc::new(SummaryClient::default());
+        let window = ContextWindow::new(client.clone(), "summary-model", 200);
+        let history = |window: ContextWindow| {
+            let storage = &storage;
+            async move {
+                window
+                    .session_history(storage, 1, AgentType::DbEngineer, 1, session_id)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        say(&storage, session_id, "first request", true).await;
+        call_tool(&storage, session_id, "first call").await;
+        say(&storage, session_id, "second request", true).await;
+        call_tool(&storage, session_id, "second call").await;
+        let third = say(&storage, session_id, "third", false).await;
+
+        // Keeping the last three rows would fit half the budget, but that
+        // would leave the second call's result without its call.
+        let rows = history(window.clone()).await;
+        assert_eq!(rows[0].content, format!("{}summary 1", SUMMARY_PREFIX));
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].id, Some(third));
+        let summary = storage.latest_summary(session_id).await.unwrap().unwrap();
+        assert_eq!(summary.covers_until_id, third);
+        assert!(client.inputs.lock().unwrap()[0].contains("second call"));
+
+        call_tool(&storage, session_id, "third call").await;
+        say(&storage, session_id, "fourth request", true).await;
+        let fifth = say(&storage, session_id, "fifth", false).await;
+
+        let rows = history(window).await;
+        assert_eq!(rows[0].content, format!("{}summary 2", SUMMARY_PREFIX));
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].id, Some(fifth));
+        let summary = storage.latest_summary(session_id).await.unwrap().unwrap();
+        assert_eq!(summary.covers_until_id, fifth);
+        // The second summary is written from the first plus the rows after it.
+        let second = &client.inputs.lock().unwrap()[1];
+        assert!(second.starts_with("Summary so far:\nsummary 1\n"));
+        assert!(second.contains("third call") && !second.contains("first call"));
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), MESSAGE_OVERHEAD_TOKENS);
+        assert_eq!(estimate_tokens("abcdefgh"), 2 + MESSAGE_OVERHEAD_TOKENS);
+        assert_eq!(estimate_tokens("abcdefghi"), 3 + MESSAGE_OVERHEAD_TOKENS);
+    }
+
+    #[test]
+    fn test_plan_cut_keeps_newest_turns_within_budget() {
+        let tokens = [10, 10, 10, 10];
+        let boundaries = [true, true, true, true];
+        assert_eq!(plan_cut(&tokens, &boundaries, 20), Some(2));
+        assert_eq!(plan_cut(&tokens, &boundaries, 25), Some(2));
+        assert_eq!(plan_cut(&tokens, &boundaries, 100), None);
+    }
+
+    #[test]
+    fn test_plan_cut_never_splits_a_turn() {
+        // Rows 1..=3 are one turn: an assistant tool call and its two results.
+        let tokens = [10, 10, 10, 10, 10];
+        let boundaries = [true, true, false, false, true];
+        assert_eq!(plan_cut(&tokens, &boundaries, 25), Some(4));
+        assert_eq!(plan_cut(&tokens, &boundaries, 40), Some(1));
+    }
+
+    #[test]
+    fn test_plan_cut_keeps_an_oversized_last_turn() {
+        let tokens = [10, 10, 500];
+        let boundaries = [true, true, true];
+        assert_eq!(plan_cut(&tokens, &boundaries, 20), Some(2));
+        // A single turn cannot be summarized away.
+        assert_eq!(plan_cut(&[500], &[true], 20), None);
+    }
+}
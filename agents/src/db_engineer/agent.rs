@@ -13,8 +13,14 @@ use super::{
     tools::{AskUserParams, StopAgentParams, UpdateTaskStatusParams},
 };
 use crate::{
+    context_window::ContextWindow,
     error::AgentError,
-    storage::{AgentStorage, AgentType, ChatMessage, SchemaStorage, TaskStatus, TaskStorage},
+    prompt_library::PromptLibrary,
+    run_control::RunControl,
+    storage::{
+        AgentStorage, AgentType, ChatMessage, LlmUsage, SchemaStorage, TaskStatus, TaskStorage,
+    },
+    tool_args::parse_or_record,
 };
 const MAX_NUDGES: u32 = 3;
 
@@ -49,6 +55,12 @@ pub struct DbEngineerAgent {
     task_storage: Arc<dyn TaskStorage>,
     model: String,
     project_id: i64,
+    /// Summarizes older turns when the history outgrows the model's budget.
+    /// `None` sends the full history.
+    context: Option<ContextWindow>,
+    prompts: PromptLibrary,
+    /// Cancellation and time/turn budget, checked every turn and tool call.
+    control: RunControl,
 }
 
 impl DbEngineerAgent {
@@ -67,9 +79,30 @@ impl DbEngineerAgent {
             task_storage,
             model: model.into(),
             project_id,
+            context: None,
+            prompts: PromptLibrary::embedded(),
+            control: RunControl::default(),
         }
     }
 
+    pub fn with_context_window(mut self, context: ContextWindow) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Resolve the system prompt through `prompts`, e.g. to pick up the
+    /// project's overrides. Embedded templates are used otherwise.
+    pub fn with_prompts(mut self, prompts: PromptLibrary) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    /// Let the caller cancel the run or bound its time and turns.
+    pub fn with_run_control(mut self, control: RunControl) -> Self {
+        self.control = control;
+        self
+    }
+
     /// Run the agent for an existing session.
     /// The caller is responsible for creating the task + session and persisting
     /// the incoming user message before calling this.
@@ -129,11 +162,29 @@ impl DbEngineerAgent {
             )
             .build();
 
+        let system = system_prompt(&self.prompts)?;
+        self.storage
+            .set_session_prompt(session_id, &system.reference)
+            .await?;
+
         let mut nudges: u32 = 0;
 
-        loop {
+        'turns: loop {
             // Reconstruct full message history from storage for every turn.
-            let history = self.storage.get_messages(session_id).await?;
+            let history = match &self.context {
+                Some(context) => {
+                    context
+                        .session_history(
+                            self.storage.as_ref(),
+                            self.project_id,
+                            AgentType::DbEngineer,
+                            task_id,
+                            session_id,
+                        )
+                        .await?
+                }
+                None => self.storage.get_messages(session_id).await?,
+            };
             let llm_messages: Vec<Message> = history
                 .into_iter()
                 .map(|m| {
@@ -155,7 +206,7 @@ impl DbEngineerAgent {
                 messages: llm_messages,
                 max_tokens: 4096,
                 model: self.model.clone(),
-                system: Some(system_prompt()),
+                system: Some(system.text.clone()),
                 temperature: Some(0.2),
                 top_p: None,
                 stop_sequences: None,
@@ -172,7 +223,26 @@ impl DbEngineerAgent {
             log::debug!("[Agent] Sending {} messages to LLM", request.messages.len());
             log::info!("[Agent] Calling LLM with model={}", self.model);
 
-            let response = self.llm_client.complete(request).await?;
+            self.control.next_turn()?;
+            let response = self
+                .control
+                .run(self.llm_client.complete(request))
+                .await??;
+
+            self.storage
+                .record_usage(LlmUsage {
+                    task_id: Some(task_id),
+                    session_id: Some(session_id),
+                    prompt: Some(system.reference.clone()),
+                    ..LlmUsage::from_response(
+                        self.project_id,
+                        AgentType::DbEngineer,
+                        self.llm_client.as_ref(),
+                        &self.model,
+                        &response,
+                    )
+                })
+                .await?;
 
             log::info!(
                 "[Agent] LLM response received, stop_reason={:?}",
@@ -209,14 +279,26 @@ impl DbEngineerAgent {
             if let Some(tool_calls) = response.tool_calls {
                 log::info!("[Agent] LLM made {} tool call(s)", tool_calls.len());
                 for tool_call in tool_calls {
+                    self.control.check()?;
                     let tool_name = tool_call.name();
                     let call_id = tool_call.id().to_string();
                     log::info!("[Agent] Tool call: name={}, call_id={}", tool_name, call_id);
                     match tool_name {
                         "generate_schema" => {
                             log::info!("[Agent] Processing generate_schema tool call");
-                            let mut params: SchemaDef =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                            let Some(mut params) = parse_or_record::<SchemaDef>(
+                                self.storage.as_ref(),
+                                session_id,
+                                AgentType::DbEngineer,
+                                &assistant_text,
+                                &tool_call,
+                                &mut nudges,
+                                MAX_NUDGES,
+                            )
+                            .await?
+                            else {
+                                continue 'turns;
+                            };
                             log::debug!(
                                 "[Agent] Schema params parsed: {} tables",
                                 params.tables.len()
@@ -316,8 +398,19 @@ impl DbEngineerAgent {
 
                         "stop_agent" => {
                             log::info!("[Agent] Processing stop_agent tool call");
-                            let params: StopAgentParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                            let Some(params) = parse_or_record::<StopAgentParams>(
+                                self.storage.as_ref(),
+                                session_id,
+                                AgentType::DbEngineer,
+                                &assistant_text,
+                                &tool_call,
+                                &mut nudges,
+                                MAX_NUDGES,
+                            )
+                            .await?
+                            else {
+                                continue 'turns;
+                            };
                             log::info!("[Agent] Stop reason: {}", params.reply);
 
                             let mut turn = Vec::new();
@@ -354,8 +447,19 @@ impl DbEngineerAgent {
 
                         "update_task_status" => {
                             log::info!("[Agent] Processing update_task_status tool call");
-                            let params: UpdateTaskStatusParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                            let Some(params) = parse_or_record::<UpdateTaskStatusParams>(
+                                self.storage.as_ref(),
+                                session_id,
+                                AgentType::DbEngineer,
+                                &assistant_text,
+                                &tool_call,
+                                &mut nudges,
+                                MAX_NUDGES,
+                            )
+                            .await?
+                            else {
+                                continue 'turns;
+                            };
                             log::info!("[Agent] Requested status: {}", params.status);
 
                             let new_status = TaskStatus::from_str(&params.status);
@@ -403,8 +507,19 @@ impl DbEngineerAgent {
 
                         "ask_user" => {
                             log::info!("[Agent] Processing ask_user tool call");
-                            let params: AskUserParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                            let Some(params) = parse_or_record::<AskUserParams>(
+                                self.storage.as_ref(),
+                                session_id,
+                                AgentType::DbEngineer,
+                                &assistant_text,
+                                &tool_call,
+                                &mut nudges,
+                                MAX_NUDGES,
+                            )
+                            .await?
+                            else {
+                                continue 'turns;
+                            };
                             log::info!("[Agent] Question: {}", params.question);
 
                             let mut turn = Vec::new();
@@ -472,7 +587,7 @@ impl DbEngineerAgent {
             );
             if nudges >= MAX_NUDGES {
                 log::error!("[Agent] Max nudges reached, giving up");
-                return Err(AgentError::Other(
+                return Err(AgentError::NudgesExhausted(
                     "Model did not produce a response after multiple nudges.".to_string(),
                 ));
             }
@@ -0,0 +1,858 @@
+use std::sync::Arc;
+
+use llm_sdk::{
+    client::LlmClient,
+    tools::{Tool, ToolCall, ToolChoice},
+    types::{CompletionRequest, Message, Role},
+};
+
+use super::{
+    prompts::system_prompt,
+    tools::{BlockTaskParams, CommentOnTaskParams, MarkTaskReadyParams, SplitTaskParams},
+};
+use crate::{
+    context_window::ContextWindow,
+    error::AgentError,
+    prompt_library::PromptLibrary,
+    run_control::RunControl,
+    storage::{
+        AgentStorage, AgentType, ChatMessage, CommentStorage, LlmUsage, ProjectNoteRow,
+        ProjectNoteStorage, StackNoteRow, StackNoteStorage, Task, TaskCommentRow, TaskStatus,
+        TaskStorage,
+    },
+    tool_args::parse_or_record,
+};
+
+const MAX_NUDGES: u32 = 3;
+
+/// Agents the EM may hand shaped work to.
+const SHAPED_AGENTS: [&str; 5] = [
+    "db_engineer",
+    "backend_engineer",
+    "frontend_engineer",
+    "ui_designer",
+    "rust_engineer",
+];
+
+#[derive(Debug)]
+pub enum EngineeringManagerResponse {
+    /// The task was split into these Ready tasks and is now Done.
+    Split(Vec<Task>),
+    /// The task is Ready for its assigned agent.
+    Ready,
+    /// The task is Blocked, for this reason.
+    Blocked(String),
+}
+
+/// First message of an EM session: the task to shape.
+pub fn shaping_request(task: &Task) -> String {
+    format!(
+        "Shape task #{} for {}: {}\n\n{}",
+        task.id.unwrap_or(0),
+        task.assigned_to_agent,
+        task.title,
+        task.description
+    )
+}
+
+/// Shapes `needs_technical_shaping` tasks: splits them into concrete tasks
+/// for the specialist agents, or marks them Ready as they are, with
+/// acceptance criteria either way.
+pub struct EngineeringManagerAgent {
+    llm_client: Arc<dyn LlmClient>,
+    storage: Arc<dyn AgentStorage>,
+    task_storage: Arc<dyn TaskStorage>,
+    comment_storage: Arc<dyn CommentStorage>,
+    stack_note_storage: Arc<dyn StackNoteStorage>,
+    project_note_storage: Arc<dyn ProjectNoteStorage>,
+    model: String,
+    project_id: i64,
+    /// Summarizes older turns when the history outgrows the model's budget.
+    /// `None` sends the full history.
+    context: Option<ContextWindow>,
+    prompts: PromptLibrary,
+    control: RunControl,
+}
+
+impl EngineeringManagerAgent {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        llm_client: Arc<dyn LlmClient>,
+        storage: Arc<dyn AgentStorage>,
+        task_storage: Arc<dyn TaskStorage>,
+        comment_storage: Arc<dyn CommentStorage>,
+        stack_note_storage: Arc<dyn StackNoteStorage>,
+        project_note_storage: Arc<dyn ProjectNoteStorage>,
+        model: impl Into<String>,
+        project_id: i64,
+    ) -> Self {
+        Self {
+            llm_client,
+            storage,
+            task_storage,
+            comment_storage,
+            stack_note_storage,
+            project_note_storage,
+            model: model.into(),
+            project_id,
+            context: None,
+            prompts: PromptLibrary::embedded(),
+            control: RunControl::default(),
+        }
+    }
+
+    pub fn with_context_window(mut self, context: ContextWindow) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Resolve the system prompt through `prompts`, e.g. to pick up the
+    /// project's overrides. Embedded templates are used otherwise.
+    pub fn with_prompts(mut self, prompts: PromptLibrary) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    /// Let the caller cancel the run or bound its time and turns.
+    pub fn with_run_control(mut self, control: RunControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    pub async fn run_for_task(
+        &self,
+        session_id: i64,
+        task_id: i64,
+    ) -> Result<EngineeringManagerResponse, AgentError> {
+        let task = self
+            .task_storage
+            .get_task(task_id)
+            .await?
+            .ok_or_else(|| AgentError::Other(format!("Task {} not found", task_id)))?;
+
+        let split_tool = Tool::from_type::<SplitTaskParams>()
+            .name("split_task")
+            .description(
+                "Replace the task with concrete tasks for the specialist agents, each with \
+                 acceptance criteria. Closes the task.",
+            )
+            .build();
+        let ready_tool = Tool::from_type::<MarkTaskReadyParams>()
+            .name("mark_task_ready")
+            .description("Hand the task to its assigned agent as it is, with acceptance criteria.")
+            .build();
+        let comment_tool = Tool::from_type::<CommentOnTaskParams>()
+            .name("comment_on_task")
+            .description("Add a comment to the task and keep shaping.")
+            .build();
+        let block_tool = Tool::from_type::<BlockTaskParams>()
+            .name("block_task")
+            .description("Block the task when it cannot be done as described.")
+            .build();
+
+        let stack_notes = self
+            .stack_note_storage
+            .list_current_notes(self.project_id)
+            .await?;
+        let project_notes = self
+            .project_note_storage
+            .list_current_notes(self.project_id)
+            .await?;
+        let comments = self.comment_storage.get_task_comments(task_id).await?;
+        let system = system_prompt(
+            &self.prompts,
+            &format_stack_notes(&stack_notes),
+            &format_project_notes(&project_notes),
+            &format_comments(&comments),
+        )?;
+        self.storage
+            .set_session_prompt(session_id, &system.reference)
+            .await?;
+
+        let mut nudges: u32 = 0;
+
+        'turns: loop {
+            let history = match &self.context {
+                Some(context) => {
+                    context
+                        .session_history(
+                            self.storage.as_ref(),
+                            self.project_id,
+                            AgentType::EngineeringManager,
+                            task_id,
+                            session_id,
+                        )
+                        .await?
+                }
+                None => self.storage.get_messages(session_id).await?,
+            };
+            let llm_messages: Vec<Message> = history
+                .into_iter()
+                .map(|m| {
+                    let role = match m.role.as_str() {
+                        "assistant" => Role::Assistant,
+                        "tool" => Role::Tool,
+                        _ => Role::User,
+                    };
+                    Message {
+                        role,
+                        content: vec![llm_sdk::types::ContentBlock::Text { text: m.content }],
+                        tool_call_id: m.tool_call_id,
+                        tool_name: m.tool_name,
+                    }
+                })
+                .collect();
+
+            let request = CompletionRequest {
+                messages: llm_messages,
+                max_tokens: 4096,
+                model: self.model.clone(),
+                system: Some(system.text.clone()),
+                temperature: Some(0.2),
+                top_p: None,
+                stop_sequences: None,
+                tools: Some(vec![
+                    split_tool.clone(),
+                    ready_tool.clone(),
+                    comment_tool.clone(),
+                    block_tool.clone(),
+                ]),
+                tool_choice: Some(ToolChoice::Auto),
+                response_format: None,
+            };
+
+            self.control.next_turn()?;
+            let response = self
+                .control
+                .run(self.llm_client.complete(request))
+                .await??;
+
+            self.storage
+                .record_usage(LlmUsage {
+                    task_id: Some(task_id),
+                    session_id: Some(session_id),
+                    prompt: Some(system.reference.clone()),
+                    ..LlmUsage::from_response(
+                        self.project_id,
+                        AgentType::EngineeringManager,
+                        self.llm_client.as_ref(),
+                        &self.model,
+                        &response,
+                    )
+                })
+                .await?;
+
+            let assistant_text = response
+                .content
+                .iter()
+                .filter_map(|b| match b {
+                    llm_sdk::types::ContentBlock::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("");
+
+            if let Some(tool_calls) = response.tool_calls {
+                for tool_call in tool_calls {
+                    self.control.check()?;
+                    match tool_call.name() {
+                        "split_task" => {
+                            let Some(params) = self
+                                .parse::<SplitTaskParams>(
+                                    session_id,
+                                    &assistant_text,
+                                    &tool_call,
+                                    &mut nudges,
+                                )
+                                .await?
+                            else {
+                                continue 'turns;
+                            };
+                            match self.split(&task, &params).await? {
+                                Ok(created) => {
+                                    let result = format!(
+                                        "Task split into {} task(s): {}.",
+                                        created.len(),
+                                        created
+                                            .iter()
+                                            .map(|t| format!("#{}", t.id.unwrap_or(0)))
+                                            .collect::<Vec<_>>()
+                                            .join(", ")
+                                    );
+                                    self.record_call(
+                                        session_id,
+                                        &assistant_text,
+                                        &tool_call,
+                                        result,
+                                    )
+                                    .await?;
+                                    return Ok(EngineeringManagerResponse::Split(created));
+                                }
+                                Err(problem) => {
+                                    self.reject(
+                                        session_id,
+                                        &assistant_text,
+                                        &tool_call,
+                                        problem,
+                                        &mut nudges,
+                                    )
+                                    .await?;
+                                    continue 'turns;
+                                }
+                            }
+                        }
+
+                        "mark_task_ready" => {
+                            let Some(params) = self
+                                .parse::<MarkTaskReadyParams>(
+                                    session_id,
+                                    &assistant_text,
+                                    &tool_call,
+                                    &mut nudges,
+                                )
+                                .await?
+                            else {
+                                continue 'turns;
+                            };
+                            if params.acceptance_criteria.is_empty() {
+                                self.reject(
+                                    session_id,
+                                    &assistant_text,
+                                    &tool_call,
+                                    "acceptance_criteria must list at least one check.".to_string(),
+                                    &mut nudges,
+                                )
+                                .await?;
+                                continue 'turns;
+                            }
+                            let mut comment = format!(
+                                "Shaped for {}.\n\n{}",
+                                task.assigned_to_agent,
+                                criteria_section(&params.acceptance_criteria)
+                            );
+                            if let Some(notes) = params.notes.filter(|n| !n.trim().is_empty()) {
+                                comment.push_str(&format!("\n\n{}", notes));
+                            }
+                            self.comment(task_id, comment).await?;
+                            self.task_storage
+                                .update_task_status(task_id, TaskStatus::Ready)
+                                .await?;
+                            self.record_call(
+                                session_id,
+                                &assistant_text,
+                                &tool_call,
+                                "Task marked ready.".to_string(),
+                            )
+                            .await?;
+                            return Ok(EngineeringManagerResponse::Ready);
+                        }
+
+                        "comment_on_task" => {
+                            let Some(params) = self
+                                .parse::<CommentOnTaskParams>(
+                                    session_id,
+                                    &assistant_text,
+                                    &tool_call,
+                                    &mut nudges,
+                                )
+                                .await?
+                            else {
+                                continue 'turns;
+                            };
+                            self.comment(task_id, params.comment).await?;
+                            self.record_call(
+                                session_id,
+                                &assistant_text,
+                                &tool_call,
+                                "Comment added.".to_string(),
+                            )
+                            .await?;
+                        }
+
+                        "block_task" => {
+                            let Some(params) = self
+                                .parse::<BlockTaskParams>(
+                                    session_id,
+                                    &assistant_text,
+                                    &tool_call,
+                                    &mut nudges,
+                                )
+                                .await?
+                            else {
+                                continue 'turns;
+                            };
+                            self.comment(task_id, format!("Blocked: {}", params.reason))
+                                .await?;
+                            self.task_storage
+                                .update_task_status(task_id, TaskStatus::Blocked)
+                                .await?;
+                            self.record_call(
+                                session_id,
+                                &assistant_text,
+                                &tool_call,
+                                "Task blocked.".to_string(),
+                            )
+                            .await?;
+                            return Ok(EngineeringManagerResponse::Blocked(params.reason));
+                        }
+
+                        unknown => {
+                            return Err(AgentError::Other(format!(
+                                "engineering_manager called unknown tool: {}",
+                                unknown
+                            )));
+                        }
+                    }
+                }
+                continue;
+            }
+
+            // No tool call — text-only response.
+            if !assistant_text.is_empty() {
+                self.storage
+                    .create_turn(vec![self.text_row(session_id, assistant_text.clone())])
+                    .await?;
+            }
+
+            nudges += 1;
+            if nudges >= MAX_NUDGES {
+                return Err(AgentError::NudgesExhausted(
+                    "engineering_manager did not finish shaping after multiple nudges".to_string(),
+                ));
+            }
+
+            self.storage
+                .create_message(ChatMessage {
+                    id: None,
+                    session_id,
+                    role: "user".to_string(),
+                    agent_type: None,
+                    content: "Please finish shaping now: call split_task, mark_task_ready or \
+                              block_task."
+                        .to_string(),
+                    tool_call_id: None,
+                    tool_name: None,
+                    turn_id: None,
+                    created_at: 0,
+                })
+                .await?;
+        }
+    }
+
+    /// Create the Ready tasks `params` describes in place of `parent` and
+    /// close `parent`, all or nothing. `Ok(Err(problem))` when the split is
+    /// invalid; nothing is created then.
+    async fn split(
+        &self,
+        parent: &Task,
+        params: &SplitTaskParams,
+    ) -> Result<Result<Vec<Task>, String>, AgentError> {
+        if params.tasks.is_empty() {
+            return Ok(Err("tasks must list at least one task.".to_string()));
+        }
+        let mut drafts = Vec::with_capacity(params.tasks.len());
+        for (idx, shaped) in params.tasks.iter().enumerate() {
+            let position = idx + 1;
+            if !SHAPED_AGENTS.contains(&shaped.assigned_to_agent.as_str()) {
+                return Ok(Err(format!(
+                    "tasks[{}].assigned_to_agent must be one of {}, not \"{}\".",
+                    idx,
+                    SHAPED_AGENTS.join(", "),
+                    shaped.assigned_to_agent
+                )));
+            }
+            if shaped.acceptance_criteria.is_empty() {
+                return Ok(Err(format!(
+                    "tasks[{}].acceptance_criteria must list at least one check.",
+                    idx
+                )));
+            }
+            if idx == 0 && !shaped.depends_on.is_empty() {
+                return Ok(Err(
+                    "tasks[0].depends_on must be empty: the first task has no earlier tasks."
+                        .to_string(),
+                ));
+            }
+            if shaped
+                .depends_on
+                .iter()
+                .any(|&dep| dep == 0 || dep >= position)
+            {
+                return Ok(Err(format!(
+                    "tasks[{}].depends_on must list positions (1 to {}) of earlier tasks.",
+                    idx, idx
+                )));
+            }
+            let description = format!(
+                "{}\n\n{}",
+                shaped.description,
+                criteria_section(&shaped.acceptance_criteria)
+            );
+            // rust_engineer tasks carry their structured parameters as JSON in
+            // source_prompt.
+            let source_prompt = match &shaped.rust_engineer {
+                Some(rust_params) => match rust_params.to_source_prompt(&description) {
+                    Ok(source_prompt) => source_prompt,
+                    Err(e) => return Ok(Err(format!("tasks[{}].rust_engineer: {}", idx, e))),
+                },
+                None if shaped.assigned_to_agent == "rust_engineer" => {
+                    return Ok(Err(format!(
+                        "tasks[{}] is a rust_engineer task and needs `rust_engineer` parameters.",
+                        idx
+                    )));
+                }
+                None => description.clone(),
+            };
+            drafts.push(Task {
+                id: None,
+                project_id: parent.project_id,
+                epic_id: parent.epic_id,
+                title: shaped.title.clone(),
+                description,
+                source_prompt,
+                assigned_to_agent: shaped.assigned_to_agent.clone(),
+                status: TaskStatus::Ready,
+                depends_on_task_id: None,
+                created_by_agent: AgentType::EngineeringManager.as_str().to_string(),
+                created_at: 0,
+                updated_at: 0,
+                created_by_task_id: parent.id,
+            });
+        }
+
+        let parent_id = parent.id.unwrap_or(0);
+        let split: Vec<(Task, Vec<usize>)> = drafts
+            .iter()
+            .zip(&params.tasks)
+            .map(|(task, shaped)| {
+                let depends_on = shaped.depends_on.iter().map(|&dep| dep - 1).collect();
+                (task.clone(), depends_on)
+            })
+            .collect();
+        let ids = self.task_storage.split_task(parent_id, split).await?;
+        let mut created: Vec<Task> = Vec::with_capacity(ids.len());
+        for ((mut task, shaped), id) in drafts.into_iter().zip(&params.tasks).zip(ids) {
+            task.id = Some(id);
+            task.depends_on_task_id = shaped
+                .depends_on
+                .first()
+                .and_then(|&dep| created[dep - 1].id);
+            created.push(task);
+        }
+
+        let mut comment = format!("{}\n\nSplit into:", params.summary);
+        for task in &created {
+            comment.push_str(&format!(
+                "\n- #{} {} ({})",
+                task.id.unwrap_or(0),
+                task.title,
+                task.assigned_to_agent
+            ));
+        }
+        self.comment(parent_id, comment).await?;
+        Ok(Ok(created))
+    }
+
+    async fn parse<T: serde::de::DeserializeOwned>(
+        &self,
+        session_id: i64,
+        assistant_text: &str,
+        tool_call: &ToolCall,
+        nudges: &mut u32,
+    ) -> Result<Option<T>, AgentError> {
+        parse_or_record::<T>(
+            self.storage.as_ref(),
+            session_id,
+            AgentType::EngineeringManager,
+            assistant_text,
+            tool_call,
+            nudges,
+            MAX_NUDGES,
+        )
+        .await
+    }
+
+    /// Answer a well-formed call that cannot be carried out with `problem`,
+    /// spending a nudge.
+    async fn reject(
+        &self,
+        session_id: i64,
+        assistant_text: &str,
+        tool_call: &ToolCall,
+        problem: String,
+        nudges: &mut u32,
+    ) -> Result<(), AgentError> {
+        *nudges += 1;
+        if *nudges >= MAX_NUDGES {
+            return Err(AgentError::NudgesExhausted(format!(
+                "engineering_manager {} rejected: {}",
+                tool_call.name(),
+                problem
+            )));
+        }
+        self.record_call(
+            session_id,
+            assistant_text,
+            tool_call,
+            format!("Invalid {}: {}", tool_call.name(), problem),
+        )
+        .await
+    }
+
+    /// Store the turn: the assistant's text, the tool call and its result.
+    async fn record_call(
+        &self,
+        session_id: i64,
+        assistant_text: &str,
+        tool_call: &ToolCall,
+        result: String,
+    ) -> Result<(), AgentError> {
+        let call_id = tool_call.id().to_string();
+        let tool_name = tool_call.name().to_string();
+        let mut turn = Vec::new();
+        if !assistant_text.is_empty() {
+            turn.push(self.text_row(session_id, assistant_text.to_string()));
+        }
+        turn.push(ChatMessage {
+            id: None,
+            session_id,
+            role: "assistant".to_string(),
+            agent_type: Some(AgentType::EngineeringManager.as_str().to_string()),
+            content: serde_json::to_string(tool_call.arguments())?,
+            tool_call_id: Some(call_id.clone()),
+            tool_name: Some(tool_name.clone()),
+            turn_id: None,
+            created_at: 0,
+        });
+        turn.push(ChatMessage {
+            id: None,
+            session_id,
+            role: "tool".to_string(),
+            agent_type: None,
+            content: result,
+            tool_call_id: Some(call_id),
+            tool_name: Some(tool_name),
+            turn_id: None,
+            created_at: 0,
+        });
+        self.storage.create_turn(turn).await?;
+        Ok(())
+    }
+
+    fn text_row(&self, session_id: i64, content: String) -> ChatMessage {
+        ChatMessage {
+            id: None,
+            session_id,
+            role: "assistant".to_string(),
+            agent_type: Some(AgentType::EngineeringManager.as_str().to_string()),
+            content,
+            tool_call_id: None,
+            tool_name: None,
+            turn_id: None,
+            created_at: 0,
+        }
+    }
+
+    async fn comment(&self, task_id: i64, content: String) -> Result<(), AgentError> {
+        self.comment_storage
+            .add_task_comment(
+                task_id,
+                "agent",
+                None,
+                Some(AgentType::EngineeringManager),
+                content,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+fn criteria_section(criteria: &[String]) -> String {
+    let mut out = String::from("Acceptance criteria:");
+    for criterion in criteria {
+        out.push_str(&format!("\n- {}", criterion));
+    }
+    out
+}
+
+fn format_stack_notes(notes: &[StackNoteRow]) -> String {
+    if notes.is_empty() {
+        return "(no stack notes yet)".to_string();
+    }
+    notes
+        .iter()
+        .map(|n| match &n.file_path {
+            Some(fp) => format!("- [{}] {} ({})", n.tag, n.note, fp),
+            None => format!("- [{}] {}", n.tag, n.note),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn format_project_notes(notes: &[ProjectNoteRow]) -> String {
+    if notes.is_empty() {
+        return "(no requirements recorded)".to_string();
+    }
+    notes
+        .iter()
+        .map(|n| format!("- [{}] {}", n.topic, n.note))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn format_comments(comments: &[TaskCommentRow]) -> String {
+    if comments.is_empty() {
+        return "(none)".to_string();
+    }
+    comments
+        .iter()
+        .map(|c| {
+            let author = c.agent_type.as_deref().unwrap_or(&c.author_type);
+            format!("- {}: {}", author, c.content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use llm_sdk::{error::LlmError, types::CompletionResponse};
+
+    use super::*;
+    use crate::engineering_manager::tools::ShapedTask;
+    use crate::storage::{sqlite::*, test_db};
+
+    /// `split` makes no LLM calls.
+    struct NoClient;
+
+    #[async_trait]
+    impl LlmClient for NoClient {
+        async fn complete(&self, _: CompletionRequest) -> Result<CompletionResponse, LlmError> {
+            Err(LlmError::internal("unexpected LLM call"))
+        }
+
+        fn provider_name(&self) -> &str {
+            "stub"
+        }
+
+        fn model_name(&self) -> &str {
+            "stub"
+        }
+    }
+
+    fn agent(path: &std::path::Path) -> EngineeringManagerAgent {
+        let db_path = path.to_str().unwrap();
+        EngineeringManagerAgent::new(
+            Arc::new(NoClient),
+            Arc::new(SqliteAgentStorage::open(db_path).unwrap()),
+            Arc::new(SqliteTaskStorage::open(db_path).unwrap()),
+            Arc::new(SqliteCommentStorage::open(db_path).unwrap()),
+            Arc::new(SqliteStackNoteStorage::open(db_path).unwrap()),
+            Arc::new(SqliteProjectNoteStorage::open(db_path).unwrap()),
+            "stub",
+            1,
+        )
+    }
+
+    fn shaped(title: &str, depends_on: Vec<usize>) -> ShapedTask {
+        ShapedTask {
+            title: title.to_string(),
+            description: format!("Build {}", title),
+            assigned_to_agent: "backend_engineer".to_string(),
+            acceptance_criteria: vec!["It works".to_string()],
+            depends_on,
+            rust_engineer: None,
+        }
+    }
+
+    fn params(tasks: Vec<ShapedTask>) -> SplitTaskParams {
+        SplitTaskParams {
+            summary: "Split by layer.".to_string(),
+            tasks,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_split_creates_tasks_with_dependencies_and_closes_parent() {
+        let path = test_db::file_with_tasks("em-split", 1);
+        let agent = agent(&path);
+        let parent = agent.task_storage.get_task(1).await.unwrap().unwrap();
+
+        let created = agent
+            .split(
+                &parent,
+                &params(vec![
+                    shaped("schema", vec![]),
+                    shaped("api", vec![1]),
+                    shaped("ui", vec![1, 2]),
+                ]),
+            )
+            .await
+            .unwrap()
+            .expect("valid split");
+
+        let ids: Vec<i64> = created.iter().map(|t| t.id.unwrap()).collect();
+        assert_eq!(created[1].depends_on_task_id, Some(ids[0]));
+        assert_eq!(created[2].depends_on_task_id, Some(ids[0]));
+        let storage = &agent.task_storage;
+        assert!(storage
+            .list_task_dependencies(ids[0])
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            storage.list_task_dependencies(ids[1]).await.unwrap(),
+            vec![ids[0]]
+        );
+        assert_eq!(
+            storage.list_task_dependencies(ids[2]).await.unwrap(),
+            vec![ids[0], ids[1]]
+        );
+        let children = storage.list_tasks_created_by(1).await.unwrap();
+        assert_eq!(children.len(), 3);
+        assert!(children.iter().all(|t| t.status == TaskStatus::Ready));
+        let parent = storage.get_task(1).await.unwrap().unwrap();
+        assert_eq!(parent.status, TaskStatus::Done);
+        let comments = agent.comment_storage.get_task_comments(1).await.unwrap();
+        assert!(comments[0]
+            .content
+            .starts_with("Split by layer.\n\nSplit into:"));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_split_rejects_dependencies_on_later_tasks() {
+        let path = test_db::file_with_tasks("em-split-invalid", 1);
+        let agent = agent(&path);
+        let parent = agent.task_storage.get_task(1).await.unwrap().unwrap();
+
+        let first = agent
+            .split(&parent, &params(vec![shaped("schema", vec![1])]))
+            .await
+            .unwrap();
+        let later = agent
+            .split(
+                &parent,
+                &params(vec![shaped("schema", vec![]), shaped("api", vec![2])]),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            first.unwrap_err(),
+            "tasks[0].depends_on must be empty: the first task has no earlier tasks."
+        );
+        assert_eq!(
+            later.unwrap_err(),
+            "tasks[1].depends_on must list positions (1 to 1) of earlier tasks."
+        );
+        let storage = &agent.task_storage;
+        assert!(storage.list_tasks_created_by(1).await.unwrap().is_empty());
+        assert_ne!(
+            storage.get_task(1).await.unwrap().unwrap().status,
+            TaskStatus::Done
+        );
+        std::fs::remove_file(&path).ok();
+    }
+}
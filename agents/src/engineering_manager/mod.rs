@@ -0,0 +1,8 @@
+pub mod agent;
+pub mod prompts;
+pub mod tools;
+
+pub use agent::{shaping_request, EngineeringManagerAgent, EngineeringManagerResponse};
+pub use tools::{
+    BlockTaskParams, CommentOnTaskParams, MarkTaskReadyParams, ShapedTask, SplitTaskParams,
+};
@@ -0,0 +1,23 @@
+use crate::{
+    error::AgentError,
+    prompt_library::{Prompt, PromptLibrary},
+};
+
+/// System prompt of the Engineering Manager (`prompts/engineering_manager.md`),
+/// with the project's stack notes, requirement notes and the comments on the
+/// task being shaped.
+pub fn system_prompt(
+    prompts: &PromptLibrary,
+    stack_notes: &str,
+    project_notes: &str,
+    task_comments: &str,
+) -> Result<Prompt, AgentError> {
+    prompts.render(
+        "engineering_manager",
+        &[
+            ("stack_notes", stack_notes),
+            ("project_notes", project_notes),
+            ("task_comments", task_comments),
+        ],
+    )
+}
@@ -0,0 +1,57 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::rust_engineer::RustEngineerTaskParams;
+
+/// Replace the task with concrete tasks for the specialist agents.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SplitTaskParams {
+    /// One or two sentences on how the work was divided, for the task comments.
+    pub summary: String,
+    /// The tasks, in the order they should be done.
+    pub tasks: Vec<ShapedTask>,
+}
+
+/// One task the shaped task is split into.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ShapedTask {
+    /// Short title (≤ 100 chars).
+    pub title: String,
+    /// What the agent should produce: the files, tables, endpoints or screens involved.
+    pub description: String,
+    /// Must be one of: "db_engineer", "backend_engineer", "frontend_engineer",
+    /// "ui_designer", "rust_engineer".
+    pub assigned_to_agent: String,
+    /// Checks that tell whether the task is done, one per item.
+    pub acceptance_criteria: Vec<String>,
+    /// 1-based positions in `tasks` of the earlier tasks that must be done
+    /// first (empty when none).
+    #[serde(default)]
+    pub depends_on: Vec<usize>,
+    /// Required when `assigned_to_agent` is "rust_engineer"; null otherwise.
+    #[serde(default)]
+    pub rust_engineer: Option<RustEngineerTaskParams>,
+}
+
+/// Hand the task to its assigned agent as it is.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MarkTaskReadyParams {
+    /// Checks that tell whether the task is done, one per item.
+    pub acceptance_criteria: Vec<String>,
+    /// Technical guidance for the assigned agent (optional).
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+/// Add a comment to the task without finishing the shaping.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CommentOnTaskParams {
+    pub comment: String,
+}
+
+/// Stop shaping: the task cannot be worked on as described.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BlockTaskParams {
+    /// What is missing or contradictory, and what would unblock the task.
+    pub reason: String,
+}
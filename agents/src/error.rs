@@ -1,3 +1,5 @@
+use llm_sdk::error::LlmError;
+use rusqlite::ErrorCode;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -17,6 +19,105 @@ pub enum AgentError {
     #[error("Agent stopped: {0}")]
     Stopped(String),
 
+    /// The run was cancelled or ran out of its time or turn budget.
+    #[error("Run cancelled: {0}")]
+    Cancelled(String),
+
+    /// Tool arguments that could not be repaired into the tool's parameters.
+    #[error("{0}")]
+    ToolArgs(#[from] crate::tool_args::ToolArgsError),
+
+    /// The model kept answering without the tool call the run needs.
+    #[error("{0}")]
+    NudgesExhausted(String),
+
+    /// A task dependency that would make a task wait for itself.
+    #[error("Dependency cycle: {0}")]
+    DependencyCycle(String),
+
+    /// Code a proposal would replace changed after the proposal was made.
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("{0}")]
     Other(String),
 }
+
+impl AgentError {
+    /// Failures that another model might not have: transport and provider
+    /// errors, tool arguments that don't parse, running out of nudges.
+    /// `run_with_fallback` moves to the next model on these.
+    pub fn is_model_failure(&self) -> bool {
+        matches!(
+            self,
+            AgentError::Llm(_) | AgentError::ToolArgs(_) | AgentError::NudgesExhausted(_)
+        )
+    }
+
+    /// Failures that may not recur on a later attempt: network errors, rate
+    /// limits and provider 5xx responses, and a busy or locked database. The
+    /// dispatcher retries runs that fail with these after a backoff.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            AgentError::Llm(LlmError::Network { .. } | LlmError::RateLimit { .. }) => true,
+            AgentError::Llm(LlmError::Api { status, .. }) => *status >= 500 || *status == 429,
+            AgentError::Db(rusqlite::Error::SqliteFailure(e, _)) => {
+                matches!(e.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sqlite_failure(code: i32) -> AgentError {
+        AgentError::Db(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(code),
+            None,
+        ))
+    }
+
+    fn api(status: u16) -> AgentError {
+        AgentError::Llm(LlmError::Api {
+            status,
+            message: "error".to_string(),
+        })
+    }
+
+    #[test]
+    fn test_is_transient_for_busy_or_locked_database_only() {
+        assert!(sqlite_failure(rusqlite::ffi::SQLITE_BUSY).is_transient());
+        assert!(sqlite_failure(rusqlite::ffi::SQLITE_LOCKED).is_transient());
+        assert!(!sqlite_failure(rusqlite::ffi::SQLITE_CONSTRAINT).is_transient());
+        assert!(!AgentError::Db(rusqlite::Error::QueryReturnedNoRows).is_transient());
+    }
+
+    #[test]
+    fn test_is_transient_for_rate_limits_and_server_errors_only() {
+        assert!(api(500).is_transient());
+        assert!(api(503).is_transient());
+        assert!(api(429).is_transient());
+        assert!(!api(400).is_transient());
+        assert!(!api(404).is_transient());
+        assert!(AgentError::Llm(LlmError::RateLimit {
+            message: "slow down".to_string(),
+            retry_after: None,
+        })
+        .is_transient());
+        assert!(!AgentError::Llm(LlmError::Authentication {
+            message: "bad key".to_string(),
+        })
+        .is_transient());
+        assert!(!AgentError::Llm(LlmError::internal("bug")).is_transient());
+    }
+
+    #[test]
+    fn test_is_transient_rejects_non_provider_errors() {
+        assert!(!AgentError::Config("missing key".to_string()).is_transient());
+        assert!(!AgentError::Other("boom".to_string()).is_transient());
+        assert!(!AgentError::NudgesExhausted("no tool call".to_string()).is_transient());
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Node, Parser};
+
+/// A deterministic property an eval case's output must have.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Check {
+    /// The code parses as Rust with no syntax errors.
+    Parses,
+    /// No markdown fences or `<think>` tags leaked into the code.
+    Clean,
+    /// No `use` lines (modes that prepend their own imports).
+    NoImports,
+    /// The code contains `text` verbatim.
+    Contains { text: String },
+    /// The `diesel::table!` columns or `pub` struct fields are exactly `names`.
+    Columns { names: Vec<String> },
+    /// The project still builds once the code is written into it. Needs a
+    /// `CompileChecker`; skipped without one.
+    Compiles,
+    /// Same token tree as the golden file, ignoring whitespace and comments.
+    /// Relative paths are resolved against the dataset file's directory.
+    GoldenAst { file: PathBuf },
+}
+
+impl Check {
+    pub fn label(&self) -> String {
+        match self {
+            Check::Parses => "parses".to_string(),
+            Check::Clean => "clean".to_string(),
+            Check::NoImports => "no_imports".to_string(),
+            Check::Contains { text } => format!("contains `{}`", text),
+            Check::Columns { .. } => "columns".to_string(),
+            Check::Compiles => "compiles".to_string(),
+            Check::GoldenAst { file } => format!("golden_ast {}", file.display()),
+        }
+    }
+
+    /// Score `code` against every check that needs nothing but the code.
+    /// `Compiles` is run by the runner and reported as skipped here.
+    pub fn evaluate(&self, code: &str) -> CheckResult {
+        let outcome = match self {
+            Check::Parses => check_parses(code),
+            Check::Clean => check_clean(code),
+            Check::NoImports => check_no_imports(code),
+            Check::Contains { text } => {
+                if code.contains(text.as_str()) {
+                    Ok(())
+                } else {
+                    Err(format!("missing `{}`", text))
+                }
+            }
+            Check::Columns { names } => check_columns(code, names),
+            Check::Compiles => return CheckResult::skipped(self, "no compile checker"),
+            Check::GoldenAst { file } => check_golden_ast(code, file),
+        };
+        CheckResult::from_outcome(self, outcome)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub check: String,
+    pub passed: bool,
+    /// Not run (e.g. no compile checker configured); does not count either way.
+    pub skipped: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl CheckResult {
+    pub fn from_outcome(check: &Check, outcome: Result<(), String>) -> Self {
+        Self {
+            check: check.label(),
+            passed: outcome.is_ok(),
+            skipped: false,
+            detail: outcome.err(),
+        }
+    }
+
+    pub fn skipped(check: &Check, reason: &str) -> Self {
+        Self {
+            check: check.label(),
+            passed: false,
+            skipped: true,
+            detail: Some(reason.to_string()),
+        }
+    }
+}
+
+/// Decides whether a project builds after an eval case wrote its code into it.
+/// Pluggable so a dataset can be scored with `cargo check`, `cargo test` or a
+/// faster custom script.
+pub trait CompileChecker: Send + Sync {
+    fn check(&self, project_path: &Path) -> Result<(), String>;
+}
+
+/// Runs a command in the project directory; a non-zero exit fails the check.
+pub struct CommandChecker {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandChecker {
+    /// Parse a whitespace-separated command line, e.g. `cargo check --quiet`.
+    pub fn parse(command_line: &str) -> Option<Self> {
+        let mut parts = command_line.split_whitespace().map(str::to_string);
+        Some(Self {
+            program: parts.next()?,
+            args: parts.collect(),
+        })
+    }
+
+    pub fn cargo_check() -> Self {
+        Self {
+            program: "cargo".to_string(),
+            args: vec!["check".to_string(), "--quiet".to_string()],
+        }
+    }
+}
+
+impl CompileChecker for CommandChecker {
+    fn check(&self, project_path: &Path) -> Result<(), String> {
+        let output = Command::new(&self.program)
+            .args(&self.args)
+            .current_dir(project_path)
+            .output()
+            .map_err(|e| format!("failed to run {}: {}", self.program, e))?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let lines: Vec<&str> = stderr.lines().collect();
+        let tail = &lines[lines.len().saturating_sub(20)..];
+        Err(tail.join("\n"))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Static checks
+// ---------------------------------------------------------------------------
+
+fn parse_rust(code: &str) -> Result<tree_sitter::Tree, String> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_rust::LANGUAGE.into())
+        .map_err(|e| format!("failed to load Rust grammar: {}", e))?;
+    parser
+        .parse(code, None)
+        .ok_or_else(|| "parser returned no tree".to_string())
+}
+
+fn check_parses(code: &str) -> Result<(), String> {
+    if code.trim().is_empty() {
+        return Err("empty code".to_string());
+    }
+    let tree = parse_rust(code)?;
+    if tree.root_node().has_error() {
+        return Err("syntax error".to_string());
+    }
+    Ok(())
+}
+
+fn check_clean(code: &str) -> Result<(), String> {
+    if code.contains("```") {
+        return Err("markdown fence".to_string());
+    }
+    if code.contains("<think>") || code.contains("</think>") {
+        return Err("reasoning tags".to_string());
+    }
+    Ok(())
+}
+
+fn check_no_imports(code: &str) -> Result<(), String> {
+    match code
+        .lines()
+        .find(|line| line.trim_start().starts_with("use "))
+    {
+        Some(line) => Err(format!("import: {}", line.trim())),
+        None => Ok(()),
+    }
+}
+
+/// Column names from `name -> Type` lines of a `diesel::table!` block, or
+/// field names from `pub name: Type` lines of a struct.
+pub fn extract_columns(code: &str) -> Vec<String> {
+    let table_column = Regex::new(r"^\s*(\w+)\s*->").unwrap();
+    let struct_field = Regex::new(r"^\s*pub\s+(\w+)\s*:").unwrap();
+    code.lines()
+        .filter_map(|line| {
+            table_column
+                .captures(line)
+                .or_else(|| struct_field.captures(line))
+                .map(|c| c[1].to_string())
+        })
+        .collect()
+}
+
+fn check_columns(code: &str, expected: &[String]) -> Result<(), String> {
+    let found: BTreeSet<String> = extract_columns(code).into_iter().collect();
+    let expected: BTreeSet<String> = expected.iter().cloned().collect();
+    let missing: Vec<&String> = expected.difference(&found).collect();
+    let extra: Vec<&String> = found.difference(&expected).collect();
+    if missing.is_empty() && extra.is_empty() {
+        return Ok(());
+    }
+    Err(format!("missing {:?}, unexpected {:?}", missing, extra))
+}
+
+/// Leaf tokens of the syntax tree, comments dropped. Two sources with the same
+/// token sequence have the same tree, whatever their formatting.
+pub fn ast_tokens(code: &str) -> Result<Vec<String>, String> {
+    let tree = parse_rust(code)?;
+    let mut tokens = Vec::new();
+    collect_leaves(tree.root_node(), code.as_bytes(), &mut tokens);
+    Ok(tokens)
+}
+
+fn collect_leaves(node: Node, source: &[u8], out: &mut Vec<String>) {
+    if node.kind() == "line_comment" || node.kind() == "block_comment" {
+        return;
+    }
+    if node.child_count() == 0 {
+        if let Ok(text) = node.utf8_text(source) {
+            out.push(text.to_string());
+        }
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_leaves(child, source, out);
+    }
+}
+
+fn check_golden_ast(code: &str, golden_file: &Path) -> Result<(), String> {
+    let golden = std::fs::read_to_string(golden_file)
+        .map_err(|e| format!("read {}: {}", golden_file.display(), e))?;
+    let expected = ast_tokens(&golden)?;
+    let actual = ast_tokens(code)?;
+    if expected == actual {
+        return Ok(());
+    }
+    let at = expected
+        .iter()
+        .zip(&actual)
+        .position(|(a, b)| a != b)
+        .unwrap_or(expected.len().min(actual.len()));
+    Err(format!(
+        "differs at token {}: expected {:?}, got {:?}",
+        at,
+        expected.get(at),
+        actual.get(at)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "diesel::table! {\n    users (id) {\n        id -> BigInt,\n        email -> Text,\n    }\n}";
+
+    #[test]
+    fn test_parses_rejects_syntax_errors() {
+        assert!(check_parses(TABLE).is_ok());
+        assert!(check_parses("pub struct User { pub id: i64,").is_err());
+        assert!(check_parses("   ").is_err());
+    }
+
+    #[test]
+    fn test_extract_columns_from_table_and_struct() {
+        assert_eq!(extract_columns(TABLE), vec!["id", "email"]);
+        let model = "pub struct User {\n    pub id: i64,\n    pub email: String,\n}";
+        assert_eq!(extract_columns(model), vec!["id", "email"]);
+    }
+
+    #[test]
+    fn test_columns_reports_missing_and_extra() {
+        let err = check_columns(TABLE, &["id".to_string(), "name".to_string()]).unwrap_err();
+        assert!(
+            err.contains("\"name\"") && err.contains("\"email\""),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_ast_tokens_ignore_formatting_and_comments() {
+        let reformatted = "diesel::table! { users (id) { // key\n id -> BigInt, email -> Text, } }";
+        assert_eq!(ast_tokens(TABLE).unwrap(), ast_tokens(reformatted).unwrap());
+        let changed = TABLE.replace("Text", "Integer");
+        assert_ne!(ast_tokens(TABLE).unwrap(), ast_tokens(&changed).unwrap());
+    }
+}
@@ -0,0 +1,215 @@
+//! Scores rust_engineer modes across models on a fixed dataset, so the choice
+//! of model (and quantization) per mode rests on pass rates rather than on a
+//! live test happening to write something.
+//!
+//! A dataset is a JSON file of cases; each case is a `RustEngineerTaskParams`
+//! plus the deterministic checks its output must pass:
+//!
+//! ```json
+//! { "cases": [ {
+//!     "id": "diesel_schema_user_contacts",
+//!     "params": { "mode": "diesel_schema", "prompt": "…" },
+//!     "checks": [ { "kind": "parses" }, { "kind": "columns", "names": ["id", "email"] } ]
+//! } ] }
+//! ```
+
+mod checks;
+mod report;
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::Instant,
+};
+
+use serde::Deserialize;
+
+pub use checks::{ast_tokens, extract_columns, Check, CheckResult, CommandChecker, CompileChecker};
+pub use report::{CaseResult, EvalReport, ModeSummary};
+
+use crate::{
+    error::AgentError,
+    prompt_library::PromptLibrary,
+    rust_engineer::{RustEngineerAgent, RustEngineerTaskParams},
+};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EvalDataset {
+    pub cases: Vec<EvalCase>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EvalCase {
+    pub id: String,
+    pub params: RustEngineerTaskParams,
+    #[serde(default)]
+    pub checks: Vec<Check>,
+}
+
+impl EvalDataset {
+    /// Load a dataset, resolving golden file paths against its directory.
+    pub fn load(path: &Path) -> Result<Self, AgentError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| AgentError::Other(format!("read {}: {}", path.display(), e)))?;
+        let mut dataset: EvalDataset = serde_json::from_str(&contents)?;
+        let base = path.parent().unwrap_or(Path::new("."));
+        for case in &mut dataset.cases {
+            for check in &mut case.checks {
+                if let Check::GoldenAst { file } = check {
+                    if file.is_relative() {
+                        *file = base.join(&*file);
+                    }
+                }
+            }
+        }
+        Ok(dataset)
+    }
+}
+
+/// Runs every case of a dataset against every model.
+///
+/// Cases with a `compiles` check run with `apply`, so the agent writes into
+/// `project_path`; the project is reset with `git checkout` / `git clean`
+/// afterwards. Point it at a throwaway clone of the template project.
+pub struct EvalRunner {
+    base_url: Option<String>,
+    project_path: PathBuf,
+    compile_checker: Option<Box<dyn CompileChecker>>,
+    prompts: PromptLibrary,
+}
+
+impl EvalRunner {
+    pub fn new(base_url: Option<String>, project_path: impl Into<PathBuf>) -> Self {
+        Self {
+            base_url,
+            project_path: project_path.into(),
+            compile_checker: None,
+            prompts: PromptLibrary::embedded(),
+        }
+    }
+
+    pub fn with_compile_checker(mut self, checker: Box<dyn CompileChecker>) -> Self {
+        self.compile_checker = Some(checker);
+        self
+    }
+
+    /// Run with these prompt templates, e.g. a directory of candidate
+    /// overrides to compare against the embedded ones.
+    pub fn with_prompts(mut self, prompts: PromptLibrary) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    pub async fn run(&self, dataset: &EvalDataset, models: &[String]) -> EvalReport {
+        let mut results = Vec::new();
+        for model in models {
+            for case in &dataset.cases {
+                log::info!("[Eval] model={} case={}", model, case.id);
+                results.push(self.run_case(model, case).await);
+            }
+        }
+        EvalReport::from_results(results)
+    }
+
+    async fn run_case(&self, model: &str, case: &EvalCase) -> CaseResult {
+        let mut result = CaseResult {
+            case_id: case.id.clone(),
+            model: model.to_string(),
+            mode: case.params.mode.clone(),
+            passed: false,
+            latency_ms: 0,
+            usage: Default::default(),
+            template: None,
+            error: None,
+            checks: Vec::new(),
+        };
+
+        let agent =
+            match RustEngineerAgent::new(model, self.base_url.clone(), self.project_path.clone()) {
+                Ok(a) => a.with_prompts(self.prompts.clone()),
+                Err(e) => {
+                    result.error = Some(e.to_string());
+                    return result;
+                }
+            };
+
+        let apply = self.compile_checker.is_some()
+            && case.checks.iter().any(|c| matches!(c, Check::Compiles));
+        let started = Instant::now();
+        let output = agent.run_task(&case.params, apply).await;
+        result.latency_ms = started.elapsed().as_millis() as u64;
+        result.usage = agent.usage();
+
+        match output {
+            Ok(output) => {
+                result.template = output.template;
+                let code = output.code.unwrap_or_default();
+                for check in &case.checks {
+                    let check_result = match (check, &self.compile_checker) {
+                        (Check::Compiles, Some(_)) if output.file_path.is_none() => {
+                            CheckResult::skipped(check, "mode wrote no file")
+                        }
+                        (Check::Compiles, Some(checker)) => {
+                            CheckResult::from_outcome(check, checker.check(&self.project_path))
+                        }
+                        _ => check.evaluate(&code),
+                    };
+                    result.checks.push(check_result);
+                }
+                result.passed =
+                    !code.trim().is_empty() && result.checks.iter().all(|c| c.passed || c.skipped);
+            }
+            Err(e) => result.error = Some(e.to_string()),
+        }
+
+        if apply {
+            reset_project(&self.project_path);
+        }
+        result
+    }
+}
+
+/// Discard everything a case wrote so the next case starts from the template.
+fn reset_project(project_path: &Path) {
+    for args in [&["checkout", "--", "."][..], &["clean", "-fdq"][..]] {
+        match Command::new("git")
+            .args(args)
+            .current_dir(project_path)
+            .status()
+        {
+            Ok(status) if status.success() => {}
+            Ok(status) => log::warn!("[Eval] git {:?} exited with {}", args, status),
+            Err(e) => log::warn!("[Eval] git {:?} failed: {}", args, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dataset_covers_every_mode() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/eval/rust_engineer.json");
+        let dataset = EvalDataset::load(&path).unwrap();
+        for mode in [
+            "diesel_schema",
+            "diesel_model_struct",
+            "diesel_model_fn",
+            "diesel_model_test",
+            "edit_impl_fn",
+            "actix_handler",
+            "middleware",
+        ] {
+            assert!(
+                dataset.cases.iter().any(|c| c.params.mode == mode),
+                "no eval case for {mode}"
+            );
+        }
+        for check in dataset.cases.iter().flat_map(|c| &c.checks) {
+            if let Check::GoldenAst { file } = check {
+                assert!(file.is_file(), "missing golden file {}", file.display());
+            }
+        }
+    }
+}
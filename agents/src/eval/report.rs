@@ -0,0 +1,148 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::checks::CheckResult;
+use crate::{error::AgentError, prompt_library::PromptRef, rust_engineer::TokenUsage};
+
+/// Outcome of one case run against one model.
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseResult {
+    pub case_id: String,
+    pub model: String,
+    pub mode: String,
+    /// Every check that ran passed, and the agent returned code.
+    pub passed: bool,
+    pub latency_ms: u64,
+    pub usage: TokenUsage,
+    /// System-prompt template the mode ran with; `None` for modes without one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<PromptRef>,
+    /// The agent call itself failed; no checks were run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub checks: Vec<CheckResult>,
+}
+
+/// Aggregate for one (model, mode, prompt version) group.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModeSummary {
+    pub model: String,
+    pub mode: String,
+    /// `id@version` of the system-prompt template, when the mode has one.
+    pub prompt: Option<String>,
+    pub cases: usize,
+    pub passed: usize,
+    pub pass_rate: f64,
+    pub avg_latency_ms: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EvalReport {
+    pub summary: Vec<ModeSummary>,
+    pub results: Vec<CaseResult>,
+}
+
+impl EvalReport {
+    /// Summaries keep the order in which (model, mode, prompt) groups first
+    /// appear, so runs of two prompt versions are compared side by side.
+    pub fn from_results(results: Vec<CaseResult>) -> Self {
+        let mut summary: Vec<ModeSummary> = Vec::new();
+        for r in &results {
+            let prompt = r.template.as_ref().map(|t| t.to_string());
+            let idx = match summary
+                .iter()
+                .position(|s| s.model == r.model && s.mode == r.mode && s.prompt == prompt)
+            {
+                Some(idx) => idx,
+                None => {
+                    summary.push(ModeSummary {
+                        model: r.model.clone(),
+                        mode: r.mode.clone(),
+                        prompt,
+                        cases: 0,
+                        passed: 0,
+                        pass_rate: 0.0,
+                        avg_latency_ms: 0,
+                        input_tokens: 0,
+                        output_tokens: 0,
+                    });
+                    summary.len() - 1
+                }
+            };
+            let s = &mut summary[idx];
+            s.cases += 1;
+            s.passed += usize::from(r.passed);
+            // Running total for now; divided below.
+            s.avg_latency_ms += r.latency_ms;
+            s.input_tokens += r.usage.input_tokens;
+            s.output_tokens += r.usage.output_tokens;
+        }
+        for s in &mut summary {
+            s.pass_rate = s.passed as f64 / s.cases as f64;
+            s.avg_latency_ms /= s.cases as u64;
+        }
+        Self { summary, results }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::from("# Rust engineer eval\n\n");
+        md.push_str(
+            "| Model | Mode | Prompt | Passed | Pass rate | Avg latency (ms) | Input tokens | Output tokens |\n",
+        );
+        md.push_str("|---|---|---|---|---|---|---|---|\n");
+        for s in &self.summary {
+            md.push_str(&format!(
+                "| {} | {} | {} | {}/{} | {:.0}% | {} | {} | {} |\n",
+                s.model,
+                s.mode,
+                s.prompt.as_deref().unwrap_or("-"),
+                s.passed,
+                s.cases,
+                s.pass_rate * 100.0,
+                s.avg_latency_ms,
+                s.input_tokens,
+                s.output_tokens
+            ));
+        }
+
+        let failures: Vec<&CaseResult> = self.results.iter().filter(|r| !r.passed).collect();
+        if !failures.is_empty() {
+            md.push_str("\n## Failures\n\n");
+            for r in failures {
+                md.push_str(&format!("- `{}` on `{}`", r.case_id, r.model));
+                if let Some(err) = &r.error {
+                    md.push_str(&format!(": error: {}", first_line(err)));
+                }
+                md.push('\n');
+                for c in r.checks.iter().filter(|c| !c.passed && !c.skipped) {
+                    md.push_str(&format!(
+                        "  - {}: {}\n",
+                        c.check,
+                        first_line(c.detail.as_deref().unwrap_or(""))
+                    ));
+                }
+            }
+        }
+        md
+    }
+
+    /// Write `eval_report.json` and `eval_report.md` into `dir`.
+    pub fn write(&self, dir: &Path) -> Result<(), AgentError> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| AgentError::Other(format!("create {}: {}", dir.display(), e)))?;
+        let json_path = dir.join("eval_report.json");
+        std::fs::write(&json_path, serde_json::to_string_pretty(self)?)
+            .map_err(|e| AgentError::Other(format!("write {}: {}", json_path.display(), e)))?;
+        let md_path = dir.join("eval_report.md");
+        std::fs::write(&md_path, self.to_markdown())
+            .map_err(|e| AgentError::Other(format!("write {}: {}", md_path.display(), e)))?;
+        Ok(())
+    }
+}
+
+fn first_line(s: &str) -> &str {
+    s.lines().next().unwrap_or("")
+}
@@ -8,8 +8,14 @@ use llm_sdk::{
 };
 
 use crate::{
+    context_window::ContextWindow,
     error::AgentError,
-    storage::{AgentStorage, ChatMessage, ContextStorage, TaskStatus, TaskStorage},
+    prompt_library::PromptLibrary,
+    run_control::RunControl,
+    storage::{
+        AgentStorage, AgentType, ChatMessage, ContextStorage, LlmUsage, TaskStatus, TaskStorage,
+    },
+    tool_args::parse_or_record,
     utils::{
         file_ops,
         tools::{CommentaryParams, ListFilesParams, ReadFileParams, UpdateTaskStatusParams},
@@ -33,6 +39,11 @@ pub struct FrontendEngineerAgent {
     model: String,
     project_id: i64,
     project_path: PathBuf,
+    /// Summarizes older turns when the history outgrows the model's budget.
+    /// `None` sends the full history.
+    context: Option<ContextWindow>,
+    prompts: PromptLibrary,
+    control: RunControl,
 }
 
 impl FrontendEngineerAgent {
@@ -53,9 +64,30 @@ impl FrontendEngineerAgent {
             model: model.into(),
             project_id,
             project_path: project_path.into(),
+            context: None,
+            prompts: PromptLibrary::embedded(),
+            control: RunControl::default(),
         }
     }
 
+    pub fn with_context_window(mut self, context: ContextWindow) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Resolve the system prompt through `prompts`, e.g. to pick up the
+    /// project's overrides. Embedded templates are used otherwise.
+    pub fn with_prompts(mut self, prompts: PromptLibrary) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    /// Let the caller cancel the run or bound its time and turns.
+    pub fn with_run_control(mut self, control: RunControl) -> Self {
+        self.control = control;
+        self
+    }
+
     pub async fn run_for_task(
         &self,
         session_id: i64,
@@ -120,14 +152,30 @@ impl FrontendEngineerAgent {
             created_at: 0,
         };
 
-        let system_prompt = super::prompts::system_prompt();
+        let system = super::prompts::system_prompt(&self.prompts)?;
+        self.storage
+            .set_session_prompt(session_id, &system.reference)
+            .await?;
 
         let mut nudges: u32 = 0;
         let mut last_tool_signature: Option<String> = None;
         let mut same_tool_call_streak: u32 = 0;
 
-        loop {
-            let history = self.storage.get_messages(session_id).await?;
+        'turns: loop {
+            let history = match &self.context {
+                Some(context) => {
+                    context
+                        .session_history(
+                            self.storage.as_ref(),
+                            self.project_id,
+                            AgentType::FrontendEngineer,
+                            task_id,
+                            session_id,
+                        )
+                        .await?
+                }
+                None => self.storage.get_messages(session_id).await?,
+            };
             let llm_messages: Vec<Message> = history
                 .into_iter()
                 .map(|m| {
@@ -149,7 +197,7 @@ impl FrontendEngineerAgent {
                 messages: llm_messages,
                 max_tokens: 8192,
                 model: self.model.clone(),
-                system: Some(system_prompt.clone()),
+                system: Some(system.text.clone()),
                 temperature: Some(0.2),
                 top_p: None,
                 stop_sequences: None,
@@ -164,7 +212,8 @@ impl FrontendEngineerAgent {
                 response_format: None,
             };
 
-            let response = match self.llm_client.complete(request).await {
+            self.control.next_turn()?;
+            let response = match self.control.run(self.llm_client.complete(request)).await? {
                 Ok(resp) => resp,
                 Err(llm_err) => {
                     let err_str = llm_err.to_string();
@@ -198,6 +247,21 @@ impl FrontendEngineerAgent {
                 }
             };
 
+            self.storage
+                .record_usage(LlmUsage {
+                    task_id: Some(task_id),
+                    session_id: Some(session_id),
+                    prompt: Some(system.reference.clone()),
+                    ..LlmUsage::from_response(
+                        self.project_id,
+                        AgentType::FrontendEngineer,
+                        self.llm_client.as_ref(),
+                        &self.model,
+                        &response,
+                    )
+                })
+                .await?;
+
             let assistant_text = response
                 .content
                 .iter()
@@ -210,6 +274,7 @@ impl FrontendEngineerAgent {
 
             if let Some(tool_calls) = response.tool_calls {
                 for tool_call in tool_calls {
+                    self.control.check()?;
                     let tool_name = tool_call.name();
                     let call_id = tool_call.id().to_string();
 
@@ -235,8 +300,19 @@ impl FrontendEngineerAgent {
                                     "stopped due to repeated identical tool call".to_string(),
                                 ));
                             }
-                            let params: ListFilesParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                            let Some(params) = parse_or_record::<ListFilesParams>(
+                                self.storage.as_ref(),
+                                session_id,
+                                AgentType::FrontendEngineer,
+                                &assistant_text,
+                                &tool_call,
+                                &mut nudges,
+                                MAX_NUDGES,
+                            )
+                            .await?
+                            else {
+                                continue 'turns;
+                            };
                             let result = file_ops::list_files(&self.project_path, &params.path);
                             let mut turn = Vec::new();
                             if !assistant_text.is_empty() {
@@ -288,8 +364,19 @@ impl FrontendEngineerAgent {
                                     "stopped due to repeated identical tool call".to_string(),
                                 ));
                             }
-                            let params: ReadFileParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                            let Some(params) = parse_or_record::<ReadFileParams>(
+                                self.storage.as_ref(),
+                                session_id,
+                                AgentType::FrontendEngineer,
+                                &assistant_text,
+                                &tool_call,
+                                &mut nudges,
+                                MAX_NUDGES,
+                            )
+                            .await?
+                            else {
+                                continue 'turns;
+                            };
                             let result = file_ops::read_file(&self.project_path, &params.path);
                             let mut turn = Vec::new();
                             if !assistant_text.is_empty() {
@@ -341,8 +428,19 @@ impl FrontendEngineerAgent {
                                     "stopped due to repeated identical tool call".to_string(),
                                 ));
                             }
-                            let params: UpdateTaskStatusParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                            let Some(params) = parse_or_record::<UpdateTaskStatusParams>(
+                                self.storage.as_ref(),
+                                session_id,
+                                AgentType::FrontendEngineer,
+                                &assistant_text,
+                                &tool_call,
+                                &mut nudges,
+                                MAX_NUDGES,
+                            )
+                            .await?
+                            else {
+                                continue 'turns;
+                            };
                             let new_status = TaskStatus::from_str(&params.status);
                             let result_text = match self
                                 .task_storage
@@ -382,8 +480,19 @@ impl FrontendEngineerAgent {
                         }
 
                         "commentary" => {
-                            let params: CommentaryParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                            let Some(params) = parse_or_record::<CommentaryParams>(
+                                self.storage.as_ref(),
+                                session_id,
+                                AgentType::FrontendEngineer,
+                                &assistant_text,
+                                &tool_call,
+                                &mut nudges,
+                                MAX_NUDGES,
+                            )
+                            .await?
+                            else {
+                                continue 'turns;
+                            };
                             let content = params
                                 .text
                                 .unwrap_or_else(|| "Commentary received.".to_string());
@@ -453,7 +562,7 @@ impl FrontendEngineerAgent {
 
             nudges += 1;
             if nudges >= MAX_NUDGES {
-                return Err(AgentError::Other(
+                return Err(AgentError::NudgesExhausted(
                     "frontend_engineer did not produce context after multiple nudges".to_string(),
                 ));
             }
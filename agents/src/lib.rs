@@ -1,29 +1,44 @@
+pub mod approval;
 pub mod backend_engineer;
 pub mod code_extractor;
 pub mod code_writer;
 pub mod codebase_index;
 pub mod config;
+pub mod context_window;
 pub mod db_engineer;
+pub mod engineering_manager;
 pub mod error;
+pub mod eval;
 pub mod frontend_engineer;
+pub mod llm_fixture;
 pub mod nocodo_description;
 pub mod product_owner;
 pub mod project_manager;
+pub mod prompt_library;
+pub mod run_control;
 pub mod rust_engineer;
+pub mod schedule;
 pub mod stack_reviewer;
 pub mod storage;
 pub mod task_policy;
+pub mod text_tools;
+pub mod tool_args;
 pub mod ui_designer;
 pub mod user_input_tool;
 pub mod utils;
+pub mod workflow;
 
 pub use nocodo_description::NOCODO_DESCRIPTION;
 
+pub use approval::ApprovalPolicy;
 pub use backend_engineer::{BackendEngineerAgent, BackendEngineerResponse};
 pub use config::AgentConfig;
+pub use context_window::ContextWindow;
 pub use db_engineer::{AgentResponse, DbEngineerAgent, StopAgentParams};
+pub use engineering_manager::{EngineeringManagerAgent, EngineeringManagerResponse};
 pub use error::AgentError;
 pub use frontend_engineer::{FrontendEngineerAgent, FrontendEngineerResponse};
+pub use llm_fixture::{FixtureClient, FixtureConfig, FixtureMode};
 pub use product_owner::{
     CompleteRequirementsParams, PoSessionResult, ProductOwnerAgent, RecordProjectNoteParams,
     SetProjectNameParams,
@@ -31,28 +46,38 @@ pub use product_owner::{
 pub use project_manager::{
     FinalizeSessionParams, FinalizeTaskDef, PmResponse, PmUserSessionResult, ProjectManagerAgent,
 };
+pub use prompt_library::{Prompt, PromptLibrary, PromptRef};
+pub use run_control::RunControl;
 pub use rust_engineer::{
-    DieselModelFnOutput, DieselModelStructOutput, DieselSchemaOutput, RustEngineerAgent,
-    RustEngineerResult,
+    ActixHandlerOutput, DieselModelFnOutput, DieselModelStructOutput, DieselModelTestOutput,
+    DieselSchemaOutput, EditImplFnOutput, MiddlewareKind, MiddlewareOutput, ProposedCode,
+    RustEngineerAgent, RustEngineerResult, RustEngineerTaskOutput, RustEngineerTaskParams,
+    TokenUsage,
 };
+pub use schedule::{Cron, Schedule};
 pub use stack_reviewer::{StackReviewResult, StackReviewerAgent};
 pub use storage::sqlite::{
-    SqliteAgentStorage, SqliteCommentStorage, SqliteContextStorage, SqliteProjectNoteStorage,
-    SqliteSchemaStorage, SqliteStackNoteStorage, SqliteTaskStorage, SqliteUiFormStorage,
-    SqliteUserChatStorage, SqliteUserStorage,
+    SqliteAgentStorage, SqliteCommentStorage, SqliteContextStorage, SqliteJobStorage,
+    SqliteProjectNoteStorage, SqliteProposalStorage, SqliteScheduleStorage, SqliteSchemaStorage,
+    SqliteStackNoteStorage, SqliteTaskStorage, SqliteUiFormStorage, SqliteUserChatStorage,
+    SqliteUserStorage,
 };
 pub use storage::{
-    AgentStorage, AgentType, ChatMessage, CommentStorage, ContextStorage, Epic, EpicStatus,
-    MessageContent, ProjectNoteRow, ProjectNoteStorage, ProjectNoteTopic, QuestionKind,
-    SchemaStorage, Session, StackNoteRow, StackNoteStorage, StackTag, StructuredQuestion,
-    StructuredResponse, Task, TaskStatus, TaskStorage, UiFormStorage, UserChatMessageRow,
-    UserChatSessionRow, UserChatStorage, UserStorage,
+    AgentStorage, AgentType, ChatMessage, CommentStorage, ContextStorage, Epic, EpicStatus, Job,
+    JobCount, JobStatus, JobStorage, LlmUsage, LlmUsageTotal, MessageContent, ProjectNoteRow,
+    ProjectNoteStorage, ProjectNoteTopic, Proposal, ProposalStatus, ProposalStorage, QuestionKind,
+    ScheduleRun, ScheduleRunStatus, ScheduleStorage, SchemaStorage, Session, StackNoteRow,
+    StackNoteStorage, StackTag, StructuredQuestion, StructuredResponse, Task, TaskStatus,
+    TaskStorage, UiFormStorage, UserChatMessageRow, UserChatSessionRow, UserChatStorage,
+    UserStorage,
 };
+pub use text_tools::{TextToolClient, ToolProtocol};
 pub use ui_designer::{
     agent::{UiDesignerAgent, UiDesignerResponse},
     FormField, FormFieldType, FormLayout, FormRow,
 };
 pub use user_input_tool::{InputType, RequestUserInputParams};
+pub use workflow::{FollowUp, Workflow, WorkflowRule};
 
 // ---------------------------------------------------------------------------
 // Factory helpers
@@ -63,7 +88,25 @@ use std::sync::Arc;
 pub(crate) fn make_llm_client(
     config: &AgentConfig,
 ) -> Result<Arc<dyn llm_sdk::client::LlmClient>, AgentError> {
-    use llm_sdk::{claude::ClaudeClient, groq::GroqClient, openai::OpenAIClient};
+    let client = make_provider_client(config)?;
+    // Outermost, so fixtures hold the text-protocol traffic the model sees.
+    Ok(match config.tool_protocol {
+        ToolProtocol::Text => Arc::new(TextToolClient::new(client)),
+        ToolProtocol::Native => client,
+    })
+}
+
+fn make_provider_client(
+    config: &AgentConfig,
+) -> Result<Arc<dyn llm_sdk::client::LlmClient>, AgentError> {
+    use llm_sdk::{
+        claude::ClaudeClient, groq::GroqClient, llama_cpp::LlamaCppClient, openai::OpenAIClient,
+    };
+    if let Some(fixture) = &config.fixture {
+        if fixture.mode == FixtureMode::Replay {
+            return Ok(Arc::new(FixtureClient::replay(&fixture.dir)));
+        }
+    }
     let client: Arc<dyn llm_sdk::client::LlmClient> = match config.provider.as_str() {
         config::PROVIDER_ANTHROPIC => Arc::new(
             ClaudeClient::new(config.api_key.clone())
@@ -73,12 +116,127 @@ pub(crate) fn make_llm_client(
             GroqClient::new(config.api_key.clone())
                 .map_err(|e| AgentError::Config(e.to_string()))?,
         ),
-        _ => Arc::new(
-            OpenAIClient::new(config.api_key.clone())
-                .map_err(|e| AgentError::Config(e.to_string()))?,
-        ),
+        config::PROVIDER_LLAMA_CPP => {
+            let client = LlamaCppClient::new().map_err(|e| AgentError::Config(e.to_string()))?;
+            match &config.base_url {
+                Some(url) => Arc::new(client.with_base_url(url.clone())),
+                None => Arc::new(client),
+            }
+        }
+        // Ollama speaks the OpenAI API; it ignores the key but the client
+        // wants a non-empty one.
+        config::PROVIDER_OLLAMA => {
+            let client = OpenAIClient::new(config::PROVIDER_OLLAMA.to_string())
+                .map_err(|e| AgentError::Config(e.to_string()))?;
+            let url = config
+                .base_url
+                .clone()
+                .unwrap_or_else(|| config::DEFAULT_OLLAMA_BASE_URL.to_string());
+            Arc::new(ProviderAlias {
+                inner: client.with_base_url(url),
+                provider: config::PROVIDER_OLLAMA,
+            })
+        }
+        _ => {
+            let client = OpenAIClient::new(config.api_key.clone())
+                .map_err(|e| AgentError::Config(e.to_string()))?;
+            match &config.base_url {
+                Some(url) => Arc::new(client.with_base_url(url.clone())),
+                None => Arc::new(client),
+            }
+        }
     };
-    Ok(client)
+    match &config.fixture {
+        Some(fixture) => Ok(Arc::new(FixtureClient::record(client, &fixture.dir))),
+        None => Ok(client),
+    }
+}
+
+/// Reports `provider` in place of the wrapped client's own name, so usage from
+/// an OpenAI-compatible local server is not booked as `openai`.
+struct ProviderAlias<C> {
+    inner: C,
+    provider: &'static str,
+}
+
+#[async_trait::async_trait]
+impl<C: llm_sdk::client::LlmClient> llm_sdk::client::LlmClient for ProviderAlias<C> {
+    async fn complete(
+        &self,
+        request: llm_sdk::types::CompletionRequest,
+    ) -> Result<llm_sdk::types::CompletionResponse, llm_sdk::error::LlmError> {
+        self.inner.complete(request).await
+    }
+
+    fn provider_name(&self) -> &str {
+        self.provider
+    }
+
+    fn model_name(&self) -> &str {
+        self.inner.model_name()
+    }
+}
+
+/// Run an agent with `config`, then with each of its fallbacks in turn while
+/// the run fails in a way another model might not (`is_model_failure`).
+/// `run` builds the agent from the config it is given and drives it to a
+/// result. Returns that result with the label of the model that produced it.
+///
+/// Session-backed agents rebuild their history from storage on every turn, so
+/// a fallback model picks up the conversation where the failed one left it.
+pub async fn run_with_fallback<T, F, Fut>(
+    config: &AgentConfig,
+    mut run: F,
+) -> Result<(T, String), AgentError>
+where
+    F: FnMut(AgentConfig) -> Fut,
+    Fut: std::future::Future<Output = Result<T, AgentError>>,
+{
+    let mut label = config.label();
+    let mut result = run(config.clone()).await;
+    for fallback in &config.fallbacks {
+        match &result {
+            Err(e) if e.is_model_failure() => {
+                log::warn!(
+                    "[Fallback] {} failed, trying {}: {}",
+                    label,
+                    fallback.label(),
+                    e
+                );
+            }
+            _ => break,
+        }
+        label = fallback.label();
+        result = run(fallback.clone()).await;
+    }
+    result.map(|value| (value, label))
+}
+
+/// A project's prompt templates: files in its `.nocodo/prompts` directory
+/// override the embedded ones. Embedded only when the project has no path.
+pub fn project_prompts(db_path: &str, project_id: i64) -> PromptLibrary {
+    let path = SqliteAgentStorage::open(db_path).and_then(|s| s.project_path(project_id));
+    match path {
+        Ok(Some(path)) => PromptLibrary::for_project(path),
+        Ok(None) => PromptLibrary::embedded(),
+        Err(e) => {
+            log::warn!(
+                "[Prompts] project {} path lookup failed, using embedded prompts: {}",
+                project_id,
+                e
+            );
+            PromptLibrary::embedded()
+        }
+    }
+}
+
+/// The history budget and summary model of `config`, on its own client.
+pub fn build_context_window(config: &AgentConfig) -> Result<ContextWindow, AgentError> {
+    Ok(ContextWindow::new(
+        make_llm_client(config)?,
+        &config.summary_model,
+        config.context_budget,
+    ))
 }
 
 pub fn build_db_engineer(
@@ -98,7 +256,9 @@ pub fn build_db_engineer(
         task_storage,
         &config.model,
         project_id,
-    ))
+    )
+    .with_context_window(build_context_window(config)?)
+    .with_prompts(project_prompts(db_path, project_id)))
 }
 
 pub fn build_project_manager(
@@ -110,13 +270,39 @@ pub fn build_project_manager(
     let storage: Arc<dyn AgentStorage> = Arc::new(SqliteAgentStorage::open(db_path)?);
     let task_storage: Arc<dyn TaskStorage> = Arc::new(SqliteTaskStorage::open(db_path)?);
 
-    Ok(ProjectManagerAgent::new(
+    Ok(
+        ProjectManagerAgent::new(client, storage, task_storage, &config.model, project_id)
+            .with_context_window(build_context_window(config)?)
+            .with_prompts(project_prompts(db_path, project_id)),
+    )
+}
+
+pub fn build_engineering_manager(
+    config: &AgentConfig,
+    db_path: &str,
+    project_id: i64,
+) -> Result<EngineeringManagerAgent, AgentError> {
+    let client = make_llm_client(config)?;
+    let storage: Arc<dyn AgentStorage> = Arc::new(SqliteAgentStorage::open(db_path)?);
+    let task_storage: Arc<dyn TaskStorage> = Arc::new(SqliteTaskStorage::open(db_path)?);
+    let comment_storage: Arc<dyn CommentStorage> = Arc::new(SqliteCommentStorage::open(db_path)?);
+    let stack_note_storage: Arc<dyn StackNoteStorage> =
+        Arc::new(SqliteStackNoteStorage::open(db_path)?);
+    let project_note_storage: Arc<dyn ProjectNoteStorage> =
+        Arc::new(SqliteProjectNoteStorage::open(db_path)?);
+
+    Ok(EngineeringManagerAgent::new(
         client,
         storage,
         task_storage,
+        comment_storage,
+        stack_note_storage,
+        project_note_storage,
         &config.model,
         project_id,
-    ))
+    )
+    .with_context_window(build_context_window(config)?)
+    .with_prompts(project_prompts(db_path, project_id)))
 }
 
 pub fn build_ui_designer(
@@ -136,7 +322,9 @@ pub fn build_ui_designer(
         task_storage,
         &config.model,
         project_id,
-    ))
+    )
+    .with_context_window(build_context_window(config)?)
+    .with_prompts(project_prompts(db_path, project_id)))
 }
 
 pub fn build_project_manager_with_task_storage(
@@ -148,13 +336,11 @@ pub fn build_project_manager_with_task_storage(
     let client = make_llm_client(config)?;
     let storage: Arc<dyn AgentStorage> = Arc::new(SqliteAgentStorage::open(db_path)?);
 
-    Ok(ProjectManagerAgent::new(
-        client,
-        storage,
-        task_storage,
-        &config.model,
-        project_id,
-    ))
+    Ok(
+        ProjectManagerAgent::new(client, storage, task_storage, &config.model, project_id)
+            .with_context_window(build_context_window(config)?)
+            .with_prompts(project_prompts(db_path, project_id)),
+    )
 }
 
 pub fn build_backend_engineer(
@@ -176,7 +362,9 @@ pub fn build_backend_engineer(
         &config.model,
         project_id,
         project_path,
-    ))
+    )
+    .with_context_window(build_context_window(config)?)
+    .with_prompts(PromptLibrary::for_project(project_path)))
 }
 
 pub fn build_frontend_engineer(
@@ -198,14 +386,23 @@ pub fn build_frontend_engineer(
         &config.model,
         project_id,
         project_path,
-    ))
+    )
+    .with_context_window(build_context_window(config)?)
+    .with_prompts(PromptLibrary::for_project(project_path)))
 }
 
 pub fn build_rust_engineer(project_path: &str) -> Result<RustEngineerAgent, AgentError> {
-    let model = std::env::var("RUST_ENGINEER_MODEL")
-        .unwrap_or_else(|_| "unsloth/Qwen3.5-0.8B-GGUF:UD-Q4_K_XL".to_string());
-    let base_url = std::env::var("LLAMA_CPP_BASE_URL").ok();
-    RustEngineerAgent::new(model, base_url, project_path)
+    build_rust_engineer_with_config(&AgentConfig::load_rust_engineer()?, project_path)
+}
+
+pub fn build_rust_engineer_with_config(
+    config: &AgentConfig,
+    project_path: &str,
+) -> Result<RustEngineerAgent, AgentError> {
+    Ok(
+        RustEngineerAgent::new(&config.model, config.base_url.clone(), project_path)?
+            .with_prompts(PromptLibrary::for_project(project_path)),
+    )
 }
 
 pub fn build_stack_reviewer(
@@ -215,13 +412,16 @@ pub fn build_stack_reviewer(
     project_path: &str,
 ) -> Result<StackReviewerAgent, AgentError> {
     let client = make_llm_client(config)?;
+    let storage: Arc<dyn AgentStorage> = Arc::new(SqliteAgentStorage::open(db_path)?);
     let stack_note_storage: Arc<dyn StackNoteStorage> =
         Arc::new(SqliteStackNoteStorage::open(db_path)?);
     Ok(StackReviewerAgent::new(
         client,
+        storage,
         stack_note_storage,
         &config.model,
         project_id,
         project_path,
-    ))
+    )
+    .with_prompts(PromptLibrary::for_project(project_path)))
 }
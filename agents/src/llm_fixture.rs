@@ -0,0 +1,272 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use llm_sdk::{
+    client::LlmClient,
+    error::LlmError,
+    types::{CompletionRequest, CompletionResponse},
+};
+
+/// Whether a `FixtureClient` talks to a real provider and saves what it sees, or
+/// answers only from saved fixtures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixtureMode {
+    Record,
+    Replay,
+}
+
+impl FixtureMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "record" => Some(FixtureMode::Record),
+            "replay" => Some(FixtureMode::Replay),
+            _ => None,
+        }
+    }
+}
+
+/// Where LLM fixtures live and how they are used. Loaded from
+/// `LLM_FIXTURE_MODE` / `LLM_FIXTURE_DIR` by `AgentConfig`.
+#[derive(Debug, Clone)]
+pub struct FixtureConfig {
+    pub mode: FixtureMode,
+    pub dir: PathBuf,
+}
+
+/// `LlmClient` that records request/response pairs to one JSON file per
+/// request, or replays them by request hash.
+///
+/// Every turn of an agent loop sends the whole conversation so far, so each
+/// request in a multi-turn flow (tool results, nudges included) hashes
+/// differently and a recorded flow replays turn by turn. The model name is left
+/// out of the hash so fixtures survive a model switch in config.
+pub struct FixtureClient {
+    inner: Option<Arc<dyn LlmClient>>,
+    dir: PathBuf,
+}
+
+impl FixtureClient {
+    /// Forward every request to `inner` and save the pair under `dir`.
+    pub fn record(inner: Arc<dyn LlmClient>, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            inner: Some(inner),
+            dir: dir.into(),
+        }
+    }
+
+    /// Answer only from fixtures under `dir`; a request with no fixture is an error.
+    pub fn replay(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            inner: None,
+            dir: dir.into(),
+        }
+    }
+
+    fn fixture_path(&self, hash: u64) -> PathBuf {
+        self.dir.join(format!("{hash:016x}.json"))
+    }
+}
+
+#[async_trait]
+impl LlmClient for FixtureClient {
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse, LlmError> {
+        let request_json = request_fingerprint(&request)?;
+        let path = self.fixture_path(fnv1a64(request_json.to_string().as_bytes()));
+
+        let Some(inner) = &self.inner else {
+            return read_fixture(&path);
+        };
+
+        let response = inner.complete(request).await?;
+        write_fixture(&path, &request_json, &response)?;
+        log::info!("[FixtureClient] recorded {}", path.display());
+        Ok(response)
+    }
+
+    fn provider_name(&self) -> &str {
+        match &self.inner {
+            Some(inner) => inner.provider_name(),
+            None => "replay",
+        }
+    }
+
+    fn model_name(&self) -> &str {
+        match &self.inner {
+            Some(inner) => inner.model_name(),
+            None => "replay",
+        }
+    }
+}
+
+/// The request as JSON with the model removed. Field order comes from the
+/// request types, so the rendering is stable across runs.
+fn request_fingerprint(request: &CompletionRequest) -> Result<serde_json::Value, LlmError> {
+    let mut value = serde_json::to_value(request)
+        .map_err(|e| LlmError::internal(format!("serialize request: {e}")))?;
+    if let Some(obj) = value.as_object_mut() {
+        obj.remove("model");
+    }
+    Ok(value)
+}
+
+/// 64-bit FNV-1a. Fixture names must not change between Rust releases, which
+/// rules out `DefaultHasher`.
+fn fnv1a64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in bytes {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+fn read_fixture(path: &Path) -> Result<CompletionResponse, LlmError> {
+    let contents = fs::read_to_string(path).map_err(|e| {
+        LlmError::internal(format!(
+            "no recorded response at {} ({e}); re-record with LLM_FIXTURE_MODE=record",
+            path.display()
+        ))
+    })?;
+    let mut fixture: serde_json::Value = serde_json::from_str(&contents)
+        .map_err(|e| LlmError::internal(format!("parse {}: {e}", path.display())))?;
+    serde_json::from_value(fixture["response"].take())
+        .map_err(|e| LlmError::internal(format!("parse {}: {e}", path.display())))
+}
+
+fn write_fixture(
+    path: &Path,
+    request: &serde_json::Value,
+    response: &CompletionResponse,
+) -> Result<(), LlmError> {
+    let fixture = serde_json::json!({
+        "request": request,
+        "response": response,
+    });
+    let contents = serde_json::to_string_pretty(&fixture)
+        .map_err(|e| LlmError::internal(format!("serialize fixture: {e}")))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| LlmError::internal(format!("create {}: {e}", parent.display())))?;
+    }
+    fs::write(path, contents)
+        .map_err(|e| LlmError::internal(format!("write {}: {e}", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use llm_sdk::types::{ContentBlock, Message, Role};
+
+    fn request(model: &str, text: &str) -> CompletionRequest {
+        CompletionRequest {
+            messages: vec![Message {
+                role: Role::User,
+                content: vec![ContentBlock::Text {
+                    text: text.to_string(),
+                }],
+                tool_call_id: None,
+                tool_name: None,
+            }],
+            max_tokens: 64,
+            model: model.to_string(),
+            system: None,
+            temperature: None,
+            top_p: None,
+            stop_sequences: None,
+            tools: None,
+            tool_choice: None,
+            response_format: None,
+        }
+    }
+
+    #[test]
+    fn test_fnv1a64_known_vectors() {
+        assert_eq!(fnv1a64(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a64(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_model() {
+        let a = request_fingerprint(&request("model-a", "hello")).unwrap();
+        let b = request_fingerprint(&request("model-b", "hello")).unwrap();
+        let c = request_fingerprint(&request("model-a", "bye")).unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    /// Answers every request the same and counts the calls.
+    struct StubClient {
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl LlmClient for StubClient {
+        async fn complete(
+            &self,
+            _request: CompletionRequest,
+        ) -> Result<CompletionResponse, LlmError> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(serde_json::from_value(serde_json::json!({
+                "content": [{ "type": "text", "text": "Recorded answer." }],
+                "role": "assistant",
+                "stop_reason": "end_turn",
+                "tool_calls": null,
+                "usage": { "input_tokens": 12, "output_tokens": 3 }
+            }))
+            .unwrap())
+        }
+
+        fn provider_name(&self) -> &str {
+            "stub"
+        }
+
+        fn model_name(&self) -> &str {
+            "model-a"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay_with_another_model() {
+        let dir = std::env::temp_dir().join(format!("llm_fixture_rt_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let stub = Arc::new(StubClient {
+            calls: Default::default(),
+        });
+
+        let recorder = FixtureClient::record(stub.clone(), &dir);
+        let recorded = recorder
+            .complete(request("model-a", "hello"))
+            .await
+            .unwrap();
+        let replayed = FixtureClient::replay(&dir)
+            .complete(request("model-b", "hello"))
+            .await
+            .unwrap();
+        let unrecorded = FixtureClient::replay(&dir)
+            .complete(request("model-b", "bye"))
+            .await;
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(stub.calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(
+            serde_json::to_value(&replayed).unwrap(),
+            serde_json::to_value(&recorded).unwrap()
+        );
+        assert!(unrecorded.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replay_without_fixture_errors() {
+        let dir = std::env::temp_dir().join(format!("llm_fixture_test_{}", std::process::id()));
+        let client = FixtureClient::replay(&dir);
+        let err = client
+            .complete(request("model", "unrecorded"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("LLM_FIXTURE_MODE=record"));
+    }
+}
@@ -11,11 +11,13 @@ use super::tools::{CompleteRequirementsParams, RecordProjectNoteParams, SetProje
 use crate::{
     config::AgentConfig,
     error::AgentError,
+    prompt_library::PromptLibrary,
     storage::{
-        AgentStorage, AgentType, CommentStorage, ProjectNoteStorage, ProjectNoteTopic,
+        AgentStorage, AgentType, CommentStorage, LlmUsage, ProjectNoteStorage, ProjectNoteTopic,
         QuestionKind, StructuredQuestion, TaskStorage,
     },
     task_policy,
+    tool_args::parse_tool_args,
     user_input_tool::{InputType, RequestUserInputParams},
 };
 
@@ -52,6 +54,7 @@ pub struct ProductOwnerAgent {
     note_storage: Arc<dyn ProjectNoteStorage>,
     model: String,
     project_id: i64,
+    prompts: PromptLibrary,
 }
 
 impl ProductOwnerAgent {
@@ -72,9 +75,17 @@ impl ProductOwnerAgent {
             note_storage,
             model: config.model,
             project_id,
+            prompts: PromptLibrary::embedded(),
         })
     }
 
+    /// Resolve system prompts through `prompts`, e.g. to pick up the
+    /// project's overrides. Embedded templates are used otherwise.
+    pub fn with_prompts(mut self, prompts: PromptLibrary) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
     /// Run the PO agent.
     ///
     /// `is_naming = false` — requirements gathering mode: ask questions, record notes,
@@ -89,7 +100,7 @@ impl ProductOwnerAgent {
         is_naming: bool,
     ) -> Result<PoSessionResult, AgentError> {
         if is_naming {
-            self.run_project_naming(messages).await
+            self.run_project_naming(session_id, messages).await
         } else {
             self.run_requirements_gathering(session_id, messages).await
         }
@@ -136,6 +147,7 @@ impl ProductOwnerAgent {
 
         let tools = vec![ask_tool, note_tool, complete_tool];
 
+        let system = requirements_gathering::system_prompt(&self.prompts)?;
         let mut llm_messages = build_llm_messages(&messages);
 
         const MAX_ITERATIONS: usize = 6;
@@ -144,7 +156,7 @@ impl ProductOwnerAgent {
                 messages: llm_messages.clone(),
                 max_tokens: 1024,
                 model: self.model.clone(),
-                system: Some(requirements_gathering::system_prompt()),
+                system: Some(system.text.clone()),
                 temperature: Some(0.3),
                 top_p: None,
                 stop_sequences: None,
@@ -160,6 +172,21 @@ impl ProductOwnerAgent {
                 request.messages.len()
             );
             let response = self.llm_client.complete(request).await?;
+
+            self.storage
+                .record_usage(LlmUsage {
+                    task_id: None,
+                    session_id: Some(session_id),
+                    prompt: Some(system.reference.clone()),
+                    ..LlmUsage::from_response(
+                        self.project_id,
+                        AgentType::ProductOwner,
+                        self.llm_client.as_ref(),
+                        &self.model,
+                        &response,
+                    )
+                })
+                .await?;
             log::info!(
                 "[PO:requirements_gathering] iteration={} stop_reason={:?}",
                 iteration,
@@ -199,8 +226,15 @@ impl ProductOwnerAgent {
 
                 let tool_result = match tool_call.name() {
                     "request_user_input" => {
-                        let params: RequestUserInputParams =
-                            tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                        let params: RequestUserInputParams = match parse_tool_args(tool_call) {
+                            Ok(p) => p,
+                            Err(e) => {
+                                log::warn!("[PO] {}", e);
+                                tool_result_messages
+                                    .push(Message::tool(tool_call.id(), e.to_string()));
+                                continue;
+                            }
+                        };
                         let kind = match params.input_type {
                             InputType::SingleChoice => QuestionKind::SingleChoice {
                                 options: params.options,
@@ -216,15 +250,12 @@ impl ProductOwnerAgent {
                         "Question queued for user".to_string()
                     }
                     "record_project_note" => {
-                        let params: RecordProjectNoteParams = match tool_call
-                            .parse_arguments()
-                            .map_err(AgentError::Llm)
-                        {
+                        let params: RecordProjectNoteParams = match parse_tool_args(tool_call) {
                             Ok(p) => p,
                             Err(e) => {
-                                log::warn!("[PO] record_project_note parse error: {}", e);
+                                log::warn!("[PO] {}", e);
                                 tool_result_messages
-                                    .push(Message::tool(tool_call.id(), format!("Error: {}", e)));
+                                    .push(Message::tool(tool_call.id(), e.to_string()));
                                 continue;
                             }
                         };
@@ -245,8 +276,15 @@ impl ProductOwnerAgent {
                         "Note recorded".to_string()
                     }
                     "complete_requirements" => {
-                        let params: CompleteRequirementsParams =
-                            tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                        let params: CompleteRequirementsParams = match parse_tool_args(tool_call) {
+                            Ok(p) => p,
+                            Err(e) => {
+                                log::warn!("[PO] {}", e);
+                                tool_result_messages
+                                    .push(Message::tool(tool_call.id(), e.to_string()));
+                                continue;
+                            }
+                        };
                         completion = Some(params.closing_message);
                         "Requirements marked complete".to_string()
                     }
@@ -299,6 +337,7 @@ impl ProductOwnerAgent {
 
     async fn run_project_naming(
         &self,
+        session_id: i64,
         messages: Vec<(String, String)>,
     ) -> Result<PoSessionResult, AgentError> {
         let name_tool = Tool::from_type::<SetProjectNameParams>()
@@ -308,13 +347,14 @@ impl ProductOwnerAgent {
             )
             .build();
 
+        let system = project_naming::system_prompt(&self.prompts)?;
         let llm_messages = build_llm_messages(&messages);
 
         let request = CompletionRequest {
             messages: llm_messages,
             max_tokens: 256,
             model: self.model.clone(),
-            system: Some(project_naming::system_prompt()),
+            system: Some(system.text),
             temperature: Some(0.2),
             top_p: None,
             stop_sequences: None,
@@ -325,6 +365,21 @@ impl ProductOwnerAgent {
 
         log::info!("[PO:project_naming] calling LLM model={}", self.model);
         let response = self.llm_client.complete(request).await?;
+
+        self.storage
+            .record_usage(LlmUsage {
+                task_id: None,
+                session_id: Some(session_id),
+                prompt: Some(system.reference),
+                ..LlmUsage::from_response(
+                    self.project_id,
+                    AgentType::ProductOwner,
+                    self.llm_client.as_ref(),
+                    &self.model,
+                    &response,
+                )
+            })
+            .await?;
         log::info!("[PO:project_naming] stop_reason={:?}", response.stop_reason);
 
         let Some(tool_calls) = response.tool_calls else {
@@ -334,8 +389,7 @@ impl ProductOwnerAgent {
 
         for tool_call in &tool_calls {
             if tool_call.name() == "set_project_name" {
-                let params: SetProjectNameParams =
-                    tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                let params: SetProjectNameParams = parse_tool_args(tool_call)?;
                 log::info!("[PO:project_naming] set_project_name={:?}", params.name);
                 if let Err(e) = self
                     .storage
@@ -1,3 +1,2 @@
-pub mod core;
 pub mod project_naming;
 pub mod requirements_gathering;
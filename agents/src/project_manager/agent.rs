@@ -14,11 +14,14 @@ use super::{
     },
 };
 use crate::{
+    context_window::ContextWindow,
     error::AgentError,
+    prompt_library::PromptLibrary,
     storage::{
-        AgentStorage, AgentType, ChatMessage, Epic, EpicStatus, QuestionKind, StructuredQuestion,
-        Task, TaskStatus, TaskStorage,
+        AgentStorage, AgentType, ChatMessage, Epic, EpicStatus, LlmUsage, QuestionKind,
+        StructuredQuestion, Task, TaskStatus, TaskStorage,
     },
+    tool_args::{parse_or_record, parse_tool_args},
     user_input_tool::{InputType, RequestUserInputParams},
 };
 
@@ -58,6 +61,10 @@ pub struct ProjectManagerAgent {
     task_storage: Arc<dyn TaskStorage>,
     model: String,
     project_id: i64,
+    /// Summarizes older turns when the history outgrows the model's budget.
+    /// `None` sends the full history.
+    context: Option<ContextWindow>,
+    prompts: PromptLibrary,
 }
 
 impl ProjectManagerAgent {
@@ -74,9 +81,23 @@ impl ProjectManagerAgent {
             task_storage,
             model: model.into(),
             project_id,
+            context: None,
+            prompts: PromptLibrary::embedded(),
         }
     }
 
+    pub fn with_context_window(mut self, context: ContextWindow) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Resolve the system prompt through `prompts`, e.g. to pick up the
+    /// project's overrides. Embedded templates are used otherwise.
+    pub fn with_prompts(mut self, prompts: PromptLibrary) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
     /// Run the PM agent for an existing session + task.
     /// The caller creates the task + session and persists the user message first.
     pub async fn chat_with_session(
@@ -93,8 +114,8 @@ impl ProjectManagerAgent {
     /// Uses a dedicated prompt that instructs PM not to re-ask questions already covered.
     pub async fn chat_for_user_session(
         &self,
-        _session_id: i64,
-        messages: Vec<(String, String)>,
+        session_id: i64,
+        mut messages: Vec<(String, String)>,
         from_po_handoff: bool,
     ) -> Result<PmUserSessionResult, AgentError> {
         let finalize_tool = Tool::from_type::<FinalizeSessionParams>()
@@ -117,9 +138,14 @@ impl ProjectManagerAgent {
             .build();
 
         let _agent_type_str = AgentType::ProjectManager.as_str().to_string();
+        let system = if from_po_handoff {
+            po_handoff::system_prompt(&self.prompts)?
+        } else {
+            user_session::system_prompt(&self.prompts)?
+        };
         let mut nudges: u32 = 0;
 
-        loop {
+        'turns: loop {
             let llm_messages: Vec<Message> = messages
                 .iter()
                 .map(|(role, content)| {
@@ -143,11 +169,7 @@ impl ProjectManagerAgent {
                 messages: llm_messages,
                 max_tokens: 4096,
                 model: self.model.clone(),
-                system: Some(if from_po_handoff {
-                    po_handoff::system_prompt()
-                } else {
-                    user_session::system_prompt()
-                }),
+                system: Some(system.text.clone()),
                 temperature: Some(0.2),
                 top_p: None,
                 stop_sequences: None,
@@ -158,6 +180,21 @@ impl ProjectManagerAgent {
 
             log::info!("[PM:user_session] Calling LLM with model={}", self.model);
             let response = self.llm_client.complete(request).await?;
+
+            self.storage
+                .record_usage(LlmUsage {
+                    task_id: None,
+                    session_id: Some(session_id),
+                    prompt: Some(system.reference.clone()),
+                    ..LlmUsage::from_response(
+                        self.project_id,
+                        AgentType::ProjectManager,
+                        self.llm_client.as_ref(),
+                        &self.model,
+                        &response,
+                    )
+                })
+                .await?;
             log::info!(
                 "[PM:user_session] LLM response received, stop_reason={:?}",
                 response.stop_reason
@@ -181,8 +218,40 @@ impl ProjectManagerAgent {
 
                     match tool_name {
                         "finalize_session" => {
-                            let params: FinalizeSessionParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                            let params: FinalizeSessionParams = match parse_tool_args(&tool_call) {
+                                Ok(params) => params,
+                                Err(e) => {
+                                    nudges += 1;
+                                    if nudges >= MAX_NUDGES {
+                                        return Err(e.into());
+                                    }
+                                    // No tool rows in a user session: show the model its
+                                    // call and what to fix as the next user turn.
+                                    log::warn!("[PM:user_session] {}", e);
+                                    messages.push((
+                                        "assistant".to_string(),
+                                        tool_call.raw_arguments().to_string(),
+                                    ));
+                                    messages.push(("user".to_string(), e.to_string()));
+                                    continue 'turns;
+                                }
+                            };
+                            if let Err(e) = params.validate() {
+                                nudges += 1;
+                                if nudges >= MAX_NUDGES {
+                                    return Err(e);
+                                }
+                                log::warn!("[PM:user_session] {}", e);
+                                messages.push((
+                                    "assistant".to_string(),
+                                    tool_call.raw_arguments().to_string(),
+                                ));
+                                messages.push((
+                                    "user".to_string(),
+                                    format!("finalize_session was not saved: {}. Call it again with every task fixed.", e),
+                                ));
+                                continue 'turns;
+                            }
                             log::info!(
                                 "[PM:user_session] Session finalized: epic={}, {} task(s)",
                                 params.epic_title,
@@ -192,8 +261,24 @@ impl ProjectManagerAgent {
                         }
 
                         "request_user_input" => {
-                            let params: RequestUserInputParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                            let params: RequestUserInputParams = match parse_tool_args(&tool_call) {
+                                Ok(params) => params,
+                                Err(e) => {
+                                    nudges += 1;
+                                    if nudges >= MAX_NUDGES {
+                                        return Err(e.into());
+                                    }
+                                    // No tool rows in a user session: show the model its
+                                    // call and what to fix as the next user turn.
+                                    log::warn!("[PM:user_session] {}", e);
+                                    messages.push((
+                                        "assistant".to_string(),
+                                        tool_call.raw_arguments().to_string(),
+                                    ));
+                                    messages.push(("user".to_string(), e.to_string()));
+                                    continue 'turns;
+                                }
+                            };
                             log::info!(
                                 "[PM:user_session] Structured question: {:?}",
                                 params.question
@@ -245,7 +330,7 @@ impl ProjectManagerAgent {
                 MAX_NUDGES
             );
             if nudges >= MAX_NUDGES {
-                return Err(AgentError::Other(
+                return Err(AgentError::NudgesExhausted(
                     "PM agent did not produce a response after multiple nudges.".to_string(),
                 ));
             }
@@ -305,10 +390,31 @@ impl ProjectManagerAgent {
             .build();
 
         let agent_type_str = AgentType::ProjectManager.as_str().to_string();
+        let system = if is_init {
+            init::system_prompt(&self.prompts)?
+        } else {
+            general::system_prompt(&self.prompts)?
+        };
+        self.storage
+            .set_session_prompt(session_id, &system.reference)
+            .await?;
         let mut nudges: u32 = 0;
 
-        loop {
-            let history = self.storage.get_messages(session_id).await?;
+        'turns: loop {
+            let history = match &self.context {
+                Some(context) => {
+                    context
+                        .session_history(
+                            self.storage.as_ref(),
+                            self.project_id,
+                            AgentType::ProjectManager,
+                            task_id,
+                            session_id,
+                        )
+                        .await?
+                }
+                None => self.storage.get_messages(session_id).await?,
+            };
             let llm_messages: Vec<Message> = history
                 .into_iter()
                 .map(|m| {
@@ -330,11 +436,7 @@ impl ProjectManagerAgent {
                 messages: llm_messages,
                 max_tokens: 4096,
                 model: self.model.clone(),
-                system: Some(if is_init {
-                    init::system_prompt()
-                } else {
-                    general::system_prompt()
-                }),
+                system: Some(system.text.clone()),
                 temperature: Some(0.2),
                 top_p: None,
                 stop_sequences: None,
@@ -355,6 +457,21 @@ impl ProjectManagerAgent {
 
             log::info!("[PM] Calling LLM with model={}", self.model);
             let response = self.llm_client.complete(request).await?;
+
+            self.storage
+                .record_usage(LlmUsage {
+                    task_id: Some(task_id),
+                    session_id: Some(session_id),
+                    prompt: Some(system.reference.clone()),
+                    ..LlmUsage::from_response(
+                        self.project_id,
+                        AgentType::ProjectManager,
+                        self.llm_client.as_ref(),
+                        &self.model,
+                        &response,
+                    )
+                })
+                .await?;
             log::info!(
                 "[PM] LLM response received, stop_reason={:?}",
                 response.stop_reason
@@ -445,8 +562,19 @@ impl ProjectManagerAgent {
                         }
 
                         "create_epic" => {
-                            let params: CreateEpicParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                            let Some(params) = parse_or_record::<CreateEpicParams>(
+                                self.storage.as_ref(),
+                                session_id,
+                                AgentType::ProjectManager,
+                                &assistant_text,
+                                &tool_call,
+                                &mut nudges,
+                                MAX_NUDGES,
+                            )
+                            .await?
+                            else {
+                                continue 'turns;
+                            };
 
                             let now = std::time::SystemTime::now()
                                 .duration_since(std::time::UNIX_EPOCH)
@@ -505,37 +633,52 @@ impl ProjectManagerAgent {
                         }
 
                         "create_task" => {
-                            let params: CreateTaskParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                            let Some(params) = parse_or_record::<CreateTaskParams>(
+                                self.storage.as_ref(),
+                                session_id,
+                                AgentType::ProjectManager,
+                                &assistant_text,
+                                &tool_call,
+                                &mut nudges,
+                                MAX_NUDGES,
+                            )
+                            .await?
+                            else {
+                                continue 'turns;
+                            };
 
                             let now = std::time::SystemTime::now()
                                 .duration_since(std::time::UNIX_EPOCH)
                                 .unwrap_or_default()
                                 .as_secs() as i64;
 
-                            let result_text = match self
-                                .task_storage
-                                .create_task(Task {
-                                    id: None,
-                                    project_id: self.project_id,
-                                    epic_id: params.epic_id,
-                                    title: params.title.clone(),
-                                    description: params.description.clone(),
-                                    source_prompt: params.source_prompt.clone(),
-                                    assigned_to_agent: params.assigned_to_agent.clone(),
-                                    status: TaskStatus::Draft,
-                                    depends_on_task_id: params.depends_on_task_id,
-                                    created_by_agent: agent_type_str.clone(),
-                                    created_at: now,
-                                    updated_at: now,
-                                })
-                                .await
-                            {
-                                Ok(id) => format!(
-                                    "Task created: id={}, title=\"{}\", assigned_to={}",
-                                    id, params.title, params.assigned_to_agent
-                                ),
+                            let result_text = match params.task_source_prompt() {
                                 Err(e) => format!("Failed to create task: {}", e),
+                                Ok(source_prompt) => match self
+                                    .task_storage
+                                    .create_task(Task {
+                                        id: None,
+                                        project_id: self.project_id,
+                                        epic_id: params.epic_id,
+                                        title: params.title.clone(),
+                                        description: params.description.clone(),
+                                        source_prompt,
+                                        assigned_to_agent: params.assigned_to_agent.clone(),
+                                        status: TaskStatus::Draft,
+                                        depends_on_task_id: params.depends_on_task_id,
+                                        created_by_agent: agent_type_str.clone(),
+                                        created_at: now,
+                                        updated_at: now,
+                                        created_by_task_id: Some(task_id),
+                                    })
+                                    .await
+                                {
+                                    Ok(id) => format!(
+                                        "Task created: id={}, title=\"{}\", assigned_to={}",
+                                        id, params.title, params.assigned_to_agent
+                                    ),
+                                    Err(e) => format!("Failed to create task: {}", e),
+                                },
                             };
 
                             // PM's own task gains InProgress when it creates sub-tasks.
@@ -574,8 +717,19 @@ impl ProjectManagerAgent {
                         }
 
                         "update_task_status" => {
-                            let params: PmUpdateTaskStatusParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                            let Some(params) = parse_or_record::<PmUpdateTaskStatusParams>(
+                                self.storage.as_ref(),
+                                session_id,
+                                AgentType::ProjectManager,
+                                &assistant_text,
+                                &tool_call,
+                                &mut nudges,
+                                MAX_NUDGES,
+                            )
+                            .await?
+                            else {
+                                continue 'turns;
+                            };
 
                             let new_status = TaskStatus::from_str(&params.status);
                             let result_text = match self
@@ -644,7 +798,7 @@ impl ProjectManagerAgent {
             nudges += 1;
             log::warn!("[PM] No response, nudge {}/{}", nudges, MAX_NUDGES);
             if nudges >= MAX_NUDGES {
-                return Err(AgentError::Other(
+                return Err(AgentError::NudgesExhausted(
                     "PM agent did not produce a response after multiple nudges.".to_string(),
                 ));
             }
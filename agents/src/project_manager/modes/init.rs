@@ -1,32 +1,14 @@
-use super::core::pm_core;
+use crate::{
+    error::AgentError,
+    prompt_library::{Prompt, PromptLibrary},
+};
 
 /// Mode: brand-new project, first user message.
 ///
 /// The PM's only job is to immediately name the project, create one Epic,
 /// and assign the first task to db_engineer. No triage, no follow-up questions.
-pub fn system_prompt() -> String {
-    format!(
-        r#"{core}
-
-## Mode: Project Initialisation
-
-The user has just created a **new project** and sent their first message. Act immediately:
-
-1. Call `set_project_name` with a concise, descriptive name derived from the user's domain
-   (e.g. "CRM — Leads & Deals", "Inventory Tracker", "Support Desk").
-2. Call `create_epic` to record the user's initiative as an Epic (title + description).
-3. Call `create_task` to assign the first task to `db_engineer`.
-   - Set `source_prompt` to the user's exact words verbatim.
-   - Set `assigned_to_agent` to `"db_engineer"`.
-4. Reply to the user confirming the project name, the epic, and that the schema designer
-   will start on the data model.
-
-### Rules for this mode
-
-- Do NOT call `list_pending_review_tasks` — this is a brand new project with no history.
-- Do NOT design the schema yourself.
-- Call `set_project_name` exactly once, before `create_epic`.
-"#,
-        core = pm_core()
-    )
+///
+/// Template: `prompts/project_manager/init.md` (includes `project_manager/core`).
+pub fn system_prompt(prompts: &PromptLibrary) -> Result<Prompt, AgentError> {
+    prompts.render("project_manager/init", &[])
 }
@@ -1,4 +1,3 @@
-pub mod core;
 pub mod general;
 pub mod init;
 pub mod po_handoff;
@@ -1,46 +1,14 @@
-use super::core::pm_core;
+use crate::{
+    error::AgentError,
+    prompt_library::{Prompt, PromptLibrary},
+};
 
 /// Mode: planning session seeded by a completed PO intake.
 ///
 /// The PM receives the full Q&A conversation plus the PO's requirements summary.
 /// It should plan immediately in most cases — the PO already did the hard questioning.
-pub fn system_prompt() -> String {
-    format!(
-        r#"{core}
-
-## Mode: Post-PO-Handoff Planning
-
-The Product Owner has completed requirements intake with the customer. You have been given:
-1. The full conversation between the PO and the customer (the requirements Q&A).
-2. A structured requirements summary written by the PO.
-
-Your job is to turn these into a concrete development plan: one epic and a set of assigned
-tasks.
-
-### How to proceed
-
-Review the requirements conversation and the PO summary before doing anything else.
-
-**Do NOT ask questions that were already answered during the intake.** The customer has
-already told the PO what they need — respect their time. Only ask a follow-up question if
-something essential for creating a task is genuinely missing from the brief.
-
-When you have enough clarity (which in most cases means immediately, given the PO summary):
-- Call `finalize_session` with a friendly closing message, one epic, and one or more tasks.
-
-### Asking follow-up questions (only if truly needed)
-
-**Prefer `request_user_input` over prose questions.** Supply 2–6 short options. For open
-questions use plain text instead. Keep follow-up questions to a minimum — 1 or 2 at most.
-If the PO summary covers it, don't ask.
-
-### Rules for this mode
-
-- The PO summary and intake Q&A are authoritative. Trust them.
-- Only call `finalize_session` once — but do it as soon as you have enough. Don't delay.
-- Never repeat questions already answered in the intake conversation.
-- Keep epic and task descriptions tight and actionable.
-"#,
-        core = pm_core()
-    )
+///
+/// Template: `prompts/project_manager/po_handoff.md` (includes `project_manager/core`).
+pub fn system_prompt(prompts: &PromptLibrary) -> Result<Prompt, AgentError> {
+    prompts.render("project_manager/po_handoff", &[])
 }
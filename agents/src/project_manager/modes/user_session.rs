@@ -1,46 +1,14 @@
-use super::core::pm_core;
+use crate::{
+    error::AgentError,
+    prompt_library::{Prompt, PromptLibrary},
+};
 
 /// Mode: direct user chat, gathering requirements to finalize an epic + tasks.
 ///
 /// The PM talks to the user, asks clarifying questions, then calls `finalize_session`
 /// once it has enough clarity.
-pub fn system_prompt() -> String {
-    format!(
-        r#"{core}
-
-## Mode: User Requirements Session
-
-You are talking directly with the user to gather requirements for their project. Your goal
-is to understand what they want to build well enough to define one epic and the concrete
-tasks needed to build it.
-
-### How to proceed
-
-1. Ask questions and clarify scope until you have a clear picture.
-2. When you have enough clarity, call `finalize_session` with:
-   - A friendly closing message to the user.
-   - One epic title and description summarising the initiative.
-   - One or more tasks, each assigned to the appropriate agent.
-
-### Asking questions
-
-**Prefer `request_user_input` over prose questions whenever you can offer a reasonable list
-of choices.** Use it for questions like "who are the users?", "what data needs tracking?",
-"which features are in scope?". Supply 2–6 short options. For genuinely open questions
-(e.g. "describe your idea") use plain text instead.
-
-You may call `request_user_input` multiple times in one turn when the questions are
-independent and all useful now. Do not batch dependent questions. Keep batches small
-(typically 2–4 questions max). Do not include synthetic catch-all options like "both",
-"all", or "all of the above" — the UI already supports selecting multiple options directly.
-
-### Rules for this mode
-
-- Only call `finalize_session` once — when you are certain you have enough information.
-  MVP-level clarity is sufficient; don't over-gather.
-- Do not finalize until you have a clear epic and at least one well-defined task.
-- Always end your turns with a question or a summary to keep the conversation moving.
-"#,
-        core = pm_core()
-    )
+///
+/// Template: `prompts/project_manager/user_session.md` (includes `project_manager/core`).
+pub fn system_prompt(prompts: &PromptLibrary) -> Result<Prompt, AgentError> {
+    prompts.render("project_manager/user_session", &[])
 }
@@ -1,6 +1,8 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::{error::AgentError, rust_engineer::RustEngineerTaskParams};
+
 /// Called at session start to surface tasks awaiting PM triage.
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ListPendingReviewTasksParams {}
@@ -23,12 +25,47 @@ pub struct CreateTaskParams {
     pub description: String,
     /// Verbatim user intent for this task — the focused agent reads this.
     pub source_prompt: String,
-    /// Target agent. Must be one of: "db_engineer".
+    /// Target agent. Must be one of: "db_engineer", "rust_engineer".
     pub assigned_to_agent: String,
     /// Epic this task belongs to (null for standalone tasks).
     pub epic_id: Option<i64>,
     /// Task that must complete before this one can start.
     pub depends_on_task_id: Option<i64>,
+    /// Required when `assigned_to_agent` is "rust_engineer"; null otherwise.
+    #[serde(default)]
+    pub rust_engineer: Option<RustEngineerTaskParams>,
+}
+
+impl CreateTaskParams {
+    /// What to store as the task's `source_prompt`; see `task_source_prompt`.
+    pub fn task_source_prompt(&self) -> Result<String, AgentError> {
+        task_source_prompt(
+            &self.title,
+            &self.assigned_to_agent,
+            self.rust_engineer.as_ref(),
+            &self.source_prompt,
+        )
+    }
+}
+
+/// rust_engineer tasks carry their structured parameters as JSON in
+/// `source_prompt`, with `text` as their prompt when they have none; every
+/// other agent reads `text`. A rust_engineer task without parameters is an
+/// error, since its agent could not run it.
+fn task_source_prompt(
+    title: &str,
+    agent: &str,
+    rust_engineer: Option<&RustEngineerTaskParams>,
+    text: &str,
+) -> Result<String, AgentError> {
+    match rust_engineer {
+        Some(params) => params.to_source_prompt(text),
+        None if agent == "rust_engineer" => Err(AgentError::Other(format!(
+            "task \"{}\" is assigned to rust_engineer but has no `rust_engineer` parameters",
+            title
+        ))),
+        None => Ok(text.to_string()),
+    }
 }
 
 /// Update the status of any task the PM is managing.
@@ -49,6 +86,9 @@ pub struct FinalizeTaskDef {
     pub description: String,
     /// Target agent type string, e.g. "db_engineer".
     pub assigned_to_agent: String,
+    /// Required when `assigned_to_agent` is "rust_engineer"; null otherwise.
+    #[serde(default)]
+    pub rust_engineer: Option<RustEngineerTaskParams>,
 }
 
 /// Called by PM to atomically finalize a user chat session: emit a closing
@@ -64,3 +104,72 @@ pub struct FinalizeSessionParams {
     /// Tasks to create under this epic.
     pub tasks: Vec<FinalizeTaskDef>,
 }
+
+impl FinalizeTaskDef {
+    /// What to store as the task's `source_prompt`; see `task_source_prompt`.
+    pub fn task_source_prompt(&self) -> Result<String, AgentError> {
+        task_source_prompt(
+            &self.title,
+            &self.assigned_to_agent,
+            self.rust_engineer.as_ref(),
+            &self.description,
+        )
+    }
+}
+
+impl FinalizeSessionParams {
+    /// Check every task can be stored before anything is, so a bad task is
+    /// sent back to the model instead of failing the whole plan.
+    pub fn validate(&self) -> Result<(), AgentError> {
+        for task in &self.tasks {
+            task.task_source_prompt()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(agent: &str, rust_engineer: Option<RustEngineerTaskParams>) -> FinalizeTaskDef {
+        FinalizeTaskDef {
+            title: "Contacts".to_string(),
+            description: "Store contacts".to_string(),
+            assigned_to_agent: agent.to_string(),
+            rust_engineer,
+        }
+    }
+
+    #[test]
+    fn test_task_source_prompt() {
+        assert_eq!(
+            task("db_engineer", None).task_source_prompt().unwrap(),
+            "Store contacts"
+        );
+
+        let params: RustEngineerTaskParams =
+            serde_json::from_str(r#"{"mode": "diesel_model_struct"}"#).unwrap();
+        let stored = task("rust_engineer", Some(params))
+            .task_source_prompt()
+            .unwrap();
+        let stored = RustEngineerTaskParams::from_source_prompt(&stored).unwrap();
+        assert_eq!(stored.mode, "diesel_model_struct");
+        assert_eq!(stored.prompt.as_deref(), Some("Store contacts"));
+    }
+
+    #[test]
+    fn test_validate_rejects_rust_engineer_task_without_params() {
+        let mut params = FinalizeSessionParams {
+            final_message: "Done".to_string(),
+            epic_title: "Contacts".to_string(),
+            epic_description: "Contacts".to_string(),
+            tasks: vec![task("db_engineer", None)],
+        };
+        assert!(params.validate().is_ok());
+
+        params.tasks.push(task("rust_engineer", None));
+        let err = params.validate().unwrap_err().to_string();
+        assert!(err.contains("no `rust_engineer` parameters"), "{err}");
+    }
+}
@@ -0,0 +1,339 @@
+//! Versioned system-prompt templates. Each template lives in
+//! `agents/prompts/<id>.md` and is embedded in the binary; a project can
+//! override any of them with a file of the same id under
+//! `<project>/.nocodo/prompts/`, so prompts can be tuned and A/B tested
+//! without recompiling.
+//!
+//! A template starts with front matter declaring its version:
+//!
+//! ```text
+//! ---
+//! version: 2
+//! ---
+//! You are the Product Owner at nocodo. {{nocodo_description}}
+//! ```
+//!
+//! `{{name}}` is replaced by a variable and `{{> other/id}}` by another
+//! template. The version of every template used is recorded with the output
+//! (see `PromptRef`), so results can be tied to the prompt that produced them.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::{error::AgentError, nocodo_description::NOCODO_DESCRIPTION};
+
+/// Where a project keeps its prompt overrides, relative to its root.
+pub const PROJECT_PROMPTS_DIR: &str = ".nocodo/prompts";
+/// Version recorded for an override without front matter.
+const UNVERSIONED: &str = "local";
+const MAX_INCLUDE_DEPTH: usize = 4;
+
+const EMBEDDED: &[(&str, &str)] = &[
+    (
+        "backend_engineer",
+        include_str!("../prompts/backend_engineer.md"),
+    ),
+    ("db_engineer", include_str!("../prompts/db_engineer.md")),
+    (
+        "engineering_manager",
+        include_str!("../prompts/engineering_manager.md"),
+    ),
+    (
+        "frontend_engineer",
+        include_str!("../prompts/frontend_engineer.md"),
+    ),
+    (
+        "product_owner/core",
+        include_str!("../prompts/product_owner/core.md"),
+    ),
+    (
+        "product_owner/project_naming",
+        include_str!("../prompts/product_owner/project_naming.md"),
+    ),
+    (
+        "product_owner/requirements_gathering",
+        include_str!("../prompts/product_owner/requirements_gathering.md"),
+    ),
+    (
+        "project_manager/core",
+        include_str!("../prompts/project_manager/core.md"),
+    ),
+    (
+        "project_manager/general",
+        include_str!("../prompts/project_manager/general.md"),
+    ),
+    (
+        "project_manager/init",
+        include_str!("../prompts/project_manager/init.md"),
+    ),
+    (
+        "project_manager/po_handoff",
+        include_str!("../prompts/project_manager/po_handoff.md"),
+    ),
+    (
+        "project_manager/user_session",
+        include_str!("../prompts/project_manager/user_session.md"),
+    ),
+    (
+        "rust_engineer/actix_handler",
+        include_str!("../prompts/rust_engineer/actix_handler.md"),
+    ),
+    (
+        "rust_engineer/diesel_model",
+        include_str!("../prompts/rust_engineer/diesel_model.md"),
+    ),
+    (
+        "rust_engineer/diesel_model_struct",
+        include_str!("../prompts/rust_engineer/diesel_model_struct.md"),
+    ),
+    (
+        "rust_engineer/diesel_model_test",
+        include_str!("../prompts/rust_engineer/diesel_model_test.md"),
+    ),
+    (
+        "rust_engineer/diesel_schema",
+        include_str!("../prompts/rust_engineer/diesel_schema.md"),
+    ),
+    (
+        "rust_engineer/edit_impl_fn",
+        include_str!("../prompts/rust_engineer/edit_impl_fn.md"),
+    ),
+    (
+        "rust_engineer/middleware_extractor",
+        include_str!("../prompts/rust_engineer/middleware_extractor.md"),
+    ),
+    (
+        "rust_engineer/middleware_transform",
+        include_str!("../prompts/rust_engineer/middleware_transform.md"),
+    ),
+    (
+        "stack_reviewer",
+        include_str!("../prompts/stack_reviewer.md"),
+    ),
+    ("ui_designer", include_str!("../prompts/ui_designer.md")),
+];
+
+/// Which template, at which version, produced a prompt. Templates that
+/// include others carry their versions too, e.g. `1+project_manager/core@2`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PromptRef {
+    pub id: String,
+    pub version: String,
+}
+
+impl std::fmt::Display for PromptRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}", self.id, self.version)
+    }
+}
+
+/// A rendered prompt and the template it came from.
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    pub text: String,
+    pub reference: PromptRef,
+}
+
+/// Resolves templates: the project's override if there is one, the embedded
+/// copy otherwise.
+#[derive(Debug, Clone, Default)]
+pub struct PromptLibrary {
+    override_dir: Option<PathBuf>,
+}
+
+impl PromptLibrary {
+    /// Only the templates built into the binary.
+    pub fn embedded() -> Self {
+        Self::default()
+    }
+
+    /// Templates in `dir` (as `<id>.md`) take precedence over embedded ones.
+    pub fn with_overrides(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            override_dir: Some(dir.into()),
+        }
+    }
+
+    /// Overrides from the project's `.nocodo/prompts` directory.
+    pub fn for_project(project_path: impl AsRef<Path>) -> Self {
+        Self::with_overrides(project_path.as_ref().join(PROJECT_PROMPTS_DIR))
+    }
+
+    /// Render template `id` with `vars`. `nocodo_description` is always
+    /// available.
+    pub fn render(&self, id: &str, vars: &[(&str, &str)]) -> Result<Prompt, AgentError> {
+        let mut version = String::new();
+        let text = self.render_into(id, vars, 0, &mut version)?;
+        Ok(Prompt {
+            text,
+            reference: PromptRef {
+                id: id.to_string(),
+                version,
+            },
+        })
+    }
+
+    fn render_into(
+        &self,
+        id: &str,
+        vars: &[(&str, &str)],
+        depth: usize,
+        version: &mut String,
+    ) -> Result<String, AgentError> {
+        let template = self.load(id)?;
+        if depth == 0 {
+            version.push_str(&template.version);
+        } else {
+            version.push_str(&format!("+{}@{}", id, template.version));
+        }
+
+        let mut out = String::with_capacity(template.body.len());
+        let mut rest = template.body.as_str();
+        while let Some(start) = rest.find("{{") {
+            let Some(end) = rest[start..].find("}}") else {
+                break;
+            };
+            out.push_str(&rest[..start]);
+            let tag = rest[start + 2..start + end].trim();
+            match tag.strip_prefix('>') {
+                Some(include) if depth < MAX_INCLUDE_DEPTH => {
+                    out.push_str(&self.render_into(include.trim(), vars, depth + 1, version)?)
+                }
+                Some(include) => {
+                    return Err(AgentError::Config(format!(
+                        "prompt {}: includes nested too deeply at {}",
+                        id,
+                        include.trim()
+                    )))
+                }
+                None => match lookup(tag, vars) {
+                    Some(value) => out.push_str(value),
+                    None => {
+                        log::warn!("[Prompts] {}: no value for {{{{{}}}}}", id, tag);
+                        out.push_str(&rest[start..start + end + 2]);
+                    }
+                },
+            }
+            rest = &rest[start + end + 2..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    fn load(&self, id: &str) -> Result<Template, AgentError> {
+        if let Some(dir) = &self.override_dir {
+            let path = dir.join(format!("{}.md", id));
+            if let Ok(source) = std::fs::read_to_string(&path) {
+                log::info!("[Prompts] {} overridden by {}", id, path.display());
+                return parse(&source)
+                    .map_err(|e| AgentError::Config(format!("prompt {}: {}", path.display(), e)));
+            }
+        }
+        let source = EMBEDDED
+            .iter()
+            .find(|(name, _)| *name == id)
+            .map(|(_, source)| *source)
+            .ok_or_else(|| AgentError::Config(format!("unknown prompt template \"{}\"", id)))?;
+        parse(source).map_err(|e| AgentError::Config(format!("embedded prompt {}: {}", id, e)))
+    }
+}
+
+fn lookup<'a>(name: &str, vars: &[(&str, &'a str)]) -> Option<&'a str> {
+    match vars.iter().find(|(key, _)| *key == name) {
+        Some((_, value)) => Some(value),
+        None if name == "nocodo_description" => Some(NOCODO_DESCRIPTION),
+        None => None,
+    }
+}
+
+struct Template {
+    version: String,
+    body: String,
+}
+
+/// Split off the front matter. A file without any is an unversioned
+/// override; trailing whitespace of the body is dropped.
+fn parse(source: &str) -> Result<Template, String> {
+    let Some(front) = source.strip_prefix("---\n") else {
+        return Ok(Template {
+            version: UNVERSIONED.to_string(),
+            body: source.trim_end().to_string(),
+        });
+    };
+    let (header, body) = front
+        .split_once("\n---\n")
+        .ok_or("front matter is not closed with ---")?;
+    let version = header
+        .lines()
+        .find_map(|line| line.strip_prefix("version:"))
+        .map(|v| v.trim().trim_matches('"').to_string())
+        .filter(|v| !v.is_empty())
+        .ok_or("front matter has no version")?;
+    Ok(Template {
+        version,
+        body: body.trim_end().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_embedded_template_parses() {
+        for (id, _) in EMBEDDED {
+            let prompt = PromptLibrary::embedded().render(id, &[]).unwrap();
+            assert!(!prompt.text.contains("{{>"), "{id}");
+            assert!(!prompt.reference.version.is_empty(), "{id}");
+        }
+    }
+
+    #[test]
+    fn test_includes_and_variables() {
+        let prompt = PromptLibrary::embedded()
+            .render("project_manager/general", &[])
+            .unwrap();
+        assert!(prompt.text.contains(NOCODO_DESCRIPTION));
+        assert!(prompt.text.contains("## Mode: General Session"));
+        assert_eq!(prompt.reference.version, "1+project_manager/core@2");
+
+        let prompt = PromptLibrary::embedded()
+            .render("stack_reviewer", &[("current_notes", "- uses actix")])
+            .unwrap();
+        assert!(prompt.text.contains("- uses actix"));
+    }
+
+    #[test]
+    fn test_project_override_takes_precedence() {
+        let dir = std::env::temp_dir().join(format!("nocodo-prompts-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("project_manager")).unwrap();
+        std::fs::write(
+            dir.join("project_manager/core.md"),
+            "---\nversion: 2-terse\n---\nYou are the PM.\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("ui_designer.md"), "Design forms.").unwrap();
+
+        let library = PromptLibrary::with_overrides(&dir);
+        let prompt = library.render("project_manager/init", &[]).unwrap();
+        assert!(prompt.text.starts_with("You are the PM.\n"));
+        assert_eq!(prompt.reference.version, "1+project_manager/core@2-terse");
+        let prompt = library.render("ui_designer", &[]).unwrap();
+        assert_eq!(prompt.text, "Design forms.");
+        assert_eq!(prompt.reference.version, UNVERSIONED);
+        assert!(library.render("db_engineer", &[]).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_front_matter_requires_version() {
+        assert!(parse("---\nauthor: me\n---\nbody").is_err());
+        assert!(parse("---\nversion: 3\nbody").is_err());
+        assert_eq!(
+            parse("---\nversion: \"3\"\n---\nbody\n").unwrap().version,
+            "3"
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! Stops a running agent from outside its run loop. The loop checks its
+//! `RunControl` before every LLM call and tool execution, and races each LLM
+//! call against it, so a cancel or an exhausted budget takes effect within
+//! one step even when a slow model is still answering.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+
+use crate::error::AgentError;
+
+/// Shared between the run and whoever may stop it; clones control the same
+/// run. The default never stops.
+#[derive(Clone, Debug, Default)]
+pub struct RunControl {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// Why the run was cancelled, once it has been.
+    reason: Mutex<Option<String>>,
+    notify: Notify,
+    started: Option<Instant>,
+    timeout: Option<Duration>,
+    max_turns: Option<u32>,
+    turns: AtomicU32,
+}
+
+impl RunControl {
+    /// A run that stops after `timeout` of wall-clock time or `max_turns` LLM
+    /// calls, whichever comes first; `None` leaves that budget unlimited.
+    pub fn new(timeout: Option<Duration>, max_turns: Option<u32>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                started: Some(Instant::now()),
+                timeout,
+                max_turns,
+                ..Inner::default()
+            }),
+        }
+    }
+
+    /// Ask the run to stop. The first reason wins.
+    pub fn cancel(&self, reason: impl Into<String>) {
+        let mut current = self.inner.reason.lock().unwrap();
+        if current.is_none() {
+            *current = Some(reason.into());
+        }
+        drop(current);
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.reason.lock().unwrap().is_some()
+    }
+
+    /// `Err(AgentError::Cancelled)` once the run was cancelled or is out of
+    /// time. Call before each tool execution.
+    pub fn check(&self) -> Result<(), AgentError> {
+        if let Some(reason) = self.inner.reason.lock().unwrap().clone() {
+            return Err(AgentError::Cancelled(reason));
+        }
+        if let (Some(started), Some(timeout)) = (self.inner.started, self.inner.timeout) {
+            if started.elapsed() >= timeout {
+                return Err(AgentError::Cancelled(timeout_reason(timeout)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Count an LLM call against the turn budget, then `check`. Call before
+    /// each LLM call; turns are counted across fallback models.
+    pub fn next_turn(&self) -> Result<(), AgentError> {
+        let turn = self.inner.turns.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Some(max_turns) = self.inner.max_turns {
+            if turn > max_turns {
+                return Err(AgentError::Cancelled(format!(
+                    "exceeded the budget of {} turns",
+                    max_turns
+                )));
+            }
+        }
+        self.check()
+    }
+
+    /// Run `fut` unless the run is cancelled or times out first, in which
+    /// case `fut` is dropped.
+    pub async fn run<F: Future>(&self, fut: F) -> Result<F::Output, AgentError> {
+        self.check()?;
+        let cancelled = async {
+            loop {
+                let notified = self.inner.notify.notified();
+                if let Err(e) = self.check() {
+                    return e;
+                }
+                notified.await;
+            }
+        };
+        let deadline = async {
+            match (self.inner.started, self.inner.timeout) {
+                (Some(started), Some(timeout)) => {
+                    tokio::time::sleep_until((started + timeout).into()).await;
+                    AgentError::Cancelled(timeout_reason(timeout))
+                }
+                _ => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            output = fut => Ok(output),
+            e = cancelled => Err(e),
+            e = deadline => Err(e),
+        }
+    }
+}
+
+fn timeout_reason(timeout: Duration) -> String {
+    format!("exceeded the run time limit of {}s", timeout.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cancel_interrupts_a_pending_call() {
+        let control = RunControl::default();
+        let remote = control.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            remote.cancel("cancelled by user");
+        });
+        let result = control.run(std::future::pending::<()>()).await;
+        assert!(matches!(result, Err(AgentError::Cancelled(r)) if r == "cancelled by user"));
+        assert!(control.check().is_err());
+    }
+
+    #[tokio::test]
+    async fn timeout_interrupts_a_pending_call() {
+        let control = RunControl::new(Some(Duration::from_millis(20)), None);
+        let result = control.run(std::future::pending::<()>()).await;
+        assert!(matches!(result, Err(AgentError::Cancelled(r)) if r.contains("time limit")));
+    }
+
+    #[test]
+    fn turn_budget() {
+        let control = RunControl::new(None, Some(2));
+        assert!(control.next_turn().is_ok());
+        assert!(control.next_turn().is_ok());
+        assert!(matches!(control.next_turn(), Err(AgentError::Cancelled(_))));
+    }
+}
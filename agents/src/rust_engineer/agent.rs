@@ -1,15 +1,26 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Mutex};
 
 use llm_sdk::{
     client::LlmClient,
     llama_cpp::LlamaCppClient,
-    types::{CompletionRequest, ContentBlock, Message, Role},
+    types::{CompletionRequest, CompletionResponse, ContentBlock, Message, Role},
 };
+use serde::Serialize;
 
-use super::modes::{diesel_model, diesel_model_struct, diesel_schema};
+use super::modes::{
+    actix_handler, diesel_model, diesel_model_struct, diesel_model_test, diesel_schema,
+    edit_impl_fn,
+    middleware::{self, MiddlewareKind},
+};
 use crate::{
-    code_extractor::{extract_struct, find_dependent_types, find_struct_file, list_impl_fns},
+    code_extractor::{
+        extract_impl_fn, extract_struct, find_dependent_types, find_impl_fn_file,
+        find_route_handlers, find_struct_file, find_test_fns, find_trait_impls, list_impl_fns,
+        CodeBlock, ROUTE_MACROS,
+    },
     error::AgentError,
+    prompt_library::{PromptLibrary, PromptRef},
+    run_control::RunControl,
 };
 
 // ---------------------------------------------------------------------------
@@ -18,6 +29,9 @@ use crate::{
 
 #[derive(Debug)]
 pub struct DieselModelFnOutput {
+    pub system_prompt: String,
+    /// Template and version the system prompt was rendered from.
+    pub template: PromptRef,
     pub prompt: String,
     pub raw_response: String,
     /// Code extracted from the response (think-stripped, fence-unwrapped).
@@ -27,6 +41,8 @@ pub struct DieselModelFnOutput {
 #[derive(Debug)]
 pub struct DieselModelStructOutput {
     pub system_prompt: String,
+    /// Template and version the system prompt was rendered from.
+    pub template: PromptRef,
     pub prompt: String,
     pub raw_response: String,
     /// Code extracted from the response (think-stripped, fence-unwrapped).
@@ -38,6 +54,8 @@ pub struct DieselModelStructOutput {
 #[derive(Debug)]
 pub struct DieselSchemaOutput {
     pub system_prompt: String,
+    /// Template and version the system prompt was rendered from.
+    pub template: PromptRef,
     pub prompt: String,
     pub raw_response: String,
     /// Code extracted from the response (think-stripped, fence-unwrapped).
@@ -46,12 +64,77 @@ pub struct DieselSchemaOutput {
     pub file_path: Option<String>,
 }
 
+#[derive(Debug)]
+pub struct ActixHandlerOutput {
+    pub system_prompt: String,
+    /// Template and version the system prompt was rendered from.
+    pub template: PromptRef,
+    pub prompt: String,
+    pub raw_response: String,
+    /// Code extracted from the response, with the route attribute normalized and
+    /// actix imports prepended.
+    pub code: Option<String>,
+    /// Relative path of the handler module the code belongs in.
+    pub file_path: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct MiddlewareOutput {
+    pub kind: MiddlewareKind,
+    pub system_prompt: String,
+    /// Template and version the system prompt was rendered from.
+    pub template: PromptRef,
+    pub prompt: String,
+    pub raw_response: String,
+    /// Code extracted from the response, with deterministic imports prepended.
+    pub code: Option<String>,
+    /// Relative file path if written to disk (None when `apply` is false).
+    pub file_path: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct DieselModelTestOutput {
+    pub system_prompt: String,
+    /// Template and version the system prompt was rendered from.
+    pub template: PromptRef,
+    pub prompt: String,
+    pub raw_response: String,
+    /// The generated `#[test]` fn (think-stripped, fence-unwrapped, no imports).
+    pub code: Option<String>,
+    /// Relative path of the model file whose test module receives the test.
+    pub file_path: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct EditImplFnOutput {
+    pub system_prompt: String,
+    /// Template and version the system prompt was rendered from.
+    pub template: PromptRef,
+    pub prompt: String,
+    pub raw_response: String,
+    /// The updated fn, re-indented to splice back in place.
+    pub code: Option<String>,
+    /// Unified diff of the fn before and after the edit.
+    pub diff: Option<String>,
+    /// Relative path of the file containing the fn.
+    pub file_path: Option<String>,
+    /// The fn as it was in the file when it was extracted for the edit.
+    pub original: String,
+}
+
 #[derive(Debug)]
 pub enum RustEngineerResult {
     Code(String),
     Empty,
 }
 
+/// Token counts summed over every LLM call an agent has made.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
 // ---------------------------------------------------------------------------
 // Agent
 // ---------------------------------------------------------------------------
@@ -60,6 +143,9 @@ pub struct RustEngineerAgent {
     client: LlamaCppClient,
     model: String,
     project_path: PathBuf,
+    usage: Mutex<TokenUsage>,
+    prompts: PromptLibrary,
+    control: RunControl,
 }
 
 impl RustEngineerAgent {
@@ -77,9 +163,34 @@ impl RustEngineerAgent {
             client,
             model: model.into(),
             project_path: project_path.into(),
+            usage: Mutex::new(TokenUsage::default()),
+            prompts: PromptLibrary::embedded(),
+            control: RunControl::default(),
         })
     }
 
+    /// Resolve system prompts through `prompts`, e.g. to pick up a project's
+    /// overrides. Embedded templates are used otherwise.
+    pub fn with_prompts(mut self, prompts: PromptLibrary) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    /// Let the caller cancel the run or bound its time and LLM calls.
+    pub fn with_run_control(mut self, control: RunControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Tokens used by all calls this agent has made so far.
+    pub fn usage(&self) -> TokenUsage {
+        *self.usage.lock().unwrap()
+    }
+
     // -----------------------------------------------------------------------
     // Mode: Diesel model impl function
     // -----------------------------------------------------------------------
@@ -121,6 +232,8 @@ impl RustEngineerAgent {
             dependent_types.len()
         );
 
+        let template = diesel_model::build_system_prompt(&self.prompts)?;
+        let system_prompt = template.text;
         let (prompt, table_name) =
             diesel_model::build_prompt(&struct_block.source, &examples, &dependent_types, fn_name);
 
@@ -135,7 +248,7 @@ impl RustEngineerAgent {
             }],
             max_tokens: 512,
             model: self.model.clone(),
-            system: None,
+            system: Some(system_prompt.clone()),
             temperature: Some(0.2),
             top_p: None,
             stop_sequences: None,
@@ -144,11 +257,7 @@ impl RustEngineerAgent {
             response_format: None,
         };
 
-        let response = self
-            .client
-            .complete(request)
-            .await
-            .map_err(AgentError::Llm)?;
+        let response = self.send(request).await?;
 
         let raw_response = response
             .content
@@ -164,6 +273,8 @@ impl RustEngineerAgent {
 
         let code = prepend_imports(&extract_code(&raw_response), &table_name);
         Ok(DieselModelFnOutput {
+            system_prompt,
+            template: template.reference,
             prompt,
             raw_response,
             code: if code.trim().is_empty() {
@@ -186,7 +297,8 @@ impl RustEngineerAgent {
         &self,
         user_prompt: &str,
     ) -> Result<DieselModelStructOutput, AgentError> {
-        let system_prompt = diesel_model_struct::build_system_prompt();
+        let template = diesel_model_struct::build_system_prompt(&self.prompts)?;
+        let system_prompt = template.text;
         let prompt = user_prompt.trim().to_string();
 
         log::info!(
@@ -215,11 +327,7 @@ impl RustEngineerAgent {
             response_format: None,
         };
 
-        let response = self
-            .client
-            .complete(request)
-            .await
-            .map_err(AgentError::Llm)?;
+        let response = self.send(request).await?;
 
         let raw_response = response
             .content
@@ -239,6 +347,7 @@ impl RustEngineerAgent {
         let code = strip_imports(&extract_code(&raw_response));
         Ok(DieselModelStructOutput {
             system_prompt,
+            template: template.reference,
             prompt,
             raw_response,
             code: if code.trim().is_empty() {
@@ -273,7 +382,8 @@ impl RustEngineerAgent {
     /// The caller supplies the task prompt, including the current table block
     /// when updating. The model returns exactly one `diesel::table!` block.
     pub async fn diesel_schema(&self, user_prompt: &str) -> Result<DieselSchemaOutput, AgentError> {
-        let system_prompt = diesel_schema::build_system_prompt();
+        let template = diesel_schema::build_system_prompt(&self.prompts)?;
+        let system_prompt = template.text;
         let prompt = user_prompt.trim().to_string();
 
         log::info!(
@@ -302,11 +412,7 @@ impl RustEngineerAgent {
             response_format: None,
         };
 
-        let response = self
-            .client
-            .complete(request)
-            .await
-            .map_err(AgentError::Llm)?;
+        let response = self.send(request).await?;
 
         let raw_response = response
             .content
@@ -326,6 +432,7 @@ impl RustEngineerAgent {
         let code = strip_imports(&extract_code(&raw_response));
         Ok(DieselSchemaOutput {
             system_prompt,
+            template: template.reference,
             prompt,
             raw_response,
             code: if code.trim().is_empty() {
@@ -350,6 +457,581 @@ impl RustEngineerAgent {
         }
         Ok(output)
     }
+
+    // -----------------------------------------------------------------------
+    // Mode: Actix Web route handler
+    // -----------------------------------------------------------------------
+
+    /// Generate one Actix Web route handler for `struct_name` by example.
+    ///
+    /// Existing `#[get]`/`#[post]`/… handlers in the project are used as style
+    /// examples, the struct's impl fns are listed as the data-access calls the
+    /// handler may make, and DTOs referenced by the struct or the examples are
+    /// resolved via `find_dependent_types`. The route attribute and actix
+    /// imports are fixed up deterministically after generation.
+    pub async fn actix_handler(
+        &self,
+        struct_name: &str,
+        handler_name: &str,
+        method: &str,
+        route_path: &str,
+    ) -> Result<ActixHandlerOutput, AgentError> {
+        let method = method.trim().to_lowercase();
+        if !ROUTE_MACROS.contains(&method.as_str()) {
+            return Err(AgentError::Other(format!(
+                "unsupported HTTP method `{}` (expected one of: {})",
+                method,
+                ROUTE_MACROS.join(", ")
+            )));
+        }
+
+        let struct_file = find_struct_file(&self.project_path, struct_name)
+            .map_err(AgentError::Other)?
+            .ok_or_else(|| {
+                AgentError::Other(format!("struct `{}` not found in project", struct_name))
+            })?;
+
+        let struct_block = extract_struct(&struct_file, struct_name)
+            .map_err(AgentError::Other)?
+            .ok_or_else(|| {
+                AgentError::Other(format!("could not extract struct `{}`", struct_name))
+            })?;
+
+        let model_fns = list_impl_fns(&struct_file, struct_name).map_err(AgentError::Other)?;
+        let handlers = find_route_handlers(&self.project_path).map_err(AgentError::Other)?;
+        let examples = actix_handler::select_examples(&handlers, struct_name, &method);
+
+        // DTOs come from the struct's own fields and from the example handlers'
+        // extractors (`web::Json<NewFoo>`); the model struct itself is already shown.
+        let mut dependent_types =
+            find_dependent_types(&self.project_path, &struct_file, &struct_block.source)
+                .map_err(AgentError::Other)?;
+        for example in &examples {
+            let deps = find_dependent_types(&self.project_path, &example.file, &example.source)
+                .map_err(AgentError::Other)?;
+            dependent_types.extend(deps);
+        }
+        dedup_blocks(&mut dependent_types);
+        dependent_types.retain(|d| d.source != struct_block.source);
+
+        let file_path = pick_handler_file(&handlers, struct_name).map(|f| {
+            f.strip_prefix(&self.project_path)
+                .unwrap_or(f)
+                .to_string_lossy()
+                .into_owned()
+        });
+
+        log::info!(
+            "[RustEngineer:actix_handler] struct={} handler={} {} {} examples={} model_fns={} dependent_types={}",
+            struct_name,
+            handler_name,
+            method,
+            route_path,
+            examples.len(),
+            model_fns.len(),
+            dependent_types.len()
+        );
+
+        let template = actix_handler::build_system_prompt(&self.prompts)?;
+        let system_prompt = template.text;
+        let prompt = actix_handler::build_prompt(
+            &struct_block.source,
+            &model_fns,
+            &dependent_types,
+            &examples,
+            handler_name,
+            &method,
+            route_path,
+        );
+
+        let raw_response = self
+            .complete_text(Some(system_prompt.clone()), &prompt, 768)
+            .await?;
+
+        log::info!(
+            "[RustEngineer:actix_handler] raw_len={}",
+            raw_response.len()
+        );
+
+        let body = strip_imports(&extract_code(&raw_response));
+        let code = if body.trim().is_empty() {
+            None
+        } else {
+            // A handler under another name would be written and registered
+            // where nothing expects it.
+            let generated = schema_codegen::parse_fn_name(&body);
+            if generated.as_deref() != Some(handler_name) {
+                return Err(AgentError::Other(format!(
+                    "generated handler is named `{}`, expected `{}`",
+                    generated.as_deref().unwrap_or("?"),
+                    handler_name
+                )));
+            }
+            let struct_module = actix_handler::module_path(&self.project_path, &struct_file);
+            Some(format!(
+                "{}\n{}",
+                actix_handler::required_imports(&method, struct_module.as_deref(), struct_name),
+                actix_handler::normalize_route_attribute(&body, &method, route_path)
+            ))
+        };
+
+        Ok(ActixHandlerOutput {
+            system_prompt,
+            template: template.reference,
+            prompt,
+            raw_response,
+            code,
+            file_path,
+        })
+    }
+
+    /// Like `actix_handler` but also writes the handler into its module and
+    /// registers it in the `configure` function via `code_writer::write_actix_handler`.
+    pub async fn actix_handler_write(
+        &self,
+        struct_name: &str,
+        handler_name: &str,
+        method: &str,
+        route_path: &str,
+    ) -> Result<ActixHandlerOutput, AgentError> {
+        let output = self
+            .actix_handler(struct_name, handler_name, method, route_path)
+            .await?;
+        if let Some(ref code) = output.code {
+            let Some(ref file_path) = output.file_path else {
+                return Err(AgentError::Other(
+                    "no existing route handlers found; cannot choose a module to write into"
+                        .to_string(),
+                ));
+            };
+            crate::code_writer::write_actix_handler(&self.project_path, file_path, code)?;
+        }
+        Ok(output)
+    }
+
+    // -----------------------------------------------------------------------
+    // Mode: Actix middleware / auth guard
+    // -----------------------------------------------------------------------
+
+    /// Generate one Actix `Transform`/`Service` middleware or `FromRequest`
+    /// extractor named `name`, following `description`.
+    ///
+    /// Modules in the project that already implement the same trait (the
+    /// template's auth code) are sent whole as examples.
+    pub async fn middleware(
+        &self,
+        name: &str,
+        kind: MiddlewareKind,
+        description: &str,
+    ) -> Result<MiddlewareOutput, AgentError> {
+        let impls =
+            find_trait_impls(&self.project_path, kind.trait_name()).map_err(AgentError::Other)?;
+
+        let mut files: Vec<&PathBuf> = Vec::new();
+        for block in &impls {
+            if !files.contains(&&block.file) {
+                files.push(&block.file);
+            }
+        }
+        let examples: Vec<String> = files
+            .into_iter()
+            .take(2)
+            .filter_map(|f| std::fs::read_to_string(f).ok())
+            .map(|src| strip_imports(&src))
+            .collect();
+
+        log::info!(
+            "[RustEngineer:middleware] name={} kind={} examples={}",
+            name,
+            kind.as_str(),
+            examples.len()
+        );
+
+        let template = middleware::build_system_prompt(&self.prompts, kind)?;
+        let system_prompt = template.text;
+        let prompt = middleware::build_prompt(kind, name, description, &examples);
+        let raw_response = self
+            .complete_text(Some(system_prompt.clone()), &prompt, 1024)
+            .await?;
+
+        log::info!("[RustEngineer:middleware] raw_len={}", raw_response.len());
+
+        let body = strip_imports(&extract_code(&raw_response));
+        let code = if body.trim().is_empty() {
+            None
+        } else {
+            Some(format!(
+                "{}\n{}\n",
+                middleware::required_imports(kind),
+                body.trim()
+            ))
+        };
+
+        Ok(MiddlewareOutput {
+            kind,
+            system_prompt,
+            template: template.reference,
+            prompt,
+            raw_response,
+            code,
+            file_path: None,
+        })
+    }
+
+    /// Like `middleware` but also writes the module to disk via
+    /// `code_writer::write_actix_middleware` and declares it. Only with
+    /// `wrap_app` is `Transform` middleware wired into the `App` builder with
+    /// `.wrap(...)`, guarding every route; otherwise wrapping it around the
+    /// right `web::scope` is left to the project.
+    pub async fn middleware_write(
+        &self,
+        name: &str,
+        kind: MiddlewareKind,
+        description: &str,
+        wrap_app: bool,
+    ) -> Result<MiddlewareOutput, AgentError> {
+        if wrap_app && kind != MiddlewareKind::Transform {
+            return Err(AgentError::Other(
+                "wrap_app only applies to transform middleware".to_string(),
+            ));
+        }
+        let mut output = self.middleware(name, kind, description).await?;
+        if let Some(ref code) = output.code {
+            let file_path = crate::code_writer::write_actix_middleware(
+                &self.project_path,
+                name,
+                code,
+                wrap_app,
+            )?;
+            output.file_path = Some(file_path);
+        }
+        Ok(output)
+    }
+
+    // -----------------------------------------------------------------------
+    // Mode: Diesel model fn test
+    // -----------------------------------------------------------------------
+
+    /// Generate a `#[test]` for an existing model fn. The test uses an
+    /// in-memory SQLite pool with embedded migrations (`test_pool()`), inserts a
+    /// fixture row built from the struct's fields and asserts on the fn result.
+    /// Existing tests in the project are used as style examples.
+    pub async fn diesel_model_test(
+        &self,
+        struct_name: &str,
+        fn_name: &str,
+    ) -> Result<DieselModelTestOutput, AgentError> {
+        let fn_file = find_impl_fn_file(&self.project_path, struct_name, fn_name)
+            .map_err(AgentError::Other)?
+            .ok_or_else(|| {
+                AgentError::Other(format!(
+                    "fn `{}::{}` not found in project",
+                    struct_name, fn_name
+                ))
+            })?;
+
+        let fn_block = extract_impl_fn(&fn_file, struct_name, fn_name)
+            .map_err(AgentError::Other)?
+            .ok_or_else(|| {
+                AgentError::Other(format!(
+                    "could not extract fn `{}::{}`",
+                    struct_name, fn_name
+                ))
+            })?;
+
+        let struct_file = find_struct_file(&self.project_path, struct_name)
+            .map_err(AgentError::Other)?
+            .ok_or_else(|| {
+                AgentError::Other(format!("struct `{}` not found in project", struct_name))
+            })?;
+
+        let struct_block = extract_struct(&struct_file, struct_name)
+            .map_err(AgentError::Other)?
+            .ok_or_else(|| {
+                AgentError::Other(format!("could not extract struct `{}`", struct_name))
+            })?;
+
+        // Prefer tests from the model's own file, then tests touching the same
+        // struct, then tests that already use the in-memory pool helper.
+        let tests = find_test_fns(&self.project_path).map_err(AgentError::Other)?;
+        let mut ranked: Vec<&CodeBlock> = tests.iter().collect();
+        ranked.sort_by_key(|t| {
+            (
+                t.file != fn_file,
+                !t.source.contains(struct_name),
+                !t.source.contains("test_pool"),
+            )
+        });
+        let examples: Vec<&CodeBlock> = ranked.into_iter().take(2).collect();
+
+        log::info!(
+            "[RustEngineer:diesel_model_test] struct={} fn={} examples={}",
+            struct_name,
+            fn_name,
+            examples.len()
+        );
+
+        let template = diesel_model_test::build_system_prompt(&self.prompts)?;
+        let system_prompt = template.text;
+        let prompt = diesel_model_test::build_prompt(
+            &struct_block.source,
+            &fn_block.source,
+            &examples,
+            fn_name,
+        );
+        let raw_response = self
+            .complete_text(Some(system_prompt.clone()), &prompt, 768)
+            .await?;
+
+        log::info!(
+            "[RustEngineer:diesel_model_test] raw_len={}",
+            raw_response.len()
+        );
+
+        let code = strip_imports(&extract_code(&raw_response));
+        Ok(DieselModelTestOutput {
+            system_prompt,
+            template: template.reference,
+            prompt,
+            raw_response,
+            code: if code.trim().is_empty() {
+                None
+            } else {
+                Some(code.trim().to_string())
+            },
+            file_path: Some(
+                fn_file
+                    .strip_prefix(&self.project_path)
+                    .unwrap_or(&fn_file)
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+        })
+    }
+
+    /// Like `diesel_model_test` but also adds the test to the model file's
+    /// `#[cfg(test)]` module via `code_writer::write_model_test`.
+    pub async fn diesel_model_test_write(
+        &self,
+        struct_name: &str,
+        fn_name: &str,
+    ) -> Result<DieselModelTestOutput, AgentError> {
+        let output = self.diesel_model_test(struct_name, fn_name).await?;
+        if let (Some(code), Some(file_path)) = (&output.code, &output.file_path) {
+            crate::code_writer::write_model_test(&self.project_path, file_path, code)?;
+        }
+        Ok(output)
+    }
+
+    // -----------------------------------------------------------------------
+    // Mode: in-place edit of an existing impl function
+    // -----------------------------------------------------------------------
+
+    /// Edit an existing impl fn according to `change` (e.g. "also filter by
+    /// status"). The fn is located with `find_impl_fn_file`/`extract_impl_fn`,
+    /// the model returns the whole updated fn, and the result must keep the
+    /// same name. The output carries a diff against the current source.
+    pub async fn edit_impl_fn(
+        &self,
+        struct_name: &str,
+        fn_name: &str,
+        change: &str,
+    ) -> Result<EditImplFnOutput, AgentError> {
+        let (_, output) = self
+            .edit_impl_fn_inner(struct_name, fn_name, change)
+            .await?;
+        Ok(output)
+    }
+
+    /// Like `edit_impl_fn` but also splices the updated fn back into its file at
+    /// the original byte range via `code_writer::replace_code_block`.
+    pub async fn edit_impl_fn_write(
+        &self,
+        struct_name: &str,
+        fn_name: &str,
+        change: &str,
+    ) -> Result<EditImplFnOutput, AgentError> {
+        let (fn_block, output) = self
+            .edit_impl_fn_inner(struct_name, fn_name, change)
+            .await?;
+        if let Some(ref code) = output.code {
+            crate::code_writer::replace_code_block(&self.project_path, &fn_block, code)?;
+        }
+        Ok(output)
+    }
+
+    async fn edit_impl_fn_inner(
+        &self,
+        struct_name: &str,
+        fn_name: &str,
+        change: &str,
+    ) -> Result<(CodeBlock, EditImplFnOutput), AgentError> {
+        let fn_file = find_impl_fn_file(&self.project_path, struct_name, fn_name)
+            .map_err(AgentError::Other)?
+            .ok_or_else(|| {
+                AgentError::Other(format!(
+                    "fn `{}::{}` not found in project",
+                    struct_name, fn_name
+                ))
+            })?;
+
+        let fn_block = extract_impl_fn(&fn_file, struct_name, fn_name)
+            .map_err(AgentError::Other)?
+            .ok_or_else(|| {
+                AgentError::Other(format!(
+                    "could not extract fn `{}::{}`",
+                    struct_name, fn_name
+                ))
+            })?;
+
+        let struct_file = find_struct_file(&self.project_path, struct_name)
+            .map_err(AgentError::Other)?
+            .unwrap_or_else(|| fn_file.clone());
+        let struct_block = extract_struct(&struct_file, struct_name).map_err(AgentError::Other)?;
+        let struct_code = struct_block
+            .as_ref()
+            .map(|b| b.source.as_str())
+            .unwrap_or_default();
+
+        let dependent_types = find_dependent_types(&self.project_path, &struct_file, struct_code)
+            .map_err(AgentError::Other)?;
+
+        let file_src = std::fs::read_to_string(&fn_file)
+            .map_err(|e| AgentError::Other(format!("read {:?}: {}", fn_file, e)))?;
+        let column = edit_impl_fn::column_of(&file_src, fn_block.start_byte);
+        let current_fn = edit_impl_fn::dedent(&fn_block.source, column);
+
+        log::info!(
+            "[RustEngineer:edit_impl_fn] struct={} fn={} lines={}-{} dependent_types={}",
+            struct_name,
+            fn_name,
+            fn_block.start_line,
+            fn_block.end_line,
+            dependent_types.len()
+        );
+
+        let template = edit_impl_fn::build_system_prompt(&self.prompts)?;
+        let system_prompt = template.text;
+        let prompt =
+            edit_impl_fn::build_prompt(struct_code, &current_fn, &dependent_types, fn_name, change);
+        let raw_response = self
+            .complete_text(Some(system_prompt.clone()), &prompt, 768)
+            .await?;
+
+        log::info!("[RustEngineer:edit_impl_fn] raw_len={}", raw_response.len());
+
+        let file_path = fn_file
+            .strip_prefix(&self.project_path)
+            .unwrap_or(&fn_file)
+            .to_string_lossy()
+            .into_owned();
+
+        let body = edit_impl_fn::strip_leading_attrs(&strip_imports(&extract_code(&raw_response)));
+        let (code, diff) = if body.trim().is_empty() {
+            (None, None)
+        } else {
+            let new_name = schema_codegen::parse_fn_name(&body);
+            if new_name.as_deref() != Some(fn_name) {
+                return Err(AgentError::Other(format!(
+                    "edited fn must keep the name `{}`, got `{}`",
+                    fn_name,
+                    new_name.unwrap_or_default()
+                )));
+            }
+            let code = edit_impl_fn::reindent(body.trim(), column);
+            let diff = edit_impl_fn::unified_diff(
+                &file_path,
+                fn_block.start_line,
+                &fn_block.source,
+                &code,
+            );
+            (Some(code), Some(diff))
+        };
+
+        let original = fn_block.source.clone();
+        Ok((
+            fn_block,
+            EditImplFnOutput {
+                system_prompt,
+                template: template.reference,
+                prompt,
+                raw_response,
+                code,
+                diff,
+                file_path: Some(file_path),
+                original,
+            },
+        ))
+    }
+
+    /// Send one request within the run's turn budget and record its token usage.
+    async fn send(&self, request: CompletionRequest) -> Result<CompletionResponse, AgentError> {
+        self.control.next_turn()?;
+        let response = self
+            .control
+            .run(self.client.complete(request))
+            .await?
+            .map_err(AgentError::Llm)?;
+        let mut usage = self.usage.lock().unwrap();
+        usage.input_tokens += u64::from(response.usage.input_tokens);
+        usage.output_tokens += u64::from(response.usage.output_tokens);
+        Ok(response)
+    }
+
+    /// Run a single-shot completion and return the concatenated text blocks.
+    async fn complete_text(
+        &self,
+        system: Option<String>,
+        prompt: &str,
+        max_tokens: u32,
+    ) -> Result<String, AgentError> {
+        let request = CompletionRequest {
+            messages: vec![Message {
+                role: Role::User,
+                content: vec![ContentBlock::Text {
+                    text: prompt.to_string(),
+                }],
+                tool_call_id: None,
+                tool_name: None,
+            }],
+            max_tokens,
+            model: self.model.clone(),
+            system,
+            temperature: Some(0.2),
+            top_p: None,
+            stop_sequences: None,
+            tools: None,
+            tool_choice: None,
+            response_format: None,
+        };
+
+        let response = self.send(request).await?;
+
+        Ok(response
+            .content
+            .iter()
+            .filter_map(|b| match b {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(""))
+    }
+}
+
+/// The module a new handler for `struct_name` belongs in: the file of an existing
+/// handler that mentions the struct, else the first file that has any handlers.
+fn pick_handler_file<'a>(handlers: &'a [CodeBlock], struct_name: &str) -> Option<&'a PathBuf> {
+    handlers
+        .iter()
+        .find(|h| h.source.contains(struct_name))
+        .or_else(|| handlers.first())
+        .map(|h| &h.file)
+}
+
+fn dedup_blocks(blocks: &mut Vec<CodeBlock>) {
+    let mut seen = std::collections::HashSet::new();
+    blocks.retain(|b| seen.insert((b.file.clone(), b.start_line)));
 }
 
 // ---------------------------------------------------------------------------
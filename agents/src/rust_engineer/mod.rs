@@ -1,7 +1,11 @@
 mod agent;
 mod modes;
+mod task;
 
 pub use agent::{
-    DieselModelFnOutput, DieselModelStructOutput, DieselSchemaOutput, RustEngineerAgent,
-    RustEngineerResult,
+    ActixHandlerOutput, DieselModelFnOutput, DieselModelStructOutput, DieselModelTestOutput,
+    DieselSchemaOutput, EditImplFnOutput, MiddlewareOutput, RustEngineerAgent, RustEngineerResult,
+    TokenUsage,
 };
+pub use modes::middleware::MiddlewareKind;
+pub use task::{ProposedCode, RustEngineerTaskOutput, RustEngineerTaskParams};
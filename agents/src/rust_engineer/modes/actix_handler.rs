@@ -0,0 +1,181 @@
+use std::path::Path;
+
+use crate::{
+    code_extractor::{route_macro_name, CodeBlock},
+    error::AgentError,
+    prompt_library::{Prompt, PromptLibrary},
+};
+
+/// Format the route attribute for a handler, e.g. `#[get("/contacts/{id}")]`.
+pub fn route_attribute(method: &str, path: &str) -> String {
+    format!("#[{method}(\"{path}\")]")
+}
+
+/// Derive the `crate::…` module path of a source file, e.g.
+/// `backend/src/models/contact.rs` → `crate::models::contact`.
+pub fn module_path(project_root: &Path, file: &Path) -> Option<String> {
+    let rel = file.strip_prefix(project_root).unwrap_or(file);
+    let parts: Vec<String> = rel
+        .with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    let src = parts.iter().rposition(|p| p == "src")?;
+    let mut segments: Vec<&str> = parts[src + 1..].iter().map(String::as_str).collect();
+    if matches!(segments.last(), Some(&"mod") | Some(&"lib") | Some(&"main")) {
+        segments.pop();
+    }
+    if segments.is_empty() {
+        return Some("crate".to_string());
+    }
+    Some(format!("crate::{}", segments.join("::")))
+}
+
+/// The deterministic import block prepended to a generated handler.
+pub fn required_imports(method: &str, struct_module: Option<&str>, struct_name: &str) -> String {
+    let mut imports = format!("use actix_web::{{{method}, web, HttpResponse, Responder}};\n");
+    if let Some(module) = struct_module {
+        imports.push_str(&format!("use {module}::{struct_name};\n"));
+    }
+    imports
+}
+
+/// Replace whatever route attribute the model wrote with the requested one, so
+/// the verb and path always match what the caller asked for.
+pub fn normalize_route_attribute(code: &str, method: &str, path: &str) -> String {
+    let body = code
+        .lines()
+        .filter(|line| route_macro_name(line).is_none())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let body = body.trim();
+
+    // Keep doc comments above the attribute, as rustfmt would.
+    let mut doc = Vec::new();
+    let mut rest = Vec::new();
+    for line in body.lines() {
+        if rest.is_empty() && line.trim_start().starts_with("///") {
+            doc.push(line);
+        } else {
+            rest.push(line);
+        }
+    }
+
+    let mut out = String::new();
+    for line in doc {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str(&route_attribute(method, path));
+    out.push('\n');
+    out.push_str(&rest.join("\n"));
+    out
+}
+
+/// Pick up to three example handlers: those that mention the struct first, then
+/// those using the same HTTP verb, then anything else.
+pub fn select_examples<'a>(
+    handlers: &'a [CodeBlock],
+    struct_name: &str,
+    method: &str,
+) -> Vec<&'a CodeBlock> {
+    let same_verb = |h: &CodeBlock| {
+        h.source
+            .lines()
+            .any(|line| route_macro_name(line) == Some(method))
+    };
+
+    let mut ranked: Vec<&CodeBlock> = handlers.iter().collect();
+    ranked.sort_by_key(|h| (!h.source.contains(struct_name), !same_verb(h)));
+    ranked.into_iter().take(3).collect()
+}
+
+/// Keep only the signature line(s) of a model fn — enough for the handler to call it.
+pub fn fn_signature(source: &str) -> String {
+    let sig = match source.find('{') {
+        Some(end) => &source[..end],
+        None => source,
+    };
+    let sig = sig
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("#["))
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("{};", sig.trim_end())
+}
+
+/// Build the system prompt for generating one Actix Web route handler: the
+/// handler rules and one example per common verb.
+///
+/// Template: `prompts/rust_engineer/actix_handler.md`.
+pub fn build_system_prompt(prompts: &PromptLibrary) -> Result<Prompt, AgentError> {
+    prompts.render("rust_engineer/actix_handler", &[])
+}
+
+/// Build the user prompt for generating one Actix Web route handler.
+///
+/// Like `diesel_model::build_prompt`, this is example-driven for small local
+/// models: the project's own handlers set the style, and the model fns show
+/// which data-access calls exist. Imports and the route attribute are fixed up
+/// deterministically by the caller.
+pub fn build_prompt(
+    struct_code: &str,
+    model_fns: &[CodeBlock],
+    dependent_types: &[CodeBlock],
+    example_handlers: &[&CodeBlock],
+    handler_name: &str,
+    method: &str,
+    path: &str,
+) -> String {
+    let mut prompt = String::new();
+
+    // ── The target struct ──────────────────────────────────────────────────
+    prompt.push_str("## The Model\n\n```rust\n");
+    prompt.push_str(struct_code);
+    prompt.push_str("\n```\n\n");
+
+    // ── Model fns the handler may call ─────────────────────────────────────
+    if !model_fns.is_empty() {
+        let struct_name =
+            schema_codegen::parse_struct_name(struct_code).unwrap_or_else(|| "Model".to_string());
+        prompt.push_str(&format!(
+            "## Model Functions (call as `{struct_name}::fn_name(...)`)\n\n```rust\n"
+        ));
+        for f in model_fns {
+            prompt.push_str(&fn_signature(&f.source));
+            prompt.push('\n');
+        }
+        prompt.push_str("```\n\n");
+    }
+
+    // ── DTOs and enums ─────────────────────────────────────────────────────
+    if !dependent_types.is_empty() {
+        prompt.push_str("## Related Types\n\n");
+        for dep in dependent_types {
+            prompt.push_str(&format!(
+                "From `{}`:\n```rust\n{}\n```\n\n",
+                dep.file.file_name().unwrap_or_default().to_string_lossy(),
+                dep.source
+            ));
+        }
+    }
+
+    // ── Existing handlers (style examples) ─────────────────────────────────
+    if !example_handlers.is_empty() {
+        prompt.push_str("Existing handlers in this project (match this style):\n```rust\n");
+        for h in example_handlers {
+            prompt.push_str(&h.source);
+            prompt.push_str("\n\n");
+        }
+        prompt.push_str("```\n\n");
+    }
+
+    // ── Final instruction ──────────────────────────────────────────────────
+    prompt.push_str("## Task\n\n");
+    prompt.push_str(&format!(
+        "Write the handler `{handler_name}` for the model above, with the attribute `{}`. Return ONLY the function with its attribute. No imports, no explanation, no markdown fences.",
+        route_attribute(method, path)
+    ));
+
+    prompt
+}
@@ -1,127 +1,13 @@
+use crate::{
+    error::AgentError,
+    prompt_library::{Prompt, PromptLibrary},
+};
+
 /// Build a compact system prompt for creating or updating one Diesel model
 /// struct. The current struct, if any, is supplied by the caller in the user
 /// prompt so this prompt can stay stable enough for tiny local models.
-pub fn build_system_prompt() -> String {
-    r#"You write one Rust Diesel model struct for SQLite.
-
-Return ONLY one struct item. No imports. No impl. No module code. No explanation. No markdown.
-You are always creating or updating exactly ONE model struct. Do not create helper structs unless the user explicitly asks for that one helper struct.
-If updating, preserve existing fields/derives/attrs unless the user asks to change them.
-
-Diesel rules:
-- Read model: derive Queryable, Selectable.
-- Add Identifiable when the table has a primary key. Default key is id.
-- Add #[diesel(primary_key(a, b))] only for non-id or composite keys.
-- Add Associations only with one or more #[diesel(belongs_to(Parent))] attrs.
-- Always add #[diesel(table_name = table_name)] for Diesel model structs.
-- For SQLite read models add #[diesel(check_for_backend(diesel::sqlite::Sqlite))].
-- Insertable and AsChangeset are usually separate input structs; include them only if user asks for that kind of struct.
-- For AsChangeset structs, do not include primary key fields unless the user asks for them.
-- Nullable<T> columns become Option<T>.
-- Common SQLite types: Integer -> i32, BigInt -> i64, Text/Varchar -> String, Bool -> bool, Timestamp -> chrono::NaiveDateTime.
-
-Relation rules:
-- Diesel associations are declared on the CHILD struct with #[diesel(belongs_to(Parent))].
-- Parent structs do NOT get has_many or has_one attributes. Diesel has no #[diesel(has_many(...))] model attribute.
-- If child has parent_id and parent type is Parent, use #[diesel(belongs_to(Parent))].
-- If the foreign key is not parent_id, use #[diesel(belongs_to(Parent, foreign_key = custom_key))].
-- If the struct joins two parents, derive Associations and add one belongs_to attr per parent.
-- Many-to-many uses a join model with composite primary key and belongs_to for both parents.
-- The related parent structs must exist elsewhere; do not output them.
-
-Examples:
-Basic read model:
-#[derive(Queryable, Selectable, Identifiable, Debug, Clone)]
-#[diesel(table_name = users)]
-#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
-pub struct User {
-    pub id: i32,
-    pub email: String,
-    pub display_name: Option<String>,
-}
-
-Insert input model:
-#[derive(Insertable)]
-#[diesel(table_name = users)]
-pub struct NewUser<'a> {
-    pub email: &'a str,
-    pub display_name: Option<&'a str>,
-}
-
-Update input model:
-#[derive(AsChangeset)]
-#[diesel(table_name = users)]
-pub struct UpdateUser {
-    pub email: Option<String>,
-    pub display_name: Option<String>,
-}
-
-Child belongs to parent by default user_id:
-#[derive(Queryable, Selectable, Identifiable, Associations, Debug, Clone)]
-#[diesel(belongs_to(User))]
-#[diesel(table_name = posts)]
-#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
-pub struct Post {
-    pub id: i32,
-    pub user_id: i32,
-    pub title: String,
-}
-
-Child belongs to parent with custom foreign key:
-#[derive(Queryable, Selectable, Identifiable, Associations, Debug, Clone)]
-#[diesel(belongs_to(User, foreign_key = owner_id))]
-#[diesel(table_name = projects)]
-#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
-pub struct Project {
-    pub id: i32,
-    pub owner_id: i32,
-    pub name: String,
-}
-
-Parent model has no relation attribute:
-#[derive(Queryable, Selectable, Identifiable, Debug, Clone)]
-#[diesel(table_name = organizations)]
-#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
-pub struct Organization {
-    pub id: i32,
-    pub name: String,
-}
-
-Join model for many-to-many:
-#[derive(Queryable, Selectable, Identifiable, Associations, Debug, Clone)]
-#[diesel(belongs_to(User))]
-#[diesel(belongs_to(Organization))]
-#[diesel(table_name = organization_users)]
-#[diesel(primary_key(user_id, organization_id))]
-#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
-pub struct OrganizationUser {
-    pub user_id: i32,
-    pub organization_id: i32,
-    pub role: String,
-}
-
-Composite primary key without relations:
-#[derive(Queryable, Selectable, Identifiable, Debug, Clone)]
-#[diesel(table_name = settings)]
-#[diesel(primary_key(scope, key))]
-#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
-pub struct Setting {
-    pub scope: String,
-    pub key: String,
-    pub value: String,
-}
-
-Timestamp and nullable fields:
-#[derive(Queryable, Selectable, Identifiable, Debug, Clone)]
-#[diesel(table_name = audit_events)]
-#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
-pub struct AuditEvent {
-    pub id: i32,
-    pub user_id: Option<i32>,
-    pub action: String,
-    pub created_at: chrono::NaiveDateTime,
-}
-
-Now write exactly one struct from the user's request."#
-        .to_string()
+///
+/// Template: `prompts/rust_engineer/diesel_model_struct.md`.
+pub fn build_system_prompt(prompts: &PromptLibrary) -> Result<Prompt, AgentError> {
+    prompts.render("rust_engineer/diesel_model_struct", &[])
 }
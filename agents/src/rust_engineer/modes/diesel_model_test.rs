@@ -0,0 +1,122 @@
+use super::diesel_model::extract_table_name;
+use crate::{
+    code_extractor::CodeBlock,
+    error::AgentError,
+    prompt_library::{Prompt, PromptLibrary},
+};
+
+/// Parse `pub name: Type` fields from a struct definition.
+pub fn extract_fields(struct_code: &str) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    for line in struct_code.lines() {
+        let trimmed = line.trim().trim_end_matches(',');
+        let Some(rest) = trimmed.strip_prefix("pub ") else {
+            continue;
+        };
+        if let Some((name, ty)) = rest.split_once(':') {
+            let name = name.trim();
+            if !name.is_empty() && !name.contains(' ') {
+                fields.push((name.to_string(), ty.trim().to_string()));
+            }
+        }
+    }
+    fields
+}
+
+/// A literal fixture value for a field type, usable in `column.eq(value)`.
+/// Returns `None` for types we can't build a sensible literal for.
+pub fn fixture_value(field: &str, ty: &str) -> Option<String> {
+    let ty = ty.trim();
+    if let Some(inner) = ty.strip_prefix("Option<").and_then(|t| t.strip_suffix('>')) {
+        return fixture_value(field, inner);
+    }
+    let value = match ty.rsplit("::").next().unwrap_or(ty) {
+        "i16" | "i32" | "i64" | "u32" | "u64" => "1".to_string(),
+        "f32" | "f64" => "1.0".to_string(),
+        "bool" => "true".to_string(),
+        "String" | "&str" => format!("\"test_{field}\""),
+        "NaiveDate" => "chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()".to_string(),
+        "NaiveDateTime" => "chrono::NaiveDate::from_ymd_opt(2024, 1, 1)\
+            .unwrap()\
+            .and_hms_opt(0, 0, 0)\
+            .unwrap()"
+            .to_string(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Build a deterministic fixture insert for one row of the struct's table, so a
+/// small model only has to call the fn and assert on the result.
+pub fn fixture_insert(struct_code: &str) -> Option<String> {
+    let table = extract_table_name(struct_code)?;
+    let values: Vec<String> = extract_fields(struct_code)
+        .iter()
+        .filter_map(|(name, ty)| {
+            fixture_value(name, ty).map(|v| format!("            {table}::{name}.eq({v}),"))
+        })
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "diesel::insert_into({table}::table)\n        .values((\n{}\n        ))\n        .execute(&mut conn)\n        .expect(\"insert fixture\");",
+        values.join("\n")
+    ))
+}
+
+/// Build the system prompt for generating one `#[test]` for a model fn: the
+/// test rules and an example test.
+///
+/// Template: `prompts/rust_engineer/diesel_model_test.md`.
+pub fn build_system_prompt(prompts: &PromptLibrary) -> Result<Prompt, AgentError> {
+    prompts.render("rust_engineer/diesel_model_test", &[])
+}
+
+/// Build the user prompt for generating one `#[test]` for a model fn.
+///
+/// The test runs against an in-memory SQLite pool with embedded migrations,
+/// provided by a `test_pool()` helper that the writer adds to the test module.
+pub fn build_prompt(
+    struct_code: &str,
+    target_fn: &str,
+    example_tests: &[&CodeBlock],
+    fn_name: &str,
+) -> String {
+    let mut prompt = String::new();
+
+    // ── The target struct ──────────────────────────────────────────────────
+    prompt.push_str("## The Model\n\n```rust\n");
+    prompt.push_str(struct_code);
+    prompt.push_str("\n```\n\n");
+
+    // ── Fixture ────────────────────────────────────────────────────────────
+    if let Some(fixture) = fixture_insert(struct_code) {
+        prompt.push_str("## Fixture Insert (use as is)\n\n```rust\n");
+        prompt.push_str(&fixture);
+        prompt.push_str("\n```\n\n");
+    }
+
+    // ── Existing tests (style examples) ────────────────────────────────────
+    if !example_tests.is_empty() {
+        prompt.push_str("Existing tests in this project (match this style):\n```rust\n");
+        for t in example_tests {
+            prompt.push_str(&t.source);
+            prompt.push_str("\n\n");
+        }
+        prompt.push_str("```\n\n");
+    }
+
+    // ── The fn under test ──────────────────────────────────────────────────
+    prompt.push_str("## Function Under Test\n\n```rust\n");
+    prompt.push_str(target_fn);
+    prompt.push_str("\n```\n\n");
+
+    // ── Final instruction ──────────────────────────────────────────────────
+    prompt.push_str("## Task\n\n");
+    prompt.push_str(&format!(
+        "Write the test `test_{fn_name}` for the function above. Return ONLY the test function. No imports, no explanation, no markdown fences."
+    ));
+
+    prompt
+}
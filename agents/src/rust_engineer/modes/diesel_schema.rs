@@ -1,106 +1,13 @@
+use crate::{
+    error::AgentError,
+    prompt_library::{Prompt, PromptLibrary},
+};
+
 /// Build a compact system prompt for creating or updating one Diesel schema
 /// table definition. The current `diesel::table!` block, if any, is supplied
 /// by the caller in the user prompt.
-pub fn build_system_prompt() -> String {
-    r#"You write one Rust Diesel schema table definition for SQLite.
-
-Return ONLY one diesel::table! block. No imports. No model structs. No migration SQL. No joinable! lines. No allow_tables_to_appear_in_same_query! line. No explanation. No markdown.
-You are always creating or updating exactly ONE table definition.
-If updating, preserve existing columns, primary key, order, and types unless the user asks to change them.
-
-Diesel schema rules:
-- Use the nocodo template style: diesel::table! { ... }
-- Format:
-  diesel::table! {
-      table_name (primary_key) {
-          column_name -> SqlType,
-      }
-  }
-- Composite primary key format: table_name (key_a, key_b).
-- Nullable columns use Nullable<Type>.
-- Foreign keys are normal columns here. Do not output diesel::joinable! lines.
-- Do not output diesel::allow_tables_to_appear_in_same_query!.
-- Do not invent audit columns unless the user asks for them.
-- Use snake_case table and column names.
-
-SQLite type mapping:
-- INTEGER primary key usually uses BigInt in nocodo template projects.
-- Integer -> 32-bit integer values.
-- BigInt -> 64-bit ids and foreign keys when matching existing BigInt ids.
-- Text -> strings and enum/string state columns.
-- Bool -> booleans.
-- Timestamp -> chrono timestamp columns.
-- Date -> date-only values.
-- Time -> time-only values.
-- Binary -> bytes.
-- Float -> f32 values.
-- Double -> f64 values.
-
-Examples:
-Single table:
-diesel::table! {
-    posts (id) {
-        id -> Integer,
-        title -> Text,
-        body -> Text,
-        published -> Bool,
-    }
-}
-
-nocodo-style BigInt ids:
-diesel::table! {
-    users (id) {
-        id -> BigInt,
-        first_name -> Text,
-        last_name -> Text,
-        status -> Text,
-        created_at -> Timestamp,
-    }
-}
-
-Nullable fields:
-diesel::table! {
-    user_contacts (id) {
-        id -> BigInt,
-        user_id -> BigInt,
-        contact_type -> Text,
-        value -> Text,
-        country_code -> Nullable<Integer>,
-        verified_at -> Nullable<Timestamp>,
-        created_at -> Timestamp,
-    }
-}
-
-Child table with foreign key column only:
-diesel::table! {
-    pages (id) {
-        id -> Integer,
-        page_number -> Integer,
-        content -> Text,
-        book_id -> Integer,
-    }
-}
-
-Join table with composite primary key:
-diesel::table! {
-    books_authors (book_id, author_id) {
-        book_id -> Integer,
-        author_id -> Integer,
-    }
-}
-
-State and token table:
-diesel::table! {
-    refresh_tokens (id) {
-        id -> BigInt,
-        user_id -> BigInt,
-        token_hash -> Text,
-        expires_at -> Timestamp,
-        revoked_at -> Nullable<Timestamp>,
-        created_at -> Timestamp,
-    }
-}
-
-Now write exactly one diesel::table! block from the user's request."#
-        .to_string()
+///
+/// Template: `prompts/rust_engineer/diesel_schema.md`.
+pub fn build_system_prompt(prompts: &PromptLibrary) -> Result<Prompt, AgentError> {
+    prompts.render("rust_engineer/diesel_schema", &[])
 }
@@ -0,0 +1,170 @@
+use super::diesel_model::{extract_column_names, extract_table_name};
+use crate::{
+    code_extractor::CodeBlock,
+    error::AgentError,
+    prompt_library::{Prompt, PromptLibrary},
+};
+
+/// Column of `byte` within its line in `src` (i.e. the indentation before an item).
+pub fn column_of(src: &str, byte: usize) -> usize {
+    let line_start = src[..byte].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    byte - line_start
+}
+
+/// Remove `column` spaces of indentation from every line but the first. Extracted
+/// impl fns start at the `fn` token, so only continuation lines carry the indent.
+pub fn dedent(source: &str, column: usize) -> String {
+    source
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 {
+                line
+            } else {
+                let strip = line.len() - line.trim_start_matches(' ').len();
+                &line[strip.min(column)..]
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Indent every line but the first by `column` spaces so the code can be spliced
+/// back at the original item's start byte.
+pub fn reindent(code: &str, column: usize) -> String {
+    let pad = " ".repeat(column);
+    code.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 || line.trim().is_empty() {
+                line.trim_end().to_string()
+            } else {
+                format!("{pad}{line}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Drop doc comments and attributes the model may have put above the fn — the
+/// originals sit outside the spliced byte range and are kept as they are.
+pub fn strip_leading_attrs(code: &str) -> String {
+    let lines: Vec<&str> = code.lines().collect();
+    let start = lines
+        .iter()
+        .position(|l| {
+            let t = l.trim_start();
+            !(t.is_empty() || t.starts_with("///") || t.starts_with("#["))
+        })
+        .unwrap_or(lines.len());
+    lines[start..].join("\n")
+}
+
+/// Minimal line diff between the old and new fn, rendered as a single unified
+/// hunk. `start_line` is the 1-based line of the fn in its file.
+pub fn unified_diff(file: &str, start_line: u32, old: &str, new: &str) -> String {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    // Longest common subsequence table; fns are small, so O(n·m) is fine.
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut body = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            body.push_str(&format!(" {}\n", a[i]));
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            body.push_str(&format!("+{}\n", b[j]));
+            j += 1;
+        } else {
+            body.push_str(&format!("-{}\n", a[i]));
+            i += 1;
+        }
+    }
+
+    format!(
+        "--- a/{file}\n+++ b/{file}\n@@ -{start_line},{} +{start_line},{} @@\n{body}",
+        a.len(),
+        b.len()
+    )
+}
+
+/// Build the system prompt for editing one existing impl fn in place: the
+/// editing rules.
+///
+/// Template: `prompts/rust_engineer/edit_impl_fn.md`.
+pub fn build_system_prompt(prompts: &PromptLibrary) -> Result<Prompt, AgentError> {
+    prompts.render("rust_engineer/edit_impl_fn", &[])
+}
+
+/// Build the user prompt for editing one existing impl fn in place.
+///
+/// The model sees the struct, the fn's current source and the requested change,
+/// and must return the whole fn with the same name and signature shape.
+pub fn build_prompt(
+    struct_code: &str,
+    current_fn: &str,
+    dependent_types: &[CodeBlock],
+    fn_name: &str,
+    change: &str,
+) -> String {
+    let table_name = extract_table_name(struct_code);
+    let columns = extract_column_names(struct_code);
+    let mut prompt = String::new();
+
+    // ── Available columns for the target table ─────────────────────────────
+    if !columns.is_empty() {
+        prompt.push_str(&format!(
+            "## Available Columns for `{}`\n\n{}\n\n",
+            table_name.as_deref().unwrap_or("unknown"),
+            columns
+                .iter()
+                .map(|c| format!("- `{c}`"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ));
+    }
+
+    // ── The target struct ──────────────────────────────────────────────────
+    prompt.push_str("## The Model\n\n```rust\n");
+    prompt.push_str(struct_code);
+    prompt.push_str("\n```\n\n");
+
+    // ── Dependent types (enums, etc.) ──────────────────────────────────────
+    if !dependent_types.is_empty() {
+        prompt.push_str("## Dependent Types\n\n");
+        for dep in dependent_types {
+            prompt.push_str(&format!(
+                "From `{}`:\n```rust\n{}\n```\n\n",
+                dep.file.file_name().unwrap_or_default().to_string_lossy(),
+                dep.source
+            ));
+        }
+    }
+
+    // ── The fn being edited ────────────────────────────────────────────────
+    prompt.push_str(&format!("## Current `{fn_name}`\n\n```rust\n"));
+    prompt.push_str(current_fn);
+    prompt.push_str("\n```\n\n");
+
+    // ── Final instruction ──────────────────────────────────────────────────
+    prompt.push_str("## Requested Change\n\n");
+    prompt.push_str(change.trim());
+    prompt.push_str(&format!(
+        "\n\nReturn the complete updated `{fn_name}`. Same name. No imports, no explanation, no markdown fences."
+    ));
+
+    prompt
+}
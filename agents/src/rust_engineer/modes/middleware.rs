@@ -0,0 +1,185 @@
+use crate::{
+    error::AgentError,
+    prompt_library::{Prompt, PromptLibrary},
+};
+
+/// The two shapes of request guard an Actix app can use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiddlewareKind {
+    /// `Transform` + `Service` pair, applied with `App::wrap`.
+    Transform,
+    /// `FromRequest` extractor, applied by adding it as a handler parameter.
+    Extractor,
+}
+
+impl MiddlewareKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MiddlewareKind::Transform => "transform",
+            MiddlewareKind::Extractor => "extractor",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "transform" | "middleware" => Some(MiddlewareKind::Transform),
+            "extractor" | "from_request" => Some(MiddlewareKind::Extractor),
+            _ => None,
+        }
+    }
+
+    /// The actix trait whose existing impls in the project serve as examples.
+    pub fn trait_name(&self) -> &'static str {
+        match self {
+            MiddlewareKind::Transform => "Transform",
+            MiddlewareKind::Extractor => "FromRequest",
+        }
+    }
+}
+
+/// The deterministic import block prepended to generated middleware.
+pub fn required_imports(kind: MiddlewareKind) -> &'static str {
+    match kind {
+        MiddlewareKind::Transform => {
+            "use actix_web::{\n    body::EitherBody,\n    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},\n    web, Error, HttpResponse,\n};\nuse futures_util::future::LocalBoxFuture;\nuse std::{\n    future::{ready, Ready},\n    rc::Rc,\n};\n"
+        }
+        MiddlewareKind::Extractor => {
+            "use actix_web::{dev::Payload, web, Error, FromRequest, HttpRequest};\nuse futures_util::future::LocalBoxFuture;\n"
+        }
+    }
+}
+
+const TRANSFORM_EXAMPLE: &str = r#"pub struct RequireAuth;
+
+impl<S, B> Transform<S, ServiceRequest> for RequireAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequireAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireAuthMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequireAuthMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let svc = self.service.clone();
+
+        Box::pin(async move {
+            if req.path().starts_with("/api/auth/") {
+                return svc.call(req).await.map(|r| r.map_into_left_body());
+            }
+
+            let token = req.cookie("session").map(|c| c.value().to_string());
+            if token.is_some() {
+                svc.call(req).await.map(|r| r.map_into_left_body())
+            } else {
+                let response = HttpResponse::Unauthorized()
+                    .json(serde_json::json!({"error": "authentication required"}));
+                Ok(req.into_response(response).map_into_right_body())
+            }
+        })
+    }
+}"#;
+
+const EXTRACTOR_EXAMPLE: &str = r#"pub struct CurrentUser {
+    pub user_id: i64,
+}
+
+impl FromRequest for CurrentUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let token = req.cookie("session").map(|c| c.value().to_string());
+
+        Box::pin(async move {
+            let Some(token) = token else {
+                return Err(actix_web::error::ErrorUnauthorized("authentication required"));
+            };
+            match token.parse::<i64>() {
+                Ok(user_id) => Ok(CurrentUser { user_id }),
+                Err(_) => Err(actix_web::error::ErrorUnauthorized("invalid session")),
+            }
+        })
+    }
+}"#;
+
+/// Build the system prompt for generating one Actix middleware or extractor:
+/// the rules for that kind.
+///
+/// Templates: `prompts/rust_engineer/middleware_transform.md` and
+/// `prompts/rust_engineer/middleware_extractor.md`.
+pub fn build_system_prompt(
+    prompts: &PromptLibrary,
+    kind: MiddlewareKind,
+) -> Result<Prompt, AgentError> {
+    prompts.render(&format!("rust_engineer/middleware_{}", kind.as_str()), &[])
+}
+
+/// Build the user prompt for generating one Actix middleware or extractor.
+///
+/// `examples` are whole modules from the project that already implement the
+/// same trait (typically the template's auth code); when there are none, a
+/// built-in example of the same shape is used instead.
+pub fn build_prompt(
+    kind: MiddlewareKind,
+    name: &str,
+    description: &str,
+    examples: &[String],
+) -> String {
+    let mut prompt = String::new();
+
+    // ── Existing implementations (style examples) ──────────────────────────
+    if examples.is_empty() {
+        prompt.push_str("## Example\n\n```rust\n");
+        prompt.push_str(match kind {
+            MiddlewareKind::Transform => TRANSFORM_EXAMPLE,
+            MiddlewareKind::Extractor => EXTRACTOR_EXAMPLE,
+        });
+        prompt.push_str("\n```\n\n");
+    } else {
+        prompt.push_str("## Existing code in this project (match this style)\n\n");
+        for example in examples {
+            prompt.push_str("```rust\n");
+            prompt.push_str(example.trim());
+            prompt.push_str("\n```\n\n");
+        }
+    }
+
+    // ── Final instruction ──────────────────────────────────────────────────
+    prompt.push_str("## Task\n\n");
+    prompt.push_str(&format!("{}\n\n", description.trim()));
+    match kind {
+        MiddlewareKind::Transform => prompt.push_str(&format!(
+            "Write the middleware factory `{name}` and its service `{name}Middleware`. Return ONLY the Rust items. No imports, no explanation, no markdown fences."
+        )),
+        MiddlewareKind::Extractor => prompt.push_str(&format!(
+            "Write the extractor struct `{name}` and its `FromRequest` impl. Return ONLY the Rust items. No imports, no explanation, no markdown fences."
+        )),
+    }
+
+    prompt
+}
@@ -1,3 +1,7 @@
+pub mod actix_handler;
 pub mod diesel_model;
 pub mod diesel_model_struct;
+pub mod diesel_model_test;
 pub mod diesel_schema;
+pub mod edit_impl_fn;
+pub mod middleware;
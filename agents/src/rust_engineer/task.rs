@@ -0,0 +1,420 @@
+use std::path::Path;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::agent::RustEngineerAgent;
+use super::modes::middleware::MiddlewareKind;
+use crate::{
+    code_extractor::extract_impl_fn, code_writer, error::AgentError, prompt_library::PromptRef,
+};
+
+/// Structured parameters for a task assigned to `rust_engineer`. Stored as JSON
+/// in the task's `source_prompt`, the same way ui_designer tasks carry their
+/// table definition.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RustEngineerTaskParams {
+    /// One of: "diesel_schema", "diesel_model_struct", "diesel_model_fn",
+    /// "diesel_model_test", "edit_impl_fn", "actix_handler", "middleware".
+    pub mode: String,
+    /// Model struct the task works on.
+    #[serde(default)]
+    pub struct_name: Option<String>,
+    /// Function to write, test or edit (actix_handler mode: the handler name).
+    #[serde(default)]
+    pub fn_name: Option<String>,
+    /// Free-text instruction: the struct/table to write, the edit to make or
+    /// what the middleware must check.
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// HTTP verb for actix_handler mode: "get", "post", "put", "patch", "delete".
+    #[serde(default)]
+    pub http_method: Option<String>,
+    /// Route path for actix_handler mode, e.g. "/contacts/{id}".
+    #[serde(default)]
+    pub route_path: Option<String>,
+    /// For middleware mode: "transform" (default) or "extractor".
+    #[serde(default)]
+    pub middleware_kind: Option<String>,
+    /// For middleware mode: the type to generate, e.g. "RequireRole".
+    #[serde(default)]
+    pub middleware_name: Option<String>,
+    /// For transform middleware: wrap the whole `App` with it, which guards
+    /// every route — login and health checks included. Off by default: the
+    /// middleware is only declared, to be wrapped around a `web::scope`.
+    #[serde(default)]
+    pub wrap_app: bool,
+}
+
+impl RustEngineerTaskParams {
+    /// Parse the parameters stored in a task's `source_prompt`.
+    pub fn from_source_prompt(source_prompt: &str) -> Result<Self, AgentError> {
+        Ok(serde_json::from_str(source_prompt)?)
+    }
+
+    /// Serialize for storage in a task's `source_prompt`. A missing `prompt`
+    /// falls back to `default_prompt` (the task description), so modes that
+    /// need free text still get the PM's intent.
+    pub fn to_source_prompt(&self, default_prompt: &str) -> Result<String, AgentError> {
+        let mut params = self.clone();
+        if params.prompt.as_deref().unwrap_or("").trim().is_empty() {
+            params.prompt = Some(default_prompt.to_string());
+        }
+        Ok(serde_json::to_string(&params)?)
+    }
+
+    fn required(&self, field: &str) -> Result<&str, AgentError> {
+        let value = match field {
+            "struct_name" => &self.struct_name,
+            "fn_name" => &self.fn_name,
+            "prompt" => &self.prompt,
+            "http_method" => &self.http_method,
+            "route_path" => &self.route_path,
+            // Tasks stored before `middleware_name` named the middleware in
+            // `struct_name`.
+            "middleware_name" if self.middleware_name.is_none() => &self.struct_name,
+            "middleware_name" => &self.middleware_name,
+            _ => &None,
+        };
+        value
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| {
+                AgentError::Other(format!("{} is required for {} mode", field, self.mode))
+            })
+    }
+}
+
+/// Mode-independent result of a task run, flattened from the per-mode outputs.
+#[derive(Debug)]
+pub struct RustEngineerTaskOutput {
+    pub system_prompt: Option<String>,
+    /// Template and version of `system_prompt`.
+    pub template: Option<PromptRef>,
+    pub prompt: String,
+    pub raw_response: String,
+    pub code: Option<String>,
+    /// Relative file path the code was written to (None when `apply` is false
+    /// or the mode has no writer).
+    pub file_path: Option<String>,
+    /// Unified diff for in-place edit modes.
+    pub diff: Option<String>,
+    /// File the code goes into for modes that write into an existing file
+    /// (actix_handler, diesel_model_test, edit_impl_fn), also without
+    /// `apply`, so the code can be written later.
+    pub target_path: Option<String>,
+    /// Source of the edited fn before the edit (edit_impl_fn), so a later
+    /// write can tell whether the fn changed in the meantime.
+    pub original: Option<String>,
+}
+
+impl RustEngineerAgent {
+    /// Run one mode from structured task parameters. With `apply`, modes that
+    /// have a writer also write their code to disk; a writer error (e.g. code
+    /// that does not parse) is returned as an error.
+    pub async fn run_task(
+        &self,
+        params: &RustEngineerTaskParams,
+        apply: bool,
+    ) -> Result<RustEngineerTaskOutput, AgentError> {
+        match params.mode.as_str() {
+            "diesel_model_struct" => {
+                let prompt = params.required("prompt")?;
+                let output = if apply {
+                    self.diesel_model_struct_write(prompt).await?
+                } else {
+                    self.diesel_model_struct(prompt).await?
+                };
+                Ok(RustEngineerTaskOutput {
+                    system_prompt: Some(output.system_prompt),
+                    template: Some(output.template),
+                    prompt: output.prompt,
+                    raw_response: output.raw_response,
+                    code: output.code,
+                    file_path: output.file_path,
+                    diff: None,
+                    target_path: None,
+                    original: None,
+                })
+            }
+            "diesel_schema" => {
+                let prompt = params.required("prompt")?;
+                let output = if apply {
+                    self.diesel_schema_write(prompt).await?
+                } else {
+                    self.diesel_schema(prompt).await?
+                };
+                Ok(RustEngineerTaskOutput {
+                    system_prompt: Some(output.system_prompt),
+                    template: Some(output.template),
+                    prompt: output.prompt,
+                    raw_response: output.raw_response,
+                    code: output.code,
+                    file_path: output.file_path,
+                    diff: None,
+                    target_path: None,
+                    original: None,
+                })
+            }
+            "actix_handler" => {
+                let struct_name = params.required("struct_name")?;
+                let fn_name = params.required("fn_name")?;
+                let method = params.required("http_method")?;
+                let route_path = params.required("route_path")?;
+                let output = if apply {
+                    self.actix_handler_write(struct_name, fn_name, method, route_path)
+                        .await?
+                } else {
+                    self.actix_handler(struct_name, fn_name, method, route_path)
+                        .await?
+                };
+                Ok(RustEngineerTaskOutput {
+                    system_prompt: Some(output.system_prompt),
+                    template: Some(output.template),
+                    prompt: output.prompt,
+                    raw_response: output.raw_response,
+                    code: output.code,
+                    file_path: if apply {
+                        output.file_path.clone()
+                    } else {
+                        None
+                    },
+                    diff: None,
+                    target_path: output.file_path,
+                    original: None,
+                })
+            }
+            "middleware" => {
+                let name = params.required("middleware_name")?;
+                let prompt = params.required("prompt")?;
+                let kind_str = params.middleware_kind.as_deref().unwrap_or("transform");
+                let kind = MiddlewareKind::parse(kind_str).ok_or_else(|| {
+                    AgentError::Other(format!(
+                        "unknown middleware_kind: {} (expected transform or extractor)",
+                        kind_str
+                    ))
+                })?;
+                let output = if apply {
+                    self.middleware_write(name, kind, prompt, params.wrap_app)
+                        .await?
+                } else {
+                    self.middleware(name, kind, prompt).await?
+                };
+                Ok(RustEngineerTaskOutput {
+                    system_prompt: Some(output.system_prompt),
+                    template: Some(output.template),
+                    prompt: output.prompt,
+                    raw_response: output.raw_response,
+                    code: output.code,
+                    file_path: output.file_path,
+                    diff: None,
+                    target_path: None,
+                    original: None,
+                })
+            }
+            "diesel_model_test" => {
+                let struct_name = params.required("struct_name")?;
+                let fn_name = params.required("fn_name")?;
+                let output = if apply {
+                    self.diesel_model_test_write(struct_name, fn_name).await?
+                } else {
+                    self.diesel_model_test(struct_name, fn_name).await?
+                };
+                Ok(RustEngineerTaskOutput {
+                    system_prompt: Some(output.system_prompt),
+                    template: Some(output.template),
+                    prompt: output.prompt,
+                    raw_response: output.raw_response,
+                    code: output.code,
+                    file_path: if apply {
+                        output.file_path.clone()
+                    } else {
+                        None
+                    },
+                    diff: None,
+                    target_path: output.file_path,
+                    original: None,
+                })
+            }
+            "edit_impl_fn" => {
+                let struct_name = params.required("struct_name")?;
+                let fn_name = params.required("fn_name")?;
+                let prompt = params.required("prompt")?;
+                let output = if apply {
+                    self.edit_impl_fn_write(struct_name, fn_name, prompt)
+                        .await?
+                } else {
+                    self.edit_impl_fn(struct_name, fn_name, prompt).await?
+                };
+                Ok(RustEngineerTaskOutput {
+                    system_prompt: Some(output.system_prompt),
+                    template: Some(output.template),
+                    prompt: output.prompt,
+                    raw_response: output.raw_response,
+                    code: output.code,
+                    file_path: if apply {
+                        output.file_path.clone()
+                    } else {
+                        None
+                    },
+                    diff: output.diff,
+                    target_path: output.file_path,
+                    original: Some(output.original),
+                })
+            }
+            "diesel_model_fn" | "diesel_model" => {
+                let struct_name = params.required("struct_name")?;
+                let fn_name = params.required("fn_name")?;
+                let output = self.diesel_model_fn(struct_name, fn_name).await?;
+                Ok(RustEngineerTaskOutput {
+                    system_prompt: Some(output.system_prompt),
+                    template: Some(output.template),
+                    prompt: output.prompt,
+                    raw_response: output.raw_response,
+                    code: output.code,
+                    file_path: None,
+                    diff: None,
+                    target_path: None,
+                    original: None,
+                })
+            }
+            other => Err(AgentError::Other(format!(
+                "unknown rust engineer mode: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Code a run produced without `apply`, with what writing it needs. Stored
+/// as the artifact of a proposal and written once a user approves it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProposedCode {
+    pub params: RustEngineerTaskParams,
+    pub code: String,
+    /// See `RustEngineerTaskOutput::target_path`.
+    #[serde(default)]
+    pub target_path: Option<String>,
+    #[serde(default)]
+    pub diff: Option<String>,
+    /// See `RustEngineerTaskOutput::original`.
+    #[serde(default)]
+    pub original: Option<String>,
+}
+
+impl ProposedCode {
+    /// Write the code the way `run_task` with `apply` would have. Returns the
+    /// relative path written, or `None` for modes without a writer. An edited
+    /// fn is only written while the fn in the file still matches `original`;
+    /// otherwise the write fails with `AgentError::Conflict`.
+    pub fn write(&self, project_path: &Path) -> Result<Option<String>, AgentError> {
+        let params = &self.params;
+        let code = self.code.as_str();
+        let target = || {
+            self.target_path.as_deref().ok_or_else(|| {
+                AgentError::Other(format!("no target file for {} code", params.mode))
+            })
+        };
+        let path = match params.mode.as_str() {
+            "diesel_model_struct" => code_writer::write_diesel_model(project_path, code)?,
+            "diesel_schema" => code_writer::write_diesel_schema(project_path, code)?,
+            "actix_handler" => code_writer::write_actix_handler(project_path, target()?, code)?,
+            "middleware" => {
+                let kind_str = params.middleware_kind.as_deref().unwrap_or("transform");
+                let kind = MiddlewareKind::parse(kind_str).ok_or_else(|| {
+                    AgentError::Other(format!("unknown middleware_kind: {}", kind_str))
+                })?;
+                code_writer::write_actix_middleware(
+                    project_path,
+                    params.required("middleware_name")?,
+                    code,
+                    params.wrap_app && kind == MiddlewareKind::Transform,
+                )?
+            }
+            "diesel_model_test" => code_writer::write_model_test(project_path, target()?, code)?,
+            "edit_impl_fn" => {
+                let struct_name = params.required("struct_name")?;
+                let fn_name = params.required("fn_name")?;
+                let fn_file = project_path.join(target()?);
+                let fn_block = extract_impl_fn(&fn_file, struct_name, fn_name)
+                    .map_err(AgentError::Other)?
+                    .ok_or_else(|| {
+                        AgentError::Other(format!(
+                            "fn `{}::{}` no longer in {}",
+                            struct_name,
+                            fn_name,
+                            target().unwrap_or_default()
+                        ))
+                    })?;
+                if self.original.as_deref() != Some(fn_block.source.as_str()) {
+                    return Err(AgentError::Conflict(format!(
+                        "fn `{}::{}` in {} changed since the edit was proposed",
+                        struct_name,
+                        fn_name,
+                        target()?
+                    )));
+                }
+                code_writer::replace_code_block(project_path, &fn_block, code)?
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTACT: &str = r#"pub struct Contact;
+
+impl Contact {
+    pub fn label(&self) -> String {
+        "contact".to_string()
+    }
+}
+"#;
+
+    fn proposed_edit(original: &str) -> ProposedCode {
+        ProposedCode {
+            params: RustEngineerTaskParams::from_source_prompt(
+                r#"{"mode":"edit_impl_fn","struct_name":"Contact","fn_name":"label"}"#,
+            )
+            .unwrap(),
+            code: "    pub fn label(&self) -> String {\n        \"person\".to_string()\n    }"
+                .to_string(),
+            target_path: Some("contact.rs".to_string()),
+            diff: None,
+            original: Some(original.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_write_edit_impl_fn_checks_original() {
+        let dir = std::env::temp_dir().join(format!("nocodo_proposed_edit_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("contact.rs");
+        std::fs::write(&path, CONTACT).unwrap();
+        let original = extract_impl_fn(&path, "Contact", "label")
+            .unwrap()
+            .unwrap()
+            .source;
+
+        // The fn was edited by hand after the proposal was made.
+        std::fs::write(&path, CONTACT.replace("\"contact\"", "\"client\"")).unwrap();
+        let stale = proposed_edit(&original).write(&dir);
+        let edited = std::fs::read_to_string(&path).unwrap();
+
+        std::fs::write(&path, CONTACT).unwrap();
+        let written = proposed_edit(&original).write(&dir);
+        let applied = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert!(matches!(stale, Err(AgentError::Conflict(_))));
+        assert!(edited.contains("\"client\""));
+        assert_eq!(written.unwrap().as_deref(), Some("contact.rs"));
+        assert!(applied.contains("\"person\""));
+        assert!(!applied.contains("\"contact\""));
+    }
+}
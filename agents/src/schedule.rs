@@ -0,0 +1,288 @@
+//! When to run project-wide agents, such as the stack reviewer, without
+//! anyone asking. Schedules are `[[schedule]]` entries in project.toml and
+//! apply to every project:
+//!
+//! ```toml
+//! [[schedule]]
+//! agent         = "stack_reviewer"
+//! cron          = "0 3 * * *"          # minute hour day month weekday, UTC
+//! files_changed = 25                   # files modified since the last run
+//! after_agents  = ["rust_engineer"]    # a task of these agents finished
+//! ```
+//!
+//! A schedule fires on whichever of its triggers comes first. "Since the last
+//! run" counts from the start of the project's last run of the agent, or
+//! from when the server started for a project it never ran on.
+
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use serde::Deserialize;
+use walkdir::WalkDir;
+
+use crate::error::AgentError;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Schedule {
+    /// Agent to run, e.g. "stack_reviewer".
+    pub agent: String,
+    #[serde(default)]
+    pub cron: Option<Cron>,
+    /// Run once this many files of the project changed.
+    #[serde(default)]
+    pub files_changed: Option<usize>,
+    /// Run once a task of one of these agents is done.
+    #[serde(default)]
+    pub after_agents: Vec<String>,
+}
+
+impl Schedule {
+    /// Whether the cron expression matched a minute after `since`, up to
+    /// `now`.
+    pub fn cron_due(&self, since: i64, now: i64) -> bool {
+        self.cron
+            .as_ref()
+            .and_then(|cron| cron.next_after(since))
+            .is_some_and(|next| next <= now)
+    }
+}
+
+/// A five-field cron expression — minute, hour, day of month, month, day of
+/// week (0 or 7 is Sunday) — evaluated in UTC. Fields take `*`, numbers,
+/// `a-b` ranges, `,` lists and `/n` steps.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Cron {
+    expr: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// `*` in the day-of-month or day-of-week field; when both are
+    /// restricted a day matching either one matches.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl TryFrom<String> for Cron {
+    type Error = AgentError;
+
+    fn try_from(expr: String) -> Result<Self, AgentError> {
+        Self::parse(&expr)
+    }
+}
+
+impl Cron {
+    pub fn parse(expr: &str) -> Result<Self, AgentError> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(AgentError::Config(format!(
+                "cron '{}' must have 5 fields: minute hour day month weekday",
+                expr
+            )));
+        };
+        let field = |source: &str, min: u32, max: u32| {
+            parse_field(source, min, max).map_err(|e| {
+                AgentError::Config(format!("cron '{}': field '{}' {}", expr, source, e))
+            })
+        };
+        let mut weekdays = field(weekday, 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            expr: expr.to_string(),
+            minutes: field(minute, 0, 59)?,
+            hours: field(hour, 0, 23)?,
+            days: field(day, 1, 31)?,
+            months: field(month, 1, 12)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.expr
+    }
+
+    /// The first matching minute after `unix_secs`, as unix seconds, or
+    /// `None` when none comes within four years (e.g. "0 0 30 2 *").
+    pub fn next_after(&self, unix_secs: i64) -> Option<i64> {
+        let end = unix_secs + 4 * 366 * 86_400;
+        let mut t = (unix_secs.div_euclid(60) + 1) * 60;
+        while t <= end {
+            let days = t.div_euclid(86_400);
+            let (_, month, day) = civil_from_days(days);
+            let weekday = (days + 4).rem_euclid(7) as u32;
+            if !self.month_matches(month) || !self.day_matches(day, weekday) {
+                t = (days + 1) * 86_400;
+                continue;
+            }
+            let secs = t.rem_euclid(86_400);
+            if self.hours & (1 << (secs / 3600)) == 0 {
+                t = t - secs % 3600 + 3600;
+                continue;
+            }
+            if self.minutes & (1 << (secs % 3600 / 60)) == 0 {
+                t += 60;
+                continue;
+            }
+            return Some(t);
+        }
+        None
+    }
+
+    fn month_matches(&self, month: u32) -> bool {
+        self.months & (1 << month) != 0
+    }
+
+    fn day_matches(&self, day: u32, weekday: u32) -> bool {
+        let by_day = self.days & (1 << day) != 0;
+        let by_weekday = self.weekdays & (1 << weekday) != 0;
+        match (self.any_day, self.any_weekday) {
+            (false, false) => by_day || by_weekday,
+            _ => by_day && by_weekday,
+        }
+    }
+}
+
+/// The bits `min..=max` that one cron field selects.
+fn parse_field(source: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for part in source.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("has an invalid step '{}'", step))?,
+            ),
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => {
+                let number = |s: &str| {
+                    s.parse::<u32>()
+                        .ok()
+                        .filter(|n| (min..=max).contains(n))
+                        .ok_or_else(|| format!("needs numbers from {} to {}", min, max))
+                };
+                match range.split_once('-') {
+                    Some((a, b)) => (number(a)?, number(b)?),
+                    // "5/15" starts at 5 and runs to the end of the range.
+                    None if step > 1 => (number(range)?, max),
+                    None => (number(range)?, number(range)?),
+                }
+            }
+        };
+        if from > to {
+            return Err(format!("has an empty range '{}'", range));
+        }
+        for n in (from..=to).step_by(step as usize) {
+            bits |= 1 << n;
+        }
+    }
+    Ok(bits)
+}
+
+/// (year, month, day) of a count of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Files under `root` modified after `since` (unix seconds), counting up to
+/// `limit`. Skips `target/`, `.git/` and `node_modules/`.
+pub fn count_changed_files(root: &Path, since: i64, limit: usize) -> usize {
+    WalkDir::new(root)
+        .follow_links(false)
+        .into_iter()
+        .filter_entry(|e| {
+            !matches!(
+                e.file_name().to_str().unwrap_or(""),
+                "target" | ".git" | "node_modules"
+            )
+        })
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| {
+            e.metadata()
+                .ok()
+                .and_then(|m| m.modified().ok())
+                .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                .is_some_and(|m| m.as_secs() as i64 > since)
+        })
+        .take(limit)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-01-01 00:00:00 UTC, a Monday.
+    const NEW_YEAR_2024: i64 = 1_704_067_200;
+
+    #[test]
+    fn next_after_finds_the_following_match() {
+        let daily = Cron::parse("30 3 * * *").unwrap();
+        assert_eq!(
+            daily.next_after(NEW_YEAR_2024),
+            Some(NEW_YEAR_2024 + 3 * 3600 + 30 * 60)
+        );
+
+        let quarter_hours = Cron::parse("*/15 * * * *").unwrap();
+        assert_eq!(
+            quarter_hours.next_after(NEW_YEAR_2024),
+            Some(NEW_YEAR_2024 + 15 * 60)
+        );
+
+        // The first Sunday of 2024 is January 7th.
+        let sundays = Cron::parse("0 0 * * 7").unwrap();
+        assert_eq!(
+            sundays.next_after(NEW_YEAR_2024),
+            Some(NEW_YEAR_2024 + 6 * 86_400)
+        );
+
+        let leap_day = Cron::parse("0 12 29 2 *").unwrap();
+        assert_eq!(
+            leap_day.next_after(NEW_YEAR_2024),
+            Some(NEW_YEAR_2024 + 59 * 86_400 + 12 * 3600)
+        );
+
+        assert_eq!(Cron::parse("0 0 30 2 *").unwrap().next_after(0), None);
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        assert!(Cron::parse("* * * *").is_err());
+        assert!(Cron::parse("60 * * * *").is_err());
+        assert!(Cron::parse("*/0 * * * *").is_err());
+        assert!(Cron::parse("0 5-2 * * *").is_err());
+    }
+
+    #[test]
+    fn cron_due_only_after_a_match() {
+        let schedule: Schedule = toml::from_str(
+            r#"
+            agent = "stack_reviewer"
+            cron = "0 * * * *"
+            "#,
+        )
+        .unwrap();
+        assert!(!schedule.cron_due(NEW_YEAR_2024, NEW_YEAR_2024 + 3599));
+        assert!(schedule.cron_due(NEW_YEAR_2024, NEW_YEAR_2024 + 3600));
+    }
+}
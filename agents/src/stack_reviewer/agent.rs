@@ -10,7 +10,9 @@ use llm_sdk::{
 
 use crate::{
     error::AgentError,
-    storage::{StackNoteStorage, StackTag},
+    prompt_library::PromptLibrary,
+    storage::{AgentStorage, AgentType, LlmUsage, StackNoteStorage, StackTag},
+    tool_args::parse_tool_args,
     utils::{
         file_ops,
         tools::{ListFilesParams, ReadFileParams},
@@ -23,10 +25,12 @@ const MAX_TURNS: u32 = 20;
 
 pub struct StackReviewerAgent {
     llm_client: Arc<dyn LlmClient>,
+    storage: Arc<dyn AgentStorage>,
     stack_note_storage: Arc<dyn StackNoteStorage>,
     model: String,
     project_id: i64,
     project_path: PathBuf,
+    prompts: PromptLibrary,
 }
 
 pub struct StackReviewResult {
@@ -37,6 +41,7 @@ pub struct StackReviewResult {
 impl StackReviewerAgent {
     pub fn new(
         llm_client: Arc<dyn LlmClient>,
+        storage: Arc<dyn AgentStorage>,
         stack_note_storage: Arc<dyn StackNoteStorage>,
         model: impl Into<String>,
         project_id: i64,
@@ -44,13 +49,22 @@ impl StackReviewerAgent {
     ) -> Self {
         Self {
             llm_client,
+            storage,
             stack_note_storage,
             model: model.into(),
             project_id,
             project_path: project_path.into(),
+            prompts: PromptLibrary::embedded(),
         }
     }
 
+    /// Resolve the system prompt through `prompts`, e.g. to pick up the
+    /// project's overrides. Embedded templates are used otherwise.
+    pub fn with_prompts(mut self, prompts: PromptLibrary) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
     pub async fn run(&self) -> Result<StackReviewResult, AgentError> {
         // Load current notes.
         let current_notes = self
@@ -59,7 +73,10 @@ impl StackReviewerAgent {
             .await?;
 
         let current_notes_text = format_notes(&current_notes);
-        let system = super::prompts::system_prompt(&current_notes_text);
+        let system = super::prompts::system_prompt(&self.prompts, &current_notes_text)?;
+        log::info!("[StackReviewer] system prompt {}", system.reference);
+        let prompt_ref = system.reference;
+        let system = system.text;
 
         // Build tools.
         let list_files_tool = Tool::from_type::<ListFilesParams>()
@@ -192,6 +209,19 @@ impl StackReviewerAgent {
                 }
             };
 
+            self.storage
+                .record_usage(LlmUsage {
+                    prompt: Some(prompt_ref.clone()),
+                    ..LlmUsage::from_response(
+                        self.project_id,
+                        AgentType::StackReviewer,
+                        self.llm_client.as_ref(),
+                        &self.model,
+                        &response,
+                    )
+                })
+                .await?;
+
             let assistant_text = response
                 .content
                 .iter()
@@ -232,54 +262,67 @@ impl StackReviewerAgent {
                     // Execute tool and get result.
                     let result = match tool_name.as_str() {
                         "list_files" | "repo_browser.list_files" => {
-                            let params: ListFilesParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
-                            file_ops::list_files(&self.project_path, &params.path)
+                            match parse_tool_args::<ListFilesParams>(&tool_call) {
+                                Ok(params) => file_ops::list_files(&self.project_path, &params.path),
+                                Err(e) => e.to_string(),
+                            }
                         }
                         "read_file" | "repo_browser.open_file" | "repo_browser.read_file" => {
-                            let params: ReadFileParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
-                            file_ops::read_file(&self.project_path, &params.path)
+                            match parse_tool_args::<ReadFileParams>(&tool_call) {
+                                Ok(params) => file_ops::read_file(&self.project_path, &params.path),
+                                Err(e) => e.to_string(),
+                            }
                         }
                         "repo_browser.search" => {
                             "Search is not available. Use list_files to explore directories and read_file to examine files.".to_string()
                         }
                         "emit_note" => {
-                            let params: EmitNoteParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
-
-                            // Same-run dedup check.
-                            if emitted_texts.contains(&params.note) {
-                                format!(
+                            match parse_tool_args::<EmitNoteParams>(&tool_call) {
+                                Err(e) => e.to_string(),
+                                // Same-run dedup check.
+                                Ok(params) if emitted_texts.contains(&params.note) => format!(
                                     "Error: note '{}' was already emitted in this review run.",
                                     params.note
-                                )
-                            } else {
-                                let tag = StackTag::from_str(&params.tag);
-                                match self
-                                    .stack_note_storage
-                                    .add_note(
-                                        self.project_id,
-                                        tag,
-                                        params.note.clone(),
-                                        params.file_path,
-                                        params.line_number,
-                                        params.replaces_note,
-                                    )
-                                    .await
-                                {
-                                    Ok(id) => {
-                                        emitted_ids.push(id);
-                                        emitted_texts.insert(params.note.clone());
-                                        format!("Note emitted with id={}.", id)
+                                ),
+                                Ok(params) => {
+                                    let tag = StackTag::from_str(&params.tag);
+                                    match self
+                                        .stack_note_storage
+                                        .add_note(
+                                            self.project_id,
+                                            tag,
+                                            params.note.clone(),
+                                            params.file_path,
+                                            params.line_number,
+                                            params.replaces_note,
+                                        )
+                                        .await
+                                    {
+                                        Ok(id) => {
+                                            emitted_ids.push(id);
+                                            emitted_texts.insert(params.note.clone());
+                                            format!("Note emitted with id={}.", id)
+                                        }
+                                        Err(e) => format!("Error emitting note: {}", e),
                                     }
-                                    Err(e) => format!("Error emitting note: {}", e),
                                 }
                             }
                         }
                         "finish_review" => {
-                            let params: FinishReviewParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                            let params = match parse_tool_args::<FinishReviewParams>(&tool_call) {
+                                Ok(params) => params,
+                                Err(e) => {
+                                    messages.push(Message {
+                                        role: Role::Tool,
+                                        content: vec![llm_sdk::types::ContentBlock::Text {
+                                            text: e.to_string(),
+                                        }],
+                                        tool_call_id: Some(call_id),
+                                        tool_name: Some(tool_name),
+                                    });
+                                    continue;
+                                }
+                            };
 
                             // Push the tool result message before returning.
                             messages.push(Message {
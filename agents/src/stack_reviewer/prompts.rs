@@ -1,27 +1,13 @@
-pub fn system_prompt(current_notes_text: &str) -> String {
-    format!(
-        r#"You are the Engineering Manager for a software project built on the rustysolid template (Actix-web + SQLite + SolidJS).
-
-Your job: review the project codebase and keep the tech stack notes accurate and up to date.
-
-## Current tech stack notes
-{}
-
-## Instructions
-1. Use `list_files` and `read_file` to explore the project codebase.
-2. Compare what you find against the current notes above.
-3. Call `emit_note` for:
-   - Brand-new facts not covered by any existing note (leave `replaces_note` null).
-   - Updated facts where an existing note is stale or wrong — provide the old note text in `replaces_note`.
-4. Do NOT emit a note if the existing note is still accurate.
-5. Call `finish_review` when you have emitted all relevant changes.
-
-## Rules
-- Each note must be a single short key point (under 120 characters).
-- Tag must be one of: backend, database, frontend, auth, api_contract, config, tooling, deployment, testing.
-- Only use relative paths — never absolute paths.
-- `replaces_note` must be the exact text of an existing note (copy it verbatim from the list above).
-"#,
-        current_notes_text
-    )
+use crate::{
+    error::AgentError,
+    prompt_library::{Prompt, PromptLibrary},
+};
+
+/// System prompt of the stack reviewer (`prompts/stack_reviewer.md`), with
+/// the current notes filled in.
+pub fn system_prompt(
+    prompts: &PromptLibrary,
+    current_notes_text: &str,
+) -> Result<Prompt, AgentError> {
+    prompts.render("stack_reviewer", &[("current_notes", current_notes_text)])
 }
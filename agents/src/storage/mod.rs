@@ -1,16 +1,19 @@
 pub mod message_content;
 pub mod sqlite;
+#[cfg(test)]
+pub(crate) mod test_db;
 
 use async_trait::async_trait;
 use serde::Serialize;
 
-use crate::error::AgentError;
+use crate::{error::AgentError, prompt_library::PromptRef};
 pub use message_content::{MessageContent, QuestionKind, StructuredQuestion, StructuredResponse};
 
 // ---------------------------------------------------------------------------
 // Agent type registry
 // ---------------------------------------------------------------------------
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AgentType {
     DbEngineer,
     ProjectManager,
@@ -19,6 +22,8 @@ pub enum AgentType {
     FrontendEngineer,
     ProductOwner,
     EngineeringManager,
+    RustEngineer,
+    StackReviewer,
 }
 
 impl AgentType {
@@ -31,6 +36,8 @@ impl AgentType {
             "frontend_engineer" => AgentType::FrontendEngineer,
             "product_owner" => AgentType::ProductOwner,
             "engineering_manager" => AgentType::EngineeringManager,
+            "rust_engineer" => AgentType::RustEngineer,
+            "stack_reviewer" => AgentType::StackReviewer,
             _ => AgentType::ProjectManager,
         }
     }
@@ -44,6 +51,8 @@ impl AgentType {
             AgentType::FrontendEngineer => "frontend_engineer",
             AgentType::ProductOwner => "product_owner",
             AgentType::EngineeringManager => "engineering_manager",
+            AgentType::RustEngineer => "rust_engineer",
+            AgentType::StackReviewer => "stack_reviewer",
         }
     }
 }
@@ -105,10 +114,14 @@ pub struct Task {
     pub source_prompt: String,
     pub assigned_to_agent: String,
     pub status: TaskStatus,
+    /// First task this one waits for. `create_task` records it in the
+    /// dependency table; `add_task_dependencies` adds more.
     pub depends_on_task_id: Option<i64>,
     pub created_by_agent: String,
     pub created_at: i64,
     pub updated_at: i64,
+    /// Task this one was created on behalf of, e.g. by a workflow rule.
+    pub created_by_task_id: Option<i64>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -117,6 +130,8 @@ pub enum TaskStatus {
     NeedsTechnicalShaping,
     Ready,
     InProgress,
+    /// The agent proposed its result; a user approves it before it is applied.
+    AwaitingApproval,
     Done,
     Blocked,
 }
@@ -128,6 +143,7 @@ impl TaskStatus {
             Self::NeedsTechnicalShaping => "needs_technical_shaping",
             Self::Ready => "ready",
             Self::InProgress => "in_progress",
+            Self::AwaitingApproval => "awaiting_approval",
             Self::Done => "done",
             Self::Blocked => "blocked",
         }
@@ -139,6 +155,7 @@ impl TaskStatus {
             "needs_technical_shaping" => Self::NeedsTechnicalShaping,
             "ready" => Self::Ready,
             "in_progress" => Self::InProgress,
+            "awaiting_approval" => Self::AwaitingApproval,
             "done" => Self::Done,
             "blocked" => Self::Blocked,
             // Legacy mappings
@@ -166,7 +183,7 @@ pub struct Session {
 pub struct ChatMessage {
     pub id: Option<i64>,
     pub session_id: i64,
-    /// "user" | "assistant" | "tool"
+    /// "user" | "assistant" | "tool" | "summary"
     pub role: String,
     /// NULL for human messages; agent identifier for agent-originated rows.
     pub agent_type: Option<String>,
@@ -182,6 +199,73 @@ pub struct ChatMessage {
     pub created_at: i64,
 }
 
+/// A stored summary standing in for the oldest rows of a chat session.
+#[derive(Debug, Clone)]
+pub struct HistorySummary {
+    pub id: i64,
+    pub content: String,
+    /// id of the first row the summary does not cover.
+    pub covers_until_id: i64,
+    pub created_at: i64,
+}
+
+// ---------------------------------------------------------------------------
+// LLM usage
+// ---------------------------------------------------------------------------
+
+/// Token usage of one LLM completion.
+#[derive(Debug, Clone)]
+pub struct LlmUsage {
+    pub project_id: i64,
+    pub agent_type: String,
+    /// `None` for user-chat runs (PO, PM planning), which have no task.
+    pub task_id: Option<i64>,
+    /// `agent_chat_session` id for task runs, `user_chat_session` id otherwise.
+    pub session_id: Option<i64>,
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    /// System-prompt template of the request; `None` for untemplated calls.
+    pub prompt: Option<PromptRef>,
+}
+
+impl LlmUsage {
+    /// Usage of `response`, attributed to the client's provider and `model`.
+    /// Callers fill in `task_id` / `session_id` / `prompt`.
+    pub fn from_response(
+        project_id: i64,
+        agent_type: AgentType,
+        client: &dyn llm_sdk::client::LlmClient,
+        model: &str,
+        response: &llm_sdk::types::CompletionResponse,
+    ) -> Self {
+        Self {
+            project_id,
+            agent_type: agent_type.as_str().to_string(),
+            task_id: None,
+            session_id: None,
+            provider: client.provider_name().to_string(),
+            model: model.to_string(),
+            input_tokens: i64::from(response.usage.input_tokens),
+            output_tokens: i64::from(response.usage.output_tokens),
+            prompt: None,
+        }
+    }
+}
+
+/// Usage summed over one (agent, task, provider, model) group of a project.
+#[derive(Debug, Clone, Serialize)]
+pub struct LlmUsageTotal {
+    pub agent_type: String,
+    pub task_id: Option<i64>,
+    pub provider: String,
+    pub model: String,
+    pub calls: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+}
+
 // ---------------------------------------------------------------------------
 // User / User-chat / Comment row structs
 // ---------------------------------------------------------------------------
@@ -262,6 +346,16 @@ pub trait AgentStorage: Send + Sync {
         agent_type: &str,
     ) -> Result<Session, AgentError>;
 
+    /// Record which model (`provider:model`) produced the session's final result.
+    async fn set_session_model(&self, session_id: i64, model: &str) -> Result<(), AgentError>;
+
+    /// Record the system-prompt template (id and version) the session runs with.
+    async fn set_session_prompt(
+        &self,
+        session_id: i64,
+        prompt: &PromptRef,
+    ) -> Result<(), AgentError>;
+
     /// Find the session for a (task_id, agent_type) pair, if it exists.
     async fn get_session_by_task(
         &self,
@@ -275,7 +369,27 @@ pub trait AgentStorage: Send + Sync {
     /// Persist all rows of one LLM response turn atomically.
     async fn create_turn(&self, messages: Vec<ChatMessage>) -> Result<i64, AgentError>;
 
+    /// Every message row of the session, oldest first. Summary rows are left out.
     async fn get_messages(&self, session_id: i64) -> Result<Vec<ChatMessage>, AgentError>;
+
+    /// Persist a role="summary" row standing in for every row of the session
+    /// with id < `covers_until_id`.
+    async fn create_summary(
+        &self,
+        session_id: i64,
+        agent_type: &str,
+        content: &str,
+        covers_until_id: i64,
+    ) -> Result<i64, AgentError>;
+
+    /// The newest summary row of the session, if it was ever summarized.
+    async fn latest_summary(&self, session_id: i64) -> Result<Option<HistorySummary>, AgentError>;
+
+    /// Persist the token usage of one completion.
+    async fn record_usage(&self, usage: LlmUsage) -> Result<(), AgentError>;
+
+    /// A project's usage summed per (agent, task, provider, model).
+    async fn list_usage_totals(&self, project_id: i64) -> Result<Vec<LlmUsageTotal>, AgentError>;
 }
 
 // ---------------------------------------------------------------------------
@@ -285,6 +399,14 @@ pub trait AgentStorage: Send + Sync {
 #[async_trait]
 pub trait TaskStorage: Send + Sync {
     async fn create_task(&self, task: Task) -> Result<i64, AgentError>;
+    /// In one transaction, create `tasks` in place of `parent_id` and move it
+    /// to Done. Each task comes with the positions (0-based) of the earlier
+    /// tasks in `tasks` it waits for. Returns the ids of the created tasks.
+    async fn split_task(
+        &self,
+        parent_id: i64,
+        tasks: Vec<(Task, Vec<usize>)>,
+    ) -> Result<Vec<i64>, AgentError>;
     async fn update_task_status(&self, task_id: i64, status: TaskStatus) -> Result<(), AgentError>;
     async fn get_task(&self, task_id: i64) -> Result<Option<Task>, AgentError>;
     async fn list_tasks_for_project(&self, project_id: i64) -> Result<Vec<Task>, AgentError>;
@@ -293,10 +415,37 @@ pub trait TaskStorage: Send + Sync {
         project_id: i64,
         agent_type: &str,
     ) -> Result<Vec<Task>, AgentError>;
+    /// Tasks whose `created_by_task_id` is `task_id`, oldest first.
+    async fn list_tasks_created_by(&self, task_id: i64) -> Result<Vec<Task>, AgentError>;
     async fn list_pending_review_tasks(&self, project_id: i64) -> Result<Vec<Task>, AgentError>;
 
-    /// All open tasks across every project that have no agent session yet and are
-    /// not assigned to project_manager. Used by the startup reconciliation pass.
+    /// Makes `task_id` wait for each of `depends_on`. Adds none of them and
+    /// fails with `DependencyCycle` when one would make the task depend on
+    /// itself, directly or through other tasks.
+    async fn add_task_dependencies(
+        &self,
+        task_id: i64,
+        depends_on: &[i64],
+    ) -> Result<(), AgentError>;
+    /// Ids of the tasks `task_id` waits for.
+    async fn list_task_dependencies(&self, task_id: i64) -> Result<Vec<i64>, AgentError>;
+    /// Tasks waiting for `task_id`, oldest first.
+    async fn list_dependents(&self, task_id: i64) -> Result<Vec<Task>, AgentError>;
+    /// Every `(task_id, depends_on_task_id)` edge in the project.
+    async fn list_dependency_edges(&self, project_id: i64) -> Result<Vec<(i64, i64)>, AgentError>;
+
+    /// Number of the project's done tasks of these agents updated after
+    /// `since`.
+    async fn count_finished_tasks(
+        &self,
+        project_id: i64,
+        agent_types: &[String],
+        since: i64,
+    ) -> Result<usize, AgentError>;
+
+    /// All ready or needs_technical_shaping tasks across every project that
+    /// have no agent session, have never been queued as a job and are not
+    /// assigned to project_manager. Used by the startup reconciliation pass.
     async fn list_open_dispatchable_tasks(&self) -> Result<Vec<Task>, AgentError>;
 
     async fn create_epic(&self, epic: Epic) -> Result<i64, AgentError>;
@@ -479,8 +628,22 @@ pub trait UserChatStorage: Send + Sync {
         content: MessageContent,
     ) -> Result<i64, AgentError>;
 
+    /// Every message row of the session, oldest first. Summary rows are left out.
     async fn get_messages(&self, session_id: i64) -> Result<Vec<UserChatMessageRow>, AgentError>;
 
+    /// Persist a content_type="summary" row standing in for every row of the
+    /// session with id < `covers_until_id`.
+    async fn append_summary(
+        &self,
+        session_id: i64,
+        agent_type: AgentType,
+        content: &str,
+        covers_until_id: i64,
+    ) -> Result<i64, AgentError>;
+
+    /// The newest summary row of the session, if it was ever summarized.
+    async fn latest_summary(&self, session_id: i64) -> Result<Option<HistorySummary>, AgentError>;
+
     async fn complete_session(&self, session_id: i64) -> Result<(), AgentError>;
 
     async fn set_handoff_session_id(
@@ -622,3 +785,311 @@ pub trait CommentStorage: Send + Sync {
 
     async fn get_task_comments(&self, task_id: i64) -> Result<Vec<TaskCommentRow>, AgentError>;
 }
+
+// ---------------------------------------------------------------------------
+// Job queue — persisted dispatch of tasks to agents
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Done => "done",
+            Self::Failed => "failed",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Self {
+        match s {
+            "running" => Self::Running,
+            "done" => Self::Done,
+            "failed" => Self::Failed,
+            _ => Self::Queued,
+        }
+    }
+}
+
+/// One run of a task by its agent. Only the worker holding the lease may run
+/// it; a lease that is not renewed by heartbeats expires and the job is
+/// claimed again.
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: i64,
+    pub task_id: i64,
+    pub project_id: i64,
+    pub agent_type: String,
+    pub source_prompt: String,
+    pub status: JobStatus,
+    /// Higher runs first; FIFO among equal priorities.
+    pub priority: i64,
+    /// Number of times the job has been claimed, including the current run.
+    pub attempts: i64,
+    /// A queued job is not claimed before this time (retry backoff).
+    pub available_at: i64,
+    pub lease_owner: Option<String>,
+    pub lease_expires_at: Option<i64>,
+    pub heartbeat_at: Option<i64>,
+    pub last_error: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobCount {
+    pub agent_type: String,
+    pub status: JobStatus,
+    pub count: i64,
+}
+
+#[async_trait]
+pub trait JobStorage: Send + Sync {
+    /// Queue a run of the task. Returns `None` when the task already has a
+    /// queued or running job.
+    async fn enqueue_job(
+        &self,
+        task_id: i64,
+        project_id: i64,
+        agent_type: &str,
+        source_prompt: &str,
+        priority: i64,
+    ) -> Result<Option<i64>, AgentError>;
+
+    /// Take a lease of `lease_secs` on the next claimable job — a queued one
+    /// that is due, or a running one whose lease expired — by priority, then
+    /// age. Jobs of `skip_agent_types`, and jobs whose task waits for a task
+    /// that is not done yet, are left in the queue. A job with an expired
+    /// lease that has already been claimed `max_attempts` times is failed
+    /// instead, and its task blocked with a comment saying why.
+    async fn claim_job(
+        &self,
+        worker: &str,
+        lease_secs: i64,
+        max_attempts: i64,
+        skip_agent_types: &[String],
+    ) -> Result<Option<Job>, AgentError>;
+
+    /// Extend the lease. Returns `false` when `worker` no longer holds it.
+    async fn heartbeat_job(
+        &self,
+        job_id: i64,
+        worker: &str,
+        lease_secs: i64,
+    ) -> Result<bool, AgentError>;
+
+    /// Put a failed run back in the queue, claimable after `delay_secs`.
+    /// Ignored when `worker` no longer holds the lease.
+    async fn retry_job(
+        &self,
+        job_id: i64,
+        worker: &str,
+        error: &str,
+        delay_secs: i64,
+    ) -> Result<(), AgentError>;
+
+    /// Finish the job, successfully or with `error`. Ignored when `worker` no
+    /// longer holds the lease.
+    async fn finish_job(
+        &self,
+        job_id: i64,
+        worker: &str,
+        error: Option<&str>,
+    ) -> Result<(), AgentError>;
+
+    /// Fail the task's queued job with `error` before any worker claims it.
+    /// Returns the job, or `None` when the task has no queued job.
+    async fn cancel_queued_job(&self, task_id: i64, error: &str)
+        -> Result<Option<Job>, AgentError>;
+
+    async fn get_job(&self, job_id: i64) -> Result<Option<Job>, AgentError>;
+
+    /// The task's job that a worker holds, if any.
+    async fn get_running_job(&self, task_id: i64) -> Result<Option<Job>, AgentError>;
+
+    /// All jobs of the project, newest first.
+    async fn list_jobs(&self, project_id: i64) -> Result<Vec<Job>, AgentError>;
+
+    /// Number of queued and running jobs of the project per agent type.
+    async fn count_open_jobs(&self, project_id: i64) -> Result<Vec<JobCount>, AgentError>;
+}
+
+// ---------------------------------------------------------------------------
+// Proposals — agent results that wait for a user's approval
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalStatus {
+    Pending,
+    Approved,
+    Rejected,
+    ChangesRequested,
+}
+
+impl ProposalStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Rejected => "rejected",
+            Self::ChangesRequested => "changes_requested",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Self {
+        match s {
+            "approved" => Self::Approved,
+            "rejected" => Self::Rejected,
+            "changes_requested" => Self::ChangesRequested,
+            _ => Self::Pending,
+        }
+    }
+}
+
+/// A result an agent proposed instead of applying it. Approving the proposal
+/// applies its artifact; rejecting it or asking for changes runs the agent
+/// again with the user's comment.
+#[derive(Debug, Clone, Serialize)]
+pub struct Proposal {
+    pub id: i64,
+    pub task_id: i64,
+    pub project_id: i64,
+    pub agent_type: String,
+    pub session_id: i64,
+    /// What approval applies, as JSON: a schema, a form layout or code.
+    pub artifact: String,
+    /// One line for the reviewer, e.g. "schema with 3 tables".
+    pub summary: String,
+    pub status: ProposalStatus,
+    /// Why the user rejected the proposal or what to change.
+    pub comment: Option<String>,
+    pub created_at: i64,
+    pub decided_at: Option<i64>,
+}
+
+#[async_trait]
+pub trait ProposalStorage: Send + Sync {
+    /// Store a pending proposal for the task and return its id. Fails while
+    /// the task has another pending proposal.
+    async fn create_proposal(
+        &self,
+        task_id: i64,
+        project_id: i64,
+        agent_type: &str,
+        session_id: i64,
+        artifact: &str,
+        summary: &str,
+    ) -> Result<i64, AgentError>;
+
+    /// The task's pending proposal, if any.
+    async fn get_pending_proposal(&self, task_id: i64) -> Result<Option<Proposal>, AgentError>;
+
+    /// All proposals of the task, newest first.
+    async fn list_task_proposals(&self, task_id: i64) -> Result<Vec<Proposal>, AgentError>;
+
+    /// All proposals of the project, newest first.
+    async fn list_proposals(&self, project_id: i64) -> Result<Vec<Proposal>, AgentError>;
+
+    /// Record the user's decision on a pending proposal. Returns false when
+    /// it was already decided.
+    async fn decide_proposal(
+        &self,
+        proposal_id: i64,
+        status: ProposalStatus,
+        comment: Option<&str>,
+    ) -> Result<bool, AgentError>;
+
+    /// Put an approved proposal back to pending, e.g. when applying it
+    /// failed.
+    async fn reopen_proposal(&self, proposal_id: i64) -> Result<(), AgentError>;
+}
+
+// ---------------------------------------------------------------------------
+// Scheduled runs — project-wide agents run on a schedule or a trigger
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleRunStatus {
+    Running,
+    Done,
+    Failed,
+}
+
+impl ScheduleRunStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::Done => "done",
+            Self::Failed => "failed",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Self {
+        match s {
+            "done" => Self::Done,
+            "failed" => Self::Failed,
+            _ => Self::Running,
+        }
+    }
+}
+
+/// One run of a project-wide agent such as the stack reviewer.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduleRun {
+    pub id: i64,
+    pub project_id: i64,
+    pub agent_type: String,
+    /// What started the run, e.g. "cron 0 3 * * *", "25 files changed" or
+    /// "requested".
+    pub reason: String,
+    pub status: ScheduleRunStatus,
+    /// The agent's summary, or the error of a failed run.
+    pub summary: Option<String>,
+    pub started_at: i64,
+    pub finished_at: Option<i64>,
+}
+
+#[async_trait]
+pub trait ScheduleStorage: Send + Sync {
+    /// Record the start of a run and return its id, or `None` when the
+    /// agent is already running on the project.
+    async fn begin_run(
+        &self,
+        project_id: i64,
+        agent_type: &str,
+        reason: &str,
+    ) -> Result<Option<i64>, AgentError>;
+
+    /// Ignored when the run is no longer running, e.g. `fail_stale_runs`
+    /// already failed it.
+    async fn finish_run(
+        &self,
+        run_id: i64,
+        status: ScheduleRunStatus,
+        summary: &str,
+    ) -> Result<(), AgentError>;
+
+    /// The project's latest run of the agent, finished or not.
+    async fn last_run(
+        &self,
+        project_id: i64,
+        agent_type: &str,
+    ) -> Result<Option<ScheduleRun>, AgentError>;
+
+    /// The project's runs, newest first.
+    async fn list_runs(&self, project_id: i64) -> Result<Vec<ScheduleRun>, AgentError>;
+
+    /// Fail the runs still running that started before `started_before` —
+    /// their server stopped during the run. Returns how many.
+    async fn fail_stale_runs(&self, started_before: i64) -> Result<usize, AgentError>;
+}
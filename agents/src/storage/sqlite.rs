@@ -5,12 +5,13 @@ use rusqlite::{params, Connection, OptionalExtension};
 
 use super::{
     AgentStorage, AgentType, ChatMessage, CommentStorage, ContextStorage, Epic, EpicCommentRow,
-    EpicStatus, MessageContent, ProjectNoteRow, ProjectNoteStorage, ProjectNoteTopic,
-    SchemaStorage, Session, StackNoteRow, StackNoteStorage, StackTag, Task, TaskCommentRow,
-    TaskStatus, TaskStorage, UiFormStorage, UserChatMessageRow, UserChatSessionRow,
-    UserChatStorage, UserRow, UserStorage,
+    EpicStatus, HistorySummary, Job, JobCount, JobStatus, JobStorage, LlmUsage, LlmUsageTotal,
+    MessageContent, ProjectNoteRow, ProjectNoteStorage, ProjectNoteTopic, Proposal, ProposalStatus,
+    ProposalStorage, ScheduleRun, ScheduleRunStatus, ScheduleStorage, SchemaStorage, Session,
+    StackNoteRow, StackNoteStorage, StackTag, Task, TaskCommentRow, TaskStatus, TaskStorage,
+    UiFormStorage, UserChatMessageRow, UserChatSessionRow, UserChatStorage, UserRow, UserStorage,
 };
-use crate::error::AgentError;
+use crate::{error::AgentError, prompt_library::PromptRef};
 
 fn now() -> i64 {
     std::time::SystemTime::now()
@@ -39,6 +40,19 @@ impl SqliteAgentStorage {
         Ok(Self::new(conn))
     }
 
+    /// Root directory of the project's code, if it has one.
+    pub fn project_path(&self, project_id: i64) -> Result<Option<String>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let path = conn
+            .query_row(
+                "SELECT path FROM project WHERE id = ?1",
+                params![project_id],
+                |row| row.get::<_, Option<String>>(0),
+            )
+            .optional()?;
+        Ok(path.flatten())
+    }
+
     pub async fn list_sessions(
         &self,
         project_id: i64,
@@ -98,6 +112,29 @@ fn map_session(row: &rusqlite::Row<'_>) -> rusqlite::Result<Session> {
     })
 }
 
+fn map_chat_message(row: &rusqlite::Row<'_>) -> rusqlite::Result<ChatMessage> {
+    Ok(ChatMessage {
+        id: Some(row.get(0)?),
+        session_id: row.get(1)?,
+        role: row.get(2)?,
+        agent_type: row.get(3)?,
+        content: row.get(4)?,
+        tool_call_id: row.get(5)?,
+        tool_name: row.get(6)?,
+        turn_id: row.get(7)?,
+        created_at: row.get(8)?,
+    })
+}
+
+fn map_history_summary(row: &rusqlite::Row<'_>) -> rusqlite::Result<HistorySummary> {
+    Ok(HistorySummary {
+        id: row.get(0)?,
+        content: row.get(1)?,
+        covers_until_id: row.get(2)?,
+        created_at: row.get(3)?,
+    })
+}
+
 #[async_trait]
 impl AgentStorage for SqliteAgentStorage {
     async fn rename_project(&self, project_id: i64, name: &str) -> Result<(), AgentError> {
@@ -132,6 +169,28 @@ impl AgentStorage for SqliteAgentStorage {
         })
     }
 
+    async fn set_session_model(&self, session_id: i64, model: &str) -> Result<(), AgentError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE agent_chat_session SET model = ?1 WHERE id = ?2",
+            params![model, session_id],
+        )?;
+        Ok(())
+    }
+
+    async fn set_session_prompt(
+        &self,
+        session_id: i64,
+        prompt: &PromptRef,
+    ) -> Result<(), AgentError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE agent_chat_session SET prompt_id = ?1, prompt_version = ?2 WHERE id = ?3",
+            params![prompt.id, prompt.version, session_id],
+        )?;
+        Ok(())
+    }
+
     async fn get_session_by_task(
         &self,
         task_id: i64,
@@ -223,26 +282,95 @@ impl AgentStorage for SqliteAgentStorage {
         let mut stmt = conn.prepare(
             "SELECT id, session_id, role, agent_type, content, tool_call_id, tool_name, turn_id, created_at
              FROM agent_chat_message
-             WHERE session_id = ?1
+             WHERE session_id = ?1 AND role != 'summary'
              ORDER BY id ASC",
         )?;
         let messages = stmt
-            .query_map(params![session_id], |row| {
-                Ok(ChatMessage {
-                    id: Some(row.get(0)?),
-                    session_id: row.get(1)?,
-                    role: row.get(2)?,
-                    agent_type: row.get(3)?,
-                    content: row.get(4)?,
-                    tool_call_id: row.get(5)?,
-                    tool_name: row.get(6)?,
-                    turn_id: row.get(7)?,
-                    created_at: row.get(8)?,
-                })
-            })?
+            .query_map(params![session_id], map_chat_message)?
             .collect::<Result<Vec<_>, _>>()?;
         Ok(messages)
     }
+
+    async fn create_summary(
+        &self,
+        session_id: i64,
+        agent_type: &str,
+        content: &str,
+        covers_until_id: i64,
+    ) -> Result<i64, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO agent_chat_message
+                 (session_id, role, agent_type, content, covers_until_id, created_at)
+             VALUES (?1, 'summary', ?2, ?3, ?4, ?5)",
+            params![session_id, agent_type, content, covers_until_id, now()],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    async fn latest_summary(&self, session_id: i64) -> Result<Option<HistorySummary>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let summary = conn
+            .query_row(
+                "SELECT id, content, covers_until_id, created_at
+                 FROM agent_chat_message
+                 WHERE session_id = ?1 AND role = 'summary'
+                 ORDER BY id DESC
+                 LIMIT 1",
+                params![session_id],
+                map_history_summary,
+            )
+            .optional()?;
+        Ok(summary)
+    }
+
+    async fn record_usage(&self, usage: LlmUsage) -> Result<(), AgentError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO llm_usage
+                 (project_id, agent_type, task_id, session_id, provider, model,
+                  input_tokens, output_tokens, prompt_id, prompt_version, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                usage.project_id,
+                usage.agent_type,
+                usage.task_id,
+                usage.session_id,
+                usage.provider,
+                usage.model,
+                usage.input_tokens,
+                usage.output_tokens,
+                usage.prompt.as_ref().map(|p| &p.id),
+                usage.prompt.as_ref().map(|p| &p.version),
+                now()
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn list_usage_totals(&self, project_id: i64) -> Result<Vec<LlmUsageTotal>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT agent_type, task_id, provider, model, COUNT(*),
+                    SUM(input_tokens), SUM(output_tokens)
+             FROM llm_usage
+             WHERE project_id = ?1
+             GROUP BY agent_type, task_id, provider, model
+             ORDER BY agent_type, task_id, provider, model",
+        )?;
+        let rows = stmt.query_map(params![project_id], |row| {
+            Ok(LlmUsageTotal {
+                agent_type: row.get(0)?,
+                task_id: row.get(1)?,
+                provider: row.get(2)?,
+                model: row.get(3)?,
+                calls: row.get(4)?,
+                input_tokens: row.get(5)?,
+                output_tokens: row.get(6)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -280,6 +408,7 @@ fn map_task(row: &rusqlite::Row<'_>) -> rusqlite::Result<Task> {
         created_by_agent: row.get(9)?,
         created_at: row.get(10)?,
         updated_at: row.get(11)?,
+        created_by_task_id: row.get(12)?,
     })
 }
 
@@ -298,42 +427,98 @@ fn map_epic(row: &rusqlite::Row<'_>) -> rusqlite::Result<Epic> {
     })
 }
 
+/// Insert `task`, and its `depends_on_task_id` as a dependency edge.
+fn insert_task(conn: &Connection, task: &Task) -> rusqlite::Result<i64> {
+    let ts = now();
+    let created_at = if task.created_at == 0 {
+        ts
+    } else {
+        task.created_at
+    };
+    let updated_at = if task.updated_at == 0 {
+        ts
+    } else {
+        task.updated_at
+    };
+    conn.execute(
+        "INSERT INTO task
+             (project_id, epic_id, title, description, source_prompt,
+              assigned_to_agent, status, depends_on_task_id, created_by_agent,
+              created_at, updated_at, created_by_task_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            task.project_id,
+            task.epic_id,
+            task.title,
+            task.description,
+            task.source_prompt,
+            task.assigned_to_agent,
+            task.status.as_str(),
+            task.depends_on_task_id,
+            task.created_by_agent,
+            created_at,
+            updated_at,
+            task.created_by_task_id,
+        ],
+    )?;
+    let id = conn.last_insert_rowid();
+    if let Some(depends_on) = task.depends_on_task_id {
+        conn.execute(
+            "INSERT OR IGNORE INTO task_dependency (task_id, depends_on_task_id, created_at)
+             VALUES (?1, ?2, ?3)",
+            params![id, depends_on, created_at],
+        )?;
+    }
+    Ok(id)
+}
+
 #[async_trait]
 impl TaskStorage for SqliteTaskStorage {
     async fn create_task(&self, task: Task) -> Result<i64, AgentError> {
-        let ts = now();
-        let created_at = if task.created_at == 0 {
-            ts
-        } else {
-            task.created_at
-        };
-        let updated_at = if task.updated_at == 0 {
-            ts
-        } else {
-            task.updated_at
-        };
         let conn = self.conn.lock().unwrap();
-        conn.execute(
-            "INSERT INTO task
-                 (project_id, epic_id, title, description, source_prompt,
-                  assigned_to_agent, status, depends_on_task_id, created_by_agent,
-                  created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
-            params![
-                task.project_id,
-                task.epic_id,
-                task.title,
-                task.description,
-                task.source_prompt,
-                task.assigned_to_agent,
-                task.status.as_str(),
-                task.depends_on_task_id,
-                task.created_by_agent,
-                created_at,
-                updated_at,
-            ],
+        Ok(insert_task(&conn, &task)?)
+    }
+
+    async fn split_task(
+        &self,
+        parent_id: i64,
+        tasks: Vec<(Task, Vec<usize>)>,
+    ) -> Result<Vec<i64>, AgentError> {
+        let ts = now();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut ids: Vec<i64> = Vec::with_capacity(tasks.len());
+        for (mut task, depends_on) in tasks {
+            let depends_on: Vec<i64> = depends_on
+                .iter()
+                .map(|&idx| {
+                    ids.get(idx).copied().ok_or_else(|| {
+                        AgentError::Other(format!(
+                            "split task {} depends on task {}, which is not an earlier one",
+                            ids.len(),
+                            idx
+                        ))
+                    })
+                })
+                .collect::<Result<_, _>>()?;
+            task.depends_on_task_id = depends_on.first().copied();
+            let id = insert_task(&tx, &task)?;
+            // Dependencies point at earlier tasks only, so they cannot form a cycle.
+            for dep in depends_on.iter().skip(1) {
+                tx.execute(
+                    "INSERT OR IGNORE INTO task_dependency (task_id, depends_on_task_id, created_at)
+                     VALUES (?1, ?2, ?3)",
+                    params![id, dep, ts],
+                )?;
+            }
+            ids.push(id);
+        }
+        tx.execute(
+            "UPDATE task SET status = ?1, updated_at = ?2 WHERE id = ?3",
+            params![TaskStatus::Done.as_str(), ts, parent_id],
         )?;
-        Ok(conn.last_insert_rowid())
+        tx.commit()?;
+        Ok(ids)
     }
 
     async fn update_task_status(&self, task_id: i64, status: TaskStatus) -> Result<(), AgentError> {
@@ -351,7 +536,7 @@ impl TaskStorage for SqliteTaskStorage {
             .query_row(
                 "SELECT id, project_id, epic_id, title, description, source_prompt,
                         assigned_to_agent, status, depends_on_task_id, created_by_agent,
-                        created_at, updated_at
+                        created_at, updated_at, created_by_task_id
                  FROM task WHERE id = ?1 LIMIT 1",
                 params![task_id],
                 map_task,
@@ -365,7 +550,7 @@ impl TaskStorage for SqliteTaskStorage {
         let mut stmt = conn.prepare(
             "SELECT id, project_id, epic_id, title, description, source_prompt,
                     assigned_to_agent, status, depends_on_task_id, created_by_agent,
-                    created_at, updated_at
+                    created_at, updated_at, created_by_task_id
              FROM task WHERE project_id = ?1 ORDER BY id ASC",
         )?;
         let tasks = stmt
@@ -383,7 +568,7 @@ impl TaskStorage for SqliteTaskStorage {
         let mut stmt = conn.prepare(
             "SELECT id, project_id, epic_id, title, description, source_prompt,
                     assigned_to_agent, status, depends_on_task_id, created_by_agent,
-                    created_at, updated_at
+                    created_at, updated_at, created_by_task_id
              FROM task WHERE project_id = ?1 AND assigned_to_agent = ?2 ORDER BY id ASC",
         )?;
         let tasks = stmt
@@ -392,12 +577,26 @@ impl TaskStorage for SqliteTaskStorage {
         Ok(tasks)
     }
 
+    async fn list_tasks_created_by(&self, task_id: i64) -> Result<Vec<Task>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, project_id, epic_id, title, description, source_prompt,
+                    assigned_to_agent, status, depends_on_task_id, created_by_agent,
+                    created_at, updated_at, created_by_task_id
+             FROM task WHERE created_by_task_id = ?1 ORDER BY id ASC",
+        )?;
+        let tasks = stmt
+            .query_map(params![task_id], map_task)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(tasks)
+    }
+
     async fn list_pending_review_tasks(&self, project_id: i64) -> Result<Vec<Task>, AgentError> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
             "SELECT id, project_id, epic_id, title, description, source_prompt,
                     assigned_to_agent, status, depends_on_task_id, created_by_agent,
-                    created_at, updated_at
+                    created_at, updated_at, created_by_task_id
              FROM task WHERE project_id = ?1 AND status = 'review' ORDER BY id ASC",
         )?;
         let tasks = stmt
@@ -406,18 +605,134 @@ impl TaskStorage for SqliteTaskStorage {
         Ok(tasks)
     }
 
+    async fn add_task_dependencies(
+        &self,
+        task_id: i64,
+        depends_on: &[i64],
+    ) -> Result<(), AgentError> {
+        let ts = now();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for &dep in depends_on {
+            // Walk what `dep` already waits for; reaching `task_id` means the
+            // new edge closes a loop. Edges added earlier in this call count.
+            let cycle = dep == task_id
+                || tx.query_row(
+                    "WITH RECURSIVE reach(id) AS (
+                         SELECT ?2
+                         UNION
+                         SELECT d.depends_on_task_id
+                         FROM task_dependency d JOIN reach ON d.task_id = reach.id
+                     )
+                     SELECT EXISTS (SELECT 1 FROM reach WHERE id = ?1)",
+                    params![task_id, dep],
+                    |row| row.get::<_, bool>(0),
+                )?;
+            if cycle {
+                return Err(AgentError::DependencyCycle(format!(
+                    "task #{} cannot depend on #{}, which already waits for it",
+                    task_id, dep
+                )));
+            }
+            tx.execute(
+                "INSERT OR IGNORE INTO task_dependency (task_id, depends_on_task_id, created_at)
+                 VALUES (?1, ?2, ?3)",
+                params![task_id, dep, ts],
+            )?;
+        }
+        tx.execute(
+            "UPDATE task
+             SET depends_on_task_id = COALESCE(depends_on_task_id, ?2), updated_at = ?3
+             WHERE id = ?1",
+            params![task_id, depends_on.first(), ts],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    async fn list_task_dependencies(&self, task_id: i64) -> Result<Vec<i64>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT depends_on_task_id FROM task_dependency
+             WHERE task_id = ?1 ORDER BY depends_on_task_id ASC",
+        )?;
+        let ids = stmt
+            .query_map(params![task_id], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ids)
+    }
+
+    async fn list_dependents(&self, task_id: i64) -> Result<Vec<Task>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT t.id, t.project_id, t.epic_id, t.title, t.description, t.source_prompt,
+                    t.assigned_to_agent, t.status, t.depends_on_task_id, t.created_by_agent,
+                    t.created_at, t.updated_at, t.created_by_task_id
+             FROM task t
+             JOIN task_dependency d ON d.task_id = t.id
+             WHERE d.depends_on_task_id = ?1
+             ORDER BY t.id ASC",
+        )?;
+        let tasks = stmt
+            .query_map(params![task_id], map_task)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(tasks)
+    }
+
+    async fn list_dependency_edges(&self, project_id: i64) -> Result<Vec<(i64, i64)>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT d.task_id, d.depends_on_task_id
+             FROM task_dependency d
+             JOIN task t ON t.id = d.task_id
+             WHERE t.project_id = ?1
+             ORDER BY d.task_id ASC, d.depends_on_task_id ASC",
+        )?;
+        let edges = stmt
+            .query_map(params![project_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(edges)
+    }
+
+    async fn count_finished_tasks(
+        &self,
+        project_id: i64,
+        agent_types: &[String],
+        since: i64,
+    ) -> Result<usize, AgentError> {
+        if agent_types.is_empty() {
+            return Ok(0);
+        }
+        let conn = self.conn.lock().unwrap();
+        let placeholders = vec!["?"; agent_types.len()].join(", ");
+        let mut values: Vec<&dyn rusqlite::ToSql> = vec![&project_id, &since];
+        values.extend(agent_types.iter().map(|a| a as &dyn rusqlite::ToSql));
+        let count: i64 = conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM task
+                 WHERE project_id = ? AND status = 'done' AND updated_at > ?
+                   AND assigned_to_agent IN ({})",
+                placeholders
+            ),
+            values.as_slice(),
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
     async fn list_open_dispatchable_tasks(&self) -> Result<Vec<Task>, AgentError> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
             "SELECT t.id, t.project_id, t.epic_id, t.title, t.description, t.source_prompt,
                     t.assigned_to_agent, t.status, t.depends_on_task_id, t.created_by_agent,
-                    t.created_at, t.updated_at
+                    t.created_at, t.updated_at, t.created_by_task_id
              FROM task t
              LEFT JOIN agent_chat_session s
                     ON s.task_id = t.id AND s.agent_type = t.assigned_to_agent
-             WHERE t.status = 'ready'
+             WHERE t.status IN ('ready', 'needs_technical_shaping')
                AND t.assigned_to_agent != 'project_manager'
                AND s.id IS NULL
+               AND NOT EXISTS (SELECT 1 FROM agent_job j WHERE j.task_id = t.id)
              ORDER BY t.id ASC",
         )?;
         let tasks = stmt
@@ -939,7 +1254,7 @@ impl UserChatStorage for SqliteUserChatStorage {
         let mut stmt = conn.prepare(
             "SELECT id, session_id, author_type, author_user_id, agent_type, turn_id, content_type, content, created_at
              FROM user_chat_message
-             WHERE session_id = ?1
+             WHERE session_id = ?1 AND content_type != 'summary'
              ORDER BY id ASC",
         )?;
         let messages = stmt
@@ -948,6 +1263,45 @@ impl UserChatStorage for SqliteUserChatStorage {
         Ok(messages)
     }
 
+    async fn append_summary(
+        &self,
+        session_id: i64,
+        agent_type: AgentType,
+        content: &str,
+        covers_until_id: i64,
+    ) -> Result<i64, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO user_chat_message
+                 (session_id, author_type, agent_type, covers_until_id, content_type, content, created_at)
+             VALUES (?1, 'agent', ?2, ?3, 'summary', ?4, ?5)",
+            params![
+                session_id,
+                agent_type.as_str(),
+                covers_until_id,
+                content,
+                now()
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    async fn latest_summary(&self, session_id: i64) -> Result<Option<HistorySummary>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let summary = conn
+            .query_row(
+                "SELECT id, content, covers_until_id, created_at
+                 FROM user_chat_message
+                 WHERE session_id = ?1 AND content_type = 'summary'
+                 ORDER BY id DESC
+                 LIMIT 1",
+                params![session_id],
+                map_history_summary,
+            )
+            .optional()?;
+        Ok(summary)
+    }
+
     async fn complete_session(&self, session_id: i64) -> Result<(), AgentError> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
@@ -1387,3 +1741,869 @@ impl ProjectNoteStorage for SqliteProjectNoteStorage {
         Ok(rows)
     }
 }
+
+// ---------------------------------------------------------------------------
+// SqliteJobStorage
+// ---------------------------------------------------------------------------
+
+pub struct SqliteJobStorage {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteJobStorage {
+    pub fn new(conn: Connection) -> Self {
+        Self {
+            conn: Mutex::new(conn),
+        }
+    }
+
+    pub fn open(path: &str) -> Result<Self, AgentError> {
+        let conn = Connection::open(path)?;
+        // Several processes may claim from the same queue; wait for a
+        // competing claim instead of failing with SQLITE_BUSY.
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        Ok(Self::new(conn))
+    }
+}
+
+const JOB_COLUMNS: &str = "id, task_id, project_id, agent_type, source_prompt, status, priority,
+     attempts, available_at, lease_owner, lease_expires_at, heartbeat_at, last_error, created_at,
+     updated_at";
+
+fn map_job(row: &rusqlite::Row<'_>) -> rusqlite::Result<Job> {
+    Ok(Job {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        project_id: row.get(2)?,
+        agent_type: row.get(3)?,
+        source_prompt: row.get(4)?,
+        status: JobStatus::from_str(&row.get::<_, String>(5)?),
+        priority: row.get(6)?,
+        attempts: row.get(7)?,
+        available_at: row.get(8)?,
+        lease_owner: row.get(9)?,
+        lease_expires_at: row.get(10)?,
+        heartbeat_at: row.get(11)?,
+        last_error: row.get(12)?,
+        created_at: row.get(13)?,
+        updated_at: row.get(14)?,
+    })
+}
+
+#[async_trait]
+impl JobStorage for SqliteJobStorage {
+    async fn enqueue_job(
+        &self,
+        task_id: i64,
+        project_id: i64,
+        agent_type: &str,
+        source_prompt: &str,
+        priority: i64,
+    ) -> Result<Option<i64>, AgentError> {
+        let ts = now();
+        let conn = self.conn.lock().unwrap();
+        // The partial unique index on open jobs turns a duplicate into a no-op.
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO agent_job
+                 (task_id, project_id, agent_type, source_prompt, status, priority,
+                  created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, 'queued', ?5, ?6, ?6)",
+            params![task_id, project_id, agent_type, source_prompt, priority, ts],
+        )?;
+        Ok((inserted > 0).then(|| conn.last_insert_rowid()))
+    }
+
+    async fn claim_job(
+        &self,
+        worker: &str,
+        lease_secs: i64,
+        max_attempts: i64,
+        skip_agent_types: &[String],
+    ) -> Result<Option<Job>, AgentError> {
+        let ts = now();
+        let mut conn = self.conn.lock().unwrap();
+        // IMMEDIATE takes the write lock up front, so two workers can never
+        // select the same job.
+        let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+        // Jobs whose worker died on every attempt: block the task and say why.
+        let dead = "status = 'running' AND lease_expires_at <= ?1 AND attempts >= ?2";
+        tx.execute(
+            &format!(
+                "INSERT INTO task_comment
+                     (task_id, author_type, agent_type, content, created_at, updated_at)
+                 SELECT task_id, 'agent', agent_type,
+                        'Run abandoned: the worker stopped responding on all '
+                            || attempts || ' attempt(s). Moved to Blocked.',
+                        ?1, ?1
+                 FROM agent_job WHERE {}",
+                dead
+            ),
+            params![ts, max_attempts],
+        )?;
+        tx.execute(
+            &format!(
+                "UPDATE task SET status = 'blocked', updated_at = ?1
+                 WHERE id IN (SELECT task_id FROM agent_job WHERE {})",
+                dead
+            ),
+            params![ts, max_attempts],
+        )?;
+        tx.execute(
+            &format!(
+                "UPDATE agent_job
+                 SET status = 'failed', lease_owner = NULL, lease_expires_at = NULL,
+                     last_error = 'lease expired after ' || attempts || ' attempt(s)',
+                     updated_at = ?1
+                 WHERE {}",
+                dead
+            ),
+            params![ts, max_attempts],
+        )?;
+        let skip = if skip_agent_types.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "AND agent_type NOT IN ({})",
+                vec!["?"; skip_agent_types.len()].join(", ")
+            )
+        };
+        let id: Option<i64> = tx
+            .query_row(
+                &format!(
+                    "SELECT id FROM agent_job j
+                     WHERE ((status = 'queued' AND available_at <= ?1)
+                            OR (status = 'running' AND lease_expires_at <= ?1))
+                       AND NOT EXISTS (
+                           SELECT 1 FROM task_dependency d
+                           JOIN task dep ON dep.id = d.depends_on_task_id
+                           WHERE d.task_id = j.task_id AND dep.status != 'done'
+                       )
+                       {}
+                     ORDER BY priority DESC, id ASC LIMIT 1",
+                    skip
+                ),
+                rusqlite::params_from_iter(
+                    std::iter::once(&ts as &dyn rusqlite::ToSql)
+                        .chain(skip_agent_types.iter().map(|a| a as &dyn rusqlite::ToSql)),
+                ),
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(id) = id else {
+            tx.commit()?;
+            return Ok(None);
+        };
+        tx.execute(
+            "UPDATE agent_job
+             SET status = 'running', attempts = attempts + 1, lease_owner = ?2,
+                 lease_expires_at = ?3, heartbeat_at = ?4, updated_at = ?4
+             WHERE id = ?1",
+            params![id, worker, ts + lease_secs, ts],
+        )?;
+        let job = tx.query_row(
+            &format!("SELECT {} FROM agent_job WHERE id = ?1", JOB_COLUMNS),
+            params![id],
+            map_job,
+        )?;
+        tx.commit()?;
+        Ok(Some(job))
+    }
+
+    async fn heartbeat_job(
+        &self,
+        job_id: i64,
+        worker: &str,
+        lease_secs: i64,
+    ) -> Result<bool, AgentError> {
+        let ts = now();
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE agent_job SET lease_expires_at = ?3, heartbeat_at = ?4, updated_at = ?4
+             WHERE id = ?1 AND lease_owner = ?2 AND status = 'running'",
+            params![job_id, worker, ts + lease_secs, ts],
+        )?;
+        Ok(updated > 0)
+    }
+
+    async fn retry_job(
+        &self,
+        job_id: i64,
+        worker: &str,
+        error: &str,
+        delay_secs: i64,
+    ) -> Result<(), AgentError> {
+        let ts = now();
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE agent_job
+             SET status = 'queued', last_error = ?3, available_at = ?4,
+                 lease_owner = NULL, lease_expires_at = NULL, updated_at = ?5
+             WHERE id = ?1 AND lease_owner = ?2 AND status = 'running'",
+            params![job_id, worker, error, ts + delay_secs, ts],
+        )?;
+        Ok(())
+    }
+
+    async fn finish_job(
+        &self,
+        job_id: i64,
+        worker: &str,
+        error: Option<&str>,
+    ) -> Result<(), AgentError> {
+        let status = match error {
+            None => JobStatus::Done,
+            Some(_) => JobStatus::Failed,
+        };
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE agent_job
+             SET status = ?3, last_error = COALESCE(?4, last_error),
+                 lease_owner = NULL, lease_expires_at = NULL, updated_at = ?5
+             WHERE id = ?1 AND lease_owner = ?2 AND status = 'running'",
+            params![job_id, worker, status.as_str(), error, now()],
+        )?;
+        Ok(())
+    }
+
+    async fn cancel_queued_job(
+        &self,
+        task_id: i64,
+        error: &str,
+    ) -> Result<Option<Job>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let job = conn
+            .query_row(
+                &format!(
+                    "UPDATE agent_job
+                     SET status = 'failed', last_error = ?2, updated_at = ?3
+                     WHERE task_id = ?1 AND status = 'queued'
+                     RETURNING {}",
+                    JOB_COLUMNS
+                ),
+                params![task_id, error, now()],
+                map_job,
+            )
+            .optional()?;
+        Ok(job)
+    }
+
+    async fn get_job(&self, job_id: i64) -> Result<Option<Job>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let job = conn
+            .query_row(
+                &format!("SELECT {} FROM agent_job WHERE id = ?1", JOB_COLUMNS),
+                params![job_id],
+                map_job,
+            )
+            .optional()?;
+        Ok(job)
+    }
+
+    async fn get_running_job(&self, task_id: i64) -> Result<Option<Job>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let job = conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM agent_job WHERE task_id = ?1 AND status = 'running'",
+                    JOB_COLUMNS
+                ),
+                params![task_id],
+                map_job,
+            )
+            .optional()?;
+        Ok(job)
+    }
+
+    async fn list_jobs(&self, project_id: i64) -> Result<Vec<Job>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM agent_job WHERE project_id = ?1 ORDER BY id DESC",
+            JOB_COLUMNS
+        ))?;
+        let jobs = stmt
+            .query_map(params![project_id], map_job)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(jobs)
+    }
+
+    async fn count_open_jobs(&self, project_id: i64) -> Result<Vec<JobCount>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT agent_type, status, COUNT(*) FROM agent_job
+             WHERE project_id = ?1 AND status IN ('queued', 'running')
+             GROUP BY agent_type, status
+             ORDER BY agent_type, status",
+        )?;
+        let counts = stmt
+            .query_map(params![project_id], |row| {
+                Ok(JobCount {
+                    agent_type: row.get(0)?,
+                    status: JobStatus::from_str(&row.get::<_, String>(1)?),
+                    count: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(counts)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// SqliteProposalStorage
+// ---------------------------------------------------------------------------
+
+pub struct SqliteProposalStorage {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteProposalStorage {
+    pub fn new(conn: Connection) -> Self {
+        Self {
+            conn: Mutex::new(conn),
+        }
+    }
+
+    pub fn open(path: &str) -> Result<Self, AgentError> {
+        let conn = Connection::open(path)?;
+        Ok(Self::new(conn))
+    }
+}
+
+const PROPOSAL_COLUMNS: &str = "id, task_id, project_id, agent_type, session_id, artifact, summary,
+     status, comment, created_at, decided_at";
+
+fn map_proposal(row: &rusqlite::Row<'_>) -> rusqlite::Result<Proposal> {
+    Ok(Proposal {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        project_id: row.get(2)?,
+        agent_type: row.get(3)?,
+        session_id: row.get(4)?,
+        artifact: row.get(5)?,
+        summary: row.get(6)?,
+        status: ProposalStatus::from_str(&row.get::<_, String>(7)?),
+        comment: row.get(8)?,
+        created_at: row.get(9)?,
+        decided_at: row.get(10)?,
+    })
+}
+
+#[async_trait]
+impl ProposalStorage for SqliteProposalStorage {
+    async fn create_proposal(
+        &self,
+        task_id: i64,
+        project_id: i64,
+        agent_type: &str,
+        session_id: i64,
+        artifact: &str,
+        summary: &str,
+    ) -> Result<i64, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO agent_proposal
+                 (task_id, project_id, agent_type, session_id, artifact, summary, status,
+                  created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'pending', ?7)",
+            params![
+                task_id,
+                project_id,
+                agent_type,
+                session_id,
+                artifact,
+                summary,
+                now()
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    async fn get_pending_proposal(&self, task_id: i64) -> Result<Option<Proposal>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let proposal = conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM agent_proposal WHERE task_id = ?1 AND status = 'pending'",
+                    PROPOSAL_COLUMNS
+                ),
+                params![task_id],
+                map_proposal,
+            )
+            .optional()?;
+        Ok(proposal)
+    }
+
+    async fn list_task_proposals(&self, task_id: i64) -> Result<Vec<Proposal>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM agent_proposal WHERE task_id = ?1 ORDER BY id DESC",
+            PROPOSAL_COLUMNS
+        ))?;
+        let proposals = stmt
+            .query_map(params![task_id], map_proposal)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(proposals)
+    }
+
+    async fn list_proposals(&self, project_id: i64) -> Result<Vec<Proposal>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM agent_proposal WHERE project_id = ?1 ORDER BY id DESC",
+            PROPOSAL_COLUMNS
+        ))?;
+        let proposals = stmt
+            .query_map(params![project_id], map_proposal)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(proposals)
+    }
+
+    async fn decide_proposal(
+        &self,
+        proposal_id: i64,
+        status: ProposalStatus,
+        comment: Option<&str>,
+    ) -> Result<bool, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE agent_proposal SET status = ?2, comment = ?3, decided_at = ?4
+             WHERE id = ?1 AND status = 'pending'",
+            params![proposal_id, status.as_str(), comment, now()],
+        )?;
+        Ok(updated > 0)
+    }
+
+    async fn reopen_proposal(&self, proposal_id: i64) -> Result<(), AgentError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE agent_proposal SET status = 'pending', decided_at = NULL
+             WHERE id = ?1 AND status = 'approved'",
+            params![proposal_id],
+        )?;
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// SqliteScheduleStorage
+// ---------------------------------------------------------------------------
+
+pub struct SqliteScheduleStorage {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteScheduleStorage {
+    pub fn new(conn: Connection) -> Self {
+        Self {
+            conn: Mutex::new(conn),
+        }
+    }
+
+    pub fn open(path: &str) -> Result<Self, AgentError> {
+        let conn = Connection::open(path)?;
+        Ok(Self::new(conn))
+    }
+}
+
+const SCHEDULE_RUN_COLUMNS: &str =
+    "id, project_id, agent_type, reason, status, summary, started_at, finished_at";
+
+fn map_schedule_run(row: &rusqlite::Row<'_>) -> rusqlite::Result<ScheduleRun> {
+    Ok(ScheduleRun {
+        id: row.get(0)?,
+        project_id: row.get(1)?,
+        agent_type: row.get(2)?,
+        reason: row.get(3)?,
+        status: ScheduleRunStatus::from_str(&row.get::<_, String>(4)?),
+        summary: row.get(5)?,
+        started_at: row.get(6)?,
+        finished_at: row.get(7)?,
+    })
+}
+
+#[async_trait]
+impl ScheduleStorage for SqliteScheduleStorage {
+    async fn begin_run(
+        &self,
+        project_id: i64,
+        agent_type: &str,
+        reason: &str,
+    ) -> Result<Option<i64>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        // The partial unique index on running runs turns an overlap into a
+        // no-op.
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO agent_schedule_run
+                 (project_id, agent_type, reason, status, started_at)
+             VALUES (?1, ?2, ?3, 'running', ?4)",
+            params![project_id, agent_type, reason, now()],
+        )?;
+        Ok((inserted > 0).then(|| conn.last_insert_rowid()))
+    }
+
+    async fn finish_run(
+        &self,
+        run_id: i64,
+        status: ScheduleRunStatus,
+        summary: &str,
+    ) -> Result<(), AgentError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE agent_schedule_run SET status = ?2, summary = ?3, finished_at = ?4
+             WHERE id = ?1 AND status = 'running'",
+            params![run_id, status.as_str(), summary, now()],
+        )?;
+        Ok(())
+    }
+
+    async fn last_run(
+        &self,
+        project_id: i64,
+        agent_type: &str,
+    ) -> Result<Option<ScheduleRun>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let run = conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM agent_schedule_run
+                     WHERE project_id = ?1 AND agent_type = ?2
+                     ORDER BY id DESC LIMIT 1",
+                    SCHEDULE_RUN_COLUMNS
+                ),
+                params![project_id, agent_type],
+                map_schedule_run,
+            )
+            .optional()?;
+        Ok(run)
+    }
+
+    async fn list_runs(&self, project_id: i64) -> Result<Vec<ScheduleRun>, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM agent_schedule_run WHERE project_id = ?1 ORDER BY id DESC",
+            SCHEDULE_RUN_COLUMNS
+        ))?;
+        let runs = stmt
+            .query_map(params![project_id], map_schedule_run)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(runs)
+    }
+
+    async fn fail_stale_runs(&self, started_before: i64) -> Result<usize, AgentError> {
+        let conn = self.conn.lock().unwrap();
+        let failed = conn.execute(
+            "UPDATE agent_schedule_run
+             SET status = 'failed', summary = 'Interrupted: the server stopped during the run.',
+                 finished_at = ?1
+             WHERE status = 'running' AND started_at < ?2",
+            params![now(), started_before],
+        )?;
+        Ok(failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_db;
+
+    const LEASE: i64 = 60;
+    const MAX_ATTEMPTS: i64 = 3;
+
+    fn jobs(tasks: i64) -> SqliteJobStorage {
+        SqliteJobStorage::new(test_db::with_tasks(tasks))
+    }
+
+    async fn enqueue(storage: &SqliteJobStorage, task_id: i64) -> Option<i64> {
+        storage
+            .enqueue_job(task_id, 1, "db_engineer", "{}", 0)
+            .await
+            .unwrap()
+    }
+
+    async fn claim(storage: &SqliteJobStorage, worker: &str, lease_secs: i64) -> Option<Job> {
+        storage
+            .claim_job(worker, lease_secs, MAX_ATTEMPTS, &[])
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_twice_is_a_no_op() {
+        let storage = jobs(1);
+        let id = enqueue(&storage, 1).await.unwrap();
+        assert_eq!(enqueue(&storage, 1).await, None);
+        assert_eq!(storage.list_jobs(1).await.unwrap().len(), 1);
+
+        // Once the job is finished the task can be queued again.
+        claim(&storage, "w1", LEASE).await.unwrap();
+        storage.finish_job(id, "w1", None).await.unwrap();
+        assert!(enqueue(&storage, 1).await.is_some());
+    }
+
+    #[test]
+    fn test_concurrent_claims_never_share_a_job() {
+        const TASKS: i64 = 20;
+        let path = test_db::file_with_tasks("claims", TASKS);
+        let path = path.to_string_lossy().into_owned();
+        let runtime = || {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+        };
+        let storage = SqliteJobStorage::open(&path).unwrap();
+        runtime().block_on(async {
+            for task_id in 1..=TASKS {
+                enqueue(&storage, task_id).await.unwrap();
+            }
+        });
+
+        let workers: Vec<_> = (0..4)
+            .map(|w| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let storage = SqliteJobStorage::open(&path).unwrap();
+                    let worker = format!("w{}", w);
+                    runtime().block_on(async {
+                        let mut claimed = Vec::new();
+                        while let Some(job) = claim(&storage, &worker, LEASE).await {
+                            claimed.push(job.id);
+                        }
+                        claimed
+                    })
+                })
+            })
+            .collect();
+        let mut claimed: Vec<i64> = workers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap())
+            .collect();
+        std::fs::remove_file(&path).ok();
+
+        claimed.sort();
+        let count = claimed.len();
+        claimed.dedup();
+        assert_eq!(claimed.len(), count, "a job was claimed twice");
+        assert_eq!(count as i64, TASKS);
+    }
+
+    #[tokio::test]
+    async fn test_expired_lease_is_claimable_again() {
+        let storage = jobs(1);
+        let id = enqueue(&storage, 1).await.unwrap();
+        // A lease of 0 seconds has expired by the next claim.
+        let first = claim(&storage, "w1", 0).await.unwrap();
+        assert_eq!((first.id, first.attempts), (id, 1));
+
+        let second = claim(&storage, "w2", LEASE).await.unwrap();
+        assert_eq!((second.id, second.attempts), (id, 2));
+        assert_eq!(second.lease_owner.as_deref(), Some("w2"));
+        // The first worker lost the lease.
+        assert!(!storage.heartbeat_job(id, "w1", LEASE).await.unwrap());
+        assert!(claim(&storage, "w3", LEASE).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_job_is_dead_lettered_after_max_attempts() {
+        let storage = jobs(1);
+        let id = enqueue(&storage, 1).await.unwrap();
+        for _ in 0..MAX_ATTEMPTS {
+            claim(&storage, "w1", 0).await.unwrap();
+        }
+
+        assert!(claim(&storage, "w1", LEASE).await.is_none());
+        let job = storage.get_job(id).await.unwrap().unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+        assert_eq!(job.attempts, MAX_ATTEMPTS);
+        assert_eq!(job.lease_owner, None);
+        assert_eq!(
+            job.last_error.as_deref(),
+            Some("lease expired after 3 attempt(s)")
+        );
+        let conn = storage.conn.lock().unwrap();
+        let status: String = conn
+            .query_row("SELECT status FROM task WHERE id = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(status, "blocked");
+        let comment: String = conn
+            .query_row(
+                "SELECT content FROM task_comment WHERE task_id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(
+            comment,
+            "Run abandoned: the worker stopped responding on all 3 attempt(s). Moved to Blocked."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_finish_and_heartbeat_by_another_worker_are_ignored() {
+        let storage = jobs(1);
+        let id = enqueue(&storage, 1).await.unwrap();
+        let claimed = claim(&storage, "w1", LEASE).await.unwrap();
+
+        assert!(!storage.heartbeat_job(id, "w2", 3600).await.unwrap());
+        storage.finish_job(id, "w2", None).await.unwrap();
+        storage.finish_job(id, "w2", Some("boom")).await.unwrap();
+
+        let job = storage.get_job(id).await.unwrap().unwrap();
+        assert_eq!(job.status, JobStatus::Running);
+        assert_eq!(job.lease_owner.as_deref(), Some("w1"));
+        assert_eq!(job.lease_expires_at, claimed.lease_expires_at);
+        assert_eq!(job.last_error, None);
+
+        assert!(storage.heartbeat_job(id, "w1", LEASE).await.unwrap());
+        storage.finish_job(id, "w1", None).await.unwrap();
+        let job = storage.get_job(id).await.unwrap().unwrap();
+        assert_eq!(job.status, JobStatus::Done);
+    }
+
+    #[tokio::test]
+    async fn test_split_task_creates_nothing_when_a_dependency_is_invalid() {
+        let tasks = SqliteTaskStorage::new(test_db::with_tasks(1));
+        let parent = tasks.get_task(1).await.unwrap().unwrap();
+        let child = |title: &str| Task {
+            id: None,
+            title: title.to_string(),
+            status: TaskStatus::Ready,
+            created_by_task_id: Some(1),
+            ..parent.clone()
+        };
+
+        let result = tasks
+            .split_task(1, vec![(child("a"), vec![]), (child("b"), vec![1])])
+            .await;
+
+        assert!(matches!(result, Err(AgentError::Other(_))));
+        assert!(tasks.list_tasks_created_by(1).await.unwrap().is_empty());
+        assert_ne!(
+            tasks.get_task(1).await.unwrap().unwrap().status,
+            TaskStatus::Done
+        );
+    }
+
+    #[tokio::test]
+    async fn test_task_cannot_depend_on_itself() {
+        let tasks = SqliteTaskStorage::new(test_db::with_tasks(1));
+
+        let result = tasks.add_task_dependencies(1, &[1]).await;
+
+        assert!(matches!(result, Err(AgentError::DependencyCycle(_))));
+        assert!(tasks.list_task_dependencies(1).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_indirect_dependency_cycle_is_rejected() {
+        let tasks = SqliteTaskStorage::new(test_db::with_tasks(3));
+        // 1 waits for 2, 2 waits for 3.
+        tasks.add_task_dependencies(1, &[2]).await.unwrap();
+        tasks.add_task_dependencies(2, &[3]).await.unwrap();
+
+        let result = tasks.add_task_dependencies(3, &[1]).await;
+
+        assert!(matches!(result, Err(AgentError::DependencyCycle(_))));
+        assert!(tasks.list_task_dependencies(3).await.unwrap().is_empty());
+        assert_eq!(
+            tasks.get_task(3).await.unwrap().unwrap().depends_on_task_id,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_claim_skips_a_job_until_its_dependency_is_done() {
+        let storage = jobs(2);
+        {
+            let conn = storage.conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO task_dependency (task_id, depends_on_task_id, created_at)
+                 VALUES (2, 1, 0)",
+                [],
+            )
+            .unwrap();
+        }
+        let waiting = enqueue(&storage, 2).await.unwrap();
+
+        assert!(claim(&storage, "w1", LEASE).await.is_none());
+
+        let set_status = |status: &str| {
+            let conn = storage.conn.lock().unwrap();
+            conn.execute("UPDATE task SET status = ?1 WHERE id = 1", [status])
+                .unwrap();
+        };
+        set_status("in_progress");
+        assert!(claim(&storage, "w1", LEASE).await.is_none());
+        set_status("done");
+        let job = claim(&storage, "w1", LEASE).await.unwrap();
+        assert_eq!(job.id, waiting);
+    }
+
+    #[tokio::test]
+    async fn test_begin_run_skips_an_agent_already_running_on_the_project() {
+        let runs = SqliteScheduleStorage::new(test_db::with_tasks(0));
+
+        let first = runs
+            .begin_run(1, "stack_reviewer", "requested")
+            .await
+            .unwrap();
+        assert!(first.is_some());
+        assert_eq!(
+            runs.begin_run(1, "stack_reviewer", "cron").await.unwrap(),
+            None
+        );
+        // Another agent on the same project is not an overlap.
+        assert!(runs
+            .begin_run(1, "db_engineer", "requested")
+            .await
+            .unwrap()
+            .is_some());
+
+        runs.finish_run(first.unwrap(), ScheduleRunStatus::Done, "ok")
+            .await
+            .unwrap();
+        assert!(runs
+            .begin_run(1, "stack_reviewer", "cron")
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_fail_stale_runs_fails_runs_started_before_the_cutoff() {
+        let runs = SqliteScheduleStorage::new(test_db::with_tasks(0));
+        let stale = runs
+            .begin_run(1, "stack_reviewer", "requested")
+            .await
+            .unwrap()
+            .unwrap();
+        let current = runs
+            .begin_run(1, "db_engineer", "requested")
+            .await
+            .unwrap()
+            .unwrap();
+        runs.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE agent_schedule_run SET started_at = 100 WHERE id = ?1",
+                [stale],
+            )
+            .unwrap();
+
+        assert_eq!(runs.fail_stale_runs(200).await.unwrap(), 1);
+        // A late finish of the interrupted run does not overwrite the failure.
+        runs.finish_run(stale, ScheduleRunStatus::Done, "ok")
+            .await
+            .unwrap();
+
+        let stale = runs.last_run(1, "stack_reviewer").await.unwrap().unwrap();
+        assert_eq!(stale.status, ScheduleRunStatus::Failed);
+        assert_eq!(
+            stale.summary.as_deref(),
+            Some("Interrupted: the server stopped during the run.")
+        );
+        assert!(stale.finished_at.is_some());
+        let current_run = runs.last_run(1, "db_engineer").await.unwrap().unwrap();
+        assert_eq!(current_run.id, current);
+        assert_eq!(current_run.status, ScheduleRunStatus::Running);
+    }
+}
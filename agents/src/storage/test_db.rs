@@ -0,0 +1,64 @@
+//! Databases migrated with the backend's SQL migrations, for storage
+//! tests.
+
+use std::path::{Path, PathBuf};
+
+use rusqlite::Connection;
+
+/// Every migration in `backend/migrations/sqlite`, applied in version order.
+pub fn migrated() -> Connection {
+    let conn = Connection::open_in_memory().expect("open in-memory db");
+    migrate(&conn);
+    conn
+}
+
+pub fn migrate(conn: &Connection) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../backend/migrations/sqlite");
+    let mut migrations: Vec<(u32, PathBuf)> = std::fs::read_dir(&dir)
+        .expect("read migrations")
+        .map(|entry| entry.expect("migration entry").path())
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?;
+            let version = name.strip_prefix('V')?.split("__").next()?.parse().ok()?;
+            Some((version, path))
+        })
+        .collect();
+    migrations.sort();
+    for (_, path) in migrations {
+        let sql = std::fs::read_to_string(&path).expect("read migration");
+        conn.execute_batch(&sql)
+            .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+    }
+}
+
+/// Project 1 with open db_engineer tasks 1..=`count`.
+pub fn with_tasks(count: i64) -> Connection {
+    let conn = migrated();
+    seed(&conn, count);
+    conn
+}
+
+/// Like `with_tasks`, in a fresh file under the temp dir, for tests that open
+/// several connections. The caller removes the file.
+pub fn file_with_tasks(name: &str, count: i64) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("nocodo-{}-{}.db", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let conn = Connection::open(&path).expect("open db file");
+    migrate(&conn);
+    seed(&conn, count);
+    path
+}
+
+fn seed(conn: &Connection, tasks: i64) {
+    conn.execute_batch("INSERT INTO project (id, name, path, created_at) VALUES (1, 'P', '', 0);")
+        .expect("seed project");
+    for id in 1..=tasks {
+        conn.execute(
+            "INSERT INTO task (id, project_id, title, description, source_prompt,
+                 assigned_to_agent, created_by_agent, created_at, updated_at)
+             VALUES (?1, 1, 'Task ' || ?1, '', '', 'db_engineer', 'project_manager', 0, 0)",
+            [id],
+        )
+        .expect("seed task");
+    }
+}
@@ -1,11 +1,30 @@
-use crate::storage::{AgentType, TaskStatus};
+use crate::storage::{AgentType, Task, TaskStatus};
 
 pub fn initial_state_for(agent_type: &AgentType) -> TaskStatus {
     match agent_type {
         AgentType::BackendEngineer | AgentType::FrontendEngineer => {
             TaskStatus::NeedsTechnicalShaping
         }
-        AgentType::DbEngineer | AgentType::UiDesigner => TaskStatus::Ready,
+        AgentType::DbEngineer | AgentType::UiDesigner | AgentType::RustEngineer => {
+            TaskStatus::Ready
+        }
+        _ => TaskStatus::Ready,
+    }
+}
+
+/// Agent that runs `task` next: the Engineering Manager while the task needs
+/// technical shaping, its assignee otherwise.
+pub fn dispatch_agent_for(task: &Task) -> &str {
+    match task.status {
+        TaskStatus::NeedsTechnicalShaping => AgentType::EngineeringManager.as_str(),
+        _ => &task.assigned_to_agent,
+    }
+}
+
+/// Status of a task while a run of `agent_type` is queued for it.
+pub fn queued_state_for(agent_type: &str) -> TaskStatus {
+    match AgentType::from_str(agent_type) {
+        AgentType::EngineeringManager => TaskStatus::NeedsTechnicalShaping,
         _ => TaskStatus::Ready,
     }
 }
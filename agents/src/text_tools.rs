@@ -0,0 +1,311 @@
+//! Tool calling over plain text, for models whose native function calling is
+//! missing or unreliable (typically small local models). The tool schemas are
+//! rendered into the system prompt, the model answers with a fenced JSON block
+//! naming the tool, and the block is parsed back into `ToolCall`s, so the run
+//! loops cannot tell the difference.
+//!
+//! History sent by the loops is rewritten the same way: assistant tool-call
+//! messages become the JSON blocks the model would have written, and tool
+//! results become user messages.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use async_trait::async_trait;
+use llm_sdk::{
+    client::LlmClient,
+    error::LlmError,
+    tools::{Tool, ToolCall},
+    types::{CompletionRequest, CompletionResponse, ContentBlock, Message, Role},
+};
+use serde_json::{json, Map, Value};
+
+use crate::tool_args::repair_json;
+
+/// How an agent's model is asked to call tools.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ToolProtocol {
+    /// The provider's function-calling API.
+    #[default]
+    Native,
+    /// Schemas in the system prompt, calls as fenced JSON blocks.
+    Text,
+}
+
+impl ToolProtocol {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "native" => Some(ToolProtocol::Native),
+            "text" => Some(ToolProtocol::Text),
+            _ => None,
+        }
+    }
+}
+
+const TOOLS_HEADER: &str = "# Tools
+
+To call a tool, reply with a fenced JSON block naming the tool and its arguments, \
+and write nothing after it:
+
+```json
+{\"tool\": \"<tool name>\", \"arguments\": { ... }}
+```
+
+The arguments must match the tool's JSON schema. To call several tools, write one \
+block per call. Text outside the blocks is shown to the user. The result of each \
+call is sent back to you in the next message.
+
+Available tools:";
+
+/// Keys small models use instead of `arguments`.
+const ARGUMENT_KEYS: [&str; 4] = ["arguments", "parameters", "args", "input"];
+
+/// Ids for parsed calls; only need to be unique within a process.
+static NEXT_CALL_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Wraps a client so requests with tools use the text protocol.
+pub struct TextToolClient {
+    inner: Arc<dyn LlmClient>,
+}
+
+impl TextToolClient {
+    pub fn new(inner: Arc<dyn LlmClient>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl LlmClient for TextToolClient {
+    async fn complete(
+        &self,
+        mut request: CompletionRequest,
+    ) -> Result<CompletionResponse, LlmError> {
+        let Some(tools) = request.tools.take() else {
+            return self.inner.complete(request).await;
+        };
+        request.tool_choice = None;
+        let prompt = render_tools(&tools);
+        request.system = Some(match request.system.take() {
+            Some(system) => format!("{}\n\n{}", system, prompt),
+            None => prompt,
+        });
+        request.messages = to_text_messages(request.messages);
+
+        let mut response = self.inner.complete(request).await?;
+        let text = response
+            .content
+            .iter()
+            .filter_map(|b| match b {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("");
+        let names: Vec<&str> = tools.iter().map(|t| t.name()).collect();
+        let (remaining, calls) = extract_tool_calls(&text, &names);
+        if !calls.is_empty() {
+            log::info!(
+                "[TextTools] parsed {} tool call(s) from model text",
+                calls.len()
+            );
+            response.content = if remaining.is_empty() {
+                Vec::new()
+            } else {
+                vec![ContentBlock::Text { text: remaining }]
+            };
+            response.tool_calls = Some(calls);
+        }
+        Ok(response)
+    }
+
+    fn provider_name(&self) -> &str {
+        self.inner.provider_name()
+    }
+
+    fn model_name(&self) -> &str {
+        self.inner.model_name()
+    }
+}
+
+/// The tools section appended to the system prompt.
+pub fn render_tools(tools: &[Tool]) -> String {
+    let mut out = TOOLS_HEADER.to_string();
+    for tool in tools {
+        out.push_str(&format!(
+            "\n\n## {}\n{}\nArguments schema: {}",
+            tool.name(),
+            tool.description(),
+            tool.parameters()
+        ));
+    }
+    out
+}
+
+/// Rewrite native tool-call history into plain user/assistant messages.
+fn to_text_messages(messages: Vec<Message>) -> Vec<Message> {
+    let mut call_names: HashMap<String, String> = HashMap::new();
+    messages
+        .into_iter()
+        .map(|m| {
+            let text = m
+                .content
+                .iter()
+                .filter_map(|b| match b {
+                    ContentBlock::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("");
+            match (&m.role, m.tool_name.clone()) {
+                (Role::Assistant, Some(name)) => {
+                    if let Some(id) = &m.tool_call_id {
+                        call_names.insert(id.clone(), name.clone());
+                    }
+                    let arguments = serde_json::from_str(&text).unwrap_or(Value::String(text));
+                    Message::assistant(render_call(&name, &arguments))
+                }
+                (Role::Tool, name) => {
+                    let name = name.or_else(|| {
+                        m.tool_call_id
+                            .as_ref()
+                            .and_then(|id| call_names.get(id).cloned())
+                    });
+                    match name {
+                        Some(name) => Message::user(format!("Result of {}:\n{}", name, text)),
+                        None => Message::user(format!("Tool result:\n{}", text)),
+                    }
+                }
+                _ => m,
+            }
+        })
+        .collect()
+}
+
+fn render_call(name: &str, arguments: &Value) -> String {
+    let block = json!({ "tool": name, "arguments": arguments });
+    format!("```json\n{}\n```", block)
+}
+
+/// Split model text into the text meant for the user and the tool calls it
+/// contains. Fenced blocks that do not name a known tool are left in the text;
+/// an unfenced reply that is a single call is accepted too.
+pub fn extract_tool_calls(text: &str, tool_names: &[&str]) -> (String, Vec<ToolCall>) {
+    let mut remaining = String::new();
+    let mut calls = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("```") {
+        let after_fence = &rest[start + 3..];
+        let Some(end) = after_fence.find("```") else {
+            break;
+        };
+        let body = after_fence[..end]
+            .split_once('\n')
+            .map_or("", |(_, body)| body);
+        match parse_call(body, tool_names) {
+            Some(call) => {
+                remaining.push_str(&rest[..start]);
+                calls.push(call);
+            }
+            None => remaining.push_str(&rest[..start + 3 + end + 3]),
+        }
+        rest = &after_fence[end + 3..];
+    }
+    remaining.push_str(rest);
+
+    if calls.is_empty() {
+        if let Some(call) = parse_call(text, tool_names) {
+            return (String::new(), vec![call]);
+        }
+    }
+    (remaining.trim().to_string(), calls)
+}
+
+fn parse_call(body: &str, tool_names: &[&str]) -> Option<ToolCall> {
+    let (value, _) = repair_json(body).ok()?;
+    let Value::Object(mut object) = value else {
+        return None;
+    };
+    let name = ["tool", "name", "function"]
+        .iter()
+        .find_map(|key| match object.remove(*key) {
+            Some(Value::String(name)) => Some(name),
+            _ => None,
+        })?;
+    if !tool_names.contains(&name.as_str()) {
+        return None;
+    }
+    // `{"tool": ..., "arguments": {...}}`, or the arguments inline next to
+    // the tool name.
+    let arguments = ARGUMENT_KEYS
+        .iter()
+        .find_map(|key| object.remove(*key))
+        .unwrap_or_else(|| Value::Object(std::mem::take(&mut object)));
+    let arguments = match arguments {
+        Value::Null => Value::Object(Map::new()),
+        Value::String(s) => repair_json(&s).map_or(Value::String(s), |(v, _)| v),
+        other => other,
+    };
+    let id = format!("text_call_{}", NEXT_CALL_ID.fetch_add(1, Ordering::Relaxed));
+    Some(ToolCall::new(id, name, arguments))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOOLS: [&str; 2] = ["generate_schema", "stop_agent"];
+
+    #[test]
+    fn test_extracts_fenced_call_and_keeps_text() {
+        let text = "Here is the schema.\n```json\n{\"tool\": \"stop_agent\", \"arguments\": {\"reason\": \"done\"}}\n```\n";
+        let (remaining, calls) = extract_tool_calls(text, &TOOLS);
+        assert_eq!(remaining, "Here is the schema.");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name(), "stop_agent");
+        assert_eq!(calls[0].arguments()["reason"], "done");
+    }
+
+    #[test]
+    fn test_accepts_inline_arguments_and_unfenced_reply() {
+        let (_, calls) = extract_tool_calls("{'name': 'stop_agent', 'reason': 'done',}", &TOOLS);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].arguments(), &json!({ "reason": "done" }));
+    }
+
+    #[test]
+    fn test_leaves_unknown_tools_and_plain_code_in_text() {
+        let text = "```json\n{\"tool\": \"drop_database\"}\n```\n```rust\nfn main() {}\n```";
+        let (remaining, calls) = extract_tool_calls(text, &TOOLS);
+        assert!(calls.is_empty());
+        assert_eq!(remaining, text);
+    }
+
+    #[test]
+    fn test_history_tool_calls_become_text() {
+        let messages = vec![
+            Message {
+                role: Role::Assistant,
+                content: vec![ContentBlock::Text {
+                    text: "{\"reason\":\"done\"}".to_string(),
+                }],
+                tool_call_id: Some("c1".to_string()),
+                tool_name: Some("stop_agent".to_string()),
+            },
+            Message::tool("c1", "stopped"),
+        ];
+        let converted = to_text_messages(messages);
+        assert!(matches!(converted[0].role, Role::Assistant));
+        let ContentBlock::Text { text } = &converted[0].content[0] else {
+            panic!("expected text");
+        };
+        let (_, calls) = extract_tool_calls(text, &TOOLS);
+        assert_eq!(calls[0].name(), "stop_agent");
+        assert!(matches!(converted[1].role, Role::User));
+        assert!(converted[1].tool_call_id.is_none());
+    }
+}
@@ -0,0 +1,570 @@
+//! Lenient parsing of tool-call arguments. Small models often send almost-JSON
+//! (code fences, single quotes, unquoted keys, trailing commas) or values of
+//! the wrong shape (`"3"` for a number, `"Text"` for `text`). The raw text is
+//! repaired into JSON first, then the value is coerced field by field until it
+//! deserializes. What cannot be fixed is reported with the path of the
+//! offending field, so the model knows exactly what to correct.
+
+use llm_sdk::tools::ToolCall;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{
+    error::AgentError,
+    storage::{AgentStorage, AgentType, ChatMessage},
+};
+
+/// Upper bound on field coercions per call; each one fixes a single field.
+const MAX_COERCIONS: usize = 16;
+
+/// Arguments that could not be repaired into the tool's parameter type.
+#[derive(Debug, Clone)]
+pub struct ToolArgsError {
+    pub tool_name: String,
+    /// Field path, e.g. `tables[0].columns[2].data_type`; `arguments` for the
+    /// top level.
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ToolArgsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid arguments for {} at `{}`: {}. Fix this field and call {} again.",
+            self.tool_name, self.path, self.message, self.tool_name
+        )
+    }
+}
+
+impl std::error::Error for ToolArgsError {}
+
+/// Parse a tool call's arguments into `T`, repairing them when needed.
+/// Every repair is logged.
+pub fn parse_tool_args<T: DeserializeOwned>(tool_call: &ToolCall) -> Result<T, ToolArgsError> {
+    let raw = tool_call.raw_arguments();
+    let value = if raw.trim().is_empty() {
+        tool_call.arguments().clone()
+    } else {
+        let (value, repairs) = repair_json(raw).map_err(|message| ToolArgsError {
+            tool_name: tool_call.name().to_string(),
+            path: "arguments".to_string(),
+            message,
+        })?;
+        if !repairs.is_empty() {
+            log::info!(
+                "[ToolArgs] {}: repaired JSON ({})",
+                tool_call.name(),
+                repairs.join(", ")
+            );
+        }
+        value
+    };
+    parse_value(tool_call.name(), value)
+}
+
+/// Deserialize `value` into `T`, coercing one offending field at a time.
+pub fn parse_value<T: DeserializeOwned>(
+    tool_name: &str,
+    mut value: Value,
+) -> Result<T, ToolArgsError> {
+    for _ in 0..=MAX_COERCIONS {
+        // Deserializing from pretty text gives errors a line number, which
+        // maps back to a field path.
+        let pretty = serde_json::to_string_pretty(&value).unwrap_or_default();
+        let err = match serde_json::from_str::<T>(&pretty) {
+            Ok(parsed) => return Ok(parsed),
+            Err(err) => err,
+        };
+        let path = path_at_line(&pretty, err.line());
+        let message = strip_position(&err.to_string());
+        match coerce(&mut value, &path, &message) {
+            Some(fix) => log::info!(
+                "[ToolArgs] {}: coerced `{}` ({})",
+                tool_name,
+                render_path(&path),
+                fix
+            ),
+            None => {
+                return Err(ToolArgsError {
+                    tool_name: tool_name.to_string(),
+                    path: render_path(&path),
+                    message,
+                })
+            }
+        }
+    }
+    Err(ToolArgsError {
+        tool_name: tool_name.to_string(),
+        path: "arguments".to_string(),
+        message: "too many invalid fields".to_string(),
+    })
+}
+
+/// `parse_tool_args` for session-backed agents. On failure the call and its
+/// error are stored (see `record_invalid_call`) and a nudge is spent: `Ok(None)`
+/// means "ask the model again", and the error is returned once nudges run out.
+pub async fn parse_or_record<T: DeserializeOwned>(
+    storage: &dyn AgentStorage,
+    session_id: i64,
+    agent_type: AgentType,
+    assistant_text: &str,
+    tool_call: &ToolCall,
+    nudges: &mut u32,
+    max_nudges: u32,
+) -> Result<Option<T>, AgentError> {
+    let error = match parse_tool_args(tool_call) {
+        Ok(args) => return Ok(Some(args)),
+        Err(error) => error,
+    };
+    *nudges += 1;
+    if *nudges >= max_nudges {
+        return Err(error.into());
+    }
+    record_invalid_call(
+        storage,
+        session_id,
+        agent_type,
+        assistant_text,
+        tool_call,
+        &error,
+    )
+    .await?;
+    Ok(None)
+}
+
+/// Store a tool call whose arguments did not parse, answered by the parse
+/// error, so the model sees exactly what to fix on its next turn.
+pub async fn record_invalid_call(
+    storage: &dyn AgentStorage,
+    session_id: i64,
+    agent_type: AgentType,
+    assistant_text: &str,
+    tool_call: &ToolCall,
+    error: &ToolArgsError,
+) -> Result<(), AgentError> {
+    log::warn!("[ToolArgs] session={} {}", session_id, error);
+    let row = |role: &str, agent_type: Option<String>, content: String| ChatMessage {
+        id: None,
+        session_id,
+        role: role.to_string(),
+        agent_type,
+        content,
+        tool_call_id: Some(tool_call.id().to_string()),
+        tool_name: Some(tool_call.name().to_string()),
+        turn_id: None,
+        created_at: 0,
+    };
+    let agent = Some(agent_type.as_str().to_string());
+    let mut turn = Vec::new();
+    if !assistant_text.is_empty() {
+        turn.push(ChatMessage {
+            tool_call_id: None,
+            tool_name: None,
+            ..row("assistant", agent.clone(), assistant_text.to_string())
+        });
+    }
+    turn.push(row(
+        "assistant",
+        agent,
+        tool_call.raw_arguments().to_string(),
+    ));
+    turn.push(row("tool", None, error.to_string()));
+    storage.create_turn(turn).await?;
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Text repair
+// ---------------------------------------------------------------------------
+
+/// Parse almost-JSON, returning the value and the names of the repairs made.
+pub(crate) fn repair_json(raw: &str) -> Result<(Value, Vec<&'static str>), String> {
+    let mut repairs = Vec::new();
+    let mut text = raw.trim();
+
+    if text.starts_with("```") {
+        repairs.push("code fence");
+        text = text.split_once('\n').map_or("", |(_, rest)| rest);
+        text = text.trim_end().trim_end_matches("```").trim();
+    }
+    if !text.starts_with(['{', '[']) {
+        if let (Some(start), Some(end)) = (text.find('{'), text.rfind('}')) {
+            if start < end {
+                repairs.push("surrounding text");
+                text = &text[start..=end];
+            }
+        }
+    }
+
+    let first_error = match serde_json::from_str(text) {
+        Ok(value) => return Ok((value, repairs)),
+        Err(e) => e,
+    };
+    let normalized = normalize(text, &mut repairs);
+    serde_json::from_str(&normalized)
+        .map(|value| (value, repairs))
+        .map_err(|_| format!("not valid JSON ({})", first_error))
+}
+
+/// Rewrite single-quoted strings, unquoted keys, Python literals and trailing
+/// commas into strict JSON.
+fn normalize(text: &str, repairs: &mut Vec<&'static str>) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    fn note(repair: &'static str, repairs: &mut Vec<&'static str>) {
+        if !repairs.contains(&repair) {
+            repairs.push(repair);
+        }
+    }
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '"' => {
+                // Copy a double-quoted string verbatim.
+                out.push(c);
+                i += 1;
+                while i < chars.len() {
+                    out.push(chars[i]);
+                    if chars[i] == '\\' && i + 1 < chars.len() {
+                        out.push(chars[i + 1]);
+                        i += 1;
+                    } else if chars[i] == '"' {
+                        break;
+                    }
+                    i += 1;
+                }
+                i += 1;
+            }
+            '\'' => {
+                note("single quotes", repairs);
+                out.push('"');
+                i += 1;
+                while i < chars.len() && chars[i] != '\'' {
+                    match chars[i] {
+                        '\\' if chars.get(i + 1) == Some(&'\'') => {
+                            out.push('\'');
+                            i += 1;
+                        }
+                        '\\' if i + 1 < chars.len() => {
+                            out.push('\\');
+                            out.push(chars[i + 1]);
+                            i += 1;
+                        }
+                        '"' => out.push_str("\\\""),
+                        other => out.push(other),
+                    }
+                    i += 1;
+                }
+                out.push('"');
+                i += 1;
+            }
+            ',' => {
+                let next = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+                if matches!(next, Some('}') | Some(']')) {
+                    note("trailing comma", repairs);
+                } else {
+                    out.push(c);
+                }
+                i += 1;
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '$' | '-'))
+                {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let next = chars[i..].iter().find(|c| !c.is_whitespace());
+                if next == Some(&':') {
+                    note("unquoted keys", repairs);
+                    out.push_str(&serde_json::to_string(&word).unwrap_or_default());
+                } else {
+                    let literal = match word.as_str() {
+                        "True" => "true",
+                        "False" => "false",
+                        "None" => "null",
+                        other => other,
+                    };
+                    if literal != word {
+                        note("Python literals", repairs);
+                    }
+                    out.push_str(literal);
+                }
+            }
+            _ => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+// ---------------------------------------------------------------------------
+// Field paths
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Path of the field on `line` (1-based) of `serde_json::to_string_pretty`
+/// output. A closing bracket line belongs to the object or array it closes.
+fn path_at_line(pretty: &str, line: usize) -> Vec<Segment> {
+    enum Frame {
+        Object(Option<String>),
+        Array(Option<usize>),
+    }
+    let render = |frames: &[Frame]| {
+        frames
+            .iter()
+            .filter_map(|f| match f {
+                Frame::Object(key) => key.clone().map(Segment::Key),
+                Frame::Array(index) => index.map(Segment::Index),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut stack: Vec<Frame> = Vec::new();
+    for (i, text) in pretty.lines().enumerate() {
+        let t = text.trim();
+        let closing = t.starts_with(['}', ']']);
+        let mut rest = t;
+        if !closing {
+            match stack.last_mut() {
+                Some(Frame::Object(key)) => {
+                    if let Some((k, value)) = split_key(t) {
+                        *key = Some(k);
+                        rest = value;
+                    }
+                }
+                Some(Frame::Array(index)) => *index = Some(index.map_or(0, |i| i + 1)),
+                None => {}
+            }
+        }
+        if i + 1 == line {
+            let depth = if closing {
+                stack.len().saturating_sub(1)
+            } else {
+                stack.len()
+            };
+            return render(&stack[..depth]);
+        }
+        if closing {
+            stack.pop();
+        } else if rest.ends_with('{') {
+            stack.push(Frame::Object(None));
+        } else if rest.ends_with('[') {
+            stack.push(Frame::Array(None));
+        }
+    }
+    Vec::new()
+}
+
+/// Split `"key": value` into the unescaped key and the value text.
+fn split_key(line: &str) -> Option<(String, &str)> {
+    if !line.starts_with('"') {
+        return None;
+    }
+    let mut escaped = false;
+    let end = line
+        .char_indices()
+        .skip(1)
+        .find(|&(_, c)| {
+            let is_end = c == '"' && !escaped;
+            escaped = c == '\\' && !escaped;
+            is_end
+        })?
+        .0;
+    let value = line[end + 1..].strip_prefix(": ")?;
+    let key = serde_json::from_str(&line[..=end]).ok()?;
+    Some((key, value))
+}
+
+fn render_path(path: &[Segment]) -> String {
+    if path.is_empty() {
+        return "arguments".to_string();
+    }
+    let mut out = String::new();
+    for segment in path {
+        match segment {
+            Segment::Key(key) if out.is_empty() => out.push_str(key),
+            Segment::Key(key) => {
+                out.push('.');
+                out.push_str(key);
+            }
+            Segment::Index(i) => out.push_str(&format!("[{}]", i)),
+        }
+    }
+    out
+}
+
+fn value_at<'a>(value: &'a mut Value, path: &[Segment]) -> Option<&'a mut Value> {
+    path.iter().try_fold(value, |v, segment| match segment {
+        Segment::Key(key) => v.get_mut(key.as_str()),
+        Segment::Index(i) => v.get_mut(*i),
+    })
+}
+
+/// Drop serde_json's " at line X column Y" suffix; the path replaces it.
+fn strip_position(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(at) => message[..at].to_string(),
+        None => message.to_string(),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Coercion
+// ---------------------------------------------------------------------------
+
+/// Fix the field at `path` for the deserialization error `message`. Returns a
+/// description of the fix, or `None` when there is nothing safe to do.
+fn coerce(value: &mut Value, path: &[Segment], message: &str) -> Option<String> {
+    let target = value_at(value, path)?;
+
+    if message.starts_with("unknown variant") {
+        let quoted: Vec<&str> = message.split('`').skip(1).step_by(2).collect();
+        let (bad, expected) = quoted.split_first()?;
+        let fixed = expected
+            .iter()
+            .find(|e| variant_key(e) == variant_key(bad))?
+            .to_string();
+        // Internally tagged enums report the error on the enclosing object.
+        let slot = match target {
+            Value::String(s) if s == bad => target,
+            Value::Object(map) => map.values_mut().find(|v| v.as_str() == Some(*bad))?,
+            _ => return None,
+        };
+        *slot = Value::String(fixed.clone());
+        return Some(format!("variant `{}` → `{}`", bad, fixed));
+    }
+
+    let expected = message.split(", expected ").nth(1)?;
+    let replacement = match (&*target, expected) {
+        (Value::String(s), e) if e.contains("bool") => Value::Bool(s.trim().parse().ok()?),
+        (Value::String(s), e) if is_integer_type(e) => Value::from(s.trim().parse::<i64>().ok()?),
+        (Value::String(s), e) if e.starts_with('f') || e.contains("number") => {
+            Value::from(s.trim().parse::<f64>().ok()?)
+        }
+        (Value::String(s), e)
+            if e.contains("sequence") || e.contains("map") || e.starts_with("struct") =>
+        {
+            // A stringified array or object; a lone string where a list is
+            // expected becomes a one-element list.
+            let sequence = e.contains("sequence");
+            match repair_json(s).map(|(parsed, _)| parsed) {
+                Ok(parsed @ Value::Array(_)) if sequence => parsed,
+                Ok(parsed @ Value::Object(_)) if !sequence => parsed,
+                _ if sequence => Value::Array(vec![target.clone()]),
+                _ => return None,
+            }
+        }
+        (Value::Number(n), e) if e.contains("string") => Value::String(n.to_string()),
+        (Value::Bool(b), e) if e.contains("string") => Value::String(b.to_string()),
+        (Value::Null, _) => return None,
+        (other, e) if e.contains("sequence") => Value::Array(vec![other.clone()]),
+        _ => return None,
+    };
+    let fix = format!("{} → {}", target, replacement);
+    *target = replacement;
+    Some(fix)
+}
+
+fn is_integer_type(expected: &str) -> bool {
+    expected.starts_with('i') || expected.starts_with('u') || expected.contains("integer")
+}
+
+/// Variant names compared without case or separators: `DateTime`, `date_time`
+/// and `date-time` are the same variant.
+fn variant_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    enum Kind {
+        SingleChoice,
+        MultipleChoice,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Item {
+        name: String,
+        count: i64,
+        kind: Kind,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Params {
+        title: String,
+        items: Vec<Item>,
+        #[serde(default)]
+        tags: Vec<String>,
+    }
+
+    fn parse(raw: &str) -> Result<Params, ToolArgsError> {
+        let (value, _) = repair_json(raw).map_err(|message| ToolArgsError {
+            tool_name: "t".to_string(),
+            path: "arguments".to_string(),
+            message,
+        })?;
+        parse_value("t", value)
+    }
+
+    #[test]
+    fn test_repairs_almost_json() {
+        let raw = "```json\n{title: 'Contacts', items: [{'name': \"a\", count: 1, kind: 'single_choice',},],}\n```";
+        let params = parse(raw).unwrap();
+        assert_eq!(params.title, "Contacts");
+        assert_eq!(params.items[0].kind, Kind::SingleChoice);
+    }
+
+    #[test]
+    fn test_coerces_casing_numbers_and_sequences() {
+        let raw = r#"{"title": "x", "tags": "solo", "items": [
+            {"name": "a", "count": 1, "kind": "single_choice"},
+            {"name": "b", "count": "2", "kind": "MultipleChoice"}
+        ]}"#;
+        let params = parse(raw).unwrap();
+        assert_eq!(params.items[1].count, 2);
+        assert_eq!(params.items[1].kind, Kind::MultipleChoice);
+        assert_eq!(params.tags, vec!["solo"]);
+    }
+
+    #[test]
+    fn test_reports_field_path() {
+        let raw = r#"{"title": "x", "items": [
+            {"name": "a", "count": 1, "kind": "single_choice"},
+            {"name": "b", "count": 2, "kind": "ranking"}
+        ]}"#;
+        let err = parse(raw).unwrap_err();
+        assert_eq!(err.path, "items[1].kind");
+        assert!(err.message.contains("unknown variant `ranking`"), "{err}");
+
+        let err = parse(r#"{"title": "x", "items": [{"name": "a", "kind": "single_choice"}]}"#)
+            .unwrap_err();
+        assert_eq!(err.path, "items[0]");
+        assert!(err.message.contains("missing field `count`"), "{err}");
+    }
+
+    #[test]
+    fn test_unrepairable_text() {
+        let err = parse("{title: ").unwrap_err();
+        assert_eq!(err.path, "arguments");
+        assert!(err.message.starts_with("not valid JSON"), "{err}");
+    }
+}
@@ -8,8 +8,14 @@ use llm_sdk::{
 
 use super::{prompts::system_prompt, tools::UpdateTaskStatusParams, FormLayout};
 use crate::{
+    context_window::ContextWindow,
     error::AgentError,
-    storage::{AgentStorage, AgentType, ChatMessage, TaskStatus, TaskStorage, UiFormStorage},
+    prompt_library::PromptLibrary,
+    run_control::RunControl,
+    storage::{
+        AgentStorage, AgentType, ChatMessage, LlmUsage, TaskStatus, TaskStorage, UiFormStorage,
+    },
+    tool_args::parse_or_record,
 };
 
 const MAX_NUDGES: u32 = 3;
@@ -27,6 +33,14 @@ pub struct UiDesignerAgent {
     task_storage: Arc<dyn TaskStorage>,
     model: String,
     project_id: i64,
+    /// Summarizes older turns when the history outgrows the model's budget.
+    /// `None` sends the full history.
+    context: Option<ContextWindow>,
+    prompts: PromptLibrary,
+    control: RunControl,
+    /// Return the form layout without saving it or closing the task, e.g.
+    /// for a user to approve first.
+    preview_mode: bool,
 }
 
 impl UiDesignerAgent {
@@ -45,9 +59,37 @@ impl UiDesignerAgent {
             task_storage,
             model: model.into(),
             project_id,
+            context: None,
+            prompts: PromptLibrary::embedded(),
+            control: RunControl::default(),
+            preview_mode: false,
         }
     }
 
+    pub fn with_context_window(mut self, context: ContextWindow) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Resolve the system prompt through `prompts`, e.g. to pick up the
+    /// project's overrides. Embedded templates are used otherwise.
+    pub fn with_prompts(mut self, prompts: PromptLibrary) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    /// Let the caller cancel the run or bound its time and turns.
+    pub fn with_run_control(mut self, control: RunControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    /// Propose the form layout instead of saving it; the task stays open.
+    pub fn with_preview_mode(mut self, preview_mode: bool) -> Self {
+        self.preview_mode = preview_mode;
+        self
+    }
+
     pub async fn run_for_task(
         &self,
         session_id: i64,
@@ -89,10 +131,28 @@ impl UiDesignerAgent {
             created_at: 0,
         };
 
+        let system = system_prompt(&self.prompts)?;
+        self.storage
+            .set_session_prompt(session_id, &system.reference)
+            .await?;
+
         let mut nudges: u32 = 0;
 
-        loop {
-            let history = self.storage.get_messages(session_id).await?;
+        'turns: loop {
+            let history = match &self.context {
+                Some(context) => {
+                    context
+                        .session_history(
+                            self.storage.as_ref(),
+                            self.project_id,
+                            AgentType::UiDesigner,
+                            task_id,
+                            session_id,
+                        )
+                        .await?
+                }
+                None => self.storage.get_messages(session_id).await?,
+            };
             let llm_messages: Vec<Message> = history
                 .into_iter()
                 .map(|m| {
@@ -114,7 +174,7 @@ impl UiDesignerAgent {
                 messages: llm_messages,
                 max_tokens: 4096,
                 model: self.model.clone(),
-                system: Some(system_prompt().to_string()),
+                system: Some(system.text.clone()),
                 temperature: Some(0.2),
                 top_p: None,
                 stop_sequences: None,
@@ -126,7 +186,26 @@ impl UiDesignerAgent {
                 response_format: None,
             };
 
-            let response = self.llm_client.complete(request).await?;
+            self.control.next_turn()?;
+            let response = self
+                .control
+                .run(self.llm_client.complete(request))
+                .await??;
+
+            self.storage
+                .record_usage(LlmUsage {
+                    task_id: Some(task_id),
+                    session_id: Some(session_id),
+                    prompt: Some(system.reference.clone()),
+                    ..LlmUsage::from_response(
+                        self.project_id,
+                        AgentType::UiDesigner,
+                        self.llm_client.as_ref(),
+                        &self.model,
+                        &response,
+                    )
+                })
+                .await?;
 
             let assistant_text = response
                 .content
@@ -140,21 +219,38 @@ impl UiDesignerAgent {
 
             if let Some(tool_calls) = response.tool_calls {
                 for tool_call in tool_calls {
+                    self.control.check()?;
                     let tool_name = tool_call.name();
                     let call_id = tool_call.id().to_string();
 
                     match tool_name {
                         "write_form_layout" => {
-                            let form: FormLayout =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                            let Some(form) = parse_or_record::<FormLayout>(
+                                self.storage.as_ref(),
+                                session_id,
+                                AgentType::UiDesigner,
+                                &assistant_text,
+                                &tool_call,
+                                &mut nudges,
+                                MAX_NUDGES,
+                            )
+                            .await?
+                            else {
+                                continue 'turns;
+                            };
                             let layout_json = serde_json::to_string(&form)?;
 
-                            self.form_storage
-                                .save_form_layout(self.project_id, &form.entity, &layout_json)
-                                .await?;
-
-                            let result_text =
-                                format!("Form layout saved for entity '{}'.", form.entity);
+                            let result_text = if self.preview_mode {
+                                format!(
+                                    "Form layout for entity '{}' proposed; it is saved once approved.",
+                                    form.entity
+                                )
+                            } else {
+                                self.form_storage
+                                    .save_form_layout(self.project_id, &form.entity, &layout_json)
+                                    .await?;
+                                format!("Form layout saved for entity '{}'.", form.entity)
+                            };
 
                             let mut turn = Vec::new();
                             if !assistant_text.is_empty() {
@@ -184,17 +280,30 @@ impl UiDesignerAgent {
                             });
                             self.storage.create_turn(turn).await?;
 
-                            let _ = self
-                                .task_storage
-                                .update_task_status(task_id, TaskStatus::Done)
-                                .await;
+                            if !self.preview_mode {
+                                let _ = self
+                                    .task_storage
+                                    .update_task_status(task_id, TaskStatus::Done)
+                                    .await;
+                            }
 
                             return Ok(UiDesignerResponse::FormGenerated(form));
                         }
 
                         "update_task_status" => {
-                            let params: UpdateTaskStatusParams =
-                                tool_call.parse_arguments().map_err(AgentError::Llm)?;
+                            let Some(params) = parse_or_record::<UpdateTaskStatusParams>(
+                                self.storage.as_ref(),
+                                session_id,
+                                AgentType::UiDesigner,
+                                &assistant_text,
+                                &tool_call,
+                                &mut nudges,
+                                MAX_NUDGES,
+                            )
+                            .await?
+                            else {
+                                continue 'turns;
+                            };
                             let new_status = TaskStatus::from_str(&params.status);
                             let result_text = match self
                                 .task_storage
@@ -256,7 +365,7 @@ impl UiDesignerAgent {
 
             nudges += 1;
             if nudges >= MAX_NUDGES {
-                return Err(AgentError::Other(
+                return Err(AgentError::NudgesExhausted(
                     "ui_designer did not call write_form_layout after multiple nudges".to_string(),
                 ));
             }
@@ -0,0 +1,546 @@
+//! Follow-up work chained on task completion. A workflow is a list of rules
+//! like "when a db_engineer task is done, create a ui_designer task per table
+//! of the latest schema". The built-in rules live in
+//! `agents/workflows/default.toml`; a project replaces them with
+//! `<project>/.nocodo/workflow.toml`:
+//!
+//! ```toml
+//! [[rule]]
+//! name          = "forms"
+//! on_done       = "db_engineer"
+//! agent         = "ui_designer"
+//! for_each      = "table"
+//! title         = "Design form for {{table}}"
+//! source_prompt = "{{table_json}}"
+//! ```
+//!
+//! Created tasks carry the finished task in `created_by_task_id`, which also
+//! keeps a rule from creating the same task twice.
+
+use std::path::Path;
+
+use serde::Deserialize;
+use shared_types::{SchemaDef, TableDef};
+
+use crate::{
+    error::AgentError,
+    storage::{Task, TaskStatus, TaskStorage},
+};
+
+/// Where a project keeps its workflow, relative to its root.
+pub const PROJECT_WORKFLOW_FILE: &str = ".nocodo/workflow.toml";
+const BUILTIN: &str = include_str!("../workflows/default.toml");
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Workflow {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<WorkflowRule>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WorkflowRule {
+    pub name: String,
+    /// Agent whose finished task fires the rule.
+    pub on_done: String,
+    /// Agent the created tasks are assigned to.
+    pub agent: String,
+    #[serde(default)]
+    pub for_each: ForEach,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    /// Source prompt of the created task; defaults to `params` as JSON, then
+    /// to the description.
+    #[serde(default)]
+    pub source_prompt: Option<String>,
+    /// Structured source prompt, e.g. rust_engineer task parameters.
+    #[serde(default)]
+    pub params: Option<toml::Table>,
+    /// Earlier rule whose task for the same table this one depends on.
+    #[serde(default)]
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForEach {
+    /// One task per finished task.
+    #[default]
+    Task,
+    /// One task per table of the project's latest schema.
+    Table,
+}
+
+/// A task created by a workflow rule.
+#[derive(Debug, Clone)]
+pub struct FollowUp {
+    pub rule: String,
+    pub task: Task,
+}
+
+impl Workflow {
+    /// The rules built into the binary.
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN).expect("built-in workflow is valid")
+    }
+
+    pub fn parse(source: &str) -> Result<Self, AgentError> {
+        let workflow: Workflow = toml::from_str(source)
+            .map_err(|e| AgentError::Config(format!("invalid workflow: {}", e)))?;
+        for (idx, rule) in workflow.rules.iter().enumerate() {
+            if let Some(after) = &rule.after {
+                let earlier = workflow.rules[..idx]
+                    .iter()
+                    .any(|r| &r.name == after && r.on_done == rule.on_done);
+                if !earlier {
+                    return Err(AgentError::Config(format!(
+                        "workflow rule {}: `after` must name an earlier rule on {}, not {}",
+                        rule.name, rule.on_done, after
+                    )));
+                }
+            }
+        }
+        Ok(workflow)
+    }
+
+    /// The project's `.nocodo/workflow.toml`, or the built-in rules when it
+    /// has none.
+    pub fn for_project(project_path: impl AsRef<Path>) -> Result<Self, AgentError> {
+        let path = project_path.as_ref().join(PROJECT_WORKFLOW_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(source) => {
+                log::info!("[Workflow] using {}", path.display());
+                Self::parse(&source)
+            }
+            Err(_) => Ok(Self::builtin()),
+        }
+    }
+
+    /// Whether any rule fires when a task of `agent_type` is done.
+    pub fn fires_on(&self, agent_type: &str) -> bool {
+        self.rules.iter().any(|r| r.on_done == agent_type)
+    }
+
+    fn fires_per_table(&self, agent_type: &str) -> bool {
+        self.rules
+            .iter()
+            .any(|r| r.on_done == agent_type && r.for_each == ForEach::Table)
+    }
+
+    /// Create the tasks the rules call for now that `done` is done, skipping
+    /// any that an earlier evaluation already created. Tasks are Ready, in
+    /// rule order; per-table rules are skipped when there is no schema, and
+    /// for tables the project already has a task for (see `covers_table`).
+    pub async fn create_follow_ups(
+        &self,
+        done: &Task,
+        schema: Option<&SchemaDef>,
+        tasks: &dyn TaskStorage,
+    ) -> Result<Vec<FollowUp>, AgentError> {
+        let done_id = done.id.unwrap_or(0);
+        let existing = tasks.list_tasks_created_by(done_id).await?;
+        let project_tasks = match (self.fires_per_table(&done.assigned_to_agent), schema) {
+            (true, Some(_)) => tasks.list_tasks_for_project(done.project_id).await?,
+            _ => Vec::new(),
+        };
+        let schema_json = match schema {
+            Some(schema) => serde_json::to_string(schema)?,
+            None => String::new(),
+        };
+        let done_id_str = done_id.to_string();
+        let base_vars = [
+            ("task_id", done_id_str.as_str()),
+            ("task_title", done.title.as_str()),
+            ("schema_json", schema_json.as_str()),
+        ];
+
+        // (rule, table) → id of the task created or found for it, for `after`.
+        let mut ids: Vec<(&str, Option<&str>, i64)> = Vec::new();
+        let mut created = Vec::new();
+        for rule in self
+            .rules
+            .iter()
+            .filter(|r| r.on_done == done.assigned_to_agent)
+        {
+            let tables: Vec<Option<&TableDef>> = match (rule.for_each, schema) {
+                (ForEach::Task, _) => vec![None],
+                (ForEach::Table, Some(schema)) => schema.tables.iter().map(Some).collect(),
+                (ForEach::Table, None) => {
+                    log::warn!(
+                        "[Workflow] rule {}: project {} has no schema, skipped",
+                        rule.name,
+                        done.project_id
+                    );
+                    continue;
+                }
+            };
+            for table in tables {
+                let table_json = match table {
+                    Some(table) => serde_json::to_string(table)?,
+                    None => String::new(),
+                };
+                let mut vars = base_vars.to_vec();
+                if let Some(table) = table {
+                    vars.push(("table", table.name.as_str()));
+                    vars.push(("table_json", table_json.as_str()));
+                }
+                let table_name = table.map(|t| t.name.as_str());
+
+                let title = fill(&rule.title, &vars);
+                if let Some(task) = existing
+                    .iter()
+                    .find(|t| t.assigned_to_agent == rule.agent && t.title == title)
+                {
+                    ids.push((&rule.name, table_name, task.id.unwrap_or(0)));
+                    continue;
+                }
+                if let Some(table) = table {
+                    if let Some(task) = project_tasks
+                        .iter()
+                        .find(|t| covers_table(t, rule, &table.name))
+                    {
+                        log::info!(
+                            "[Workflow] rule {}: task {} already covers table {}, skipped",
+                            rule.name,
+                            task.id.unwrap_or(0),
+                            table.name
+                        );
+                        ids.push((&rule.name, table_name, task.id.unwrap_or(0)));
+                        continue;
+                    }
+                }
+
+                let description = fill(&rule.description, &vars);
+                let source_prompt = match (&rule.source_prompt, &rule.params) {
+                    (Some(template), _) => fill(template, &vars),
+                    (None, Some(params)) => serde_json::to_string(&fill_value(
+                        toml::Value::Table(params.clone()),
+                        &vars,
+                    ))?,
+                    (None, None) => description.clone(),
+                };
+                let depends_on_task_id = rule.after.as_deref().and_then(|after| {
+                    ids.iter()
+                        .find(|(name, t, _)| *name == after && *t == table_name)
+                        .map(|(_, _, id)| *id)
+                });
+                let mut task = Task {
+                    id: None,
+                    project_id: done.project_id,
+                    epic_id: done.epic_id,
+                    title,
+                    description,
+                    source_prompt,
+                    assigned_to_agent: rule.agent.clone(),
+                    status: TaskStatus::Ready,
+                    depends_on_task_id,
+                    created_by_agent: "workflow".to_string(),
+                    created_at: 0,
+                    updated_at: 0,
+                    created_by_task_id: Some(done_id),
+                };
+                let id = tasks.create_task(task.clone()).await?;
+                task.id = Some(id);
+                ids.push((&rule.name, table_name, id));
+                created.push(FollowUp {
+                    rule: rule.name.clone(),
+                    task,
+                });
+            }
+        }
+        Ok(created)
+    }
+}
+
+/// Whether `task` already does for `table` what `rule` would create a task
+/// for: it is assigned to the rule's agent, has the rule's `params.mode` if
+/// the rule sets one, and is about the table. A source prompt holding a table
+/// definition must be that table's; otherwise the title, the description or a
+/// free-text source prompt must name it. This finds tasks the project manager
+/// planned, and tasks an earlier finished task already had the rule create.
+fn covers_table(task: &Task, rule: &WorkflowRule, table: &str) -> bool {
+    if task.assigned_to_agent != rule.agent {
+        return false;
+    }
+    let source = serde_json::from_str::<serde_json::Value>(&task.source_prompt).ok();
+    let rule_mode = rule
+        .params
+        .as_ref()
+        .and_then(|p| p.get("mode"))
+        .and_then(|m| m.as_str());
+    if let Some(mode) = rule_mode {
+        let task_mode = source.as_ref().and_then(|v| v.get("mode")?.as_str());
+        if task_mode != Some(mode) {
+            return false;
+        }
+    }
+    match source {
+        Some(value) => match serde_json::from_value::<TableDef>(value) {
+            Ok(def) => def.name == table,
+            // Structured parameters may quote other tables, e.g. in foreign
+            // keys, so only the title and description count.
+            Err(_) => names_table(&task.title, table) || names_table(&task.description, table),
+        },
+        None => [&task.title, &task.description, &task.source_prompt]
+            .iter()
+            .any(|text| names_table(text, table)),
+    }
+}
+
+/// Whether `text` names `table` as a word of its own, ignoring case and an
+/// `s` plural: "contacts" names `contact`, `contact_address` does not.
+fn names_table(text: &str, table: &str) -> bool {
+    let text = text.to_lowercase();
+    let table = table.to_lowercase();
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(&table).any(|(start, _)| {
+        let rest = &text[start + table.len()..];
+        let rest = rest.strip_prefix('s').unwrap_or(rest);
+        let before = text[..start].chars().next_back();
+        !before.is_some_and(is_ident) && !rest.chars().next().is_some_and(is_ident)
+    })
+}
+
+/// Replace `{{name}}` with its value in `vars`; unknown names are kept.
+fn fill(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let name = rest[start + 2..start + end].trim();
+        match vars.iter().find(|(key, _)| *key == name) {
+            Some((_, value)) => out.push_str(value),
+            None => out.push_str(&rest[start..start + end + 2]),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn fill_value(value: toml::Value, vars: &[(&str, &str)]) -> toml::Value {
+    match value {
+        toml::Value::String(s) => toml::Value::String(fill(&s, vars)),
+        toml::Value::Array(items) => {
+            toml::Value::Array(items.into_iter().map(|v| fill_value(v, vars)).collect())
+        }
+        toml::Value::Table(table) => toml::Value::Table(
+            table
+                .into_iter()
+                .map(|(k, v)| (k, fill_value(v, vars)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{sqlite::SqliteTaskStorage, test_db};
+
+    #[test]
+    fn builtin_rules_parse() {
+        let workflow = Workflow::builtin();
+        assert!(workflow.fires_on("db_engineer"));
+        assert!(!workflow.fires_on("ui_designer"));
+        // Rules that write code are opt-in.
+        assert!(workflow.rules.iter().all(|r| r.agent != "rust_engineer"));
+    }
+
+    #[test]
+    fn after_must_name_an_earlier_rule() {
+        let source = r#"
+            [[rule]]
+            name = "b"
+            on_done = "db_engineer"
+            agent = "ui_designer"
+            title = "B"
+            after = "a"
+        "#;
+        assert!(matches!(
+            Workflow::parse(source),
+            Err(AgentError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn fill_keeps_unknown_names() {
+        assert_eq!(
+            fill("Form for {{ table }} {{other}}", &[("table", "contact")]),
+            "Form for contact {{other}}"
+        );
+    }
+
+    #[tokio::test]
+    async fn create_follow_ups_per_table_with_after_once() {
+        let workflow = Workflow::parse(
+            r#"
+            [[rule]]
+            name = "schema"
+            on_done = "db_engineer"
+            agent = "rust_engineer"
+            for_each = "table"
+            title = "Schema for {{table}}"
+
+            [[rule]]
+            name = "model"
+            on_done = "db_engineer"
+            agent = "rust_engineer"
+            for_each = "table"
+            title = "Model for {{table}}"
+            after = "schema"
+
+            [[rule]]
+            name = "review"
+            on_done = "db_engineer"
+            agent = "stack_reviewer"
+            title = "Review {{task_title}}"
+            "#,
+        )
+        .unwrap();
+        let schema: SchemaDef = serde_json::from_value(serde_json::json!({
+            "name": "contacts",
+            "tables": [
+                { "name": "contact", "columns": [] },
+                { "name": "address", "columns": [] },
+            ],
+        }))
+        .unwrap();
+        let tasks = SqliteTaskStorage::new(test_db::with_tasks(1));
+        let done = tasks.get_task(1).await.unwrap().unwrap();
+
+        let created = workflow
+            .create_follow_ups(&done, Some(&schema), &tasks)
+            .await
+            .unwrap();
+        let titles: Vec<&str> = created.iter().map(|f| f.task.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            [
+                "Schema for contact",
+                "Schema for address",
+                "Model for contact",
+                "Model for address",
+                "Review Task 1",
+            ]
+        );
+        for follow_up in &created {
+            assert_eq!(follow_up.task.created_by_task_id, Some(1));
+            assert_eq!(follow_up.task.status, TaskStatus::Ready);
+        }
+        // Each model task waits for the schema task of its own table.
+        assert_eq!(created[2].task.depends_on_task_id, created[0].task.id);
+        assert_eq!(created[3].task.depends_on_task_id, created[1].task.id);
+        assert_eq!(created[0].task.depends_on_task_id, None);
+        assert_eq!(created[4].task.depends_on_task_id, None);
+
+        let again = workflow
+            .create_follow_ups(&done, Some(&schema), &tasks)
+            .await
+            .unwrap();
+        assert!(again.is_empty());
+        assert_eq!(tasks.list_tasks_created_by(1).await.unwrap().len(), 5);
+
+        // Without a schema only the per-task rule applies, and it already ran.
+        let without_schema = workflow
+            .create_follow_ups(&done, None, &tasks)
+            .await
+            .unwrap();
+        assert!(without_schema.is_empty());
+    }
+
+    #[tokio::test]
+    async fn create_follow_ups_skips_tables_with_a_task() {
+        let workflow = Workflow::parse(
+            r#"
+            [[rule]]
+            name = "forms"
+            on_done = "db_engineer"
+            agent = "ui_designer"
+            for_each = "table"
+            title = "Design form for {{table}}"
+            source_prompt = "{{table_json}}"
+
+            [[rule]]
+            name = "schema"
+            on_done = "db_engineer"
+            agent = "rust_engineer"
+            for_each = "table"
+            title = "Schema for {{table}}"
+
+            [rule.params]
+            mode = "diesel_schema"
+            prompt = "{{table_json}}"
+
+            [[rule]]
+            name = "model"
+            on_done = "db_engineer"
+            agent = "rust_engineer"
+            for_each = "table"
+            title = "Model for {{table}}"
+            after = "schema"
+
+            [rule.params]
+            mode = "diesel_model_struct"
+            prompt = "{{table_json}}"
+            "#,
+        )
+        .unwrap();
+        let schema: SchemaDef = serde_json::from_value(serde_json::json!({
+            "name": "contacts",
+            "tables": [
+                { "name": "contact", "columns": [] },
+                { "name": "contact_address", "columns": [] },
+            ],
+        }))
+        .unwrap();
+        let conn = test_db::with_tasks(2);
+        // The project manager planned the contacts form and the schema of
+        // `contact`; task 2 is a db_engineer task that already had the form
+        // of `contact_address` created.
+        conn.execute_batch(
+            r#"INSERT INTO task (id, project_id, title, description, source_prompt,
+                   assigned_to_agent, created_by_agent, created_at, updated_at)
+               VALUES (3, 1, 'Contacts screen', '', 'A form to add and edit contacts',
+                   'ui_designer', 'project_manager', 0, 0),
+                   (4, 1, 'Contact table in Diesel', '',
+                   '{"mode":"diesel_schema","prompt":"The contact table"}',
+                   'rust_engineer', 'project_manager', 0, 0),
+                   (5, 1, 'Design form for contact_address', '',
+                   '{"name":"contact_address","columns":[]}',
+                   'ui_designer', 'workflow', 0, 0);"#,
+        )
+        .unwrap();
+        let tasks = SqliteTaskStorage::new(conn);
+        let done = tasks.get_task(1).await.unwrap().unwrap();
+
+        let created = workflow
+            .create_follow_ups(&done, Some(&schema), &tasks)
+            .await
+            .unwrap();
+        let titles: Vec<&str> = created.iter().map(|f| f.task.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            [
+                "Schema for contact_address",
+                "Model for contact",
+                "Model for contact_address",
+            ]
+        );
+        // The model of `contact` waits for the planned schema task.
+        assert_eq!(created[1].task.depends_on_task_id, Some(4));
+        assert_eq!(created[2].task.depends_on_task_id, created[0].task.id);
+    }
+
+    #[test]
+    fn names_table_matches_whole_words() {
+        assert!(names_table("Edit Contacts", "contact"));
+        assert!(names_table("the contact form", "contact"));
+        assert!(!names_table("contact_address form", "contact"));
+        assert!(!names_table("recontact", "contact"));
+    }
+}
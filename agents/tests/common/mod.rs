@@ -12,8 +12,8 @@ pub static LIVE_TEST_LOCK: Mutex<()> = Mutex::new(());
 pub struct LiveTestConfig {
     models: String,
     template_path: PathBuf,
-    project_path: PathBuf,
-    base_url: String,
+    pub project_path: PathBuf,
+    pub base_url: String,
 }
 
 impl LiveTestConfig {
@@ -26,9 +26,7 @@ impl LiveTestConfig {
 
         Self {
             models,
-            template_path: PathBuf::from(required_env(
-                "RUST_ENGINEER_TEST_PROJECT_TEMPLATE_PATH",
-            )),
+            template_path: PathBuf::from(required_env("RUST_ENGINEER_TEST_PROJECT_TEMPLATE_PATH")),
             project_path: PathBuf::from(required_env("RUST_ENGINEER_TEST_PROJECT_PATH")),
             base_url: required_env("LLAMA_CPP_BASE_URL"),
         }
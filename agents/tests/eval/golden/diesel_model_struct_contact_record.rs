@@ -0,0 +1,12 @@
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone)]
+#[diesel(table_name = user_contacts)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ContactRecord {
+    pub id: i64,
+    pub user_id: i64,
+    pub contact_type: String,
+    pub value: String,
+    pub country_code: Option<i32>,
+    pub verified_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
@@ -0,0 +1,11 @@
+diesel::table! {
+    user_contacts (id) {
+        id -> BigInt,
+        user_id -> BigInt,
+        contact_type -> Text,
+        value -> Text,
+        country_code -> Nullable<Integer>,
+        verified_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
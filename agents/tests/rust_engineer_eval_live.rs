@@ -0,0 +1,64 @@
+// The eval does not use the assertion helpers.
+#[allow(dead_code)]
+mod common;
+use common::*;
+
+use std::path::PathBuf;
+
+use nocodo_agents::{
+    eval::{CommandChecker, EvalDataset, EvalRunner},
+    prompt_library::PromptLibrary,
+};
+
+/// Scores every model in RUST_ENGINEER_TEST_MODELS on the eval dataset and
+/// writes eval_report.json / eval_report.md. Reports rather than asserts, so a
+/// weak model shows up as a low pass rate instead of a failed run.
+///
+/// - RUST_ENGINEER_EVAL_DATASET: dataset file (default tests/eval/rust_engineer.json)
+/// - RUST_ENGINEER_EVAL_REPORT_DIR: output dir (default the cargo test tmp dir)
+/// - RUST_ENGINEER_EVAL_CHECK_CMD: compile checker run in the project
+///   (default `cargo check --quiet`; `none` skips compile checks)
+/// - RUST_ENGINEER_EVAL_PROMPTS_DIR: prompt template overrides to score
+///   instead of the embedded ones
+#[tokio::test(flavor = "current_thread")]
+#[ignore]
+async fn live_rust_engineer_eval() {
+    let _guard = LIVE_TEST_LOCK.lock().expect("live test lock poisoned");
+    let cfg = LiveTestConfig::from_env();
+    recreate_project_from_template(&cfg);
+
+    let dataset_path = std::env::var("RUST_ENGINEER_EVAL_DATASET")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/eval/rust_engineer.json")
+        });
+    let dataset = EvalDataset::load(&dataset_path)
+        .unwrap_or_else(|e| panic!("failed to load {}: {e}", dataset_path.display()));
+
+    let mut runner = EvalRunner::new(Some(cfg.base_url.clone()), cfg.project_path.clone());
+    match std::env::var("RUST_ENGINEER_EVAL_CHECK_CMD").as_deref() {
+        Ok("none") => {}
+        Ok(cmd) => {
+            let checker = CommandChecker::parse(cmd).expect("empty RUST_ENGINEER_EVAL_CHECK_CMD");
+            runner = runner.with_compile_checker(Box::new(checker));
+        }
+        Err(_) => runner = runner.with_compile_checker(Box::new(CommandChecker::cargo_check())),
+    }
+    if let Ok(dir) = std::env::var("RUST_ENGINEER_EVAL_PROMPTS_DIR") {
+        runner = runner.with_prompts(PromptLibrary::with_overrides(dir));
+    }
+
+    let models: Vec<String> = cfg.models().map(str::to_string).collect();
+    let report = runner.run(&dataset, &models).await;
+
+    let report_dir = std::env::var("RUST_ENGINEER_EVAL_REPORT_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("rust_engineer_eval"));
+    report
+        .write(&report_dir)
+        .unwrap_or_else(|e| panic!("failed to write report: {e}"));
+
+    println!("{}", report.to_markdown());
+    println!("report written to {}", report_dir.display());
+    assert_eq!(report.results.len(), models.len() * dataset.cases.len());
+}
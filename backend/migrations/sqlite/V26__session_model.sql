@@ -0,0 +1 @@
+ALTER TABLE agent_chat_session ADD COLUMN model TEXT NULL;
@@ -0,0 +1,14 @@
+CREATE TABLE llm_usage (
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id    INTEGER NOT NULL REFERENCES project(id),
+    agent_type    TEXT    NOT NULL,
+    task_id       INTEGER NULL REFERENCES task(id),
+    session_id    INTEGER NULL,  -- agent_chat_session for task runs, user_chat_session otherwise
+    provider      TEXT    NOT NULL,
+    model         TEXT    NOT NULL,
+    input_tokens  INTEGER NOT NULL,
+    output_tokens INTEGER NOT NULL,
+    created_at    INTEGER NOT NULL
+);
+
+CREATE INDEX idx_llm_usage_project ON llm_usage(project_id);
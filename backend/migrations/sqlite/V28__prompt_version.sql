@@ -0,0 +1,4 @@
+ALTER TABLE agent_chat_session ADD COLUMN prompt_id TEXT NULL;
+ALTER TABLE agent_chat_session ADD COLUMN prompt_version TEXT NULL;
+ALTER TABLE llm_usage ADD COLUMN prompt_id TEXT NULL;
+ALTER TABLE llm_usage ADD COLUMN prompt_version TEXT NULL;
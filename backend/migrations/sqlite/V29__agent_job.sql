@@ -0,0 +1,24 @@
+-- Persistent dispatch queue. A worker claims a queued job by taking a lease;
+-- while it runs the worker heartbeats to extend the lease, and a job whose
+-- lease expires (the worker died) becomes claimable again.
+CREATE TABLE agent_job (
+    id               INTEGER PRIMARY KEY AUTOINCREMENT,
+    task_id          INTEGER NOT NULL REFERENCES task(id),
+    project_id       INTEGER NOT NULL REFERENCES project(id),
+    agent_type       TEXT    NOT NULL,
+    source_prompt    TEXT    NOT NULL,
+    status           TEXT    NOT NULL DEFAULT 'queued',  -- queued | running | done | failed
+    attempts         INTEGER NOT NULL DEFAULT 0,
+    lease_owner      TEXT    NULL,
+    lease_expires_at INTEGER NULL,
+    heartbeat_at     INTEGER NULL,
+    last_error       TEXT    NULL,
+    created_at       INTEGER NOT NULL,
+    updated_at       INTEGER NOT NULL
+);
+
+-- At most one open job per task, so enqueueing twice cannot run a task twice.
+CREATE UNIQUE INDEX idx_agent_job_open_task
+    ON agent_job(task_id) WHERE status IN ('queued', 'running');
+CREATE INDEX idx_agent_job_status ON agent_job(status, id);
+CREATE INDEX idx_agent_job_project ON agent_job(project_id);
@@ -0,0 +1,5 @@
+-- Higher priority jobs are claimed first; equal priorities run in FIFO order.
+ALTER TABLE agent_job ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
+
+DROP INDEX idx_agent_job_status;
+CREATE INDEX idx_agent_job_status ON agent_job(status, priority DESC, id);
@@ -0,0 +1,3 @@
+-- A failed run that is retried goes back to 'queued' and may not be claimed
+-- before available_at (exponential backoff).
+ALTER TABLE agent_job ADD COLUMN available_at INTEGER NOT NULL DEFAULT 0;
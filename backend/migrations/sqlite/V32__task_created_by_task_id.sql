@@ -0,0 +1,4 @@
+-- Provenance of tasks created on behalf of another task, e.g. by a workflow
+-- rule when that task is done.
+ALTER TABLE task ADD COLUMN created_by_task_id INTEGER REFERENCES task(id);
+CREATE INDEX idx_task_created_by_task ON task(created_by_task_id);
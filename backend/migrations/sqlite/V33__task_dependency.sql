@@ -0,0 +1,16 @@
+-- Tasks a task waits for. The dispatcher runs a task only once every task it
+-- depends on is done. task.depends_on_task_id stays as the first dependency.
+CREATE TABLE task_dependency (
+    task_id            INTEGER NOT NULL REFERENCES task(id),
+    depends_on_task_id INTEGER NOT NULL REFERENCES task(id),
+    created_at         INTEGER NOT NULL,
+    PRIMARY KEY (task_id, depends_on_task_id),
+    CHECK (task_id != depends_on_task_id)
+);
+CREATE INDEX idx_task_dependency_depends_on ON task_dependency(depends_on_task_id);
+
+INSERT INTO task_dependency (task_id, depends_on_task_id, created_at)
+SELECT id, depends_on_task_id, created_at FROM task
+WHERE depends_on_task_id IS NOT NULL
+  AND depends_on_task_id != id
+  AND depends_on_task_id IN (SELECT id FROM task);
@@ -0,0 +1,21 @@
+-- Results an agent proposed instead of applying them, for agent types that
+-- need a user's approval. The task waits in 'awaiting_approval' while its
+-- proposal is pending.
+CREATE TABLE agent_proposal (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    task_id     INTEGER NOT NULL REFERENCES task(id),
+    project_id  INTEGER NOT NULL REFERENCES project(id),
+    agent_type  TEXT    NOT NULL,
+    session_id  INTEGER NOT NULL REFERENCES agent_chat_session(id),
+    artifact    TEXT    NOT NULL,  -- JSON: schema, form layout or generated code
+    summary     TEXT    NOT NULL,
+    status      TEXT    NOT NULL DEFAULT 'pending',  -- pending | approved | rejected | changes_requested
+    comment     TEXT    NULL,
+    created_at  INTEGER NOT NULL,
+    decided_at  INTEGER NULL
+);
+
+-- At most one pending proposal per task.
+CREATE UNIQUE INDEX idx_agent_proposal_pending_task
+    ON agent_proposal(task_id) WHERE status = 'pending';
+CREATE INDEX idx_agent_proposal_project ON agent_proposal(project_id, id);
@@ -0,0 +1,18 @@
+-- Runs of project-wide agents (the stack reviewer) started by a schedule, a
+-- trigger or a user. The latest row per project and agent is the baseline
+-- of the next trigger.
+CREATE TABLE agent_schedule_run (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id  INTEGER NOT NULL REFERENCES project(id),
+    agent_type  TEXT    NOT NULL,
+    reason      TEXT    NOT NULL,
+    status      TEXT    NOT NULL DEFAULT 'running',  -- running | done | failed
+    summary     TEXT    NULL,
+    started_at  INTEGER NOT NULL,
+    finished_at INTEGER NULL
+);
+
+-- At most one running run per project and agent, so runs never overlap.
+CREATE UNIQUE INDEX idx_agent_schedule_run_running
+    ON agent_schedule_run(project_id, agent_type) WHERE status = 'running';
+CREATE INDEX idx_agent_schedule_run_project ON agent_schedule_run(project_id, agent_type, id);
@@ -0,0 +1,7 @@
+-- Summary rows record the id of the first row they do not cover in their own
+-- column instead of overloading turn_id.
+ALTER TABLE agent_chat_message ADD COLUMN covers_until_id INTEGER NULL;
+ALTER TABLE user_chat_message ADD COLUMN covers_until_id INTEGER NULL;
+
+UPDATE agent_chat_message SET covers_until_id = turn_id, turn_id = NULL WHERE role = 'summary';
+UPDATE user_chat_message SET covers_until_id = turn_id, turn_id = NULL WHERE content_type = 'summary';
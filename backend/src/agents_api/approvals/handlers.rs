@@ -0,0 +1,533 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+use nocodo_agents::{
+    task_policy, AgentError, AgentStorage, ChatMessage, CommentStorage, ProposalStatus,
+    ProposalStorage, SqliteAgentStorage, SqliteCommentStorage, SqliteProposalStorage,
+    SqliteTaskStorage, TaskStatus, TaskStorage,
+};
+
+use crate::agents_api::approvals::types::{
+    ApproveResponse, ProposalsQuery, ProposalsResponse, ReviewRequest, ReviewResponse,
+    TaskProposalsResponse,
+};
+use crate::agents_api::dispatcher::{run_workflow, DispatchEvent};
+use crate::agents_api::state::AgentState;
+
+/// GET /api/agents/proposals?project_id=X
+/// Every proposal of the project, newest first, pending or decided.
+#[get("/api/agents/proposals")]
+pub async fn list_proposals(
+    state: web::Data<AgentState>,
+    query: web::Query<ProposalsQuery>,
+) -> impl Responder {
+    let storage = match SqliteProposalStorage::open(&state.db_path) {
+        Ok(s) => s,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Storage error: {}", e) }));
+        }
+    };
+
+    match storage.list_proposals(query.project_id).await {
+        Ok(proposals) => HttpResponse::Ok().json(ProposalsResponse {
+            project_id: query.project_id,
+            proposals,
+        }),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(serde_json::json!({ "error": format!("Failed to list proposals: {}", e) })),
+    }
+}
+
+/// GET /api/tasks/{task_id}/proposals
+/// The task's proposals, newest first.
+#[get("/api/tasks/{task_id}/proposals")]
+pub async fn list_task_proposals(
+    state: web::Data<AgentState>,
+    path: web::Path<i64>,
+) -> impl Responder {
+    let task_id = path.into_inner();
+
+    let storage = match SqliteProposalStorage::open(&state.db_path) {
+        Ok(s) => s,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Storage error: {}", e) }));
+        }
+    };
+
+    match storage.list_task_proposals(task_id).await {
+        Ok(proposals) => HttpResponse::Ok().json(TaskProposalsResponse { task_id, proposals }),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(serde_json::json!({ "error": format!("Failed to list proposals: {}", e) })),
+    }
+}
+
+/// POST /api/tasks/{task_id}/approve
+/// Apply the task's pending proposal — save the schema or form layout, write
+/// the code — and close the task, which runs its workflow rules. The
+/// proposal is approved before it is applied, so it is applied once; when
+/// applying fails it goes back to pending.
+#[post("/api/tasks/{task_id}/approve")]
+pub async fn approve_task(state: web::Data<AgentState>, path: web::Path<i64>) -> impl Responder {
+    let task_id = path.into_inner();
+
+    let storage = match SqliteProposalStorage::open(&state.db_path) {
+        Ok(s) => s,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Storage error: {}", e) }));
+        }
+    };
+    let proposal = match storage.get_pending_proposal(task_id).await {
+        Ok(Some(p)) => p,
+        Ok(None) => {
+            return HttpResponse::Conflict().json(serde_json::json!({
+                "error": format!("Task {} has no proposal awaiting approval", task_id)
+            }));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Failed to load proposal: {}", e) }));
+        }
+    };
+    let Some(agent) = state.jobs.agents().get(&proposal.agent_type) else {
+        return HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("No handler for agent type {}", proposal.agent_type)
+        }));
+    };
+
+    // Deciding first makes concurrent approvals apply the proposal once.
+    match storage
+        .decide_proposal(proposal.id, ProposalStatus::Approved, None)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::Conflict().json(serde_json::json!({
+                "error": format!("Proposal {} was already decided", proposal.id)
+            }));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(
+                serde_json::json!({ "error": format!("Failed to approve proposal: {}", e) }),
+            );
+        }
+    }
+    let applied = match agent.apply(&proposal, &state.db_path).await {
+        Ok(applied) => applied,
+        Err(e) => {
+            if let Err(e) = storage.reopen_proposal(proposal.id).await {
+                log::error!("[Approvals] proposal={} reopen error: {}", proposal.id, e);
+            }
+            let body = serde_json::json!({ "error": format!("Failed to apply proposal: {}", e) });
+            return match e {
+                AgentError::Conflict(_) => HttpResponse::Conflict().json(body),
+                _ => HttpResponse::InternalServerError().json(body),
+            };
+        }
+    };
+    log::info!(
+        "[Approvals] task={} proposal={} approved: {}",
+        task_id,
+        proposal.id,
+        applied
+    );
+
+    add_session_message(
+        &state.db_path,
+        proposal.session_id,
+        &proposal.agent_type,
+        format!("Approved. {}", applied),
+    )
+    .await;
+    match SqliteTaskStorage::open(&state.db_path) {
+        Ok(tasks) => {
+            if let Err(e) = tasks.update_task_status(task_id, TaskStatus::Done).await {
+                log::error!("[Approvals] task={} status error: {}", task_id, e);
+            }
+        }
+        Err(e) => log::error!("[Approvals] task={} storage error: {}", task_id, e),
+    }
+    add_task_comment(&state.db_path, task_id, format!("Approved: {}", applied)).await;
+    if let Err(e) = run_workflow(&state.jobs, &state.db_path, task_id).await {
+        log::error!("[Approvals] task={} follow-up error: {}", task_id, e);
+    }
+    state.board_notify.notify_waiters();
+
+    HttpResponse::Ok().json(ApproveResponse {
+        task_id,
+        proposal_id: proposal.id,
+        applied,
+    })
+}
+
+/// POST /api/tasks/{task_id}/reject
+/// Discard the task's pending proposal and run its agent again, with the
+/// comment on its session, to propose something else.
+#[post("/api/tasks/{task_id}/reject")]
+pub async fn reject_task(
+    state: web::Data<AgentState>,
+    path: web::Path<i64>,
+    request: web::Json<ReviewRequest>,
+) -> impl Responder {
+    send_back(
+        &state,
+        path.into_inner(),
+        ProposalStatus::Rejected,
+        request.into_inner().comment,
+    )
+    .await
+}
+
+/// POST /api/tasks/{task_id}/request-changes
+/// Run the task's agent again, with the comment on its session, to revise
+/// its pending proposal.
+#[post("/api/tasks/{task_id}/request-changes")]
+pub async fn request_changes(
+    state: web::Data<AgentState>,
+    path: web::Path<i64>,
+    request: web::Json<ReviewRequest>,
+) -> impl Responder {
+    send_back(
+        &state,
+        path.into_inner(),
+        ProposalStatus::ChangesRequested,
+        request.into_inner().comment,
+    )
+    .await
+}
+
+/// Record the user's decision on the pending proposal, append the comment to
+/// the agent's session and queue the agent again.
+async fn send_back(
+    state: &AgentState,
+    task_id: i64,
+    decision: ProposalStatus,
+    comment: String,
+) -> HttpResponse {
+    let comment = comment.trim().to_string();
+    if comment.is_empty() {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({ "error": "comment must not be empty" }));
+    }
+
+    let storage = match SqliteProposalStorage::open(&state.db_path) {
+        Ok(s) => s,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Storage error: {}", e) }));
+        }
+    };
+    let proposal = match storage.get_pending_proposal(task_id).await {
+        Ok(Some(p)) => p,
+        Ok(None) => {
+            return HttpResponse::Conflict().json(serde_json::json!({
+                "error": format!("Task {} has no proposal awaiting approval", task_id)
+            }));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Failed to load proposal: {}", e) }));
+        }
+    };
+    let task_storage = match SqliteTaskStorage::open(&state.db_path) {
+        Ok(s) => s,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Storage error: {}", e) }));
+        }
+    };
+    let task = match task_storage.get_task(task_id).await {
+        Ok(Some(t)) => t,
+        Ok(None) => {
+            return HttpResponse::NotFound()
+                .json(serde_json::json!({ "error": format!("Task {} not found", task_id) }));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Failed to load task: {}", e) }));
+        }
+    };
+
+    match storage
+        .decide_proposal(proposal.id, decision, Some(&comment))
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::Conflict().json(serde_json::json!({
+                "error": format!("Proposal {} was already decided", proposal.id)
+            }));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Failed to update proposal: {}", e) }));
+        }
+    }
+
+    let (label, instruction) = match decision {
+        ProposalStatus::Rejected => ("Rejected", "Start over and propose something different."),
+        _ => ("Changes requested", "Revise your proposal accordingly."),
+    };
+    let feedback = ChatMessage {
+        role: "user".to_string(),
+        agent_type: None,
+        ..session_message(
+            proposal.session_id,
+            &proposal.agent_type,
+            format!("{}: {}\n\n{}", label, comment, instruction),
+        )
+    };
+    if let Err(e) = append_message(&state.db_path, feedback).await {
+        log::error!("[Approvals] task={} message error: {}", task_id, e);
+    }
+
+    let job_id = match state
+        .jobs
+        .enqueue(DispatchEvent {
+            task_id,
+            project_id: task.project_id,
+            assigned_to_agent: proposal.agent_type.clone(),
+            source_prompt: task.source_prompt,
+        })
+        .await
+    {
+        Ok(Some(id)) => id,
+        Ok(None) => {
+            return HttpResponse::Conflict().json(serde_json::json!({
+                "error": format!("Task {} is already queued or running", task_id)
+            }));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Failed to queue task: {}", e) }));
+        }
+    };
+    log::info!(
+        "[Approvals] task={} proposal={} {}; queued job={}",
+        task_id,
+        proposal.id,
+        decision.as_str(),
+        job_id
+    );
+
+    let status = task_policy::queued_state_for(&proposal.agent_type);
+    if let Err(e) = task_storage.update_task_status(task_id, status).await {
+        log::error!("[Approvals] task={} status error: {}", task_id, e);
+    }
+    add_task_comment(&state.db_path, task_id, format!("{}: {}", label, comment)).await;
+    state.board_notify.notify_waiters();
+
+    HttpResponse::Accepted().json(ReviewResponse {
+        task_id,
+        proposal_id: proposal.id,
+        job_id,
+    })
+}
+
+fn session_message(session_id: i64, agent_type: &str, content: String) -> ChatMessage {
+    ChatMessage {
+        id: None,
+        session_id,
+        role: "assistant".to_string(),
+        agent_type: Some(agent_type.to_string()),
+        content,
+        tool_call_id: None,
+        tool_name: None,
+        turn_id: None,
+        created_at: 0,
+    }
+}
+
+async fn append_message(db_path: &str, message: ChatMessage) -> Result<(), AgentError> {
+    SqliteAgentStorage::open(db_path)?
+        .create_message(message)
+        .await
+        .map(|_| ())
+}
+
+async fn add_session_message(db_path: &str, session_id: i64, agent_type: &str, content: String) {
+    let message = session_message(session_id, agent_type, content);
+    if let Err(e) = append_message(db_path, message).await {
+        log::error!("[Approvals] session={} message error: {}", session_id, e);
+    }
+}
+
+async fn add_task_comment(db_path: &str, task_id: i64, content: String) {
+    let added = match SqliteCommentStorage::open(db_path) {
+        Ok(comments) => comments
+            .add_task_comment(task_id, "user", None, None, content)
+            .await
+            .map(|_| ()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = added {
+        log::error!("[Approvals] task={} comment error: {}", task_id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, App};
+    use async_trait::async_trait;
+    use nocodo_agents::{AgentConfig, AgentError, Proposal};
+
+    use super::*;
+    use crate::agents_api::dispatcher::JobQueue;
+    use crate::agents_api::registry::{AgentRegistry, DispatchableAgent, Outcome, TaskRun};
+    use crate::config::DispatchConfig;
+
+    /// A db_engineer whose `apply` succeeds or fails as `applies` says.
+    struct StubAgent {
+        applies: bool,
+    }
+
+    #[async_trait]
+    impl DispatchableAgent for StubAgent {
+        type Output = ();
+
+        fn agent_type(&self) -> &'static str {
+            "db_engineer"
+        }
+
+        fn load_config(&self) -> Result<AgentConfig, AgentError> {
+            Err(AgentError::Config("not run".to_string()))
+        }
+
+        async fn run_for_task(
+            &self,
+            _run: &TaskRun<'_>,
+            _config: AgentConfig,
+        ) -> Result<(), AgentError> {
+            Ok(())
+        }
+
+        fn outcome(&self, _output: ()) -> Outcome {
+            Outcome::finished("done")
+        }
+
+        fn proposes(&self) -> bool {
+            true
+        }
+
+        async fn apply(&self, _proposal: &Proposal, _db_path: &str) -> Result<String, AgentError> {
+            if self.applies {
+                Ok("schema saved".to_string())
+            } else {
+                Err(AgentError::Other("disk full".to_string()))
+            }
+        }
+    }
+
+    /// Agent state on a migrated temp-file database with task 1 awaiting
+    /// approval of proposal 1. The caller removes the file.
+    async fn state(name: &str, applies: bool) -> (std::path::PathBuf, AgentState) {
+        let path = std::env::temp_dir().join(format!(
+            "nocodo-approvals-{}-{}.db",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let db_path = path.to_string_lossy().into_owned();
+        crate::db::run_startup_migrations(&db_path).expect("migrate");
+        rusqlite::Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO project (id, name, path, created_at) VALUES (1, 'P', '', 0);
+                 INSERT INTO task (id, project_id, title, description, source_prompt,
+                     assigned_to_agent, created_by_agent, status, created_at, updated_at)
+                 VALUES (1, 1, 'A', '', '', 'db_engineer', 'project_manager',
+                     'awaiting_approval', 0, 0);",
+            )
+            .expect("seed db");
+        let session = SqliteAgentStorage::open(&db_path)
+            .unwrap()
+            .create_task_session(1, 1, "db_engineer")
+            .await
+            .unwrap();
+        SqliteProposalStorage::open(&db_path)
+            .unwrap()
+            .create_proposal(1, 1, "db_engineer", session.id.unwrap(), "{}", "a schema")
+            .await
+            .unwrap();
+
+        let mut registry = AgentRegistry::default();
+        registry.register(StubAgent { applies });
+        let jobs = JobQueue::open(&db_path, DispatchConfig::default(), registry).unwrap();
+        (path, AgentState::for_tests(db_path, jobs))
+    }
+
+    async fn proposal_and_task(db_path: &str) -> (ProposalStatus, TaskStatus) {
+        let proposals = SqliteProposalStorage::open(db_path)
+            .unwrap()
+            .list_task_proposals(1)
+            .await
+            .unwrap();
+        let task = SqliteTaskStorage::open(db_path)
+            .unwrap()
+            .get_task(1)
+            .await
+            .unwrap()
+            .unwrap();
+        (proposals[0].status, task.status)
+    }
+
+    #[actix_web::test]
+    async fn test_approve_task_applies_the_proposal_once() {
+        let (path, state) = state("approve", true).await;
+        let db_path = state.db_path.clone();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .service(approve_task),
+        )
+        .await;
+
+        let approve = || {
+            test::TestRequest::post()
+                .uri("/api/tasks/1/approve")
+                .to_request()
+        };
+        let first = test::call_service(&app, approve()).await;
+        let second = test::call_service(&app, approve()).await;
+        let statuses = proposal_and_task(&db_path).await;
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(first.status(), actix_web::http::StatusCode::OK);
+        assert_eq!(second.status(), actix_web::http::StatusCode::CONFLICT);
+        assert_eq!(statuses, (ProposalStatus::Approved, TaskStatus::Done));
+    }
+
+    #[actix_web::test]
+    async fn test_approve_task_reopens_the_proposal_when_applying_fails() {
+        let (path, state) = state("approve-fails", false).await;
+        let db_path = state.db_path.clone();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .service(approve_task),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/tasks/1/approve")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        let statuses = proposal_and_task(&db_path).await;
+        let pending = SqliteProposalStorage::open(&db_path)
+            .unwrap()
+            .get_pending_proposal(1)
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(
+            resp.status(),
+            actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            statuses,
+            (ProposalStatus::Pending, TaskStatus::AwaitingApproval)
+        );
+        assert_eq!(pending.unwrap().decided_at, None);
+    }
+}
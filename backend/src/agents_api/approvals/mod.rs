@@ -0,0 +1,12 @@
+pub mod handlers;
+pub mod types;
+
+use actix_web::web;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(handlers::list_proposals)
+        .service(handlers::list_task_proposals)
+        .service(handlers::approve_task)
+        .service(handlers::reject_task)
+        .service(handlers::request_changes);
+}
@@ -0,0 +1,41 @@
+use nocodo_agents::Proposal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct ProposalsQuery {
+    pub project_id: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProposalsResponse {
+    pub project_id: i64,
+    pub proposals: Vec<Proposal>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaskProposalsResponse {
+    pub task_id: i64,
+    pub proposals: Vec<Proposal>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewRequest {
+    /// Why the proposal is rejected, or what to change.
+    pub comment: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApproveResponse {
+    pub task_id: i64,
+    pub proposal_id: i64,
+    /// What was applied, e.g. "Schema saved as version 4."
+    pub applied: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReviewResponse {
+    pub task_id: i64,
+    pub proposal_id: i64,
+    /// The run queued to revise the proposal.
+    pub job_id: i64,
+}
@@ -273,8 +273,7 @@ pub async fn index_reindex_handler(
         }
     };
 
-    let index_path = code_index_path(&state.db_path, req.project_id);
-    let mut idx = match CodeIndex::open(&index_path) {
+    let mut idx = match open_index(&state.db_path, req.project_id) {
         Ok(i) => i,
         Err(e) => {
             return HttpResponse::InternalServerError()
@@ -298,8 +297,7 @@ pub async fn index_list_structs_handler(
     request: web::Json<CodeIndexBuildRequest>,
 ) -> impl Responder {
     let req = request.into_inner();
-    let index_path = code_index_path(&state.db_path, req.project_id);
-    let idx = match CodeIndex::open(&index_path) {
+    let idx = match open_index(&state.db_path, req.project_id) {
         Ok(i) => i,
         Err(e) => {
             return HttpResponse::InternalServerError()
@@ -320,8 +318,7 @@ pub async fn index_list_free_fns_handler(
     request: web::Json<CodeIndexBuildRequest>,
 ) -> impl Responder {
     let req = request.into_inner();
-    let index_path = code_index_path(&state.db_path, req.project_id);
-    let idx = match CodeIndex::open(&index_path) {
+    let idx = match open_index(&state.db_path, req.project_id) {
         Ok(i) => i,
         Err(e) => {
             return HttpResponse::InternalServerError()
@@ -342,8 +339,7 @@ pub async fn index_list_impl_fns_handler(
     request: web::Json<CodeIndexListImplFnsRequest>,
 ) -> impl Responder {
     let req = request.into_inner();
-    let index_path = code_index_path(&state.db_path, req.project_id);
-    let idx = match CodeIndex::open(&index_path) {
+    let idx = match open_index(&state.db_path, req.project_id) {
         Ok(i) => i,
         Err(e) => {
             return HttpResponse::InternalServerError()
@@ -364,8 +360,7 @@ pub async fn index_get_struct_handler(
     request: web::Json<CodeIndexGetStructRequest>,
 ) -> impl Responder {
     let req = request.into_inner();
-    let index_path = code_index_path(&state.db_path, req.project_id);
-    let idx = match CodeIndex::open(&index_path) {
+    let idx = match open_index(&state.db_path, req.project_id) {
         Ok(i) => i,
         Err(e) => {
             return HttpResponse::InternalServerError()
@@ -389,8 +384,7 @@ pub async fn index_get_free_fn_handler(
     request: web::Json<CodeIndexGetFreeFnRequest>,
 ) -> impl Responder {
     let req = request.into_inner();
-    let index_path = code_index_path(&state.db_path, req.project_id);
-    let idx = match CodeIndex::open(&index_path) {
+    let idx = match open_index(&state.db_path, req.project_id) {
         Ok(i) => i,
         Err(e) => {
             return HttpResponse::InternalServerError()
@@ -414,8 +408,7 @@ pub async fn index_get_impl_fn_handler(
     request: web::Json<CodeIndexGetImplFnRequest>,
 ) -> impl Responder {
     let req = request.into_inner();
-    let index_path = code_index_path(&state.db_path, req.project_id);
-    let idx = match CodeIndex::open(&index_path) {
+    let idx = match open_index(&state.db_path, req.project_id) {
         Ok(i) => i,
         Err(e) => {
             return HttpResponse::InternalServerError()
@@ -437,6 +430,24 @@ pub async fn index_get_impl_fn_handler(
 // Helpers
 // ---------------------------------------------------------------------------
 
+/// Open the project's code index, rebuilding it when `CodeIndex::open` had
+/// to recreate it — a new index, or one written by an older version.
+fn open_index(db_path: &str, project_id: i64) -> Result<CodeIndex, String> {
+    let mut idx = CodeIndex::open(&code_index_path(db_path, project_id))?;
+    if idx.was_reset() {
+        if let Some(project_path) = resolve_project_path(db_path, project_id) {
+            let stats = idx.build(Path::new(&project_path))?;
+            log::info!(
+                "[CodeIndex] project={} rebuilt: {} structs, {} fns",
+                project_id,
+                stats.structs,
+                stats.free_fns + stats.impl_fns
+            );
+        }
+    }
+    Ok(idx)
+}
+
 fn resolve_project_path(db_path: &str, project_id: i64) -> Option<String> {
     get_project_path(db_path, project_id).ok().flatten()
 }
@@ -129,6 +129,7 @@ pub async fn gather_context(
             created_by_agent: "user".to_string(),
             created_at: 0,
             updated_at: 0,
+            created_by_task_id: None,
         })
         .await
     {
@@ -177,16 +178,20 @@ pub async fn gather_context(
             .json(serde_json::json!({ "error": format!("{}", e) }));
     }
 
-    // Fire the dispatch event so the background dispatcher picks it up.
-    state
-        .dispatch_tx
-        .send(crate::agents_api::dispatcher::DispatchEvent {
+    // Queue the job so the background dispatcher picks it up.
+    if let Err(e) = state
+        .jobs
+        .enqueue(crate::agents_api::dispatcher::DispatchEvent {
             task_id,
             project_id,
             assigned_to_agent: agent_type_str,
             source_prompt: project_path,
         })
-        .ok();
+        .await
+    {
+        return HttpResponse::InternalServerError()
+            .json(serde_json::json!({ "error": format!("{}", e) }));
+    }
     state.board_notify.notify_waiters();
 
     HttpResponse::Accepted().json(GatherContextQueued { task_id })
@@ -2,14 +2,16 @@ use crate::agents_api::db_engineer::types::{
     BoardQuery, BoardResponse, EpicItem, EpicListQuery, ListEpicsResponse, ListTasksQuery,
     ListTasksResponse, SchemaCodegenResponse, SchemaPreviewQuery, SchemaPreviewResponse, TaskItem,
 };
+use crate::agents_api::dispatcher::QueueStats;
 use crate::agents_api::state::AgentState;
 use actix_web::{get, web, HttpResponse, Responder};
 use nocodo_agents::{
-    AgentStorage, SchemaStorage, SqliteAgentStorage, SqliteSchemaStorage, SqliteTaskStorage,
-    TaskStorage,
+    AgentError, AgentStorage, SchemaStorage, SqliteAgentStorage, SqliteSchemaStorage,
+    SqliteTaskStorage, TaskStorage,
 };
 use rusqlite::params as sql_params;
 use shared_types::SchemaDef;
+use std::collections::HashMap;
 use std::time::Duration;
 
 const AGENT_TYPE: &str = "db_engineer";
@@ -29,23 +31,8 @@ pub async fn list_tasks(
         }
     };
 
-    match task_storage.list_tasks_for_project(query.project_id).await {
-        Ok(tasks) => HttpResponse::Ok().json(ListTasksResponse {
-            tasks: tasks
-                .into_iter()
-                .map(|t| TaskItem {
-                    id: t.id.unwrap_or(0),
-                    project_id: t.project_id,
-                    epic_id: t.epic_id,
-                    title: t.title,
-                    source_prompt: t.source_prompt,
-                    assigned_to_agent: t.assigned_to_agent,
-                    status: t.status.as_str().to_string(),
-                    created_at: t.created_at,
-                    updated_at: t.updated_at,
-                })
-                .collect(),
-        }),
+    match fetch_task_items(&task_storage, query.project_id).await {
+        Ok(tasks) => HttpResponse::Ok().json(ListTasksResponse { tasks }),
         Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
             "error": format!("Failed to list tasks: {}", e)
         })),
@@ -135,6 +122,7 @@ pub async fn get_board(
             epics,
             updated_at,
             project_name,
+            queue: fetch_queue_stats(&state, project_id).await,
         });
     }
 
@@ -154,6 +142,7 @@ pub async fn get_board(
                 epics,
                 updated_at,
                 project_name,
+                queue: fetch_queue_stats(&state, project_id).await,
             })
         }
         Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": e })),
@@ -174,27 +163,52 @@ fn fetch_project_name(db_path: &str, project_id: i64) -> String {
         .unwrap_or_default()
 }
 
-async fn fetch_board_data(
+async fn fetch_queue_stats(state: &AgentState, project_id: i64) -> QueueStats {
+    state.jobs.stats(project_id).await.unwrap_or_else(|e| {
+        log::warn!("[Board] queue stats error: {}", e);
+        QueueStats::default()
+    })
+}
+
+/// The project's tasks, each with the tasks it depends on.
+async fn fetch_task_items(
     storage: &SqliteTaskStorage,
     project_id: i64,
-) -> Result<(Vec<TaskItem>, Vec<EpicItem>), String> {
-    let tasks = storage
+) -> Result<Vec<TaskItem>, AgentError> {
+    let mut depends_on: HashMap<i64, Vec<i64>> = HashMap::new();
+    for (task_id, dep_id) in storage.list_dependency_edges(project_id).await? {
+        depends_on.entry(task_id).or_default().push(dep_id);
+    }
+    Ok(storage
         .list_tasks_for_project(project_id)
-        .await
-        .map_err(|e| e.to_string())?
+        .await?
         .into_iter()
-        .map(|t| TaskItem {
-            id: t.id.unwrap_or(0),
-            project_id: t.project_id,
-            epic_id: t.epic_id,
-            title: t.title,
-            source_prompt: t.source_prompt,
-            assigned_to_agent: t.assigned_to_agent,
-            status: t.status.as_str().to_string(),
-            created_at: t.created_at,
-            updated_at: t.updated_at,
+        .map(|t| {
+            let id = t.id.unwrap_or(0);
+            TaskItem {
+                id,
+                project_id: t.project_id,
+                epic_id: t.epic_id,
+                title: t.title,
+                source_prompt: t.source_prompt,
+                assigned_to_agent: t.assigned_to_agent,
+                status: t.status.as_str().to_string(),
+                created_by_task_id: t.created_by_task_id,
+                depends_on: depends_on.remove(&id).unwrap_or_default(),
+                created_at: t.created_at,
+                updated_at: t.updated_at,
+            }
         })
-        .collect();
+        .collect())
+}
+
+async fn fetch_board_data(
+    storage: &SqliteTaskStorage,
+    project_id: i64,
+) -> Result<(Vec<TaskItem>, Vec<EpicItem>), String> {
+    let tasks = fetch_task_items(storage, project_id)
+        .await
+        .map_err(|e| e.to_string())?;
 
     let epics = storage
         .list_epics(project_id)
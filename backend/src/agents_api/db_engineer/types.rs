@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 use shared_types::SchemaDef;
+
+use crate::agents_api::dispatcher::QueueStats;
 pub use shared_types::{EpicItem, ListEpicsResponse, ListTasksResponse, TaskItem};
 
 #[derive(Debug, Deserialize)]
@@ -41,4 +43,6 @@ pub struct BoardResponse {
     pub epics: Vec<EpicItem>,
     pub updated_at: i64,
     pub project_name: String,
+    /// Queue depth and running agents.
+    pub queue: QueueStats,
 }
@@ -0,0 +1,119 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+use nocodo_agents::{AgentError, SqliteTaskStorage, TaskStorage};
+
+use crate::agents_api::dependencies::types::{AddDependenciesRequest, DependenciesResponse};
+use crate::agents_api::state::AgentState;
+
+/// GET /api/tasks/{task_id}/dependencies
+/// The tasks this task waits for and the tasks waiting for it.
+#[get("/api/tasks/{task_id}/dependencies")]
+pub async fn list_dependencies(
+    state: web::Data<AgentState>,
+    path: web::Path<i64>,
+) -> impl Responder {
+    let task_id = path.into_inner();
+
+    let storage = match SqliteTaskStorage::open(&state.db_path) {
+        Ok(s) => s,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Storage error: {}", e) }));
+        }
+    };
+
+    match dependencies_of(&storage, task_id).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(serde_json::json!({ "error": format!("Failed to load dependencies: {}", e) })),
+    }
+}
+
+/// POST /api/tasks/{task_id}/dependencies
+/// Make the task wait for more tasks of its project. The dispatcher runs it
+/// once all of them are done. Rejected with 409, adding none, when a
+/// dependency would make the task wait for itself.
+#[post("/api/tasks/{task_id}/dependencies")]
+pub async fn add_dependencies(
+    state: web::Data<AgentState>,
+    path: web::Path<i64>,
+    request: web::Json<AddDependenciesRequest>,
+) -> impl Responder {
+    let task_id = path.into_inner();
+    let body = request.into_inner();
+
+    let storage = match SqliteTaskStorage::open(&state.db_path) {
+        Ok(s) => s,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Storage error: {}", e) }));
+        }
+    };
+
+    let task = match storage.get_task(task_id).await {
+        Ok(Some(t)) => t,
+        Ok(None) => {
+            return HttpResponse::NotFound()
+                .json(serde_json::json!({ "error": format!("Task {} not found", task_id) }));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Failed to load task: {}", e) }));
+        }
+    };
+    for &dep_id in &body.depends_on {
+        match storage.get_task(dep_id).await {
+            Ok(Some(dep)) if dep.project_id == task.project_id => {}
+            Ok(Some(_)) => {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": format!("Task {} belongs to another project", dep_id)
+                }));
+            }
+            Ok(None) => {
+                return HttpResponse::NotFound()
+                    .json(serde_json::json!({ "error": format!("Task {} not found", dep_id) }));
+            }
+            Err(e) => {
+                return HttpResponse::InternalServerError()
+                    .json(serde_json::json!({ "error": format!("Failed to load task: {}", e) }));
+            }
+        }
+    }
+
+    match storage
+        .add_task_dependencies(task_id, &body.depends_on)
+        .await
+    {
+        Ok(()) => {}
+        Err(e @ AgentError::DependencyCycle(_)) => {
+            return HttpResponse::Conflict().json(serde_json::json!({ "error": e.to_string() }));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(
+                serde_json::json!({ "error": format!("Failed to add dependencies: {}", e) }),
+            );
+        }
+    }
+    state.board_notify.notify_waiters();
+
+    match dependencies_of(&storage, task_id).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(serde_json::json!({ "error": format!("Failed to load dependencies: {}", e) })),
+    }
+}
+
+async fn dependencies_of(
+    storage: &SqliteTaskStorage,
+    task_id: i64,
+) -> Result<DependenciesResponse, AgentError> {
+    Ok(DependenciesResponse {
+        task_id,
+        depends_on: storage.list_task_dependencies(task_id).await?,
+        dependents: storage
+            .list_dependents(task_id)
+            .await?
+            .into_iter()
+            .filter_map(|t| t.id)
+            .collect(),
+    })
+}
@@ -0,0 +1,9 @@
+pub mod handlers;
+pub mod types;
+
+use actix_web::web;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(handlers::list_dependencies)
+        .service(handlers::add_dependencies);
+}
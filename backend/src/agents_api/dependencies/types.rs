@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct AddDependenciesRequest {
+    /// Tasks of the same project that must be done before this one runs.
+    pub depends_on: Vec<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DependenciesResponse {
+    pub task_id: i64,
+    /// Tasks this one waits for.
+    pub depends_on: Vec<i64>,
+    /// Tasks waiting for this one.
+    pub dependents: Vec<i64>,
+}
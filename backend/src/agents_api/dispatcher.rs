@@ -1,11 +1,33 @@
-use std::sync::Arc;
-use tokio::sync::{mpsc, Notify};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
 
 use nocodo_agents::{
-    build_backend_engineer, build_db_engineer, build_frontend_engineer, build_ui_designer,
-    AgentConfig, AgentResponse, AgentStorage, BackendEngineerResponse, ChatMessage,
-    FrontendEngineerResponse, SqliteAgentStorage, UiDesignerResponse,
+    run_with_fallback, task_policy, AgentError, AgentStorage, AgentType, ApprovalPolicy,
+    ChatMessage, CommentStorage, Job, JobStatus, JobStorage, ProposalStorage, RunControl,
+    SchemaStorage, SqliteAgentStorage, SqliteCommentStorage, SqliteJobStorage,
+    SqliteProposalStorage, SqliteSchemaStorage, SqliteTaskStorage, TaskStatus, TaskStorage,
+    Workflow,
 };
+use shared_types::SchemaDef;
+
+use crate::agents_api::registry::{AgentRegistry, Proposed, TaskRun};
+use crate::agents_api::rust_engineer::handlers::get_project_path;
+use crate::config::DispatchConfig;
+
+/// Lease a worker takes on a claimed job. Heartbeats renew it while the agent
+/// runs; if the process dies the job is claimable again once it lapses.
+const LEASE_SECS: i64 = 60;
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(20);
+/// How often an idle dispatcher looks for jobs it was not notified about:
+/// expired leases, or jobs queued by another process.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Backoff before retrying a transiently failed run: doubles per attempt
+/// from the base, up to the cap.
+const RETRY_BASE_SECS: i64 = 30;
+const RETRY_MAX_SECS: i64 = 600;
 
 // ---------------------------------------------------------------------------
 // DispatchEvent
@@ -19,454 +41,1683 @@ pub struct DispatchEvent {
     pub source_prompt: String,
 }
 
+impl From<&Job> for DispatchEvent {
+    fn from(job: &Job) -> Self {
+        Self {
+            task_id: job.task_id,
+            project_id: job.project_id,
+            assigned_to_agent: job.agent_type.clone(),
+            source_prompt: job.source_prompt.clone(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// JobQueue — handle used by HTTP handlers to queue agent runs
+// ---------------------------------------------------------------------------
+
+#[derive(Clone)]
+pub struct JobQueue {
+    jobs: Arc<SqliteJobStorage>,
+    notify: Arc<Notify>,
+    limits: Arc<DispatchConfig>,
+    running: Arc<Mutex<Running>>,
+    runs: Arc<Mutex<Runs>>,
+    agents: Arc<AgentRegistry>,
+    /// Agents whose results wait for a user's approval, unless the project
+    /// has its own list.
+    approval: Arc<ApprovalPolicy>,
+}
+
+/// Agent runs in progress in this process.
+#[derive(Debug, Default)]
+struct Running {
+    total: usize,
+    by_agent: HashMap<String, usize>,
+    by_provider: HashMap<String, usize>,
+}
+
+/// Controls of the runs in progress in this process, and cancels that reached
+/// a claimed job before its run was tracked.
+#[derive(Debug, Default)]
+struct Runs {
+    /// By task id.
+    controls: HashMap<i64, RunControl>,
+    /// Cancel reasons by job id, applied by `track_run`.
+    cancels: HashMap<i64, String>,
+}
+
+/// Queue depth and running agents, shown on the board.
+#[derive(Debug, Default, Serialize)]
+pub struct QueueStats {
+    /// Queued and running jobs of the project per agent type.
+    pub agents: Vec<AgentQueueStats>,
+    /// Runs in progress in this backend across all projects.
+    pub running: usize,
+    pub max_concurrent: usize,
+    pub providers: Vec<ProviderQueueStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AgentQueueStats {
+    pub agent_type: String,
+    pub queued: i64,
+    pub running: i64,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProviderQueueStats {
+    pub provider: String,
+    pub running: usize,
+    pub limit: Option<usize>,
+}
+
+impl JobQueue {
+    pub fn open(
+        db_path: &str,
+        limits: DispatchConfig,
+        agents: AgentRegistry,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            jobs: Arc::new(SqliteJobStorage::open(db_path)?),
+            notify: Arc::new(Notify::new()),
+            limits: Arc::new(limits),
+            running: Arc::new(Mutex::new(Running::default())),
+            runs: Arc::new(Mutex::new(Runs::default())),
+            agents: Arc::new(agents),
+            approval: Arc::new(ApprovalPolicy::default()),
+        })
+    }
+
+    /// Have the agents `approval` lists propose their results for approval.
+    /// Listed agents that cannot propose are logged and apply their results
+    /// as before.
+    pub fn with_approval(mut self, approval: ApprovalPolicy) -> Self {
+        for agent_type in &approval.agents {
+            if !self.agents.proposes(agent_type) {
+                log::warn!(
+                    "[Dispatcher] approval: {} cannot propose its results; they are applied without approval",
+                    agent_type
+                );
+            }
+        }
+        self.approval = Arc::new(approval);
+        self
+    }
+
+    pub fn agents(&self) -> &AgentRegistry {
+        &self.agents
+    }
+
+    /// Persist a run of the event's task and wake the dispatcher. Returns
+    /// `None` when the task is already queued or running.
+    pub async fn enqueue(&self, event: DispatchEvent) -> Result<Option<i64>, AgentError> {
+        let priority = self
+            .limits
+            .priorities
+            .get(&event.assigned_to_agent)
+            .copied()
+            .unwrap_or(0);
+        let job_id = self
+            .jobs
+            .enqueue_job(
+                event.task_id,
+                event.project_id,
+                &event.assigned_to_agent,
+                &event.source_prompt,
+                priority,
+            )
+            .await?;
+        match job_id {
+            Some(id) => log::info!(
+                "[Dispatcher] Queued job={} task_id={} agent={} priority={}",
+                id,
+                event.task_id,
+                event.assigned_to_agent,
+                priority
+            ),
+            None => log::info!(
+                "[Dispatcher] task_id={} already queued — skipped",
+                event.task_id
+            ),
+        }
+        self.notify.notify_one();
+        Ok(job_id)
+    }
+
+    /// Stop the task's run: a running one is cancelled at its next turn or
+    /// tool call, a queued one is failed before it starts.
+    pub async fn cancel(&self, task_id: i64, reason: &str) -> Result<Cancelled, AgentError> {
+        if let Some(job) = self
+            .jobs
+            .cancel_queued_job(task_id, &format!("cancelled: {}", reason))
+            .await?
+        {
+            return Ok(Cancelled::Queued(job));
+        }
+        let Some(job) = self.jobs.get_running_job(task_id).await? else {
+            return Ok(Cancelled::NotRunning);
+        };
+        let mut runs = self.runs.lock().unwrap();
+        match runs.controls.get(&task_id) {
+            Some(control) => control.cancel(reason),
+            // Claimed but not started yet: the run is cancelled as it starts.
+            None => {
+                runs.cancels.insert(job.id, reason.to_string());
+            }
+        }
+        Ok(Cancelled::Running)
+    }
+
+    pub async fn stats(&self, project_id: i64) -> Result<QueueStats, AgentError> {
+        let counts = self.jobs.count_open_jobs(project_id).await?;
+        let mut agents: Vec<AgentQueueStats> = Vec::new();
+        for count in counts {
+            let idx = match agents.iter().position(|a| a.agent_type == count.agent_type) {
+                Some(idx) => idx,
+                None => {
+                    agents.push(AgentQueueStats {
+                        limit: self.limits.agent_limits.get(&count.agent_type).copied(),
+                        agent_type: count.agent_type,
+                        queued: 0,
+                        running: 0,
+                    });
+                    agents.len() - 1
+                }
+            };
+            match count.status {
+                JobStatus::Running => agents[idx].running += count.count,
+                _ => agents[idx].queued += count.count,
+            }
+        }
+
+        let running = self.running.lock().unwrap();
+        let mut providers: Vec<ProviderQueueStats> = running
+            .by_provider
+            .iter()
+            .map(|(provider, n)| ProviderQueueStats {
+                provider: provider.clone(),
+                running: *n,
+                limit: self.limits.provider_limit(provider),
+            })
+            .collect();
+        providers.sort_by(|a, b| a.provider.cmp(&b.provider));
+        Ok(QueueStats {
+            agents,
+            running: running.total,
+            max_concurrent: self.limits.max_concurrent,
+            providers,
+        })
+    }
+
+    /// Agent types that are at their own limit or at the limit of a provider
+    /// their run may call, or `None` when no run may start at all.
+    fn saturated_agents(&self) -> Option<Vec<String>> {
+        let running = self.running.lock().unwrap();
+        if running.total >= self.limits.max_concurrent {
+            return None;
+        }
+        let saturated = self
+            .agents
+            .agent_types()
+            .filter(|agent_type| {
+                self.at_limit(&running, agent_type, self.agents.providers(agent_type))
+            })
+            .map(|agent_type| agent_type.to_string())
+            .collect();
+        Some(saturated)
+    }
+
+    /// Whether a run of `agent_type` that may call `providers` would go over
+    /// the agent's own limit or a provider's.
+    fn at_limit(&self, running: &Running, agent_type: &str, providers: &[String]) -> bool {
+        let at_agent_limit = self
+            .limits
+            .agent_limits
+            .get(agent_type)
+            .is_some_and(|limit| running.by_agent.get(agent_type).copied().unwrap_or(0) >= *limit);
+        let at_provider_limit = providers.iter().any(|provider| {
+            self.limits.provider_limit(provider).is_some_and(|limit| {
+                running.by_provider.get(provider).copied().unwrap_or(0) >= limit
+            })
+        });
+        at_agent_limit || at_provider_limit
+    }
+
+    /// Count a run against the limits until the returned slot is dropped.
+    /// The run may fall back to any model of its chain, so it counts against
+    /// every provider it may call.
+    fn take_slot(&self, agent_type: &str) -> Slot {
+        let providers = self.agents.providers(agent_type).to_vec();
+        let mut running = self.running.lock().unwrap();
+        self.count_run(&mut running, agent_type, providers)
+    }
+
+    /// Like `take_slot` for a run that does not come from a job, such as a
+    /// scheduled stack review, but only when the run fits within the limits.
+    pub(crate) fn try_slot(&self, agent_type: &str, providers: Vec<String>) -> Option<Slot> {
+        let mut running = self.running.lock().unwrap();
+        if running.total >= self.limits.max_concurrent
+            || self.at_limit(&running, agent_type, &providers)
+        {
+            return None;
+        }
+        Some(self.count_run(&mut running, agent_type, providers))
+    }
+
+    /// Wait until `try_slot` gets a slot. Polls rather than waiting on
+    /// `notify`, which wakes the dispatcher.
+    pub(crate) async fn wait_for_slot(&self, agent_type: &str, providers: Vec<String>) -> Slot {
+        loop {
+            if let Some(slot) = self.try_slot(agent_type, providers.clone()) {
+                return slot;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    fn count_run(&self, running: &mut Running, agent_type: &str, providers: Vec<String>) -> Slot {
+        running.total += 1;
+        *running.by_agent.entry(agent_type.to_string()).or_default() += 1;
+        for provider in &providers {
+            *running.by_provider.entry(provider.clone()).or_default() += 1;
+        }
+        Slot {
+            queue: self.clone(),
+            agent_type: agent_type.to_string(),
+            providers,
+        }
+    }
+
+    /// Register the run's control so `cancel` can reach it until the
+    /// returned guard is dropped. A cancel that came in after the job was
+    /// claimed stops the run before its first turn.
+    fn track_run(&self, job: &Job) -> TrackedRun {
+        let control = RunControl::new(
+            Some(Duration::from_secs(self.limits.run_timeout_secs)),
+            Some(self.limits.max_turns),
+        );
+        let mut runs = self.runs.lock().unwrap();
+        if let Some(reason) = runs.cancels.remove(&job.id) {
+            control.cancel(reason);
+        }
+        runs.controls.insert(job.task_id, control.clone());
+        TrackedRun {
+            queue: self.clone(),
+            task_id: job.task_id,
+            control,
+        }
+    }
+}
+
+/// What `JobQueue::cancel` stopped.
+#[derive(Debug)]
+pub enum Cancelled {
+    /// The run was signalled; the dispatcher records the stop when it returns.
+    Running,
+    /// The job had not started and is now failed.
+    Queued(Job),
+    NotRunning,
+}
+
+/// A run registered with `JobQueue::track_run`.
+struct TrackedRun {
+    queue: JobQueue,
+    task_id: i64,
+    control: RunControl,
+}
+
+impl Drop for TrackedRun {
+    fn drop(&mut self) {
+        self.queue
+            .runs
+            .lock()
+            .unwrap()
+            .controls
+            .remove(&self.task_id);
+    }
+}
+
+/// A run counted in `Running`; releasing it wakes the dispatcher to claim
+/// the next job.
+pub(crate) struct Slot {
+    queue: JobQueue,
+    agent_type: String,
+    providers: Vec<String>,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let mut running = self.queue.running.lock().unwrap();
+        running.total -= 1;
+        if let Some(n) = running.by_agent.get_mut(&self.agent_type) {
+            *n -= 1;
+        }
+        for provider in &self.providers {
+            if let Some(n) = running.by_provider.get_mut(provider) {
+                *n -= 1;
+            }
+        }
+        self.queue.notify.notify_one();
+    }
+}
+
 // ---------------------------------------------------------------------------
-// AgentDispatcher — background task that receives events and spawns agents
+// AgentDispatcher — background task that claims jobs and spawns agents
 // ---------------------------------------------------------------------------
 
 pub struct AgentDispatcher {
-    rx: mpsc::UnboundedReceiver<DispatchEvent>,
+    queue: JobQueue,
+    worker: String,
     db_path: String,
     board_notify: Arc<Notify>,
 }
 
 impl AgentDispatcher {
-    pub fn new(
-        rx: mpsc::UnboundedReceiver<DispatchEvent>,
-        db_path: String,
-        board_notify: Arc<Notify>,
-    ) -> Self {
+    pub fn new(queue: JobQueue, db_path: String, board_notify: Arc<Notify>) -> Self {
         Self {
-            rx,
+            queue,
+            worker: format!("backend-{}", std::process::id()),
             db_path,
             board_notify,
         }
     }
 
-    pub async fn run(mut self) {
-        log::info!("[Dispatcher] Started");
-        while let Some(event) = self.rx.recv().await {
-            log::info!(
-                "[Dispatcher] Received task_id={} agent={}",
-                event.task_id,
-                event.assigned_to_agent
-            );
-            let db_path = self.db_path.clone();
-            let notify = self.board_notify.clone();
-            tokio::spawn(async move {
-                dispatch_task(event, db_path).await;
-                notify.notify_waiters();
-            });
+    pub async fn run(self) {
+        log::info!(
+            "[Dispatcher] Started as worker {} (max {} concurrent)",
+            self.worker,
+            self.queue.limits.max_concurrent
+        );
+        loop {
+            // Claim until the queue is empty or every remaining job is held
+            // back by a limit; a finished run wakes us for the next one.
+            while let Some(skip) = self.queue.saturated_agents() {
+                match self
+                    .queue
+                    .jobs
+                    .claim_job(
+                        &self.worker,
+                        LEASE_SECS,
+                        self.queue.limits.max_attempts,
+                        &skip,
+                    )
+                    .await
+                {
+                    Ok(Some(job)) => self.spawn(job),
+                    Ok(None) => break,
+                    Err(e) => {
+                        log::error!("[Dispatcher] claim error: {}", e);
+                        break;
+                    }
+                }
+            }
+            tokio::select! {
+                _ = self.queue.notify.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
         }
-        log::warn!("[Dispatcher] Channel closed — exiting");
     }
-}
-
-// ---------------------------------------------------------------------------
-// Per-task dispatch
-// ---------------------------------------------------------------------------
 
-async fn dispatch_task(event: DispatchEvent, db_path: String) {
-    match event.assigned_to_agent.as_str() {
-        "db_engineer" => dispatch_db_engineer(event, &db_path).await,
-        "ui_designer" => dispatch_ui_designer(event, &db_path).await,
-        "backend_engineer" => dispatch_backend_engineer(event, &db_path).await,
-        "frontend_engineer" => dispatch_frontend_engineer(event, &db_path).await,
-        other => log::warn!("[Dispatcher] No handler for agent type: {}", other),
+    /// Run a claimed job, heartbeating its lease until the agent returns.
+    fn spawn(&self, job: Job) {
+        log::info!(
+            "[Dispatcher] Claimed job={} task_id={} agent={} attempt={}",
+            job.id,
+            job.task_id,
+            job.agent_type,
+            job.attempts
+        );
+        let slot = self.queue.take_slot(&job.agent_type);
+        let run = self.queue.track_run(&job);
+        let queue = self.queue.clone();
+        let worker = self.worker.clone();
+        let db_path = self.db_path.clone();
+        let notify = self.board_notify.clone();
+        notify.notify_waiters();
+        tokio::spawn(async move {
+            let heartbeat = tokio::spawn(heartbeat(queue.jobs.clone(), job.id, worker.clone()));
+            let result = run_task(&queue, DispatchEvent::from(&job), &db_path, &run.control).await;
+            heartbeat.abort();
+            drop(run);
+            match result {
+                Ok(()) => {
+                    if let Err(e) = queue.jobs.finish_job(job.id, &worker, None).await {
+                        log::error!("[Dispatcher] job={} finish error: {}", job.id, e);
+                    }
+                    if job.agent_type == AgentType::EngineeringManager.as_str() {
+                        if let Err(e) = queue_shaped_work(&queue, &db_path, job.task_id).await {
+                            log::error!("[Dispatcher] task={} follow-up error: {}", job.task_id, e);
+                        }
+                    }
+                }
+                Err(failure) => handle_failure(&queue, &job, &worker, &db_path, failure).await,
+            }
+            if let Err(e) = block_dependents(&db_path, job.task_id).await {
+                log::error!("[Dispatcher] task={} dependents error: {}", job.task_id, e);
+            }
+            drop(slot);
+            notify.notify_waiters();
+        });
     }
 }
 
-async fn dispatch_db_engineer(event: DispatchEvent, db_path: &str) {
-    let task_id = event.task_id;
-
-    let agent_storage = match SqliteAgentStorage::open(db_path) {
-        Ok(s) => s,
-        Err(e) => {
-            log::error!(
-                "[Dispatcher] db_engineer task={} storage error: {}",
-                task_id,
-                e
-            );
-            return;
-        }
+/// Queue what the Engineering Manager shaped the task into: the task itself
+/// once it is Ready, or the Ready tasks it was split into.
+async fn queue_shaped_work(
+    queue: &JobQueue,
+    db_path: &str,
+    task_id: i64,
+) -> Result<(), AgentError> {
+    let tasks = SqliteTaskStorage::open(db_path)?;
+    let Some(task) = tasks.get_task(task_id).await? else {
+        return Ok(());
     };
+    let mut ready = Vec::new();
+    match task.status {
+        TaskStatus::Ready => ready.push(task),
+        TaskStatus::Done => ready.extend(
+            tasks
+                .list_tasks_created_by(task_id)
+                .await?
+                .into_iter()
+                .filter(|t| t.status == TaskStatus::Ready),
+        ),
+        _ => {}
+    }
+    for task in ready {
+        queue
+            .enqueue(DispatchEvent {
+                task_id: task.id.unwrap_or(0),
+                project_id: task.project_id,
+                assigned_to_agent: task.assigned_to_agent,
+                source_prompt: task.source_prompt,
+            })
+            .await?;
+    }
+    Ok(())
+}
 
-    let session = match agent_storage
-        .create_task_session(event.project_id, task_id, "db_engineer")
-        .await
+/// Once the task is done, create and queue the tasks the project's workflow
+/// rules call for, and list them in a comment on the task. Called where a
+/// task reaches Done: at the end of its run and on approval.
+pub(crate) async fn run_workflow(
+    queue: &JobQueue,
+    db_path: &str,
+    task_id: i64,
+) -> Result<(), AgentError> {
+    let tasks = SqliteTaskStorage::open(db_path)?;
+    let Some(task) = tasks.get_task(task_id).await? else {
+        return Ok(());
+    };
+    if task.status != TaskStatus::Done {
+        return Ok(());
+    }
+    let workflow = match get_project_path(db_path, task.project_id).map_err(AgentError::Other)? {
+        Some(path) => Workflow::for_project(path)?,
+        None => Workflow::builtin(),
+    };
+    if !workflow.fires_on(&task.assigned_to_agent) {
+        return Ok(());
+    }
+    let schema = match SqliteSchemaStorage::open(db_path)?
+        .get_latest_schema_for_project(task.project_id)
+        .await?
     {
-        Ok(s) => s,
-        Err(e) => {
-            log::error!(
-                "[Dispatcher] db_engineer task={} session error: {}",
-                task_id,
-                e
-            );
-            return;
-        }
+        Some(json) => Some(serde_json::from_str::<SchemaDef>(&json)?),
+        None => None,
     };
-    let session_id = session.id.unwrap_or(0);
 
-    if let Err(e) = agent_storage
-        .create_message(ChatMessage {
-            id: None,
-            session_id,
-            role: "user".to_string(),
-            agent_type: None,
-            content: event.source_prompt.clone(),
-            tool_call_id: None,
-            tool_name: None,
-            turn_id: None,
-            created_at: 0,
-        })
-        .await
-    {
-        log::error!(
-            "[Dispatcher] db_engineer task={} message error: {}",
+    let follow_ups = workflow
+        .create_follow_ups(&task, schema.as_ref(), &tasks)
+        .await?;
+    if follow_ups.is_empty() {
+        return Ok(());
+    }
+    let mut comment = String::from("Workflow created follow-up tasks:");
+    for follow_up in follow_ups {
+        let created = follow_up.task;
+        let created_id = created.id.unwrap_or(0);
+        log::info!(
+            "[Dispatcher] task={} workflow rule {} created task={} for {}",
             task_id,
-            e
+            follow_up.rule,
+            created_id,
+            created.assigned_to_agent
         );
-        return;
+        comment.push_str(&format!(
+            "\n- #{} {} ({}, rule `{}`)",
+            created_id, created.title, created.assigned_to_agent, follow_up.rule
+        ));
+        queue
+            .enqueue(DispatchEvent {
+                task_id: created_id,
+                project_id: created.project_id,
+                assigned_to_agent: created.assigned_to_agent,
+                source_prompt: created.source_prompt,
+            })
+            .await?;
     }
+    SqliteCommentStorage::open(db_path)?
+        .add_task_comment(task_id, "agent", None, None, comment)
+        .await?;
+    Ok(())
+}
 
-    let config = match AgentConfig::load_db_engineer() {
-        Ok(c) => c,
-        Err(e) => {
-            log::error!(
-                "[Dispatcher] db_engineer task={} config error: {}",
-                task_id,
-                e
-            );
-            return;
+/// Record why the run failed as a task comment, then queue it again after a
+/// backoff or — once out of attempts, or when retrying cannot help — fail the
+/// job and block the task.
+async fn handle_failure(
+    queue: &JobQueue,
+    job: &Job,
+    worker: &str,
+    db_path: &str,
+    failure: RunFailure,
+) {
+    if let Some(reason) = &failure.cancelled {
+        log::warn!(
+            "[Dispatcher] {} task={} stopped: {}",
+            job.agent_type,
+            job.task_id,
+            reason
+        );
+        if let Err(e) = queue
+            .jobs
+            .finish_job(job.id, worker, Some(&failure.message))
+            .await
+        {
+            log::error!("[Dispatcher] job={} update error: {}", job.id, e);
         }
+        record_stop(db_path, job.task_id, &job.agent_type, reason).await;
+        return;
+    }
+
+    let max_attempts = queue.limits.max_attempts;
+    let retry = failure.transient && job.attempts < max_attempts;
+    let delay = backoff_secs(job.attempts);
+    log::error!(
+        "[Dispatcher] {} task={} attempt {}/{} failed{}: {}",
+        job.agent_type,
+        job.task_id,
+        job.attempts,
+        max_attempts,
+        if retry { ", retrying" } else { "" },
+        failure.message
+    );
+
+    let (job_update, status, next) = if retry {
+        (
+            queue
+                .jobs
+                .retry_job(job.id, worker, &failure.message, delay)
+                .await,
+            task_policy::queued_state_for(&job.agent_type),
+            format!("Retrying in {}s.", delay),
+        )
+    } else {
+        (
+            queue
+                .jobs
+                .finish_job(job.id, worker, Some(&failure.message))
+                .await,
+            TaskStatus::Blocked,
+            "Moved to Blocked; re-queue the task once the cause is fixed.".to_string(),
+        )
     };
+    if let Err(e) = job_update {
+        log::error!("[Dispatcher] job={} update error: {}", job.id, e);
+    }
 
-    let agent = match build_db_engineer(&config, db_path, event.project_id) {
-        Ok(a) => a,
-        Err(e) => {
-            log::error!(
-                "[Dispatcher] db_engineer task={} build error: {}",
-                task_id,
-                e
-            );
-            return;
-        }
+    let status_update = match SqliteTaskStorage::open(db_path) {
+        Ok(tasks) => tasks.update_task_status(job.task_id, status).await,
+        Err(e) => Err(e),
     };
+    if let Err(e) = status_update {
+        log::error!("[Dispatcher] task={} status error: {}", job.task_id, e);
+    }
 
-    match agent.chat_with_session(session_id, task_id, false).await {
-        Ok(AgentResponse::SchemaGenerated { text, .. }) => {
-            log::info!(
-                "[Dispatcher] db_engineer task={} schema generated: {}…",
-                task_id,
-                text.chars().take(80).collect::<String>()
-            );
-        }
-        Ok(AgentResponse::Text(text)) => {
-            log::info!(
-                "[Dispatcher] db_engineer task={} text: {}…",
-                task_id,
-                text.chars().take(80).collect::<String>()
-            );
-        }
-        Ok(AgentResponse::Question(q)) => {
-            log::info!(
-                "[Dispatcher] db_engineer task={} asked question: {}…",
-                task_id,
-                q.chars().take(80).collect::<String>()
-            );
-        }
-        Ok(AgentResponse::Stopped(reason)) => {
-            log::warn!(
-                "[Dispatcher] db_engineer task={} stopped: {}",
-                task_id,
-                reason
-            );
-        }
-        Err(e) => {
-            log::error!("[Dispatcher] db_engineer task={} error: {}", task_id, e);
-        }
+    let comment = format!(
+        "Run failed (attempt {} of {}): {}\n\n{}",
+        job.attempts, max_attempts, failure.message, next
+    );
+    let comment_added = match SqliteCommentStorage::open(db_path) {
+        Ok(comments) => comments
+            .add_task_comment(
+                job.task_id,
+                "agent",
+                None,
+                Some(AgentType::from_str(&job.agent_type)),
+                comment,
+            )
+            .await
+            .map(|_| ()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = comment_added {
+        log::error!("[Dispatcher] task={} comment error: {}", job.task_id, e);
     }
 }
 
-async fn dispatch_ui_designer(event: DispatchEvent, db_path: &str) {
-    let task_id = event.task_id;
-
-    let agent_storage = match SqliteAgentStorage::open(db_path) {
-        Ok(s) => s,
-        Err(e) => {
-            log::error!(
-                "[Dispatcher] ui_designer task={} storage error: {}",
-                task_id,
-                e
-            );
-            return;
-        }
+/// Move a stopped run's task to Blocked and explain the stop on its session,
+/// when it has one, and as a task comment.
+pub(crate) async fn record_stop(db_path: &str, task_id: i64, agent_type: &str, reason: &str) {
+    let status_update = match SqliteTaskStorage::open(db_path) {
+        Ok(tasks) => tasks.update_task_status(task_id, TaskStatus::Blocked).await,
+        Err(e) => Err(e),
     };
+    if let Err(e) = status_update {
+        log::error!("[Dispatcher] task={} status error: {}", task_id, e);
+    }
 
-    // The HTTP handler creates the session and stores the first message before
-    // sending the dispatch event. For startup reconciliation (no session yet),
-    // create session + store source_prompt here.
-    let session_id = match agent_storage
-        .get_session_by_task(task_id, "ui_designer")
-        .await
-    {
-        Ok(Some(s)) => s.id.unwrap_or(0),
-        Ok(None) => {
-            let session = match agent_storage
-                .create_task_session(event.project_id, task_id, "ui_designer")
-                .await
-            {
-                Ok(s) => s,
-                Err(e) => {
-                    log::error!(
-                        "[Dispatcher] ui_designer task={} session error: {}",
-                        task_id,
-                        e
-                    );
-                    return;
-                }
-            };
-            let sid = session.id.unwrap_or(0);
-            if let Err(e) = agent_storage
+    let message_added = match SqliteAgentStorage::open(db_path) {
+        Ok(agent_storage) => match agent_storage.get_session_by_task(task_id, agent_type).await {
+            Ok(Some(session)) => agent_storage
                 .create_message(ChatMessage {
                     id: None,
-                    session_id: sid,
-                    role: "user".to_string(),
-                    agent_type: None,
-                    content: event.source_prompt.clone(),
+                    session_id: session.id.unwrap_or(0),
+                    role: "assistant".to_string(),
+                    agent_type: Some(agent_type.to_string()),
+                    content: format!(
+                        "Stopped: {}. The task is Blocked until it is re-queued.",
+                        reason
+                    ),
                     tool_call_id: None,
                     tool_name: None,
                     turn_id: None,
                     created_at: 0,
                 })
                 .await
-            {
-                log::error!(
-                    "[Dispatcher] ui_designer task={} message error: {}",
-                    task_id,
-                    e
-                );
-                return;
-            }
-            sid
-        }
-        Err(e) => {
-            log::error!(
-                "[Dispatcher] ui_designer task={} session lookup error: {}",
-                task_id,
-                e
-            );
-            return;
-        }
+                .map(|_| ()),
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
     };
+    if let Err(e) = message_added {
+        log::error!("[Dispatcher] task={} message error: {}", task_id, e);
+    }
 
-    let config = match AgentConfig::load_ui_designer() {
-        Ok(c) => c,
-        Err(e) => {
-            log::error!(
-                "[Dispatcher] ui_designer task={} config error: {}",
+    let comment = format!(
+        "Run stopped: {}.\n\nMoved to Blocked; re-queue the task to run it again.",
+        reason
+    );
+    let comment_added = match SqliteCommentStorage::open(db_path) {
+        Ok(comments) => comments
+            .add_task_comment(
                 task_id,
-                e
-            );
-            return;
-        }
+                "agent",
+                None,
+                Some(AgentType::from_str(agent_type)),
+                comment,
+            )
+            .await
+            .map(|_| ()),
+        Err(e) => Err(e),
     };
+    if let Err(e) = comment_added {
+        log::error!("[Dispatcher] task={} comment error: {}", task_id, e);
+    }
+    if let Err(e) = block_dependents(db_path, task_id).await {
+        log::error!("[Dispatcher] task={} dependents error: {}", task_id, e);
+    }
+}
 
-    let agent = match build_ui_designer(&config, db_path, event.project_id) {
-        Ok(a) => a,
-        Err(e) => {
-            log::error!(
-                "[Dispatcher] ui_designer task={} build error: {}",
-                task_id,
-                e
-            );
-            return;
-        }
+/// Once the task is Blocked, block the queued tasks waiting for it —
+/// directly or through other tasks — and say why on each. Their jobs stay
+/// queued and run once the task is re-queued and done.
+pub(crate) async fn block_dependents(db_path: &str, task_id: i64) -> Result<(), AgentError> {
+    let tasks = SqliteTaskStorage::open(db_path)?;
+    let Some(task) = tasks.get_task(task_id).await? else {
+        return Ok(());
     };
-
-    match agent.run_for_task(session_id, task_id).await {
-        Ok(UiDesignerResponse::FormGenerated(form)) => {
+    if task.status != TaskStatus::Blocked {
+        return Ok(());
+    }
+    let comments = SqliteCommentStorage::open(db_path)?;
+    let mut pending = vec![task];
+    while let Some(blocked) = pending.pop() {
+        let blocked_id = blocked.id.unwrap_or(0);
+        for dependent in tasks.list_dependents(blocked_id).await? {
+            if !matches!(
+                dependent.status,
+                TaskStatus::Ready | TaskStatus::NeedsTechnicalShaping
+            ) {
+                continue;
+            }
+            let dependent_id = dependent.id.unwrap_or(0);
             log::info!(
-                "[Dispatcher] ui_designer task={} form generated for entity '{}'",
-                task_id,
-                form.entity
+                "[Dispatcher] task={} blocked by dependency task={}",
+                dependent_id,
+                blocked_id
             );
+            tasks
+                .update_task_status(dependent_id, TaskStatus::Blocked)
+                .await?;
+            comments
+                .add_task_comment(
+                    dependent_id,
+                    "agent",
+                    None,
+                    None,
+                    format!(
+                        "Blocked: depends on #{} ({}), which is blocked. It runs once #{} is done.",
+                        blocked_id, blocked.title, blocked_id
+                    ),
+                )
+                .await?;
+            pending.push(dependent);
         }
-        Ok(UiDesignerResponse::Stopped(reason)) => {
-            log::warn!(
-                "[Dispatcher] ui_designer task={} stopped: {}",
-                task_id,
-                reason
-            );
-        }
-        Err(e) => {
-            log::error!("[Dispatcher] ui_designer task={} error: {}", task_id, e);
+    }
+    Ok(())
+}
+
+fn backoff_secs(attempt: i64) -> i64 {
+    let exp = attempt.clamp(1, 16) - 1;
+    (RETRY_BASE_SECS << exp).min(RETRY_MAX_SECS)
+}
+
+async fn heartbeat(jobs: Arc<SqliteJobStorage>, job_id: i64, worker: String) {
+    let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match jobs.heartbeat_job(job_id, &worker, LEASE_SECS).await {
+            Ok(true) => {}
+            Ok(false) => {
+                log::warn!("[Dispatcher] job={} lease lost", job_id);
+                return;
+            }
+            Err(e) => log::error!("[Dispatcher] job={} heartbeat error: {}", job_id, e),
         }
     }
 }
 
-async fn dispatch_backend_engineer(event: DispatchEvent, db_path: &str) {
-    dispatch_engineer_agent(event, db_path, "backend_engineer").await;
+// ---------------------------------------------------------------------------
+// Per-task dispatch
+// ---------------------------------------------------------------------------
+
+/// Why a run failed. Transient failures are retried with backoff; the rest
+/// block the task straight away.
+#[derive(Debug)]
+struct RunFailure {
+    message: String,
+    transient: bool,
+    /// Set when the run was cancelled or ran out of budget, with the reason.
+    cancelled: Option<String>,
 }
 
-async fn dispatch_frontend_engineer(event: DispatchEvent, db_path: &str) {
-    dispatch_engineer_agent(event, db_path, "frontend_engineer").await;
+impl RunFailure {
+    /// `stage` names the step that failed, e.g. "session".
+    fn new(stage: &str, e: AgentError) -> Self {
+        Self {
+            transient: e.is_transient(),
+            message: format!("{} error: {}", stage, e),
+            cancelled: match e {
+                AgentError::Cancelled(reason) => Some(reason),
+                _ => None,
+            },
+        }
+    }
+
+    fn permanent(message: String) -> Self {
+        Self {
+            message,
+            transient: false,
+            cancelled: None,
+        }
+    }
 }
 
-async fn dispatch_engineer_agent(event: DispatchEvent, db_path: &str, agent_type: &str) {
+/// The common lifecycle of a run: the task's session (with the agent's first
+/// message when it is new), its running status, the model fallback chain,
+/// the model that answered, and the outcome's status and closing message —
+/// or, for an agent that needs approval, its stored proposal. A task the run
+/// moved to Done fires the workflow rules.
+async fn run_task(
+    queue: &JobQueue,
+    event: DispatchEvent,
+    db_path: &str,
+    control: &RunControl,
+) -> Result<(), RunFailure> {
+    let agent_type = event.assigned_to_agent.as_str();
+    let agent = queue.agents.get(agent_type).ok_or_else(|| {
+        RunFailure::permanent(format!("no handler for agent type {}", agent_type))
+    })?;
     let task_id = event.task_id;
 
-    let agent_storage = match SqliteAgentStorage::open(db_path) {
-        Ok(s) => s,
-        Err(e) => {
+    let task_storage =
+        SqliteTaskStorage::open(db_path).map_err(|e| RunFailure::new("storage", e))?;
+    let task = task_storage
+        .get_task(task_id)
+        .await
+        .map_err(|e| RunFailure::new("task", e))?
+        .ok_or_else(|| RunFailure::permanent(format!("task {} not found", task_id)))?;
+    let agent_storage =
+        SqliteAgentStorage::open(db_path).map_err(|e| RunFailure::new("storage", e))?;
+
+    // HTTP handlers may have created the session and stored the first message
+    // before queueing the job.
+    let session_id = task_session(&agent_storage, &event, agent_type, |sid| {
+        user_message(sid, agent.first_message(&task, &event))
+    })
+    .await?;
+
+    if let Some(status) = agent.running_status() {
+        if let Err(e) = task_storage.update_task_status(task_id, status).await {
             log::error!(
-                "[Dispatcher] {} task={} storage error: {}",
+                "[Dispatcher] {} task={} status error: {}",
                 agent_type,
                 task_id,
                 e
             );
-            return;
         }
+    }
+
+    let config = agent
+        .load_config()
+        .map_err(|e| RunFailure::new("config", e))?;
+    let approval = match get_project_path(db_path, event.project_id) {
+        Ok(Some(path)) => queue
+            .approval
+            .for_project(path)
+            .map_err(|e| RunFailure::new("approval", e))?,
+        _ => queue.approval.as_ref().clone(),
     };
+    let needs_approval = approval.requires_approval(agent_type);
+    if needs_approval && !agent.proposes() {
+        log::warn!(
+            "[Dispatcher] {} task={} needs approval, but the agent cannot propose; applying its result",
+            agent_type,
+            task_id
+        );
+    }
+    let run = TaskRun {
+        event: &event,
+        db_path,
+        session_id,
+        agent_storage: &agent_storage,
+        control,
+        propose: needs_approval && agent.proposes(),
+    };
+    let result = run_with_fallback(&config, |config| agent.run(&run, config)).await;
+    let outcome = record_model(&agent_storage, agent_type, task_id, session_id, result)
+        .await
+        .map_err(|e| RunFailure::new("run", e))?;
 
-    // The HTTP handler may have already created a session. Check first.
-    let session_id = match agent_storage.get_session_by_task(task_id, agent_type).await {
-        Ok(Some(s)) => s.id.unwrap_or(0),
-        Ok(None) => {
-            let session = match agent_storage
-                .create_task_session(event.project_id, task_id, agent_type)
-                .await
-            {
-                Ok(s) => s,
-                Err(e) => {
-                    log::error!(
-                        "[Dispatcher] {} task={} session error: {}",
-                        agent_type,
-                        task_id,
-                        e
-                    );
-                    return;
-                }
-            };
-            let sid = session.id.unwrap_or(0);
-            if let Err(e) = agent_storage
-                .create_message(ChatMessage {
-                    id: None,
-                    session_id: sid,
-                    role: "user".to_string(),
-                    agent_type: None,
-                    content: event.source_prompt.clone(),
-                    tool_call_id: None,
-                    tool_name: None,
-                    turn_id: None,
-                    created_at: 0,
-                })
-                .await
-            {
-                log::error!(
-                    "[Dispatcher] {} task={} message error: {}",
-                    agent_type,
-                    task_id,
-                    e
-                );
-                return;
-            }
-            sid
-        }
-        Err(e) => {
+    if outcome.stopped {
+        log::warn!(
+            "[Dispatcher] {} task={} stopped: {}",
+            agent_type,
+            task_id,
+            outcome.summary
+        );
+    } else {
+        log::info!(
+            "[Dispatcher] {} task={} {}",
+            agent_type,
+            task_id,
+            outcome.summary
+        );
+    }
+    if let Some(message) = outcome.message {
+        let row = ChatMessage {
+            role: "assistant".to_string(),
+            agent_type: Some(agent_type.to_string()),
+            ..user_message(session_id, message)
+        };
+        if let Err(e) = agent_storage.create_message(row).await {
             log::error!(
-                "[Dispatcher] {} task={} session lookup error: {}",
+                "[Dispatcher] {} task={} message error: {}",
                 agent_type,
                 task_id,
                 e
             );
-            return;
         }
+    }
+    let status = match &outcome.proposal {
+        Some(proposed) => {
+            propose(db_path, &event, session_id, proposed).await?;
+            Some(TaskStatus::AwaitingApproval)
+        }
+        None => outcome.status,
     };
-
-    let config = match AgentConfig::load_context_agent() {
-        Ok(c) => c,
-        Err(e) => {
+    if let Some(status) = status {
+        if let Err(e) = task_storage.update_task_status(task_id, status).await {
             log::error!(
-                "[Dispatcher] {} task={} config error: {}",
+                "[Dispatcher] {} task={} status error: {}",
                 agent_type,
                 task_id,
                 e
             );
-            return;
         }
+    }
+    // The agent may have moved the task to Done itself, or its outcome did.
+    // A task the Engineering Manager split is done without being built.
+    if task.status != TaskStatus::Done && agent_type != AgentType::EngineeringManager.as_str() {
+        if let Err(e) = run_workflow(queue, db_path, task_id).await {
+            log::error!("[Dispatcher] task={} follow-up error: {}", task_id, e);
+        }
+    }
+    Ok(())
+}
+
+/// Store what the run proposed and say on the task that it awaits approval.
+async fn propose(
+    db_path: &str,
+    event: &DispatchEvent,
+    session_id: i64,
+    proposed: &Proposed,
+) -> Result<(), RunFailure> {
+    let proposal_id = SqliteProposalStorage::open(db_path)
+        .map_err(|e| RunFailure::new("storage", e))?
+        .create_proposal(
+            event.task_id,
+            event.project_id,
+            &event.assigned_to_agent,
+            session_id,
+            &proposed.artifact,
+            &proposed.summary,
+        )
+        .await
+        .map_err(|e| RunFailure::new("proposal", e))?;
+    log::info!(
+        "[Dispatcher] {} task={} proposed {} (proposal={})",
+        event.assigned_to_agent,
+        event.task_id,
+        proposed.summary,
+        proposal_id
+    );
+    let comment = format!(
+        "Proposed {}. It is applied once approved; reject it or request changes to send it back.",
+        proposed.summary
+    );
+    let comment_added = match SqliteCommentStorage::open(db_path) {
+        Ok(comments) => comments
+            .add_task_comment(
+                event.task_id,
+                "agent",
+                None,
+                Some(AgentType::from_str(&event.assigned_to_agent)),
+                comment,
+            )
+            .await
+            .map(|_| ()),
+        Err(e) => Err(e),
     };
+    if let Err(e) = comment_added {
+        log::error!("[Dispatcher] task={} comment error: {}", event.task_id, e);
+    }
+    Ok(())
+}
 
-    let project_path = event.source_prompt.clone();
+/// The task's session, or — when the HTTP handler did not create one, as for
+/// startup reconciliation — a new one holding `source_prompt` as its first
+/// message.
+async fn task_session(
+    agent_storage: &SqliteAgentStorage,
+    event: &DispatchEvent,
+    agent_type: &str,
+    first_message: impl FnOnce(i64) -> ChatMessage,
+) -> Result<i64, RunFailure> {
+    let existing = agent_storage
+        .get_session_by_task(event.task_id, agent_type)
+        .await
+        .map_err(|e| RunFailure::new("session lookup", e))?;
+    if let Some(session) = existing {
+        return Ok(session.id.unwrap_or(0));
+    }
+    let session = agent_storage
+        .create_task_session(event.project_id, event.task_id, agent_type)
+        .await
+        .map_err(|e| RunFailure::new("session", e))?;
+    let session_id = session.id.unwrap_or(0);
+    agent_storage
+        .create_message(first_message(session_id))
+        .await
+        .map_err(|e| RunFailure::new("message", e))?;
+    Ok(session_id)
+}
 
-    if agent_type == "backend_engineer" {
-        let agent = match build_backend_engineer(&config, db_path, event.project_id, &project_path)
-        {
-            Ok(a) => a,
-            Err(e) => {
-                log::error!(
-                    "[Dispatcher] {} task={} build error: {}",
-                    agent_type,
-                    task_id,
-                    e
-                );
-                return;
-            }
+fn user_message(session_id: i64, content: String) -> ChatMessage {
+    ChatMessage {
+        id: None,
+        session_id,
+        role: "user".to_string(),
+        agent_type: None,
+        content,
+        tool_call_id: None,
+        tool_name: None,
+        turn_id: None,
+        created_at: 0,
+    }
+}
+
+/// Store the model that produced a successful run on the task's session and
+/// hand back the agent's own result.
+async fn record_model<T>(
+    agent_storage: &SqliteAgentStorage,
+    agent_type: &str,
+    task_id: i64,
+    session_id: i64,
+    result: Result<(T, String), AgentError>,
+) -> Result<T, AgentError> {
+    let (value, model) = result?;
+    log::info!(
+        "[Dispatcher] {} task={} answered by {}",
+        agent_type,
+        task_id,
+        model
+    );
+    if let Err(e) = agent_storage.set_session_model(session_id, &model).await {
+        log::error!(
+            "[Dispatcher] {} task={} model record error: {}",
+            agent_type,
+            task_id,
+            e
+        );
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
+    use nocodo_agents::{AgentConfig, ProposalStatus, ToolProtocol};
+
+    use super::*;
+    use crate::agents_api::registry::{DispatchableAgent, Outcome};
+
+    /// An agent whose primary model is on `providers[0]` and whose fallbacks
+    /// are on the rest.
+    struct StubAgent {
+        agent_type: &'static str,
+        providers: &'static [&'static str],
+        config_loads: Arc<AtomicUsize>,
+    }
+
+    fn model_config(provider: &str, fallbacks: Vec<AgentConfig>) -> AgentConfig {
+        AgentConfig {
+            provider: provider.to_string(),
+            model: "model".to_string(),
+            api_key: String::new(),
+            base_url: None,
+            fixture: None,
+            fallbacks,
+            context_budget: 1000,
+            summary_model: "model".to_string(),
+            tool_protocol: ToolProtocol::Native,
+        }
+    }
+
+    #[async_trait]
+    impl DispatchableAgent for StubAgent {
+        type Output = ();
+
+        fn agent_type(&self) -> &'static str {
+            self.agent_type
+        }
+
+        fn load_config(&self) -> Result<AgentConfig, AgentError> {
+            self.config_loads.fetch_add(1, Ordering::SeqCst);
+            let fallbacks = self.providers[1..]
+                .iter()
+                .map(|p| model_config(p, Vec::new()))
+                .collect();
+            Ok(model_config(self.providers[0], fallbacks))
+        }
+
+        async fn run_for_task(
+            &self,
+            _run: &TaskRun<'_>,
+            _config: AgentConfig,
+        ) -> Result<(), AgentError> {
+            Ok(())
+        }
+
+        fn outcome(&self, _output: ()) -> Outcome {
+            Outcome::finished("done")
+        }
+    }
+
+    fn queue(
+        limits: DispatchConfig,
+        agents: &[(&'static str, &'static [&'static str])],
+    ) -> (JobQueue, Arc<AtomicUsize>) {
+        let config_loads = Arc::new(AtomicUsize::new(0));
+        let mut registry = AgentRegistry::default();
+        for (agent_type, providers) in agents {
+            registry.register(StubAgent {
+                agent_type,
+                providers,
+                config_loads: config_loads.clone(),
+            });
+        }
+        let queue = JobQueue::open(":memory:", limits, registry).unwrap();
+        (queue, config_loads)
+    }
+
+    fn saturated(queue: &JobQueue) -> Option<Vec<String>> {
+        queue.saturated_agents().map(|mut agents| {
+            agents.sort();
+            agents
+        })
+    }
+
+    #[test]
+    fn test_saturated_agents_per_agent_limit() {
+        let limits = DispatchConfig {
+            agent_limits: HashMap::from([("a".to_string(), 1)]),
+            ..DispatchConfig::default()
         };
-        match agent.run_for_task(session_id, task_id).await {
-            Ok(BackendEngineerResponse::ContextSaved { context }) => {
-                log::info!(
-                    "[Dispatcher] {} task={} context saved ({} chars)",
-                    agent_type,
-                    task_id,
-                    context.len()
-                );
-            }
-            Ok(BackendEngineerResponse::Stopped(reason)) => {
-                log::warn!(
-                    "[Dispatcher] {} task={} stopped: {}",
-                    agent_type,
-                    task_id,
-                    reason
-                );
-            }
-            Err(e) => {
-                log::error!("[Dispatcher] {} task={} error: {}", agent_type, task_id, e);
+        let (queue, config_loads) = queue(limits, &[("a", &["openai"]), ("b", &["openai"])]);
+        assert_eq!(saturated(&queue), Some(vec![]));
+
+        let slot = queue.take_slot("a");
+        assert_eq!(saturated(&queue), Some(vec!["a".to_string()]));
+        let other = queue.take_slot("b");
+        assert_eq!(saturated(&queue), Some(vec!["a".to_string()]));
+        drop(slot);
+        drop(other);
+        assert_eq!(saturated(&queue), Some(vec![]));
+
+        // Providers were resolved when the agents were registered.
+        assert_eq!(config_loads.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_saturated_agents_per_provider_limit_counts_fallbacks() {
+        // llama_cpp defaults to one run at a time.
+        let limits = DispatchConfig {
+            provider_limits: HashMap::from([("groq".to_string(), 2)]),
+            ..DispatchConfig::default()
+        };
+        let (queue, _) = queue(
+            limits,
+            &[
+                ("local", &["llama_cpp"]),
+                ("hosted", &["groq", "llama_cpp"]),
+                ("other", &["groq"]),
+            ],
+        );
+
+        // A run that may fall back to llama_cpp holds its only slot.
+        let hosted = queue.take_slot("hosted");
+        assert_eq!(
+            saturated(&queue),
+            Some(vec!["hosted".to_string(), "local".to_string()])
+        );
+        let other = queue.take_slot("other");
+        assert_eq!(
+            saturated(&queue),
+            Some(vec![
+                "hosted".to_string(),
+                "local".to_string(),
+                "other".to_string()
+            ])
+        );
+        drop(hosted);
+        assert_eq!(saturated(&queue), Some(vec![]));
+
+        let local = queue.take_slot("local");
+        assert_eq!(
+            saturated(&queue),
+            Some(vec!["hosted".to_string(), "local".to_string()])
+        );
+        drop(local);
+        drop(other);
+        assert_eq!(saturated(&queue), Some(vec![]));
+    }
+
+    #[test]
+    fn test_saturated_agents_at_max_concurrent() {
+        let limits = DispatchConfig {
+            max_concurrent: 1,
+            ..DispatchConfig::default()
+        };
+        let (queue, _) = queue(limits, &[("a", &["openai"])]);
+        let slot = queue.take_slot("a");
+        assert_eq!(saturated(&queue), None);
+        drop(slot);
+        assert_eq!(saturated(&queue), Some(vec![]));
+    }
+
+    #[test]
+    fn test_try_slot_respects_limits_for_unregistered_agents() {
+        let limits = DispatchConfig {
+            agent_limits: HashMap::from([("stack_reviewer".to_string(), 1)]),
+            ..DispatchConfig::default()
+        };
+        let (queue, _) = queue(limits, &[("local", &["llama_cpp"])]);
+
+        // A job run on llama_cpp holds its only slot.
+        let local = queue.take_slot("local");
+        assert!(queue
+            .try_slot("stack_reviewer", vec!["llama_cpp".to_string()])
+            .is_none());
+        let review = queue
+            .try_slot("stack_reviewer", vec!["openai".to_string()])
+            .expect("openai has no limit");
+        assert!(queue
+            .try_slot("stack_reviewer", vec!["openai".to_string()])
+            .is_none());
+        drop(local);
+
+        // The review counts against llama_cpp once it may call it.
+        drop(review);
+        let review = queue
+            .try_slot("stack_reviewer", vec!["llama_cpp".to_string()])
+            .expect("llama_cpp is free");
+        assert_eq!(saturated(&queue), Some(vec!["local".to_string()]));
+        drop(review);
+        assert_eq!(saturated(&queue), Some(vec![]));
+    }
+
+    /// A migrated temp-file database with project 1 and db_engineer task 1.
+    /// The caller removes the file.
+    fn seeded_db(name: &str) -> (std::path::PathBuf, String) {
+        let path = std::env::temp_dir().join(format!(
+            "nocodo-dispatcher-{}-{}.db",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let db_path = path.to_string_lossy().into_owned();
+        crate::db::run_startup_migrations(&db_path).expect("migrate");
+        rusqlite::Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO project (id, name, path, created_at) VALUES (1, 'P', '', 0);
+                 INSERT INTO task (id, project_id, title, description, source_prompt,
+                     assigned_to_agent, created_by_agent, created_at, updated_at)
+                 VALUES (1, 1, 'A', '', '', 'db_engineer', 'project_manager', 0, 0);",
+            )
+            .expect("seed db");
+        (path, db_path)
+    }
+
+    #[test]
+    fn test_backoff_secs_doubles_from_base_up_to_cap() {
+        assert_eq!(backoff_secs(0), RETRY_BASE_SECS);
+        assert_eq!(backoff_secs(1), RETRY_BASE_SECS);
+        assert_eq!(backoff_secs(2), RETRY_BASE_SECS * 2);
+        assert_eq!(backoff_secs(3), RETRY_BASE_SECS * 4);
+        assert_eq!(backoff_secs(5), RETRY_BASE_SECS * 16);
+        assert_eq!(backoff_secs(6), RETRY_MAX_SECS);
+        assert_eq!(backoff_secs(1_000), RETRY_MAX_SECS);
+    }
+
+    #[actix_web::test]
+    async fn test_handle_failure_retries_transient_failures_with_backoff() {
+        let (path, db_path) = seeded_db("retry");
+        let limits = DispatchConfig {
+            max_attempts: 3,
+            ..DispatchConfig::default()
+        };
+        let queue = JobQueue::open(&db_path, limits, AgentRegistry::default()).unwrap();
+        let failure = || RunFailure {
+            message: "provider error".to_string(),
+            transient: true,
+            cancelled: None,
+        };
+        queue
+            .jobs
+            .enqueue_job(1, 1, "db_engineer", "", 0)
+            .await
+            .unwrap();
+
+        let mut delays = Vec::new();
+        for attempt in 1..=2 {
+            let job = queue
+                .jobs
+                .claim_job("w", LEASE_SECS, 3, &[])
+                .await
+                .unwrap()
+                .expect("job is due");
+            assert_eq!(job.attempts, attempt);
+            handle_failure(&queue, &job, "w", &db_path, failure()).await;
+            let retried = queue.jobs.get_job(job.id).await.unwrap().unwrap();
+            assert_eq!(retried.status, JobStatus::Queued);
+            delays.push(retried.available_at - retried.updated_at);
+            // Make the retry due straight away.
+            rusqlite::Connection::open(&path)
+                .unwrap()
+                .execute("UPDATE agent_job SET available_at = 0", [])
+                .unwrap();
+        }
+        assert_eq!(delays, vec![RETRY_BASE_SECS, RETRY_BASE_SECS * 2]);
+
+        // The last attempt blocks the task instead of retrying.
+        let job = queue
+            .jobs
+            .claim_job("w", LEASE_SECS, 3, &[])
+            .await
+            .unwrap()
+            .unwrap();
+        handle_failure(&queue, &job, "w", &db_path, failure()).await;
+        let failed = queue.jobs.get_job(job.id).await.unwrap().unwrap();
+        let task = SqliteTaskStorage::open(&db_path)
+            .unwrap()
+            .get_task(1)
+            .await
+            .unwrap()
+            .unwrap();
+        let comments = SqliteCommentStorage::open(&db_path)
+            .unwrap()
+            .get_task_comments(1)
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(failed.status, JobStatus::Failed);
+        assert_eq!(task.status, TaskStatus::Blocked);
+        let comments: Vec<&str> = comments.iter().map(|c| c.content.as_str()).collect();
+        assert!(comments[0].ends_with(&format!("Retrying in {}s.", RETRY_BASE_SECS)));
+        assert!(comments[1].ends_with(&format!("Retrying in {}s.", RETRY_BASE_SECS * 2)));
+        assert!(comments[2].starts_with("Run failed (attempt 3 of 3)"));
+        assert!(
+            comments[2].ends_with("Moved to Blocked; re-queue the task once the cause is fixed.")
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_handle_failure_blocks_permanent_failures_on_first_attempt() {
+        let (path, db_path) = seeded_db("permanent");
+        let queue = JobQueue::open(
+            &db_path,
+            DispatchConfig::default(),
+            AgentRegistry::default(),
+        )
+        .unwrap();
+        queue
+            .jobs
+            .enqueue_job(1, 1, "db_engineer", "", 0)
+            .await
+            .unwrap();
+        let job = queue
+            .jobs
+            .claim_job("w", LEASE_SECS, 3, &[])
+            .await
+            .unwrap()
+            .unwrap();
+        let failure = RunFailure::new("agent", AgentError::Config("missing key".to_string()));
+        handle_failure(&queue, &job, "w", &db_path, failure).await;
+        let failed = queue.jobs.get_job(job.id).await.unwrap().unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(failed.status, JobStatus::Failed);
+    }
+
+    #[actix_web::test]
+    async fn test_cancel_reaches_a_claimed_job_before_its_run_is_tracked() {
+        let (path, db_path) = seeded_db("cancel");
+        let queue = JobQueue::open(
+            &db_path,
+            DispatchConfig::default(),
+            AgentRegistry::default(),
+        )
+        .unwrap();
+        queue
+            .jobs
+            .enqueue_job(1, 1, "db_engineer", "", 0)
+            .await
+            .unwrap();
+        let job = queue
+            .jobs
+            .claim_job("w", LEASE_SECS, 3, &[])
+            .await
+            .unwrap()
+            .unwrap();
+
+        let cancelled = queue.cancel(1, "cancelled by user").await.unwrap();
+        let run = queue.track_run(&job);
+        let untracked = queue.cancel(2, "cancelled by user").await.unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert!(matches!(cancelled, Cancelled::Running));
+        assert!(matches!(
+            run.control.check(),
+            Err(AgentError::Cancelled(reason)) if reason == "cancelled by user"
+        ));
+        assert!(matches!(untracked, Cancelled::NotRunning));
+        drop(run);
+        assert!(queue.runs.lock().unwrap().controls.is_empty());
+    }
+
+    /// How `ScriptedAgent` ends its run.
+    #[derive(Clone, Copy)]
+    enum Ending {
+        Done,
+        Fail,
+        Propose,
+    }
+
+    /// A db_engineer that ends each run as `ending` says, without a model.
+    struct ScriptedAgent {
+        ending: Ending,
+    }
+
+    #[async_trait]
+    impl DispatchableAgent for ScriptedAgent {
+        type Output = ();
+
+        fn agent_type(&self) -> &'static str {
+            "db_engineer"
+        }
+
+        fn load_config(&self) -> Result<AgentConfig, AgentError> {
+            Ok(model_config("openai", Vec::new()))
+        }
+
+        async fn run_for_task(
+            &self,
+            _run: &TaskRun<'_>,
+            _config: AgentConfig,
+        ) -> Result<(), AgentError> {
+            match self.ending {
+                Ending::Fail => Err(AgentError::Config("missing API key".to_string())),
+                Ending::Done | Ending::Propose => Ok(()),
             }
         }
-    } else {
-        let agent = match build_frontend_engineer(&config, db_path, event.project_id, &project_path)
-        {
-            Ok(a) => a,
-            Err(e) => {
-                log::error!(
-                    "[Dispatcher] {} task={} build error: {}",
-                    agent_type,
-                    task_id,
-                    e
-                );
-                return;
+
+        fn outcome(&self, _output: ()) -> Outcome {
+            Outcome {
+                status: Some(TaskStatus::Done),
+                message: Some("Schema saved.".to_string()),
+                ..Outcome::finished("schema saved")
             }
+        }
+
+        fn proposes(&self) -> bool {
+            true
+        }
+
+        fn proposal(&self, _run: &TaskRun<'_>, _output: &()) -> Option<Proposed> {
+            Some(Proposed {
+                artifact: "{}".to_string(),
+                summary: "schema with 1 table".to_string(),
+            })
+        }
+    }
+
+    /// A migrated shared in-memory database with db_engineer task 1 Ready,
+    /// and the connection that keeps it alive.
+    fn memory_db(name: &str) -> (rusqlite::Connection, String) {
+        let db_path = format!("file:{}?mode=memory&cache=shared", name);
+        let keep = rusqlite::Connection::open(&db_path).unwrap();
+        crate::db::run_startup_migrations(&db_path).expect("migrate");
+        keep.execute_batch(
+            "INSERT INTO project (id, name, path, created_at) VALUES (1, 'P', '', 0);
+             INSERT INTO task (id, project_id, title, description, source_prompt,
+                 assigned_to_agent, created_by_agent, status, created_at, updated_at)
+             VALUES (1, 1, 'Contacts', '', 'Store contacts', 'db_engineer',
+                 'project_manager', 'ready', 0, 0);",
+        )
+        .expect("seed db");
+        (keep, db_path)
+    }
+
+    async fn run_scripted(
+        db_path: &str,
+        ending: Ending,
+        approval: ApprovalPolicy,
+    ) -> Result<(), RunFailure> {
+        let mut registry = AgentRegistry::default();
+        registry.register(ScriptedAgent { ending });
+        let queue = JobQueue::open(db_path, DispatchConfig::default(), registry)
+            .unwrap()
+            .with_approval(approval);
+        let event = DispatchEvent {
+            task_id: 1,
+            project_id: 1,
+            assigned_to_agent: "db_engineer".to_string(),
+            source_prompt: "Store contacts".to_string(),
         };
-        match agent.run_for_task(session_id, task_id).await {
-            Ok(FrontendEngineerResponse::ContextSaved { context }) => {
-                log::info!(
-                    "[Dispatcher] {} task={} context saved ({} chars)",
-                    agent_type,
-                    task_id,
-                    context.len()
-                );
-            }
-            Ok(FrontendEngineerResponse::Stopped(reason)) => {
-                log::warn!(
-                    "[Dispatcher] {} task={} stopped: {}",
-                    agent_type,
-                    task_id,
-                    reason
-                );
-            }
-            Err(e) => {
-                log::error!("[Dispatcher] {} task={} error: {}", agent_type, task_id, e);
-            }
+        run_task(&queue, event, db_path, &RunControl::default()).await
+    }
+
+    async fn task_status(db_path: &str) -> TaskStatus {
+        let tasks = SqliteTaskStorage::open(db_path).unwrap();
+        tasks.get_task(1).await.unwrap().unwrap().status
+    }
+
+    async fn session_messages(db_path: &str) -> Vec<(String, String)> {
+        let storage = SqliteAgentStorage::open(db_path).unwrap();
+        let session = storage
+            .get_session_by_task(1, "db_engineer")
+            .await
+            .unwrap()
+            .expect("session created");
+        storage
+            .get_messages(session.id.unwrap())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.role, m.content))
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn test_run_task_applies_the_outcome() {
+        let (_keep, db_path) = memory_db("run-task-done");
+
+        run_scripted(&db_path, Ending::Done, ApprovalPolicy::default())
+            .await
+            .expect("run succeeds");
+
+        assert_eq!(task_status(&db_path).await, TaskStatus::Done);
+        assert_eq!(
+            session_messages(&db_path).await,
+            [
+                ("user".to_string(), "Store contacts".to_string()),
+                ("assistant".to_string(), "Schema saved.".to_string()),
+            ]
+        );
+        let proposals = SqliteProposalStorage::open(&db_path)
+            .unwrap()
+            .list_task_proposals(1)
+            .await
+            .unwrap();
+        assert!(proposals.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_run_task_reports_a_failed_run() {
+        let (_keep, db_path) = memory_db("run-task-fail");
+
+        let failure = run_scripted(&db_path, Ending::Fail, ApprovalPolicy::default())
+            .await
+            .unwrap_err();
+
+        assert!(!failure.transient);
+        assert!(failure.cancelled.is_none());
+        assert_eq!(
+            failure.message,
+            "run error: Configuration error: missing API key"
+        );
+        // The run started; `handle_failure` decides where the task goes next.
+        assert_eq!(task_status(&db_path).await, TaskStatus::InProgress);
+        assert_eq!(session_messages(&db_path).await.len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_run_task_stores_a_proposal_for_approval() {
+        let (_keep, db_path) = memory_db("run-task-propose");
+        let approval = ApprovalPolicy {
+            agents: vec!["db_engineer".to_string()],
+        };
+
+        run_scripted(&db_path, Ending::Propose, approval)
+            .await
+            .expect("run succeeds");
+
+        assert_eq!(task_status(&db_path).await, TaskStatus::AwaitingApproval);
+        let proposal = SqliteProposalStorage::open(&db_path)
+            .unwrap()
+            .get_pending_proposal(1)
+            .await
+            .unwrap()
+            .expect("proposal stored");
+        assert_eq!(proposal.status, ProposalStatus::Pending);
+        assert_eq!(proposal.summary, "schema with 1 table");
+        assert_eq!(proposal.artifact, "{}");
+        let comments = SqliteCommentStorage::open(&db_path)
+            .unwrap()
+            .get_task_comments(1)
+            .await
+            .unwrap();
+        assert!(comments[0]
+            .content
+            .starts_with("Proposed schema with 1 table."));
+    }
+
+    #[actix_web::test]
+    async fn test_block_dependents_cascades_over_two_levels() {
+        let (keep, db_path) = memory_db("block-dependents");
+        // 2 waits for 1 and 3 for 2; 4 waits for 1 but is already done.
+        keep.execute_batch(
+            "UPDATE task SET status = 'blocked' WHERE id = 1;
+             INSERT INTO task (id, project_id, title, description, source_prompt,
+                 assigned_to_agent, created_by_agent, status, created_at, updated_at)
+             VALUES (2, 1, 'API', '', '', 'backend_engineer', 'project_manager', 'ready', 0, 0),
+                    (3, 1, 'UI', '', '', 'frontend_engineer', 'project_manager', 'ready', 0, 0),
+                    (4, 1, 'Docs', '', '', 'backend_engineer', 'project_manager', 'done', 0, 0);
+             INSERT INTO task_dependency (task_id, depends_on_task_id, created_at)
+             VALUES (2, 1, 0), (3, 2, 0), (4, 1, 0);",
+        )
+        .expect("seed dependencies");
+
+        block_dependents(&db_path, 1).await.unwrap();
+
+        let tasks = SqliteTaskStorage::open(&db_path).unwrap();
+        let comments = &SqliteCommentStorage::open(&db_path).unwrap();
+        for (task_id, status) in [
+            (2, TaskStatus::Blocked),
+            (3, TaskStatus::Blocked),
+            (4, TaskStatus::Done),
+        ] {
+            let task = tasks.get_task(task_id).await.unwrap().unwrap();
+            assert_eq!(task.status, status, "task {}", task_id);
         }
+        let comment = |task_id| async move {
+            comments
+                .get_task_comments(task_id)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|c| c.content)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            comment(2).await,
+            ["Blocked: depends on #1 (Contacts), which is blocked. It runs once #1 is done."]
+        );
+        assert_eq!(
+            comment(3).await,
+            ["Blocked: depends on #2 (API), which is blocked. It runs once #2 is done."]
+        );
+        assert!(comment(4).await.is_empty());
     }
 }
@@ -0,0 +1,275 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+use nocodo_agents::{
+    task_policy, CommentStorage, JobStorage, SqliteCommentStorage, SqliteJobStorage,
+    SqliteTaskStorage, TaskStatus, TaskStorage,
+};
+
+use crate::agents_api::dispatcher::{record_stop, Cancelled, DispatchEvent};
+use crate::agents_api::jobs::types::{CancelResponse, JobsQuery, JobsResponse, RequeueResponse};
+use crate::agents_api::state::AgentState;
+
+/// GET /api/agents/jobs?project_id=X
+/// Every dispatch job of the project, newest first, with its status, attempts
+/// and current lease.
+#[get("/api/agents/jobs")]
+pub async fn list_jobs(
+    state: web::Data<AgentState>,
+    query: web::Query<JobsQuery>,
+) -> impl Responder {
+    let storage = match SqliteJobStorage::open(&state.db_path) {
+        Ok(s) => s,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Storage error: {}", e) }));
+        }
+    };
+
+    match storage.list_jobs(query.project_id).await {
+        Ok(jobs) => HttpResponse::Ok().json(JobsResponse {
+            project_id: query.project_id,
+            jobs,
+        }),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(serde_json::json!({ "error": format!("Failed to list jobs: {}", e) })),
+    }
+}
+
+/// POST /api/tasks/{task_id}/requeue
+/// Queue a fresh run of a task, typically one a failing agent left Blocked.
+/// The agent of the task's last job runs it again — the Engineering Manager
+/// for a task it had not finished shaping — and the task goes back to the
+/// status it waits in for that agent. The run gets a full set of attempts.
+/// A task awaiting approval is sent back through its proposal instead.
+#[post("/api/tasks/{task_id}/requeue")]
+pub async fn requeue_task(state: web::Data<AgentState>, path: web::Path<i64>) -> impl Responder {
+    let task_id = path.into_inner();
+
+    let task_storage = match SqliteTaskStorage::open(&state.db_path) {
+        Ok(s) => s,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Storage error: {}", e) }));
+        }
+    };
+
+    let task = match task_storage.get_task(task_id).await {
+        Ok(Some(t)) => t,
+        Ok(None) => {
+            return HttpResponse::NotFound()
+                .json(serde_json::json!({ "error": format!("Task {} not found", task_id) }));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Failed to load task: {}", e) }));
+        }
+    };
+    if matches!(task.status, TaskStatus::Done | TaskStatus::AwaitingApproval)
+        || task.assigned_to_agent == "project_manager"
+    {
+        return HttpResponse::Conflict().json(serde_json::json!({
+            "error": format!("Task {} cannot be re-queued ({})", task_id, task.status.as_str())
+        }));
+    }
+
+    let last_agent = match SqliteJobStorage::open(&state.db_path) {
+        Ok(jobs) => match jobs.list_jobs(task.project_id).await {
+            Ok(jobs) => jobs
+                .into_iter()
+                .find(|j| j.task_id == task_id)
+                .map(|j| j.agent_type),
+            Err(e) => {
+                return HttpResponse::InternalServerError()
+                    .json(serde_json::json!({ "error": format!("Failed to list jobs: {}", e) }));
+            }
+        },
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Storage error: {}", e) }));
+        }
+    };
+    let agent_type =
+        last_agent.unwrap_or_else(|| task_policy::dispatch_agent_for(&task).to_string());
+    let status = task_policy::queued_state_for(&agent_type);
+
+    let job_id = match state
+        .jobs
+        .enqueue(DispatchEvent {
+            task_id,
+            project_id: task.project_id,
+            assigned_to_agent: agent_type,
+            source_prompt: task.source_prompt,
+        })
+        .await
+    {
+        Ok(Some(id)) => id,
+        Ok(None) => {
+            return HttpResponse::Conflict().json(serde_json::json!({
+                "error": format!("Task {} is already queued or running", task_id)
+            }));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Failed to queue task: {}", e) }));
+        }
+    };
+
+    if let Err(e) = task_storage.update_task_status(task_id, status).await {
+        log::error!("[Jobs] task={} status error: {}", task_id, e);
+    }
+    if let Ok(comments) = SqliteCommentStorage::open(&state.db_path) {
+        if let Err(e) = comments
+            .add_task_comment(task_id, "user", None, None, "Re-queued.".to_string())
+            .await
+        {
+            log::error!("[Jobs] task={} comment error: {}", task_id, e);
+        }
+    }
+    state.board_notify.notify_waiters();
+
+    HttpResponse::Accepted().json(RequeueResponse { task_id, job_id })
+}
+
+/// POST /api/tasks/{task_id}/cancel
+/// Stop the task's agent run. A running agent stops at its next LLM call or
+/// tool execution and the dispatcher records the stop; a queued run is failed
+/// here. Either way the task is moved to Blocked with a session message and a
+/// comment saying why.
+#[post("/api/tasks/{task_id}/cancel")]
+pub async fn cancel_task(state: web::Data<AgentState>, path: web::Path<i64>) -> impl Responder {
+    let task_id = path.into_inner();
+
+    let cancelled = match state.jobs.cancel(task_id, "cancelled by user").await {
+        Ok(c) => c,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Failed to cancel task: {}", e) }));
+        }
+    };
+
+    let status = match cancelled {
+        Cancelled::Running => "stopping",
+        Cancelled::Queued(job) => {
+            record_stop(
+                &state.db_path,
+                task_id,
+                &job.agent_type,
+                "cancelled by user",
+            )
+            .await;
+            state.board_notify.notify_waiters();
+            "cancelled"
+        }
+        Cancelled::NotRunning => {
+            return HttpResponse::Conflict().json(serde_json::json!({
+                "error": format!("Task {} has no queued or running agent run", task_id)
+            }));
+        }
+    };
+
+    HttpResponse::Accepted().json(CancelResponse {
+        task_id,
+        status: status.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, App};
+    use nocodo_agents::JobStatus;
+
+    use super::*;
+    use crate::agents_api::dispatcher::JobQueue;
+    use crate::agents_api::registry::AgentRegistry;
+    use crate::config::DispatchConfig;
+
+    /// Agent state on a migrated temp-file database with db_engineer task 1,
+    /// without a dispatcher claiming its jobs. The caller removes the file.
+    fn state(name: &str) -> (std::path::PathBuf, AgentState) {
+        let path =
+            std::env::temp_dir().join(format!("nocodo-jobs-{}-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db_path = path.to_string_lossy().into_owned();
+        crate::db::run_startup_migrations(&db_path).expect("migrate");
+        rusqlite::Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO project (id, name, path, created_at) VALUES (1, 'P', '', 0);
+                 INSERT INTO task (id, project_id, title, description, source_prompt,
+                     assigned_to_agent, created_by_agent, status, created_at, updated_at)
+                 VALUES (1, 1, 'A', '', '', 'db_engineer', 'project_manager', 'ready', 0, 0);",
+            )
+            .expect("seed db");
+        let jobs = JobQueue::open(
+            &db_path,
+            DispatchConfig::default(),
+            AgentRegistry::default(),
+        )
+        .unwrap();
+        (path, AgentState::for_tests(db_path, jobs))
+    }
+
+    #[actix_web::test]
+    async fn test_cancel_task_fails_a_queued_run_and_blocks_the_task() {
+        let (path, state) = state("cancel-queued");
+        let job_id = state
+            .jobs
+            .enqueue(DispatchEvent {
+                task_id: 1,
+                project_id: 1,
+                assigned_to_agent: "db_engineer".to_string(),
+                source_prompt: String::new(),
+            })
+            .await
+            .unwrap()
+            .expect("job queued");
+        let db_path = state.db_path.clone();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .service(cancel_task),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/tasks/1/cancel")
+            .to_request();
+        let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let again = test::TestRequest::post()
+            .uri("/api/tasks/1/cancel")
+            .to_request();
+        let again = test::call_service(&app, again).await;
+
+        let job = SqliteJobStorage::open(&db_path)
+            .unwrap()
+            .get_job(job_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let task = SqliteTaskStorage::open(&db_path)
+            .unwrap()
+            .get_task(1)
+            .await
+            .unwrap()
+            .unwrap();
+        let comments = SqliteCommentStorage::open(&db_path)
+            .unwrap()
+            .get_task_comments(1)
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(resp["status"], "cancelled");
+        assert_eq!(again.status(), actix_web::http::StatusCode::CONFLICT);
+        assert_eq!(job.status, JobStatus::Failed);
+        assert_eq!(
+            job.last_error.as_deref(),
+            Some("cancelled: cancelled by user")
+        );
+        assert_eq!(task.status, TaskStatus::Blocked);
+        assert_eq!(comments.len(), 1);
+        assert_eq!(
+            comments[0].content,
+            "Run stopped: cancelled by user.\n\nMoved to Blocked; re-queue the task to run it again."
+        );
+    }
+}
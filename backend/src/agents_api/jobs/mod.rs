@@ -0,0 +1,10 @@
+pub mod handlers;
+pub mod types;
+
+use actix_web::web;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(handlers::list_jobs)
+        .service(handlers::requeue_task)
+        .service(handlers::cancel_task);
+}
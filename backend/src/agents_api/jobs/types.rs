@@ -0,0 +1,26 @@
+use nocodo_agents::Job;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct JobsQuery {
+    pub project_id: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JobsResponse {
+    pub project_id: i64,
+    pub jobs: Vec<Job>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RequeueResponse {
+    pub task_id: i64,
+    pub job_id: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CancelResponse {
+    pub task_id: i64,
+    /// `stopping` for a running agent, `cancelled` for a queued run.
+    pub status: String,
+}
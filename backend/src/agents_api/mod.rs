@@ -1,13 +1,19 @@
+pub mod approvals;
 pub mod code_extractor;
 pub mod comments;
 pub mod context;
 pub mod db_engineer;
+pub mod dependencies;
 pub mod dispatcher;
+pub mod jobs;
 pub mod project_notes;
+pub mod registry;
 pub mod rust_engineer;
+pub mod scheduler;
 pub mod stack_reviewer;
 pub mod state;
 pub mod ui_designer;
+pub mod usage;
 pub mod user_chat;
 
 pub use state::AgentState;
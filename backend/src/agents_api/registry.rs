@@ -0,0 +1,723 @@
+//! The agents the dispatcher can run. Each is one `DispatchableAgent` impl
+//! registered in `AgentRegistry::builtin`; the dispatcher handles what every
+//! run has in common — the task's session and first message, status updates,
+//! model fallback, logging and failures — in `dispatcher::run_task`.
+
+use async_trait::async_trait;
+use std::path::Path;
+use std::sync::Arc;
+
+use nocodo_agents::engineering_manager::shaping_request;
+use nocodo_agents::{
+    build_backend_engineer, build_db_engineer, build_engineering_manager, build_frontend_engineer,
+    build_rust_engineer_with_config, build_ui_designer, AgentConfig, AgentError, AgentResponse,
+    AgentStorage, BackendEngineerResponse, ChatMessage, EngineeringManagerResponse, FormLayout,
+    FrontendEngineerResponse, LlmUsage, Proposal, ProposalStatus, ProposalStorage, ProposedCode,
+    RunControl, RustEngineerTaskOutput, RustEngineerTaskParams, SchemaStorage, SqliteAgentStorage,
+    SqliteProposalStorage, SqliteSchemaStorage, SqliteUiFormStorage, Task, TaskStatus,
+    UiDesignerResponse, UiFormStorage,
+};
+
+use super::dispatcher::DispatchEvent;
+use crate::agents_api::rust_engineer::handlers::get_project_path;
+
+/// One run of an agent on a task, as the dispatcher hands it over.
+pub struct TaskRun<'a> {
+    pub event: &'a DispatchEvent,
+    pub db_path: &'a str,
+    pub session_id: i64,
+    pub agent_storage: &'a SqliteAgentStorage,
+    pub control: &'a RunControl,
+    /// The result needs a user's approval: the agent proposes it instead of
+    /// applying it.
+    pub propose: bool,
+}
+
+/// How a successful run leaves its task.
+#[derive(Debug)]
+pub struct Outcome {
+    /// Status the task moves to; `None` keeps the one the agent set.
+    pub status: Option<TaskStatus>,
+    /// What the run produced, for the log.
+    pub summary: String,
+    /// The agent stopped short of its goal; logged as a warning.
+    pub stopped: bool,
+    /// Posted on the task's session as the agent's last message.
+    pub message: Option<String>,
+    /// Result proposed for approval; the task then waits in
+    /// `awaiting_approval` whatever `status` says.
+    pub proposal: Option<Proposed>,
+}
+
+/// A result an agent proposed instead of applying it.
+#[derive(Debug)]
+pub struct Proposed {
+    /// What approval applies, as JSON.
+    pub artifact: String,
+    /// One line for the reviewer.
+    pub summary: String,
+}
+
+impl Outcome {
+    pub fn finished(summary: impl Into<String>) -> Self {
+        Self {
+            status: None,
+            summary: summary.into(),
+            stopped: false,
+            message: None,
+            proposal: None,
+        }
+    }
+
+    pub fn stopped(summary: impl Into<String>) -> Self {
+        Self {
+            stopped: true,
+            ..Self::finished(summary)
+        }
+    }
+}
+
+/// An agent the dispatcher runs on queued tasks.
+#[async_trait]
+pub trait DispatchableAgent: Send + Sync + 'static {
+    /// What `run_for_task` returns.
+    type Output: Send;
+
+    /// Agent type its jobs are queued under, e.g. "db_engineer".
+    fn agent_type(&self) -> &'static str;
+
+    /// Its primary model and fallbacks.
+    fn load_config(&self) -> Result<AgentConfig, AgentError>;
+
+    /// First message of a new session for the task.
+    fn first_message(&self, _task: &Task, event: &DispatchEvent) -> String {
+        event.source_prompt.clone()
+    }
+
+    /// Status the task moves to when a run starts; `None` keeps it.
+    fn running_status(&self) -> Option<TaskStatus> {
+        Some(TaskStatus::InProgress)
+    }
+
+    /// Run on the task's session with `config`, one model of the chain.
+    async fn run_for_task(
+        &self,
+        run: &TaskRun<'_>,
+        config: AgentConfig,
+    ) -> Result<Self::Output, AgentError>;
+
+    fn outcome(&self, output: Self::Output) -> Outcome;
+
+    /// Whether the agent can propose its result for approval instead of
+    /// applying it, through `proposal` and `apply`.
+    fn proposes(&self) -> bool {
+        false
+    }
+
+    /// What a run with `propose` set proposed; `None` when it produced
+    /// nothing to approve, e.g. it stopped.
+    fn proposal(&self, _run: &TaskRun<'_>, _output: &Self::Output) -> Option<Proposed> {
+        None
+    }
+
+    /// Apply an approved proposal, e.g. save the schema. Returns what was
+    /// applied, for the task's session.
+    async fn apply(&self, _proposal: &Proposal, _db_path: &str) -> Result<String, AgentError> {
+        Err(AgentError::Other(format!(
+            "{} has no results to approve",
+            self.agent_type()
+        )))
+    }
+}
+
+/// `DispatchableAgent` without its output type, so the registry can hold
+/// any of them.
+#[async_trait]
+pub trait RegisteredAgent: Send + Sync {
+    fn agent_type(&self) -> &'static str;
+    fn load_config(&self) -> Result<AgentConfig, AgentError>;
+    fn first_message(&self, task: &Task, event: &DispatchEvent) -> String;
+    fn running_status(&self) -> Option<TaskStatus>;
+    fn proposes(&self) -> bool;
+    async fn run(&self, run: &TaskRun<'_>, config: AgentConfig) -> Result<Outcome, AgentError>;
+    async fn apply(&self, proposal: &Proposal, db_path: &str) -> Result<String, AgentError>;
+}
+
+#[async_trait]
+impl<A: DispatchableAgent> RegisteredAgent for A {
+    fn agent_type(&self) -> &'static str {
+        DispatchableAgent::agent_type(self)
+    }
+
+    fn load_config(&self) -> Result<AgentConfig, AgentError> {
+        DispatchableAgent::load_config(self)
+    }
+
+    fn first_message(&self, task: &Task, event: &DispatchEvent) -> String {
+        DispatchableAgent::first_message(self, task, event)
+    }
+
+    fn running_status(&self) -> Option<TaskStatus> {
+        DispatchableAgent::running_status(self)
+    }
+
+    fn proposes(&self) -> bool {
+        DispatchableAgent::proposes(self)
+    }
+
+    async fn run(&self, run: &TaskRun<'_>, config: AgentConfig) -> Result<Outcome, AgentError> {
+        let output = self.run_for_task(run, config).await?;
+        let proposal = if run.propose {
+            self.proposal(run, &output)
+        } else {
+            None
+        };
+        Ok(Outcome {
+            proposal,
+            ..self.outcome(output)
+        })
+    }
+
+    async fn apply(&self, proposal: &Proposal, db_path: &str) -> Result<String, AgentError> {
+        DispatchableAgent::apply(self, proposal, db_path).await
+    }
+}
+
+/// The agents the dispatcher runs, by agent type.
+#[derive(Default)]
+pub struct AgentRegistry {
+    agents: Vec<Registered>,
+}
+
+struct Registered {
+    agent: Arc<dyn RegisteredAgent>,
+    /// Providers of the agent's primary and fallback models, resolved once at
+    /// registration so the dispatcher never loads config while counting runs.
+    providers: Vec<String>,
+}
+
+impl AgentRegistry {
+    /// Every agent nocodo dispatches.
+    pub fn builtin() -> Self {
+        let mut registry = Self::default();
+        registry.register(DbEngineer);
+        registry.register(EngineeringManager);
+        registry.register(UiDesigner);
+        registry.register(ContextEngineer::Backend);
+        registry.register(ContextEngineer::Frontend);
+        registry.register(RustEngineer);
+        registry
+    }
+
+    /// Add `agent`, replacing one registered under the same agent type.
+    pub fn register(&mut self, agent: impl DispatchableAgent) {
+        let agent_type = DispatchableAgent::agent_type(&agent);
+        let providers = match DispatchableAgent::load_config(&agent) {
+            Ok(config) => chain_providers(&config),
+            Err(e) => {
+                log::warn!(
+                    "[Dispatcher] {} config error, provider limits do not apply: {}",
+                    agent_type,
+                    e
+                );
+                Vec::new()
+            }
+        };
+        self.agents.retain(|a| a.agent.agent_type() != agent_type);
+        self.agents.push(Registered {
+            agent: Arc::new(agent),
+            providers,
+        });
+    }
+
+    pub fn get(&self, agent_type: &str) -> Option<Arc<dyn RegisteredAgent>> {
+        self.find(agent_type).map(|a| a.agent.clone())
+    }
+
+    pub fn agent_types(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.agents.iter().map(|a| a.agent.agent_type())
+    }
+
+    /// Providers a run of the agent type may call: its primary model's and
+    /// its fallbacks', without duplicates.
+    pub fn providers(&self, agent_type: &str) -> &[String] {
+        self.find(agent_type)
+            .map(|a| a.providers.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Whether the agent type is registered and can propose its results.
+    pub fn proposes(&self, agent_type: &str) -> bool {
+        self.find(agent_type).is_some_and(|a| a.agent.proposes())
+    }
+
+    fn find(&self, agent_type: &str) -> Option<&Registered> {
+        self.agents
+            .iter()
+            .find(|a| a.agent.agent_type() == agent_type)
+    }
+}
+
+pub(crate) fn chain_providers(config: &AgentConfig) -> Vec<String> {
+    let mut providers = vec![config.provider.clone()];
+    for fallback in &config.fallbacks {
+        if !providers.contains(&fallback.provider) {
+            providers.push(fallback.provider.clone());
+        }
+    }
+    providers
+}
+
+// ---------------------------------------------------------------------------
+// Agents
+// ---------------------------------------------------------------------------
+
+struct DbEngineer;
+
+#[async_trait]
+impl DispatchableAgent for DbEngineer {
+    type Output = AgentResponse;
+
+    fn agent_type(&self) -> &'static str {
+        "db_engineer"
+    }
+
+    fn load_config(&self) -> Result<AgentConfig, AgentError> {
+        AgentConfig::load_db_engineer()
+    }
+
+    async fn run_for_task(
+        &self,
+        run: &TaskRun<'_>,
+        config: AgentConfig,
+    ) -> Result<AgentResponse, AgentError> {
+        build_db_engineer(&config, run.db_path, run.event.project_id)?
+            .with_run_control(run.control.clone())
+            .chat_with_session(run.session_id, run.event.task_id, run.propose)
+            .await
+    }
+
+    fn outcome(&self, output: AgentResponse) -> Outcome {
+        match output {
+            AgentResponse::SchemaGenerated { text, .. } => {
+                Outcome::finished(format!("schema generated: {}", preview(&text)))
+            }
+            AgentResponse::Text(text) => Outcome::finished(format!("text: {}", preview(&text))),
+            AgentResponse::Question(q) => {
+                Outcome::finished(format!("asked question: {}", preview(&q)))
+            }
+            AgentResponse::Stopped(reason) => Outcome::stopped(reason),
+        }
+    }
+
+    fn proposes(&self) -> bool {
+        true
+    }
+
+    fn proposal(&self, _run: &TaskRun<'_>, output: &AgentResponse) -> Option<Proposed> {
+        let AgentResponse::SchemaGenerated { schema, .. } = output else {
+            return None;
+        };
+        Some(Proposed {
+            artifact: serde_json::to_string(schema).ok()?,
+            summary: format!("schema with {} table(s)", schema.tables.len()),
+        })
+    }
+
+    async fn apply(&self, proposal: &Proposal, db_path: &str) -> Result<String, AgentError> {
+        let version = SqliteSchemaStorage::open(db_path)?
+            .save_schema(proposal.project_id, proposal.session_id, &proposal.artifact)
+            .await?;
+        Ok(format!("Schema saved as version {}.", version))
+    }
+}
+
+struct EngineeringManager;
+
+#[async_trait]
+impl DispatchableAgent for EngineeringManager {
+    type Output = EngineeringManagerResponse;
+
+    fn agent_type(&self) -> &'static str {
+        "engineering_manager"
+    }
+
+    fn load_config(&self) -> Result<AgentConfig, AgentError> {
+        AgentConfig::load_engineering_manager()
+    }
+
+    fn first_message(&self, task: &Task, _event: &DispatchEvent) -> String {
+        shaping_request(task)
+    }
+
+    /// Shaping is not work on the task; it stays `needs_technical_shaping`.
+    fn running_status(&self) -> Option<TaskStatus> {
+        None
+    }
+
+    async fn run_for_task(
+        &self,
+        run: &TaskRun<'_>,
+        config: AgentConfig,
+    ) -> Result<EngineeringManagerResponse, AgentError> {
+        build_engineering_manager(&config, run.db_path, run.event.project_id)?
+            .with_run_control(run.control.clone())
+            .run_for_task(run.session_id, run.event.task_id)
+            .await
+    }
+
+    fn outcome(&self, output: EngineeringManagerResponse) -> Outcome {
+        match output {
+            EngineeringManagerResponse::Split(created) => {
+                Outcome::finished(format!("split into {} task(s)", created.len()))
+            }
+            EngineeringManagerResponse::Ready => Outcome::finished("marked ready"),
+            EngineeringManagerResponse::Blocked(reason) => {
+                Outcome::stopped(format!("blocked: {}", reason))
+            }
+        }
+    }
+}
+
+struct UiDesigner;
+
+#[async_trait]
+impl DispatchableAgent for UiDesigner {
+    type Output = UiDesignerResponse;
+
+    fn agent_type(&self) -> &'static str {
+        "ui_designer"
+    }
+
+    fn load_config(&self) -> Result<AgentConfig, AgentError> {
+        AgentConfig::load_ui_designer()
+    }
+
+    async fn run_for_task(
+        &self,
+        run: &TaskRun<'_>,
+        config: AgentConfig,
+    ) -> Result<UiDesignerResponse, AgentError> {
+        build_ui_designer(&config, run.db_path, run.event.project_id)?
+            .with_run_control(run.control.clone())
+            .with_preview_mode(run.propose)
+            .run_for_task(run.session_id, run.event.task_id)
+            .await
+    }
+
+    fn outcome(&self, output: UiDesignerResponse) -> Outcome {
+        match output {
+            UiDesignerResponse::FormGenerated(form) => {
+                Outcome::finished(format!("form generated for entity '{}'", form.entity))
+            }
+            UiDesignerResponse::Stopped(reason) => Outcome::stopped(reason),
+        }
+    }
+
+    fn proposes(&self) -> bool {
+        true
+    }
+
+    fn proposal(&self, _run: &TaskRun<'_>, output: &UiDesignerResponse) -> Option<Proposed> {
+        let UiDesignerResponse::FormGenerated(form) = output else {
+            return None;
+        };
+        Some(Proposed {
+            artifact: serde_json::to_string(form).ok()?,
+            summary: format!("form layout for entity '{}'", form.entity),
+        })
+    }
+
+    async fn apply(&self, proposal: &Proposal, db_path: &str) -> Result<String, AgentError> {
+        let form: FormLayout = serde_json::from_str(&proposal.artifact)?;
+        SqliteUiFormStorage::open(db_path)?
+            .save_form_layout(proposal.project_id, &form.entity, &proposal.artifact)
+            .await?;
+        Ok(format!("Form layout saved for entity '{}'.", form.entity))
+    }
+}
+
+/// The backend and frontend engineers, which summarize one side of the
+/// project. Their jobs carry the project path as the source prompt.
+enum ContextEngineer {
+    Backend,
+    Frontend,
+}
+
+/// Both engineers answer with the context they saved or why they stopped.
+enum ContextResponse {
+    Saved(String),
+    Stopped(String),
+}
+
+#[async_trait]
+impl DispatchableAgent for ContextEngineer {
+    type Output = ContextResponse;
+
+    fn agent_type(&self) -> &'static str {
+        match self {
+            Self::Backend => "backend_engineer",
+            Self::Frontend => "frontend_engineer",
+        }
+    }
+
+    fn load_config(&self) -> Result<AgentConfig, AgentError> {
+        AgentConfig::load_context_agent()
+    }
+
+    async fn run_for_task(
+        &self,
+        run: &TaskRun<'_>,
+        config: AgentConfig,
+    ) -> Result<ContextResponse, AgentError> {
+        let project_path = run.event.source_prompt.as_str();
+        let project_id = run.event.project_id;
+        let (session_id, task_id) = (run.session_id, run.event.task_id);
+        Ok(match self {
+            Self::Backend => {
+                match build_backend_engineer(&config, run.db_path, project_id, project_path)?
+                    .with_run_control(run.control.clone())
+                    .run_for_task(session_id, task_id)
+                    .await?
+                {
+                    BackendEngineerResponse::ContextSaved { context } => {
+                        ContextResponse::Saved(context)
+                    }
+                    BackendEngineerResponse::Stopped(reason) => ContextResponse::Stopped(reason),
+                }
+            }
+            Self::Frontend => {
+                match build_frontend_engineer(&config, run.db_path, project_id, project_path)?
+                    .with_run_control(run.control.clone())
+                    .run_for_task(session_id, task_id)
+                    .await?
+                {
+                    FrontendEngineerResponse::ContextSaved { context } => {
+                        ContextResponse::Saved(context)
+                    }
+                    FrontendEngineerResponse::Stopped(reason) => ContextResponse::Stopped(reason),
+                }
+            }
+        })
+    }
+
+    fn outcome(&self, output: ContextResponse) -> Outcome {
+        match output {
+            ContextResponse::Saved(context) => {
+                Outcome::finished(format!("context saved ({} chars)", context.len()))
+            }
+            ContextResponse::Stopped(reason) => Outcome::stopped(reason),
+        }
+    }
+}
+
+/// Runs one mode on the project's llama.cpp model with `apply`, so generated
+/// code is written (and parse-checked) on success — or, when the code needs
+/// approval, proposes it and writes it once approved.
+struct RustEngineer;
+
+#[async_trait]
+impl DispatchableAgent for RustEngineer {
+    type Output = RustEngineerTaskOutput;
+
+    fn agent_type(&self) -> &'static str {
+        "rust_engineer"
+    }
+
+    fn load_config(&self) -> Result<AgentConfig, AgentError> {
+        AgentConfig::load_rust_engineer()
+    }
+
+    async fn run_for_task(
+        &self,
+        run: &TaskRun<'_>,
+        config: AgentConfig,
+    ) -> Result<RustEngineerTaskOutput, AgentError> {
+        let result = run_rust_engineer(run, &config).await;
+        // The session shows the exchange on success and what went wrong
+        // otherwise; the dispatcher decides between retrying and blocking.
+        let messages = match &result {
+            Ok(output) => vec![
+                ("user", output.prompt.clone()),
+                ("assistant", output.raw_response.clone()),
+            ],
+            Err(AgentError::Cancelled(_)) => Vec::new(),
+            Err(e) => vec![("assistant", format!("Failed: {}", e))],
+        };
+        for (role, content) in messages {
+            if let Err(e) = run
+                .agent_storage
+                .create_message(rust_engineer_message(run.session_id, role, content))
+                .await
+            {
+                log::error!(
+                    "[Dispatcher] rust_engineer task={} message error: {}",
+                    run.event.task_id,
+                    e
+                );
+            }
+        }
+        result
+    }
+
+    /// A task is done when the model produced code and, for modes with a
+    /// writer, the code was written; anything else blocks it for a human to
+    /// look at.
+    fn outcome(&self, output: RustEngineerTaskOutput) -> Outcome {
+        let Some(code) = &output.code else {
+            let summary = "Failed: the model returned no code".to_string();
+            return Outcome {
+                status: Some(TaskStatus::Blocked),
+                message: Some(summary.clone()),
+                ..Outcome::stopped(summary)
+            };
+        };
+        let mut summary = match &output.file_path {
+            Some(path) => format!("Wrote `{}`:\n\n```rust\n{}\n```", path, code.trim()),
+            None => format!("```rust\n{}\n```", code.trim()),
+        };
+        if let Some(diff) = &output.diff {
+            summary.push_str(&format!("\n\n```diff\n{}```", diff));
+        }
+        Outcome {
+            status: Some(TaskStatus::Done),
+            message: Some(summary.clone()),
+            ..Outcome::finished(preview(&summary))
+        }
+    }
+
+    fn proposes(&self) -> bool {
+        true
+    }
+
+    fn proposal(&self, run: &TaskRun<'_>, output: &RustEngineerTaskOutput) -> Option<Proposed> {
+        let proposed = ProposedCode {
+            params: RustEngineerTaskParams::from_source_prompt(&run.event.source_prompt).ok()?,
+            code: output.code.clone()?,
+            target_path: output.target_path.clone(),
+            diff: output.diff.clone(),
+            original: output.original.clone(),
+        };
+        Some(Proposed {
+            summary: match &proposed.target_path {
+                Some(path) => format!("{} code for `{}`", proposed.params.mode, path),
+                None => format!("{} code", proposed.params.mode),
+            },
+            artifact: serde_json::to_string(&proposed).ok()?,
+        })
+    }
+
+    async fn apply(&self, proposal: &Proposal, db_path: &str) -> Result<String, AgentError> {
+        let proposed: ProposedCode = serde_json::from_str(&proposal.artifact)?;
+        let project_path = get_project_path(db_path, proposal.project_id)
+            .map_err(AgentError::Other)?
+            .ok_or_else(|| {
+                AgentError::Other(format!("project {} not found", proposal.project_id))
+            })?;
+        Ok(match proposed.write(Path::new(&project_path))? {
+            Some(path) => format!("Wrote `{}`.", path),
+            None => format!("{} code has no file to write to.", proposed.params.mode),
+        })
+    }
+}
+
+/// Parse the task's parameters, resolve the project path and run the mode.
+/// Token usage is recorded on the session whether or not the run succeeds.
+async fn run_rust_engineer(
+    run: &TaskRun<'_>,
+    config: &AgentConfig,
+) -> Result<RustEngineerTaskOutput, AgentError> {
+    let event = run.event;
+    let params = RustEngineerTaskParams::from_source_prompt(&event.source_prompt)
+        .map_err(|e| AgentError::Other(format!("invalid rust_engineer parameters: {}", e)))?;
+    let project_path = get_project_path(run.db_path, event.project_id)
+        .map_err(AgentError::Other)?
+        .ok_or_else(|| AgentError::Other(format!("project {} not found", event.project_id)))?;
+    let params = with_review_feedback(params, run.db_path, event.task_id).await?;
+    let agent = build_rust_engineer_with_config(config, &project_path)
+        .map_err(|e| AgentError::Config(format!("build: {}", e)))?
+        .with_run_control(run.control.clone());
+    let output = agent.run_task(&params, !run.propose).await;
+
+    let usage = agent.usage();
+    let template = output.as_ref().ok().and_then(|o| o.template.clone());
+    if let Some(template) = &template {
+        if let Err(e) = run
+            .agent_storage
+            .set_session_prompt(run.session_id, template)
+            .await
+        {
+            log::error!(
+                "[Dispatcher] rust_engineer task={} prompt record error: {}",
+                event.task_id,
+                e
+            );
+        }
+    }
+    if let Err(e) = run
+        .agent_storage
+        .record_usage(LlmUsage {
+            project_id: event.project_id,
+            agent_type: "rust_engineer".to_string(),
+            task_id: Some(event.task_id),
+            session_id: Some(run.session_id),
+            provider: config.provider.clone(),
+            model: agent.model().to_string(),
+            input_tokens: usage.input_tokens as i64,
+            output_tokens: usage.output_tokens as i64,
+            prompt: template,
+        })
+        .await
+    {
+        log::error!(
+            "[Dispatcher] rust_engineer task={} usage error: {}",
+            event.task_id,
+            e
+        );
+    }
+    output
+}
+
+/// The rust_engineer does not read its session, so the user's comment on
+/// the last proposal it was sent back with goes into the prompt.
+async fn with_review_feedback(
+    mut params: RustEngineerTaskParams,
+    db_path: &str,
+    task_id: i64,
+) -> Result<RustEngineerTaskParams, AgentError> {
+    let last = SqliteProposalStorage::open(db_path)?
+        .list_task_proposals(task_id)
+        .await?
+        .into_iter()
+        .next();
+    if let (Some(prompt), Some(last)) = (params.prompt.as_mut(), last) {
+        if let (ProposalStatus::Rejected | ProposalStatus::ChangesRequested, Some(comment)) =
+            (last.status, &last.comment)
+        {
+            prompt.push_str(&format!(
+                "\n\nA reviewer sent back the previous attempt: {}",
+                comment
+            ));
+        }
+    }
+    Ok(params)
+}
+
+fn rust_engineer_message(session_id: i64, role: &str, content: String) -> ChatMessage {
+    ChatMessage {
+        id: None,
+        session_id,
+        role: role.to_string(),
+        agent_type: (role != "user").then(|| "rust_engineer".to_string()),
+        content,
+        tool_call_id: None,
+        tool_name: None,
+        turn_id: None,
+        created_at: 0,
+    }
+}
+
+/// The first 80 characters, for log lines.
+fn preview(text: &str) -> String {
+    format!("{}…", text.chars().take(80).collect::<String>())
+}
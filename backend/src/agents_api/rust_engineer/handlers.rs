@@ -1,7 +1,7 @@
 use std::path::Path;
 
 use actix_web::{post, web, HttpResponse, Responder};
-use nocodo_agents::build_rust_engineer;
+use nocodo_agents::{build_rust_engineer, MiddlewareKind};
 use rusqlite::OptionalExtension;
 use serde::{Deserialize, Serialize};
 
@@ -14,6 +14,18 @@ pub struct RunRequest {
     pub struct_name: Option<String>,
     pub fn_name: Option<String>,
     pub prompt: Option<String>,
+    /// HTTP verb for `actix_handler` mode (`get`, `post`, `put`, `patch`, `delete`).
+    pub http_method: Option<String>,
+    /// Route path for `actix_handler` mode, e.g. `/contacts/{id}`.
+    pub route_path: Option<String>,
+    /// For `middleware` mode: `transform` (default) or `extractor`.
+    pub middleware_kind: Option<String>,
+    /// For `middleware` mode: the type to generate, e.g. `RequireRole`.
+    pub middleware_name: Option<String>,
+    /// For `transform` middleware with `apply`: wrap the whole `App`, which
+    /// guards every route. Defaults to `false`.
+    #[serde(default)]
+    pub wrap_app: bool,
     /// When `true`, write generated code to disk. Defaults to `false`.
     #[serde(default)]
     pub apply: bool,
@@ -28,6 +40,9 @@ pub struct RunResponse {
     /// Relative file path of the written file when `apply` is `true`.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file_path: Option<String>,
+    /// Unified diff of the change for in-place edit modes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
 }
 
 /// POST /api/rust-engineer/run
@@ -82,6 +97,7 @@ pub async fn run(state: web::Data<AgentState>, body: web::Json<RunRequest>) -> i
                         raw_response: output.raw_response,
                         code: output.code,
                         file_path: output.file_path,
+                        diff: None,
                     }),
                     Err(e) => HttpResponse::InternalServerError()
                         .json(serde_json::json!({ "error": format!("{}", e) })),
@@ -94,6 +110,7 @@ pub async fn run(state: web::Data<AgentState>, body: web::Json<RunRequest>) -> i
                         raw_response: output.raw_response,
                         code: output.code,
                         file_path: None,
+                        diff: None,
                     }),
                     Err(e) => HttpResponse::InternalServerError()
                         .json(serde_json::json!({ "error": format!("{}", e) })),
@@ -120,6 +137,7 @@ pub async fn run(state: web::Data<AgentState>, body: web::Json<RunRequest>) -> i
                         raw_response: output.raw_response,
                         code: output.code,
                         file_path: output.file_path,
+                        diff: None,
                     }),
                     Err(e) => HttpResponse::InternalServerError()
                         .json(serde_json::json!({ "error": format!("{}", e) })),
@@ -132,12 +150,215 @@ pub async fn run(state: web::Data<AgentState>, body: web::Json<RunRequest>) -> i
                         raw_response: output.raw_response,
                         code: output.code,
                         file_path: None,
+                        diff: None,
                     }),
                     Err(e) => HttpResponse::InternalServerError()
                         .json(serde_json::json!({ "error": format!("{}", e) })),
                 }
             }
         }
+        "actix_handler" => {
+            let Some(struct_name) = body
+                .struct_name
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+            else {
+                return HttpResponse::BadRequest().json(
+                    serde_json::json!({ "error": "struct_name is required for actix_handler mode" }),
+                );
+            };
+            let Some(fn_name) = body
+                .fn_name
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+            else {
+                return HttpResponse::BadRequest().json(
+                    serde_json::json!({ "error": "fn_name is required for actix_handler mode" }),
+                );
+            };
+            let Some(http_method) = body
+                .http_method
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+            else {
+                return HttpResponse::BadRequest().json(
+                    serde_json::json!({ "error": "http_method is required for actix_handler mode" }),
+                );
+            };
+            let Some(route_path) = body
+                .route_path
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+            else {
+                return HttpResponse::BadRequest().json(
+                    serde_json::json!({ "error": "route_path is required for actix_handler mode" }),
+                );
+            };
+
+            let result = if body.apply {
+                agent
+                    .actix_handler_write(struct_name, fn_name, http_method, route_path)
+                    .await
+            } else {
+                agent
+                    .actix_handler(struct_name, fn_name, http_method, route_path)
+                    .await
+            };
+            match result {
+                Ok(output) => HttpResponse::Ok().json(RunResponse {
+                    system_prompt: Some(output.system_prompt),
+                    prompt: output.prompt,
+                    raw_response: output.raw_response,
+                    code: output.code,
+                    file_path: if body.apply { output.file_path } else { None },
+                    diff: None,
+                }),
+                Err(e) => HttpResponse::InternalServerError()
+                    .json(serde_json::json!({ "error": format!("{}", e) })),
+            }
+        }
+        "middleware" => {
+            let Some(name) = body
+                .middleware_name
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+            else {
+                return HttpResponse::BadRequest().json(
+                    serde_json::json!({ "error": "middleware_name is required for middleware mode" }),
+                );
+            };
+            let Some(prompt) = body
+                .prompt
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+            else {
+                return HttpResponse::BadRequest().json(
+                    serde_json::json!({ "error": "prompt is required for middleware mode" }),
+                );
+            };
+            let kind_str = body.middleware_kind.as_deref().unwrap_or("transform");
+            let Some(kind) = MiddlewareKind::parse(kind_str) else {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": format!("unknown middleware_kind: {} (expected transform or extractor)", kind_str)
+                }));
+            };
+
+            let result = if body.apply {
+                agent
+                    .middleware_write(name, kind, prompt, body.wrap_app)
+                    .await
+            } else {
+                agent.middleware(name, kind, prompt).await
+            };
+            match result {
+                Ok(output) => HttpResponse::Ok().json(RunResponse {
+                    system_prompt: Some(output.system_prompt),
+                    prompt: output.prompt,
+                    raw_response: output.raw_response,
+                    code: output.code,
+                    file_path: output.file_path,
+                    diff: None,
+                }),
+                Err(e) => HttpResponse::InternalServerError()
+                    .json(serde_json::json!({ "error": format!("{}", e) })),
+            }
+        }
+        "diesel_model_test" => {
+            let Some(struct_name) = body
+                .struct_name
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+            else {
+                return HttpResponse::BadRequest().json(
+                    serde_json::json!({ "error": "struct_name is required for diesel_model_test mode" }),
+                );
+            };
+            let Some(fn_name) = body
+                .fn_name
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+            else {
+                return HttpResponse::BadRequest().json(
+                    serde_json::json!({ "error": "fn_name is required for diesel_model_test mode" }),
+                );
+            };
+
+            let result = if body.apply {
+                agent.diesel_model_test_write(struct_name, fn_name).await
+            } else {
+                agent.diesel_model_test(struct_name, fn_name).await
+            };
+            match result {
+                Ok(output) => HttpResponse::Ok().json(RunResponse {
+                    system_prompt: Some(output.system_prompt),
+                    prompt: output.prompt,
+                    raw_response: output.raw_response,
+                    code: output.code,
+                    file_path: if body.apply { output.file_path } else { None },
+                    diff: None,
+                }),
+                Err(e) => HttpResponse::InternalServerError()
+                    .json(serde_json::json!({ "error": format!("{}", e) })),
+            }
+        }
+        "edit_impl_fn" => {
+            let Some(struct_name) = body
+                .struct_name
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+            else {
+                return HttpResponse::BadRequest().json(
+                    serde_json::json!({ "error": "struct_name is required for edit_impl_fn mode" }),
+                );
+            };
+            let Some(fn_name) = body
+                .fn_name
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+            else {
+                return HttpResponse::BadRequest().json(
+                    serde_json::json!({ "error": "fn_name is required for edit_impl_fn mode" }),
+                );
+            };
+            let Some(prompt) = body
+                .prompt
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+            else {
+                return HttpResponse::BadRequest().json(
+                    serde_json::json!({ "error": "prompt is required for edit_impl_fn mode" }),
+                );
+            };
+
+            let result = if body.apply {
+                agent.edit_impl_fn_write(struct_name, fn_name, prompt).await
+            } else {
+                agent.edit_impl_fn(struct_name, fn_name, prompt).await
+            };
+            match result {
+                Ok(output) => HttpResponse::Ok().json(RunResponse {
+                    system_prompt: Some(output.system_prompt),
+                    prompt: output.prompt,
+                    raw_response: output.raw_response,
+                    code: output.code,
+                    file_path: if body.apply { output.file_path } else { None },
+                    diff: output.diff,
+                }),
+                Err(e) => HttpResponse::InternalServerError()
+                    .json(serde_json::json!({ "error": format!("{}", e) })),
+            }
+        }
         "diesel_model_fn" | "diesel_model" => {
             let Some(struct_name) = body
                 .struct_name
@@ -161,11 +382,12 @@ pub async fn run(state: web::Data<AgentState>, body: web::Json<RunRequest>) -> i
 
             match agent.diesel_model_fn(struct_name, fn_name).await {
                 Ok(output) => HttpResponse::Ok().json(RunResponse {
-                    system_prompt: None,
+                    system_prompt: Some(output.system_prompt),
                     prompt: output.prompt,
                     raw_response: output.raw_response,
                     code: output.code,
                     file_path: None,
+                    diff: None,
                 }),
                 Err(e) => HttpResponse::InternalServerError()
                     .json(serde_json::json!({ "error": format!("{}", e) })),
@@ -176,7 +398,7 @@ pub async fn run(state: web::Data<AgentState>, body: web::Json<RunRequest>) -> i
     }
 }
 
-fn get_project_path(db_path: &str, project_id: i64) -> Result<Option<String>, String> {
+pub(crate) fn get_project_path(db_path: &str, project_id: i64) -> Result<Option<String>, String> {
     let conn = rusqlite::Connection::open(db_path).map_err(|e| e.to_string())?;
     conn.query_row(
         "SELECT path FROM project WHERE id = ?1",
@@ -0,0 +1,292 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use nocodo_agents::{
+    build_stack_reviewer, schedule, AgentConfig, AgentError, Schedule, ScheduleRunStatus,
+    ScheduleStorage, SqliteScheduleStorage, SqliteTaskStorage, TaskStorage,
+};
+
+use super::dispatcher::JobQueue;
+use super::registry::chain_providers;
+
+/// Agents that run on a whole project rather than on a task, and so can be
+/// scheduled.
+pub const SCHEDULABLE_AGENTS: &[&str] = &["stack_reviewer"];
+/// How often the scheduler checks every project's schedules.
+const TICK_INTERVAL: Duration = Duration::from_secs(60);
+/// Stop counting changed files past this many; no trigger needs more.
+const MAX_COUNTED_FILES: usize = 10_000;
+
+// ---------------------------------------------------------------------------
+// Scheduler — runs project-wide agents on a schedule or a trigger
+// ---------------------------------------------------------------------------
+
+/// Starts runs of project-wide agents — from the `[[schedule]]` entries of
+/// project.toml, or on request — and records each in `agent_schedule_run`.
+/// A project never has two runs of the same agent at once, and each run
+/// waits for a slot within the job queue's agent and provider limits.
+#[derive(Clone)]
+pub struct Scheduler {
+    db_path: String,
+    jobs: JobQueue,
+    schedules: Arc<Vec<Schedule>>,
+    run_timeout: Duration,
+    /// Baseline of the triggers of projects the agent never ran on.
+    started_at: i64,
+}
+
+impl Scheduler {
+    pub fn new(
+        db_path: String,
+        jobs: JobQueue,
+        schedules: Vec<Schedule>,
+        run_timeout: Duration,
+    ) -> Self {
+        let schedules = schedules
+            .into_iter()
+            .filter(|s| {
+                let known = SCHEDULABLE_AGENTS.contains(&s.agent.as_str());
+                if !known {
+                    log::warn!(
+                        "[Scheduler] {} cannot run on a schedule; expected one of {:?}",
+                        s.agent,
+                        SCHEDULABLE_AGENTS
+                    );
+                }
+                known
+            })
+            .collect();
+        Self {
+            db_path,
+            jobs,
+            schedules: Arc::new(schedules),
+            run_timeout,
+            started_at: now(),
+        }
+    }
+
+    pub async fn run(self) {
+        log::info!(
+            "[Scheduler] Started with {} schedule(s)",
+            self.schedules.len()
+        );
+        loop {
+            if let Err(e) = self.tick().await {
+                log::error!("[Scheduler] tick error: {}", e);
+            }
+            tokio::time::sleep(TICK_INTERVAL).await;
+        }
+    }
+
+    /// Fail the runs a stopped server left running, then start every
+    /// project's due schedules.
+    async fn tick(&self) -> Result<(), AgentError> {
+        let runs = SqliteScheduleStorage::open(&self.db_path)?;
+        // A live run ends within its timeout; one running for longer lost
+        // its server.
+        let stale_before =
+            now() - self.run_timeout.as_secs() as i64 - TICK_INTERVAL.as_secs() as i64;
+        let failed = runs.fail_stale_runs(stale_before).await?;
+        if failed > 0 {
+            log::warn!("[Scheduler] failed {} interrupted run(s)", failed);
+        }
+        if self.schedules.is_empty() {
+            return Ok(());
+        }
+        for (project_id, project_path) in list_projects(&self.db_path).map_err(AgentError::Other)? {
+            if !std::path::Path::new(&project_path).is_dir() {
+                continue;
+            }
+            for schedule in self.schedules.iter() {
+                let reason = match self.due(&runs, project_id, &project_path, schedule).await {
+                    Ok(Some(reason)) => reason,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        log::error!(
+                            "[Scheduler] {} project={} trigger error: {}",
+                            schedule.agent,
+                            project_id,
+                            e
+                        );
+                        continue;
+                    }
+                };
+                if let Err(e) = self
+                    .start(project_id, &project_path, &schedule.agent, &reason)
+                    .await
+                {
+                    log::error!(
+                        "[Scheduler] {} project={} start error: {}",
+                        schedule.agent,
+                        project_id,
+                        e
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Why the schedule should run on the project now, or `None` when it
+    /// should not (or its agent is still running there).
+    async fn due(
+        &self,
+        runs: &SqliteScheduleStorage,
+        project_id: i64,
+        project_path: &str,
+        schedule: &Schedule,
+    ) -> Result<Option<String>, AgentError> {
+        let since = match runs.last_run(project_id, &schedule.agent).await? {
+            Some(run) if run.status == ScheduleRunStatus::Running => return Ok(None),
+            Some(run) => run.started_at,
+            None => self.started_at,
+        };
+        if schedule.cron_due(since, now()) {
+            let cron = schedule.cron.as_ref().map(|c| c.as_str()).unwrap_or("");
+            return Ok(Some(format!("cron {}", cron)));
+        }
+        if !schedule.after_agents.is_empty() {
+            let finished = SqliteTaskStorage::open(&self.db_path)?
+                .count_finished_tasks(project_id, &schedule.after_agents, since)
+                .await?;
+            if finished > 0 {
+                return Ok(Some(format!(
+                    "{} {} task(s) finished",
+                    finished,
+                    schedule.after_agents.join("/")
+                )));
+            }
+        }
+        if let Some(threshold) = schedule.files_changed {
+            let root = std::path::PathBuf::from(project_path);
+            let changed = tokio::task::spawn_blocking(move || {
+                schedule::count_changed_files(&root, since, MAX_COUNTED_FILES)
+            })
+            .await
+            .map_err(|e| AgentError::Other(e.to_string()))?;
+            if changed >= threshold.max(1) {
+                return Ok(Some(format!("{} files changed", changed)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Record a run of `agent_type` on the project and start it. Returns the
+    /// run id, or `None` when the agent is already running on the project.
+    pub async fn start(
+        &self,
+        project_id: i64,
+        project_path: &str,
+        agent_type: &str,
+        reason: &str,
+    ) -> Result<Option<i64>, AgentError> {
+        if !SCHEDULABLE_AGENTS.contains(&agent_type) {
+            return Err(AgentError::Config(format!(
+                "{} cannot run on a whole project",
+                agent_type
+            )));
+        }
+        let runs = SqliteScheduleStorage::open(&self.db_path)?;
+        let Some(run_id) = runs.begin_run(project_id, agent_type, reason).await? else {
+            log::info!(
+                "[Scheduler] {} project={} already running; {} skipped",
+                agent_type,
+                project_id,
+                reason
+            );
+            return Ok(None);
+        };
+        log::info!(
+            "[Scheduler] {} project={} run={} started: {}",
+            agent_type,
+            project_id,
+            run_id,
+            reason
+        );
+
+        let scheduler = self.clone();
+        let project_path = project_path.to_string();
+        let agent_type = agent_type.to_string();
+        tokio::spawn(async move {
+            let result = match tokio::time::timeout(
+                scheduler.run_timeout,
+                scheduler.run_agent(project_id, &project_path, &agent_type),
+            )
+            .await
+            {
+                Ok(result) => result,
+                Err(_) => Err(AgentError::Cancelled(format!(
+                    "ran past {}s",
+                    scheduler.run_timeout.as_secs()
+                ))),
+            };
+            let (status, summary) = match result {
+                Ok(summary) => (ScheduleRunStatus::Done, summary),
+                Err(e) => (ScheduleRunStatus::Failed, e.to_string()),
+            };
+            log::info!(
+                "[Scheduler] {} project={} run={} {}: {}",
+                agent_type,
+                project_id,
+                run_id,
+                status.as_str(),
+                summary
+            );
+            if let Err(e) = runs.finish_run(run_id, status, &summary).await {
+                log::error!("[Scheduler] run={} update error: {}", run_id, e);
+            }
+        });
+        Ok(Some(run_id))
+    }
+
+    /// Run the agent once it gets a slot; the wait counts against the run's
+    /// timeout.
+    async fn run_agent(
+        &self,
+        project_id: i64,
+        project_path: &str,
+        agent_type: &str,
+    ) -> Result<String, AgentError> {
+        match agent_type {
+            "stack_reviewer" => {
+                let config = AgentConfig::load_stack_reviewer()?;
+                let _slot = self
+                    .jobs
+                    .wait_for_slot(agent_type, chain_providers(&config))
+                    .await;
+                let agent = build_stack_reviewer(&config, &self.db_path, project_id, project_path)?;
+                let result = agent.run().await?;
+                Ok(format!(
+                    "{} notes emitted. {}",
+                    result.emitted_ids.len(),
+                    result.summary
+                ))
+            }
+            other => Err(AgentError::Config(format!(
+                "{} cannot run on a whole project",
+                other
+            ))),
+        }
+    }
+}
+
+/// Every project that has a path.
+fn list_projects(db_path: &str) -> Result<Vec<(i64, String)>, String> {
+    let conn = rusqlite::Connection::open(db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT id, path FROM project WHERE path != '' ORDER BY id")
+        .map_err(|e| e.to_string())?;
+    let projects = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(projects)
+}
+
+fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
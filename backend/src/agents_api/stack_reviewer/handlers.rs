@@ -1,7 +1,9 @@
 use std::path::Path;
 
 use actix_web::{get, post, web, HttpResponse, Responder};
-use nocodo_agents::{build_stack_reviewer, AgentConfig, SqliteStackNoteStorage, StackNoteStorage};
+use nocodo_agents::{
+    ScheduleStorage, SqliteScheduleStorage, SqliteStackNoteStorage, StackNoteStorage,
+};
 use rusqlite::OptionalExtension;
 use serde::Deserialize;
 
@@ -17,7 +19,13 @@ pub struct NotesQuery {
     pub project_id: i64,
 }
 
+#[derive(Deserialize)]
+pub struct RunsQuery {
+    pub project_id: i64,
+}
+
 /// POST /api/stack-reviewer/run
+/// Start a stack review of the project; 409 while one is running.
 #[post("/api/stack-reviewer/run")]
 pub async fn run_review(
     state: web::Data<AgentState>,
@@ -48,35 +56,47 @@ pub async fn run_review(
         }));
     }
 
-    let db_path = state.db_path.clone();
-    let config: AgentConfig = state.config.clone();
-
-    tokio::spawn(async move {
-        let agent = match build_stack_reviewer(&config, &db_path, project_id, &project_path) {
-            Ok(a) => a,
-            Err(e) => {
-                log::error!("[stack_reviewer] Failed to build agent: {}", e);
-                return;
-            }
-        };
-        match agent.run().await {
-            Ok(result) => {
-                log::info!(
-                    "[stack_reviewer] done: {} notes emitted. {}",
-                    result.emitted_ids.len(),
-                    result.summary
-                );
-            }
-            Err(e) => {
-                log::error!("[stack_reviewer] Agent run failed: {}", e);
-            }
+    match state
+        .scheduler
+        .start(project_id, &project_path, "stack_reviewer", "requested")
+        .await
+    {
+        Ok(Some(run_id)) => HttpResponse::Accepted().json(serde_json::json!({
+            "status": "started",
+            "project_id": project_id,
+            "run_id": run_id,
+        })),
+        Ok(None) => HttpResponse::Conflict().json(serde_json::json!({
+            "error": format!("A stack review of project {} is already running", project_id)
+        })),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(serde_json::json!({ "error": format!("{}", e) })),
+    }
+}
+
+/// GET /api/stack-reviewer/runs?project_id=N
+/// The project's stack reviews — scheduled, triggered or requested — newest
+/// first.
+#[get("/api/stack-reviewer/runs")]
+pub async fn list_runs(
+    state: web::Data<AgentState>,
+    query: web::Query<RunsQuery>,
+) -> impl Responder {
+    let project_id = query.project_id;
+
+    let storage = match SqliteScheduleStorage::open(&state.db_path) {
+        Ok(s) => s,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("{}", e) }));
         }
-    });
+    };
 
-    HttpResponse::Accepted().json(serde_json::json!({
-        "status": "started",
-        "project_id": project_id,
-    }))
+    match storage.list_runs(project_id).await {
+        Ok(runs) => HttpResponse::Ok().json(serde_json::json!({ "runs": runs })),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(serde_json::json!({ "error": format!("{}", e) })),
+    }
 }
 
 /// GET /api/stack-reviewer/notes?project_id=N
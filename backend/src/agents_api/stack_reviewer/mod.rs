@@ -4,5 +4,6 @@ use actix_web::web;
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(handlers::run_review)
-        .service(handlers::list_notes);
+        .service(handlers::list_notes)
+        .service(handlers::list_runs);
 }
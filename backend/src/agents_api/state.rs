@@ -1,16 +1,21 @@
-use nocodo_agents::AgentConfig;
+use nocodo_agents::{AgentConfig, ApprovalPolicy, Schedule};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex, Notify};
+use std::time::Duration;
+use tokio::sync::{Mutex, Notify};
 
-use super::dispatcher::{AgentDispatcher, DispatchEvent};
+use super::dispatcher::{AgentDispatcher, JobQueue};
+use super::registry::AgentRegistry;
+use super::scheduler::Scheduler;
+use crate::config::DispatchConfig;
 
 /// Shared application state for agent handlers.
 pub struct AgentState {
-    pub config: AgentConfig,
     pub db_path: String,
-    /// Send a DispatchEvent to kick off an agent for a newly created task.
-    pub dispatch_tx: mpsc::UnboundedSender<DispatchEvent>,
+    /// Queue a DispatchEvent to kick off an agent for a newly created task.
+    pub jobs: JobQueue,
+    /// Starts runs of project-wide agents such as the stack reviewer.
+    pub scheduler: Scheduler,
     /// Notifies long-polling board clients when tasks or epics change.
     pub board_notify: Arc<Notify>,
     /// Per-session Notifies for user-chat long-polling.
@@ -18,19 +23,28 @@ pub struct AgentState {
 }
 
 impl AgentState {
-    pub fn new(db_path: String) -> Result<Self, String> {
-        let config =
-            AgentConfig::load().map_err(|e| format!("Failed to load agent config: {}", e))?;
+    pub fn new(
+        db_path: String,
+        dispatch: DispatchConfig,
+        approval: ApprovalPolicy,
+        schedules: Vec<Schedule>,
+    ) -> Result<Self, String> {
+        AgentConfig::load().map_err(|e| format!("Failed to load agent config: {}", e))?;
 
         let board_notify = Arc::new(Notify::new());
-        let (tx, rx) = mpsc::unbounded_channel::<DispatchEvent>();
-        let dispatcher = AgentDispatcher::new(rx, db_path.clone(), board_notify.clone());
+        let run_timeout = Duration::from_secs(dispatch.run_timeout_secs);
+        let jobs = JobQueue::open(&db_path, dispatch, AgentRegistry::builtin())
+            .map_err(|e| format!("Failed to open job queue: {}", e))?
+            .with_approval(approval);
+        let dispatcher = AgentDispatcher::new(jobs.clone(), db_path.clone(), board_notify.clone());
         tokio::spawn(dispatcher.run());
+        let scheduler = Scheduler::new(db_path.clone(), jobs.clone(), schedules, run_timeout);
+        tokio::spawn(scheduler.clone().run());
 
         Ok(Self {
-            config,
             db_path,
-            dispatch_tx: tx,
+            jobs,
+            scheduler,
             board_notify,
             chat_notify: Arc::new(Mutex::new(HashMap::new())),
         })
@@ -44,3 +58,18 @@ impl AgentState {
             .clone()
     }
 }
+
+#[cfg(test)]
+impl AgentState {
+    /// State over `jobs` on `db_path`, without a dispatcher claiming its jobs
+    /// or a scheduler running.
+    pub(crate) fn for_tests(db_path: String, jobs: JobQueue) -> Self {
+        Self {
+            scheduler: Scheduler::new(db_path.clone(), jobs.clone(), Vec::new(), Duration::ZERO),
+            db_path,
+            jobs,
+            board_notify: Arc::new(Notify::new()),
+            chat_notify: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
@@ -122,6 +122,7 @@ pub async fn generate_form(
             created_by_agent: "user".to_string(),
             created_at: 0,
             updated_at: 0,
+            created_by_task_id: None,
         })
         .await
     {
@@ -162,18 +163,22 @@ pub async fn generate_form(
             .json(serde_json::json!({ "error": format!("{}", e) }));
     }
 
-    // Fire the dispatch event so the background dispatcher picks it up.
+    // Queue the job so the background dispatcher picks it up.
     // source_prompt is not used by dispatch_ui_designer (session already created),
     // but included for the startup reconciliation path.
-    state
-        .dispatch_tx
-        .send(crate::agents_api::dispatcher::DispatchEvent {
+    if let Err(e) = state
+        .jobs
+        .enqueue(crate::agents_api::dispatcher::DispatchEvent {
             task_id,
             project_id,
             assigned_to_agent: AGENT_TYPE.to_string(),
             source_prompt: table_def_json,
         })
-        .ok();
+        .await
+    {
+        return HttpResponse::InternalServerError()
+            .json(serde_json::json!({ "error": format!("{}", e) }));
+    }
     state.board_notify.notify_waiters();
 
     HttpResponse::Accepted().json(GenerateFormQueued { task_id })
@@ -0,0 +1,224 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use nocodo_agents::{AgentStorage, LlmUsageTotal, SqliteAgentStorage};
+
+use crate::agents_api::state::AgentState;
+use crate::agents_api::usage::types::{UsageGroup, UsageQuery, UsageResponse};
+use crate::config::Config;
+
+/// GET /api/agents/usage?project_id=X
+/// Token usage and cost of every recorded LLM call in the project, totalled
+/// per agent, per task and per provider/model.
+#[get("/api/agents/usage")]
+pub async fn get_usage(
+    state: web::Data<AgentState>,
+    config: web::Data<Config>,
+    query: web::Query<UsageQuery>,
+) -> impl Responder {
+    let storage = match SqliteAgentStorage::open(&state.db_path) {
+        Ok(s) => s,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Storage error: {}", e) }));
+        }
+    };
+
+    let rows = match storage.list_usage_totals(query.project_id).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": format!("Failed to load usage: {}", e) }));
+        }
+    };
+
+    HttpResponse::Ok().json(summarize(query.project_id, &rows, &config))
+}
+
+/// Total `rows` overall, per agent, per task and per provider/model, costed
+/// with the configured prices.
+fn summarize(project_id: i64, rows: &[LlmUsageTotal], config: &Config) -> UsageResponse {
+    let mut total = UsageGroup {
+        cost_usd: Some(0.0),
+        ..Default::default()
+    };
+    let mut by_agent: Vec<UsageGroup> = Vec::new();
+    let mut by_task: Vec<UsageGroup> = Vec::new();
+    let mut by_model: Vec<UsageGroup> = Vec::new();
+
+    for row in rows {
+        let cost = config.usage_cost(
+            &row.provider,
+            &row.model,
+            row.input_tokens,
+            row.output_tokens,
+        );
+        add(&mut total, row, cost);
+        add(
+            group(
+                &mut by_agent,
+                |g| g.agent_type.as_deref() == Some(&row.agent_type),
+                || UsageGroup {
+                    agent_type: Some(row.agent_type.clone()),
+                    ..Default::default()
+                },
+            ),
+            row,
+            cost,
+        );
+        if let Some(task_id) = row.task_id {
+            add(
+                group(
+                    &mut by_task,
+                    |g| g.task_id == Some(task_id),
+                    || UsageGroup {
+                        agent_type: Some(row.agent_type.clone()),
+                        task_id: Some(task_id),
+                        ..Default::default()
+                    },
+                ),
+                row,
+                cost,
+            );
+        }
+        add(
+            group(
+                &mut by_model,
+                |g| {
+                    g.provider.as_deref() == Some(&row.provider)
+                        && g.model.as_deref() == Some(&row.model)
+                },
+                || UsageGroup {
+                    provider: Some(row.provider.clone()),
+                    model: Some(row.model.clone()),
+                    ..Default::default()
+                },
+            ),
+            row,
+            cost,
+        );
+    }
+
+    UsageResponse {
+        project_id,
+        total,
+        by_agent,
+        by_task,
+        by_model,
+    }
+}
+
+/// The group matching `matches`, appended via `new` on first sight. New groups
+/// start with a zero cost so unpriced rows can turn it to `None`.
+fn group(
+    groups: &mut Vec<UsageGroup>,
+    matches: impl Fn(&UsageGroup) -> bool,
+    new: impl FnOnce() -> UsageGroup,
+) -> &mut UsageGroup {
+    let idx = match groups.iter().position(matches) {
+        Some(idx) => idx,
+        None => {
+            groups.push(UsageGroup {
+                cost_usd: Some(0.0),
+                ..new()
+            });
+            groups.len() - 1
+        }
+    };
+    &mut groups[idx]
+}
+
+fn add(group: &mut UsageGroup, row: &LlmUsageTotal, cost: Option<f64>) {
+    group.calls += row.calls;
+    group.input_tokens += row.input_tokens;
+    group.output_tokens += row.output_tokens;
+    group.cost_usd = match (group.cost_usd, cost) {
+        (Some(a), Some(b)) => Some(a + b),
+        _ => None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ModelPrice;
+    use nocodo_agents::LlmUsage;
+
+    fn usage(agent_type: &str, task_id: Option<i64>, model: &str, input: i64) -> LlmUsage {
+        LlmUsage {
+            project_id: 1,
+            agent_type: agent_type.to_string(),
+            task_id,
+            session_id: None,
+            provider: "openai".to_string(),
+            model: model.to_string(),
+            input_tokens: input,
+            output_tokens: input / 10,
+            prompt: None,
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_summarize_groups_and_prices_recorded_usage() {
+        let path = std::env::temp_dir().join(format!("nocodo-usage-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db_path = path.to_string_lossy().into_owned();
+        crate::db::run_startup_migrations(&db_path).expect("migrate");
+        rusqlite::Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO project (id, name, path, created_at) VALUES (1, 'P', '', 0);
+                 INSERT INTO task (id, project_id, title, description, source_prompt,
+                     assigned_to_agent, created_by_agent, created_at, updated_at)
+                 VALUES (7, 1, 'A', '', '', 'db_engineer', 'project_manager', 0, 0),
+                        (8, 1, 'B', '', '', 'db_engineer', 'project_manager', 0, 0);",
+            )
+            .expect("seed db");
+        let storage = SqliteAgentStorage::open(&db_path).unwrap();
+        for row in [
+            usage("db_engineer", Some(7), "priced", 1_000_000),
+            usage("db_engineer", Some(7), "priced", 1_000_000),
+            usage("db_engineer", Some(8), "priced", 2_000_000),
+            usage("project_manager", None, "unpriced", 500),
+        ] {
+            storage.record_usage(row).await.unwrap();
+        }
+        let rows = storage.list_usage_totals(1).await.unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let config = Config {
+            pricing: vec![ModelPrice {
+                provider: "openai".to_string(),
+                model: "priced".to_string(),
+                input_per_mtok: 1.0,
+                output_per_mtok: 10.0,
+            }],
+            ..Config::default()
+        };
+        let summary = summarize(1, &rows, &config);
+
+        assert_eq!(summary.total.calls, 4);
+        assert_eq!(summary.total.input_tokens, 4_000_500);
+        // One unpriced model leaves the project total without a cost.
+        assert_eq!(summary.total.cost_usd, None);
+
+        let db = &summary.by_agent[0];
+        assert_eq!(db.agent_type.as_deref(), Some("db_engineer"));
+        assert_eq!(db.calls, 3);
+        // 4M input at $1 plus 400k output at $10 per million.
+        assert_eq!(db.cost_usd, Some(8.0));
+        let pm = &summary.by_agent[1];
+        assert_eq!(pm.agent_type.as_deref(), Some("project_manager"));
+        assert_eq!(pm.cost_usd, None);
+
+        // Usage without a task is left out of the per-task totals.
+        let tasks: Vec<(Option<i64>, i64, Option<f64>)> = summary
+            .by_task
+            .iter()
+            .map(|g| (g.task_id, g.calls, g.cost_usd))
+            .collect();
+        assert_eq!(
+            tasks,
+            vec![(Some(7), 2, Some(4.0)), (Some(8), 1, Some(4.0))]
+        );
+        assert_eq!(summary.by_model.len(), 2);
+    }
+}
@@ -0,0 +1,8 @@
+pub mod handlers;
+pub mod types;
+
+use actix_web::web;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(handlers::get_usage);
+}
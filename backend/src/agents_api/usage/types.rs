@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    pub project_id: i64,
+}
+
+/// Token and cost totals for one group of recorded completions.
+#[derive(Debug, Default, Serialize)]
+pub struct UsageGroup {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub calls: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    /// `None` when any model in the group has no configured price.
+    pub cost_usd: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageResponse {
+    pub project_id: i64,
+    pub total: UsageGroup,
+    pub by_agent: Vec<UsageGroup>,
+    pub by_task: Vec<UsageGroup>,
+    pub by_model: Vec<UsageGroup>,
+}
@@ -1,12 +1,12 @@
 use crate::agents_api::state::AgentState;
 use actix_web::{get, post, web, HttpResponse, Responder};
 use nocodo_agents::{
-    build_project_manager, AgentConfig, AgentStorage, AgentType, CommentStorage,
-    FinalizeSessionParams, MessageContent, PmUserSessionResult, PoSessionResult, ProductOwnerAgent,
-    ProjectNoteStorage, SqliteAgentStorage, SqliteCommentStorage, SqliteProjectNoteStorage,
-    SqliteTaskStorage, SqliteUserChatStorage, SqliteUserStorage, StructuredQuestion,
-    StructuredResponse, TaskStorage, UserChatMessageRow, UserChatSessionRow, UserChatStorage,
-    UserStorage,
+    build_context_window, build_project_manager, project_prompts, run_with_fallback, AgentConfig,
+    AgentStorage, AgentType, CommentStorage, FinalizeSessionParams, MessageContent,
+    PmUserSessionResult, PoSessionResult, ProductOwnerAgent, ProjectNoteStorage,
+    SqliteAgentStorage, SqliteCommentStorage, SqliteProjectNoteStorage, SqliteTaskStorage,
+    SqliteUserChatStorage, SqliteUserStorage, StructuredQuestion, StructuredResponse, TaskStorage,
+    UserChatMessageRow, UserChatSessionRow, UserChatStorage, UserStorage,
 };
 use serde::Deserialize;
 use serde::Serialize;
@@ -39,6 +39,44 @@ fn merge_consecutive_roles(messages: Vec<(String, String)>) -> Vec<(String, Stri
     result
 }
 
+/// Replace the oldest rows with a stored summary once the history outgrows
+/// the agent's context budget. On failure the full history is used.
+async fn fit_context_window(
+    config: &AgentConfig,
+    db_path: &str,
+    chat_storage: &SqliteUserChatStorage,
+    agent_type: AgentType,
+    project_id: i64,
+    session_id: i64,
+    rows: Vec<UserChatMessageRow>,
+) -> (Option<String>, Vec<UserChatMessageRow>) {
+    let fitted = async {
+        let window = build_context_window(config)?;
+        let usage_storage = SqliteAgentStorage::open(db_path)?;
+        window
+            .user_chat_history(
+                chat_storage,
+                &usage_storage,
+                project_id,
+                agent_type,
+                session_id,
+                rows.clone(),
+            )
+            .await
+    };
+    match fitted.await {
+        Ok(fitted) => fitted,
+        Err(e) => {
+            log::warn!(
+                "user_chat: fit session {} to context window: {}",
+                session_id,
+                e
+            );
+            (None, rows)
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Request / Response types
 // ---------------------------------------------------------------------------
@@ -573,16 +611,35 @@ async fn run_po_intake(
         }
     }
 
-    let raw_messages: Vec<(String, String)> = messages
-        .iter()
-        .map(|m| {
+    let config = match AgentConfig::load() {
+        Ok(c) => c,
+        Err(e) => {
+            log::warn!("user_chat: load config: {}", e);
+            return;
+        }
+    };
+
+    let (summary, messages) = fit_context_window(
+        &config,
+        &db_path,
+        &chat_storage,
+        AgentType::ProductOwner,
+        project_id,
+        session_id,
+        messages,
+    )
+    .await;
+    let raw_messages: Vec<(String, String)> = summary
+        .map(|summary| ("user".to_string(), summary))
+        .into_iter()
+        .chain(messages.iter().map(|m| {
             let role = match m.author_type.as_str() {
                 "user" => "user",
                 _ => "assistant",
             };
             let text = MessageContent::from_row(&m.content_type, &m.content).to_llm_text();
             (role.to_string(), text)
-        })
+        }))
         .collect();
     // If we just injected a greeting, include it as a prior assistant turn so the
     // LLM doesn't repeat the introduction.
@@ -610,14 +667,6 @@ async fn run_po_intake(
         );
     }
 
-    let config = match AgentConfig::load() {
-        Ok(c) => c,
-        Err(e) => {
-            log::warn!("user_chat: load config: {}", e);
-            return;
-        }
-    };
-
     let po_storage: Arc<dyn AgentStorage> = match SqliteAgentStorage::open(&db_path) {
         Ok(s) => Arc::new(s),
         Err(e) => {
@@ -647,28 +696,33 @@ async fn run_po_intake(
                 return;
             }
         };
-    let po = match ProductOwnerAgent::new(
-        po_storage,
-        po_task_storage,
-        po_comment_storage,
-        po_note_storage,
-        config,
-        project_id,
-    ) {
-        Ok(a) => a,
-        Err(e) => {
-            log::warn!("user_chat: build PO: {}", e);
-            return;
-        }
+    let po_prompts = project_prompts(&db_path, project_id);
+    let make_po = |config: AgentConfig| {
+        ProductOwnerAgent::new(
+            po_storage.clone(),
+            po_task_storage.clone(),
+            po_comment_storage.clone(),
+            po_note_storage.clone(),
+            config,
+            project_id,
+        )
+        .map(|po| po.with_prompts(po_prompts.clone()))
     };
 
     log::info!(
         "[PO:session={}] calling respond_in_session (requirements_gathering)",
         session_id
     );
-    let po_result = po
-        .respond_in_session(session_id, llm_messages.clone(), false)
-        .await;
+    let po_result = run_with_fallback(&config, |config| {
+        let po = make_po(config);
+        let messages = llm_messages.clone();
+        async move { po?.respond_in_session(session_id, messages, false).await }
+    })
+    .await
+    .map(|(result, model)| {
+        log::info!("[PO:session={}] answered by {}", session_id, model);
+        result
+    });
     log::info!(
         "[PO:session={}] respond_in_session returned: {:?}",
         session_id,
@@ -701,7 +755,13 @@ async fn run_po_intake(
                 "[PO:session={}] calling respond_in_session (project_naming)",
                 session_id
             );
-            let naming_result = po.respond_in_session(session_id, llm_messages, true).await;
+            let naming_result = run_with_fallback(&config, |config| {
+                let po = make_po(config);
+                let messages = llm_messages.clone();
+                async move { po?.respond_in_session(session_id, messages, true).await }
+            })
+            .await
+            .map(|(result, _)| result);
             log::info!(
                 "[PO:session={}] project_naming returned: {:?}",
                 session_id,
@@ -976,26 +1036,39 @@ async fn run_pm_planning(
         }
     };
 
-    let mut raw_messages: Vec<(String, String)> = Vec::new();
+    let config = match AgentConfig::load() {
+        Ok(c) => c,
+        Err(e) => {
+            log::warn!("user_chat: load config (PM): {}", e);
+            return;
+        }
+    };
 
     // Intake conversation first (user ↔ PO), so PM sees exactly what was asked and answered.
-    for m in &intake_messages {
-        let role = match m.author_type.as_str() {
-            "user" => "user",
-            _ => "assistant",
-        };
-        let text = MessageContent::from_row(&m.content_type, &m.content).to_llm_text();
-        if !text.trim().is_empty() {
-            raw_messages.push((role.to_string(), text));
-        }
+    // Intake rows predate the planning session's, so the combined history stays in id order.
+    let history: Vec<UserChatMessageRow> = intake_messages.into_iter().chain(messages).collect();
+    let (summary, history) = fit_context_window(
+        &config,
+        &db_path,
+        &chat_storage,
+        AgentType::ProjectManager,
+        project_id,
+        session_id,
+        history,
+    )
+    .await;
+
+    let mut raw_messages: Vec<(String, String)> = Vec::new();
+    if let Some(summary) = summary {
+        raw_messages.push(("user".to_string(), summary));
     }
 
-    // Planning session messages.
-    // The seeded PO summary (first message, author_type="agent"/product_owner) is mapped as
-    // "user" so PM reads it as a briefing it received, not as its own prior statement.
-    for m in &messages {
-        let is_po_seed =
-            m.author_type == "agent" && m.agent_type.as_deref() == Some("product_owner");
+    // The seeded PO summary (first planning message, author_type="agent"/product_owner) is
+    // mapped as "user" so PM reads it as a briefing it received, not as its own prior statement.
+    for m in &history {
+        let is_po_seed = m.session_id == session_id
+            && m.author_type == "agent"
+            && m.agent_type.as_deref() == Some("product_owner");
         let role = if is_po_seed {
             "user"
         } else {
@@ -1018,26 +1091,22 @@ async fn run_pm_planning(
     // questions as separate rows but the LLM requires strictly alternating turns.
     let llm_messages = merge_consecutive_roles(raw_messages);
 
-    let config = match AgentConfig::load() {
-        Ok(c) => c,
-        Err(e) => {
-            log::warn!("user_chat: load config (PM): {}", e);
-            return;
-        }
-    };
-
-    let pm = match build_project_manager(&config, &db_path, project_id) {
-        Ok(a) => a,
-        Err(e) => {
-            log::warn!("user_chat: build PM: {}", e);
-            return;
+    let pm_result = run_with_fallback(&config, |config| {
+        let db_path = db_path.clone();
+        let messages = llm_messages.clone();
+        async move {
+            build_project_manager(&config, &db_path, project_id)?
+                .chat_for_user_session(session_id, messages, true)
+                .await
         }
-    };
+    })
+    .await
+    .map(|(result, model)| {
+        log::info!("[PM:session={}] answered by {}", session_id, model);
+        result
+    });
 
-    match pm
-        .chat_for_user_session(session_id, llm_messages, true)
-        .await
-    {
+    match pm_result {
         Ok(PmUserSessionResult::Finalized { params }) => {
             handle_pm_finalized(&db_path, session_id, project_id, params, &chat_storage).await;
             notify_session(&chat_notify, session_id).await;
@@ -1153,92 +1222,34 @@ async fn handle_pm_finalized(
     session_id: i64,
     project_id: i64,
     params: FinalizeSessionParams,
-    _chat_storage: &SqliteUserChatStorage,
+    chat_storage: &SqliteUserChatStorage,
 ) {
-    let ts = now();
-    let FinalizeSessionParams {
-        final_message,
-        epic_title,
-        epic_description,
-        tasks,
-    } = params;
-
-    let mut conn = match rusqlite::Connection::open(db_path) {
-        Ok(c) => c,
-        Err(e) => {
-            log::warn!("user_chat: open db: {}", e);
-            return;
-        }
-    };
-
-    let tx = match conn.transaction() {
-        Ok(t) => t,
+    let task_ids = match save_finalized_plan(db_path, session_id, project_id, &params) {
+        Ok(task_ids) => task_ids,
         Err(e) => {
-            log::warn!("user_chat: begin tx: {}", e);
+            // Nothing was saved and the session stays open; tell the user
+            // rather than leave them waiting on a plan that never appears.
+            log::error!("user_chat: session={} finalize: {}", session_id, e);
+            if let Err(e) = chat_storage
+                .append_message(
+                    session_id,
+                    "agent",
+                    None,
+                    Some(AgentType::ProjectManager),
+                    None,
+                    MessageContent::Text(format!(
+                        "I could not save the plan: {}. Reply to let me try again.",
+                        e
+                    )),
+                )
+                .await
+            {
+                log::warn!("user_chat: store PM finalize error: {}", e);
+            }
             return;
         }
     };
 
-    if let Err(e) = tx.execute(
-        "INSERT INTO user_chat_message (session_id, author_type, agent_type, content_type, content, created_at) \
-         VALUES (?1, 'agent', 'project_manager', 'text', ?2, ?3)",
-        rusqlite::params![session_id, final_message, ts],
-    ) {
-        log::warn!("user_chat: insert PM message: {}", e);
-        return;
-    }
-
-    if let Err(e) = tx.execute(
-        "INSERT INTO epic (project_id, title, description, source_prompt, created_by_agent, status, created_at, updated_at) \
-         VALUES (?1, ?2, ?3, ?3, 'project_manager', 'open', ?4, ?5)",
-        rusqlite::params![project_id, epic_title, epic_description, ts, ts],
-    ) {
-        log::warn!("user_chat: insert epic: {}", e);
-        return;
-    }
-    let epic_id = tx.last_insert_rowid();
-
-    let mut task_ids = Vec::new();
-    for task_def in &tasks {
-        match tx.execute(
-            "INSERT INTO task (project_id, epic_id, title, description, source_prompt, assigned_to_agent, \
-             status, source_session_id, created_by_agent, created_at, updated_at) \
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'draft', ?7, 'project_manager', ?8, ?9)",
-            rusqlite::params![
-                project_id,
-                epic_id,
-                task_def.title,
-                task_def.description,
-                task_def.description,
-                task_def.assigned_to_agent,
-                session_id,
-                ts,
-                ts
-            ],
-        ) {
-            Ok(_) => {
-                task_ids.push(tx.last_insert_rowid());
-            }
-            Err(e) => {
-                log::warn!("user_chat: insert task: {}", e);
-                return;
-            }
-        }
-    }
-
-    if let Err(e) = tx.execute(
-        "UPDATE user_chat_session SET status = 'completed', completed_at = ?1, updated_at = ?1 WHERE id = ?2",
-        rusqlite::params![ts, session_id],
-    ) {
-        log::warn!("user_chat: complete session: {}", e);
-        return;
-    }
-
-    if let Err(e) = tx.commit() {
-        log::warn!("user_chat: commit tx: {}", e);
-        return;
-    }
-
     let val_po_storage: Arc<dyn AgentStorage> = match SqliteAgentStorage::open(db_path) {
         Ok(s) => Arc::new(s),
         Err(e) => {
@@ -1284,7 +1295,7 @@ async fn handle_pm_finalized(
         val_config,
         project_id,
     ) {
-        Ok(a) => a,
+        Ok(a) => a.with_prompts(project_prompts(db_path, project_id)),
         Err(e) => {
             log::warn!("user_chat: PO validate build: {}", e);
             return;
@@ -1295,3 +1306,161 @@ async fn handle_pm_finalized(
         log::warn!("user_chat: PO validate tasks: {}", e);
     }
 }
+
+/// Store the PM's closing message, the epic and its tasks and complete the
+/// session, all or nothing. Returns the ids of the tasks created.
+fn save_finalized_plan(
+    db_path: &str,
+    session_id: i64,
+    project_id: i64,
+    params: &FinalizeSessionParams,
+) -> Result<Vec<i64>, String> {
+    let ts = now();
+    let mut conn = rusqlite::Connection::open(db_path).map_err(|e| format!("open db: {}", e))?;
+    let tx = conn.transaction().map_err(|e| format!("begin tx: {}", e))?;
+
+    tx.execute(
+        "INSERT INTO user_chat_message (session_id, author_type, agent_type, content_type, content, created_at) \
+         VALUES (?1, 'agent', 'project_manager', 'text', ?2, ?3)",
+        rusqlite::params![session_id, params.final_message, ts],
+    )
+    .map_err(|e| format!("insert PM message: {}", e))?;
+
+    tx.execute(
+        "INSERT INTO epic (project_id, title, description, source_prompt, created_by_agent, status, created_at, updated_at) \
+         VALUES (?1, ?2, ?3, ?3, 'project_manager', 'open', ?4, ?5)",
+        rusqlite::params![project_id, params.epic_title, params.epic_description, ts, ts],
+    )
+    .map_err(|e| format!("insert epic: {}", e))?;
+    let epic_id = tx.last_insert_rowid();
+
+    let mut task_ids = Vec::new();
+    for task_def in &params.tasks {
+        let source_prompt = task_def.task_source_prompt().map_err(|e| e.to_string())?;
+        tx.execute(
+            "INSERT INTO task (project_id, epic_id, title, description, source_prompt, assigned_to_agent, \
+             status, source_session_id, created_by_agent, created_at, updated_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'draft', ?7, 'project_manager', ?8, ?9)",
+            rusqlite::params![
+                project_id,
+                epic_id,
+                task_def.title,
+                task_def.description,
+                source_prompt,
+                task_def.assigned_to_agent,
+                session_id,
+                ts,
+                ts
+            ],
+        )
+        .map_err(|e| format!("insert task \"{}\": {}", task_def.title, e))?;
+        task_ids.push(tx.last_insert_rowid());
+    }
+
+    tx.execute(
+        "UPDATE user_chat_session SET status = 'completed', completed_at = ?1, updated_at = ?1 WHERE id = ?2",
+        rusqlite::params![ts, session_id],
+    )
+    .map_err(|e| format!("complete session: {}", e))?;
+
+    tx.commit().map_err(|e| format!("commit: {}", e))?;
+    Ok(task_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nocodo_agents::FinalizeTaskDef;
+
+    fn session_db(name: &str) -> (std::path::PathBuf, i64) {
+        let path = std::env::temp_dir().join(format!(
+            "nocodo-user-chat-{}-{}.db",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        crate::db::run_startup_migrations(&path.to_string_lossy()).expect("migrate");
+        let conn = rusqlite::Connection::open(&path).expect("open db");
+        conn.execute_batch(
+            "INSERT INTO project (id, name, path, created_at) VALUES (1, 'Contacts', '', 0);
+             INSERT INTO user (id, display_name, created_at, updated_at) VALUES (1, 'Tester', 0, 0);
+             INSERT INTO user_chat_session (id, project_id, created_by_user_id, created_at, updated_at)
+                 VALUES (1, 1, 1, 0, 0);",
+        )
+        .expect("seed db");
+        (path, 1)
+    }
+
+    fn plan(tasks: Vec<FinalizeTaskDef>) -> FinalizeSessionParams {
+        FinalizeSessionParams {
+            final_message: "Planned.".to_string(),
+            epic_title: "Contacts".to_string(),
+            epic_description: "Store contacts".to_string(),
+            tasks,
+        }
+    }
+
+    fn task(agent: &str) -> FinalizeTaskDef {
+        FinalizeTaskDef {
+            title: format!("{} task", agent),
+            description: "Store contacts".to_string(),
+            assigned_to_agent: agent.to_string(),
+            rust_engineer: None,
+        }
+    }
+
+    fn count(path: &std::path::Path, sql: &str) -> i64 {
+        rusqlite::Connection::open(path)
+            .unwrap()
+            .query_row(sql, [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_save_finalized_plan_completes_the_session() {
+        let (path, session_id) = session_db("saved");
+        let db_path = path.to_string_lossy();
+
+        let task_ids =
+            save_finalized_plan(&db_path, session_id, 1, &plan(vec![task("db_engineer")]))
+                .expect("plan saved");
+
+        assert_eq!(task_ids.len(), 1);
+        assert_eq!(count(&path, "SELECT COUNT(*) FROM epic"), 1);
+        assert_eq!(
+            count(
+                &path,
+                "SELECT COUNT(*) FROM user_chat_session WHERE status = 'completed'"
+            ),
+            1
+        );
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_save_finalized_plan_saves_nothing_on_a_bad_task() {
+        let (path, session_id) = session_db("rejected");
+        let db_path = path.to_string_lossy();
+
+        let err = save_finalized_plan(
+            &db_path,
+            session_id,
+            1,
+            &plan(vec![task("db_engineer"), task("rust_engineer")]),
+        )
+        .unwrap_err();
+
+        assert!(err.contains("no `rust_engineer` parameters"), "{err}");
+        assert_eq!(count(&path, "SELECT COUNT(*) FROM epic"), 0);
+        assert_eq!(count(&path, "SELECT COUNT(*) FROM task"), 0);
+        assert_eq!(count(&path, "SELECT COUNT(*) FROM user_chat_message"), 0);
+        assert_eq!(
+            count(
+                &path,
+                "SELECT COUNT(*) FROM user_chat_session WHERE status = 'open'"
+            ),
+            1
+        );
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,248 @@
+//! Scripted OpenAI-compatible LLM server for offline end-to-end tests.
+//!
+//! Serves `POST /v1/chat/completions` (and `/chat/completions`, as llama.cpp
+//! does) from a scenario file. Each scenario agent is picked by a substring of
+//! the request's system prompt and answers with its turns in order; once the
+//! turns run out the last one repeats.
+//!
+//! ```json
+//! {
+//!   "agents": [
+//!     {
+//!       "name": "db_engineer",
+//!       "match": "database schema designer",
+//!       "turns": [
+//!         { "tool_calls": [{ "name": "generate_schema", "arguments": { "name": "crm", "tables": [] } }] }
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Usage: `mock-llm <scenario.json> [port]`. Port 0 (the default) picks a free
+//! port; the bound address is printed as the first line of stdout.
+//! `GET /mock/calls` lists the (agent, turn) pairs served so far.
+
+use std::sync::Mutex;
+
+use actix_web::{get, post, web, App, HttpResponse, HttpServer, Responder};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+struct Scenario {
+    agents: Vec<ScenarioAgent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScenarioAgent {
+    name: String,
+    /// Substring of the system prompt that identifies this agent.
+    #[serde(rename = "match")]
+    match_text: String,
+    turns: Vec<Turn>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Turn {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<ScriptedToolCall>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ScriptedToolCall {
+    name: String,
+    arguments: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Call {
+    agent: String,
+    turn: usize,
+}
+
+struct MockState {
+    scenario: Scenario,
+    /// Next turn index per scenario agent.
+    cursors: Mutex<Vec<usize>>,
+    calls: Mutex<Vec<Call>>,
+}
+
+/// The system prompt of an OpenAI-style request, whether sent as a `system`
+/// message (string or text parts) or a top-level `system` field.
+fn system_prompt(body: &serde_json::Value) -> Option<String> {
+    if let Some(system) = body["system"].as_str() {
+        return Some(system.to_string());
+    }
+    let message = body["messages"]
+        .as_array()?
+        .iter()
+        .find(|m| m["role"] == "system")?;
+    match &message["content"] {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Array(parts) => Some(
+            parts
+                .iter()
+                .filter_map(|p| p["text"].as_str())
+                .collect::<Vec<_>>()
+                .join(""),
+        ),
+        _ => None,
+    }
+}
+
+/// Rough token estimate (4 chars per token), so usage fields are non-zero.
+fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+fn completion_response(model: &str, turn: &Turn, seq: usize, prompt: &str) -> serde_json::Value {
+    let tool_calls: Vec<serde_json::Value> = turn
+        .tool_calls
+        .iter()
+        .enumerate()
+        .map(|(i, call)| {
+            serde_json::json!({
+                "id": format!("call_{seq}_{i}"),
+                "type": "function",
+                "function": {
+                    "name": call.name,
+                    "arguments": call.arguments.to_string(),
+                },
+            })
+        })
+        .collect();
+
+    let mut message = serde_json::json!({
+        "role": "assistant",
+        "content": turn.content,
+    });
+    if !tool_calls.is_empty() {
+        message["tool_calls"] = serde_json::Value::Array(tool_calls);
+    }
+
+    let completion_tokens = estimate_tokens(&message.to_string());
+    let prompt_tokens = estimate_tokens(prompt);
+    serde_json::json!({
+        "id": format!("mock-{seq}"),
+        "object": "chat.completion",
+        "created": 0,
+        "model": model,
+        "choices": [{
+            "index": 0,
+            "message": message,
+            "finish_reason": if turn.tool_calls.is_empty() { "stop" } else { "tool_calls" },
+        }],
+        "usage": {
+            "prompt_tokens": prompt_tokens,
+            "completion_tokens": completion_tokens,
+            "total_tokens": prompt_tokens + completion_tokens,
+        },
+    })
+}
+
+async fn complete(state: web::Data<MockState>, body: web::Json<serde_json::Value>) -> HttpResponse {
+    let body = body.into_inner();
+    let system = system_prompt(&body).unwrap_or_else(|| body.to_string());
+
+    let Some(agent_idx) = state
+        .scenario
+        .agents
+        .iter()
+        .position(|a| system.contains(&a.match_text))
+    else {
+        let head: String = system.chars().take(200).collect();
+        eprintln!("mock-llm: no scenario agent matches system prompt: {head}");
+        return HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": { "message": format!("no scenario agent matches system prompt: {head}") }
+        }));
+    };
+    let agent = &state.scenario.agents[agent_idx];
+
+    let turn_idx = {
+        let mut cursors = state.cursors.lock().unwrap();
+        let idx = cursors[agent_idx].min(agent.turns.len().saturating_sub(1));
+        cursors[agent_idx] += 1;
+        idx
+    };
+    let Some(turn) = agent.turns.get(turn_idx) else {
+        return HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": { "message": format!("scenario agent `{}` has no turns", agent.name) }
+        }));
+    };
+
+    let seq = {
+        let mut calls = state.calls.lock().unwrap();
+        calls.push(Call {
+            agent: agent.name.clone(),
+            turn: turn_idx,
+        });
+        calls.len()
+    };
+    println!("mock-llm: {} turn {}", agent.name, turn_idx);
+
+    let model = body["model"].as_str().unwrap_or("mock");
+    HttpResponse::Ok().json(completion_response(model, turn, seq, &body.to_string()))
+}
+
+#[post("/v1/chat/completions")]
+async fn chat_completions_v1(
+    state: web::Data<MockState>,
+    body: web::Json<serde_json::Value>,
+) -> impl Responder {
+    complete(state, body).await
+}
+
+#[post("/chat/completions")]
+async fn chat_completions(
+    state: web::Data<MockState>,
+    body: web::Json<serde_json::Value>,
+) -> impl Responder {
+    complete(state, body).await
+}
+
+#[get("/mock/calls")]
+async fn list_calls(state: web::Data<MockState>) -> impl Responder {
+    HttpResponse::Ok().json(&*state.calls.lock().unwrap())
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let scenario_path = args.next().ok_or_else(|| {
+        std::io::Error::other("usage: mock-llm <scenario.json> [port]".to_string())
+    })?;
+    let port: u16 = match args.next() {
+        Some(p) => p
+            .parse()
+            .map_err(|e| std::io::Error::other(format!("invalid port {p}: {e}")))?,
+        None => 0,
+    };
+
+    let contents = std::fs::read_to_string(&scenario_path)?;
+    let scenario: Scenario = serde_json::from_str(&contents)
+        .map_err(|e| std::io::Error::other(format!("parse {scenario_path}: {e}")))?;
+    let cursors = Mutex::new(vec![0; scenario.agents.len()]);
+    let state = web::Data::new(MockState {
+        scenario,
+        cursors,
+        calls: Mutex::new(Vec::new()),
+    });
+
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(state.clone())
+            .app_data(web::JsonConfig::default().limit(16 * 1024 * 1024))
+            .service(chat_completions_v1)
+            .service(chat_completions)
+            .service(list_calls)
+    })
+    .workers(1)
+    .bind(("127.0.0.1", port))?;
+
+    for addr in server.addrs() {
+        println!("mock-llm listening on http://{addr}");
+    }
+    server.run().await
+}
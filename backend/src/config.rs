@@ -1,5 +1,6 @@
+use nocodo_agents::{ApprovalPolicy, Schedule};
 use serde::Deserialize;
-use std::{fs, path::PathBuf};
+use std::{collections::HashMap, fs, path::PathBuf};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
@@ -22,6 +23,18 @@ pub struct Config {
     pub api_keys: Option<ApiKeysConfig>,
     #[serde(default)]
     pub deploy: Option<DeployConfig>,
+    #[serde(default)]
+    pub pricing: Vec<ModelPrice>,
+    #[serde(default)]
+    pub dispatch: Option<DispatchConfig>,
+    /// Agents whose results wait for a user's approval; a project's
+    /// `.nocodo/approval.toml` replaces the list.
+    #[serde(default)]
+    pub approval: Option<ApprovalPolicy>,
+    /// `[[schedule]]` entries: when to run project-wide agents such as the
+    /// stack reviewer on every project.
+    #[serde(default, rename = "schedule")]
+    pub schedules: Vec<Schedule>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -73,12 +86,49 @@ fn default_true() -> bool {
 pub struct AgentsConfig {
     pub provider: String,
     pub model: String,
+    /// Server URL for local providers (`llama_cpp`, `ollama`) or any
+    /// OpenAI-compatible endpoint.
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// Comma-separated `provider:model` list tried in order when a run fails.
+    #[serde(default)]
+    pub fallback_models: Option<String>,
+    /// Estimated tokens of history sent per turn before older turns are summarized.
+    #[serde(default)]
+    pub context_budget: Option<usize>,
+    /// Model that writes those summaries; defaults to `model`.
+    #[serde(default)]
+    pub summary_model: Option<String>,
+    /// `native` or `text`; `text` puts tool schemas in the prompt for
+    /// models without function calling.
+    #[serde(default)]
+    pub tool_protocol: Option<String>,
+    /// Comma-separated model names that default to the `text` tool protocol.
+    #[serde(default)]
+    pub text_tool_models: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct PmAgentConfig {
     pub provider: String,
     pub model: String,
+    /// Server URL for local providers (`llama_cpp`, `ollama`) or any
+    /// OpenAI-compatible endpoint.
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// Comma-separated `provider:model` list tried in order when a run fails.
+    #[serde(default)]
+    pub fallback_models: Option<String>,
+    /// Estimated tokens of history sent per turn before older turns are summarized.
+    #[serde(default)]
+    pub context_budget: Option<usize>,
+    /// Model that writes those summaries; defaults to `model`.
+    #[serde(default)]
+    pub summary_model: Option<String>,
+    /// `native` or `text`; `text` puts tool schemas in the prompt for
+    /// models without function calling.
+    #[serde(default)]
+    pub tool_protocol: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -89,6 +139,86 @@ pub struct ApiKeysConfig {
     pub anthropic_api_key: Option<String>,
 }
 
+/// USD price per million tokens for one provider/model, used to cost recorded
+/// LLM usage. Local providers cost nothing unless priced here.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ModelPrice {
+    pub provider: String,
+    pub model: String,
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+/// How many agent runs the dispatcher starts at once, and in which order.
+#[derive(Debug, Deserialize, Clone)]
+pub struct DispatchConfig {
+    /// Runs at once across all agents.
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+    /// Runs at once per agent type, e.g. `db_engineer = 1`.
+    #[serde(default)]
+    pub agent_limits: HashMap<String, usize>,
+    /// Runs at once per provider, e.g. `llama_cpp = 1`. A run counts against
+    /// the providers of its primary and fallback models. Local providers
+    /// default to 1.
+    #[serde(default)]
+    pub provider_limits: HashMap<String, usize>,
+    /// Claim order per agent type, higher first; default 0. Jobs of equal
+    /// priority run oldest first.
+    #[serde(default)]
+    pub priorities: HashMap<String, i64>,
+    /// Runs of a task before a failing one is moved to Blocked.
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: i64,
+    /// Wall-clock limit of one run; a run past it is stopped and its task
+    /// moved to Blocked.
+    #[serde(default = "default_run_timeout_secs")]
+    pub run_timeout_secs: u64,
+    /// LLM calls one run may make, across fallback models.
+    #[serde(default = "default_max_turns")]
+    pub max_turns: u32,
+}
+
+fn default_max_concurrent() -> usize {
+    4
+}
+
+fn default_max_attempts() -> i64 {
+    3
+}
+
+fn default_run_timeout_secs() -> u64 {
+    1800
+}
+
+fn default_max_turns() -> u32 {
+    50
+}
+
+impl Default for DispatchConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: default_max_concurrent(),
+            agent_limits: HashMap::new(),
+            provider_limits: HashMap::new(),
+            priorities: HashMap::new(),
+            max_attempts: default_max_attempts(),
+            run_timeout_secs: default_run_timeout_secs(),
+            max_turns: default_max_turns(),
+        }
+    }
+}
+
+impl DispatchConfig {
+    pub fn provider_limit(&self, provider: &str) -> Option<usize> {
+        match self.provider_limits.get(provider) {
+            Some(limit) => Some(*limit),
+            None if matches!(provider, "llama_cpp" | "ollama") => Some(1),
+            None => None,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct DeployConfig {
     pub server_ip: String,
@@ -126,11 +256,40 @@ impl Default for Config {
             context_agent: None,
             api_keys: None,
             deploy: None,
+            pricing: Vec::new(),
+            dispatch: None,
+            approval: None,
+            schedules: Vec::new(),
         }
     }
 }
 
 impl Config {
+    /// Cost in USD of `input_tokens` / `output_tokens` on provider/model.
+    /// `None` when a hosted model has no `[[pricing]]` entry; local providers
+    /// default to free.
+    pub fn usage_cost(
+        &self,
+        provider: &str,
+        model: &str,
+        input_tokens: i64,
+        output_tokens: i64,
+    ) -> Option<f64> {
+        match self
+            .pricing
+            .iter()
+            .find(|p| p.provider == provider && p.model == model)
+        {
+            Some(price) => Some(
+                (input_tokens as f64 * price.input_per_mtok
+                    + output_tokens as f64 * price.output_per_mtok)
+                    / 1_000_000.0,
+            ),
+            None if matches!(provider, "llama_cpp" | "ollama") => Some(0.0),
+            None => None,
+        }
+    }
+
     pub fn load() -> Result<Self, String> {
         match Self::find_config_file() {
             Some(path) => {
@@ -165,14 +324,42 @@ impl Config {
         if let Some(agents) = &self.agents {
             set_env_if_unset("AGENT_PROVIDER", Some(agents.provider.as_str()));
             set_env_if_unset("AGENT_MODEL", Some(agents.model.as_str()));
+            set_env_if_unset("AGENT_BASE_URL", agents.base_url.as_deref());
+            set_env_if_unset("AGENT_FALLBACK_MODELS", agents.fallback_models.as_deref());
+            set_env_if_unset(
+                "AGENT_CONTEXT_BUDGET",
+                agents.context_budget.map(|b| b.to_string()).as_deref(),
+            );
+            set_env_if_unset("AGENT_SUMMARY_MODEL", agents.summary_model.as_deref());
+            set_env_if_unset("AGENT_TOOL_PROTOCOL", agents.tool_protocol.as_deref());
+            set_env_if_unset("TEXT_TOOL_MODELS", agents.text_tool_models.as_deref());
         }
         if let Some(pm) = &self.pm_agent {
             set_env_if_unset("PM_AGENT_PROVIDER", Some(pm.provider.as_str()));
             set_env_if_unset("PM_AGENT_MODEL", Some(pm.model.as_str()));
+            set_env_if_unset("PM_AGENT_BASE_URL", pm.base_url.as_deref());
+            set_env_if_unset("PM_AGENT_FALLBACK_MODELS", pm.fallback_models.as_deref());
+            set_env_if_unset(
+                "PM_AGENT_CONTEXT_BUDGET",
+                pm.context_budget.map(|b| b.to_string()).as_deref(),
+            );
+            set_env_if_unset("PM_AGENT_SUMMARY_MODEL", pm.summary_model.as_deref());
+            set_env_if_unset("PM_AGENT_TOOL_PROTOCOL", pm.tool_protocol.as_deref());
         }
         if let Some(ctx) = &self.context_agent {
             set_env_if_unset("CONTEXT_AGENT_PROVIDER", Some(ctx.provider.as_str()));
             set_env_if_unset("CONTEXT_AGENT_MODEL", Some(ctx.model.as_str()));
+            set_env_if_unset("CONTEXT_AGENT_BASE_URL", ctx.base_url.as_deref());
+            set_env_if_unset(
+                "CONTEXT_AGENT_FALLBACK_MODELS",
+                ctx.fallback_models.as_deref(),
+            );
+            set_env_if_unset(
+                "CONTEXT_AGENT_CONTEXT_BUDGET",
+                ctx.context_budget.map(|b| b.to_string()).as_deref(),
+            );
+            set_env_if_unset("CONTEXT_AGENT_SUMMARY_MODEL", ctx.summary_model.as_deref());
+            set_env_if_unset("CONTEXT_AGENT_TOOL_PROTOCOL", ctx.tool_protocol.as_deref());
         }
     }
 
@@ -201,6 +388,13 @@ impl Config {
                 self.admin_gui.port = p;
             }
         }
+        if let Ok(v) = std::env::var("DISPATCH_MAX_CONCURRENT") {
+            if let Ok(n) = v.parse() {
+                self.dispatch
+                    .get_or_insert_with(DispatchConfig::default)
+                    .max_concurrent = n;
+            }
+        }
         if let Ok(v) = std::env::var("DEFAULT_PROJECTS_PATH") {
             self.projects
                 .get_or_insert_with(|| ProjectsConfig { default_path: None })
@@ -285,10 +479,62 @@ pub fn read_project_conf(key: &str) -> Option<String> {
         "AUTH_FROM_EMAIL" => config.auth.as_ref().and_then(|a| a.from_email.clone()),
         "AGENT_PROVIDER" => config.agents.as_ref().map(|a| a.provider.clone()),
         "AGENT_MODEL" => config.agents.as_ref().map(|a| a.model.clone()),
+        "AGENT_BASE_URL" => config.agents.as_ref().and_then(|a| a.base_url.clone()),
         "PM_AGENT_PROVIDER" => config.pm_agent.as_ref().map(|a| a.provider.clone()),
         "PM_AGENT_MODEL" => config.pm_agent.as_ref().map(|a| a.model.clone()),
+        "PM_AGENT_BASE_URL" => config.pm_agent.as_ref().and_then(|a| a.base_url.clone()),
         "CONTEXT_AGENT_PROVIDER" => config.context_agent.as_ref().map(|a| a.provider.clone()),
         "CONTEXT_AGENT_MODEL" => config.context_agent.as_ref().map(|a| a.model.clone()),
+        "CONTEXT_AGENT_BASE_URL" => config
+            .context_agent
+            .as_ref()
+            .and_then(|a| a.base_url.clone()),
+        "AGENT_FALLBACK_MODELS" => config
+            .agents
+            .as_ref()
+            .and_then(|a| a.fallback_models.clone()),
+        "PM_AGENT_FALLBACK_MODELS" => config
+            .pm_agent
+            .as_ref()
+            .and_then(|a| a.fallback_models.clone()),
+        "CONTEXT_AGENT_FALLBACK_MODELS" => config
+            .context_agent
+            .as_ref()
+            .and_then(|a| a.fallback_models.clone()),
+        "AGENT_CONTEXT_BUDGET" => config
+            .agents
+            .as_ref()
+            .and_then(|a| a.context_budget.map(|b| b.to_string())),
+        "AGENT_SUMMARY_MODEL" => config.agents.as_ref().and_then(|a| a.summary_model.clone()),
+        "PM_AGENT_CONTEXT_BUDGET" => config
+            .pm_agent
+            .as_ref()
+            .and_then(|a| a.context_budget.map(|b| b.to_string())),
+        "PM_AGENT_SUMMARY_MODEL" => config
+            .pm_agent
+            .as_ref()
+            .and_then(|a| a.summary_model.clone()),
+        "CONTEXT_AGENT_CONTEXT_BUDGET" => config
+            .context_agent
+            .as_ref()
+            .and_then(|a| a.context_budget.map(|b| b.to_string())),
+        "CONTEXT_AGENT_SUMMARY_MODEL" => config
+            .context_agent
+            .as_ref()
+            .and_then(|a| a.summary_model.clone()),
+        "AGENT_TOOL_PROTOCOL" => config.agents.as_ref().and_then(|a| a.tool_protocol.clone()),
+        "PM_AGENT_TOOL_PROTOCOL" => config
+            .pm_agent
+            .as_ref()
+            .and_then(|a| a.tool_protocol.clone()),
+        "CONTEXT_AGENT_TOOL_PROTOCOL" => config
+            .context_agent
+            .as_ref()
+            .and_then(|a| a.tool_protocol.clone()),
+        "TEXT_TOOL_MODELS" => config
+            .agents
+            .as_ref()
+            .and_then(|a| a.text_tool_models.clone()),
         "OPENAI_API_KEY" => config
             .api_keys
             .as_ref()
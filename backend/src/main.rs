@@ -1,6 +1,6 @@
 use actix_cors::Cors;
 use actix_web::{get, web, App, HttpResponse, HttpServer, Responder};
-use nocodo_agents::{SqliteTaskStorage, TaskStorage};
+use nocodo_agents::{task_policy, SqliteTaskStorage, TaskStorage};
 use shared_types::HeartbeatResponse;
 
 mod agents_api;
@@ -96,7 +96,12 @@ async fn main() -> std::io::Result<()> {
         .and_then(|d| d.website_domain.as_ref())
         .map(|w| format!("http://{}", w));
 
-    let agent_state = match agents_api::AgentState::new(config.database.url.clone()) {
+    let agent_state = match agents_api::AgentState::new(
+        config.database.url.clone(),
+        config.dispatch.clone().unwrap_or_default(),
+        config.approval.clone().unwrap_or_default(),
+        config.schedules.clone(),
+    ) {
         Ok(state) => web::Data::new(state),
         Err(e) => {
             eprintln!("Warning: Failed to initialize agent state: {}", e);
@@ -104,6 +109,10 @@ async fn main() -> std::io::Result<()> {
         }
     };
 
+    // Tasks created while no dispatcher was running (e.g. by the PM) have no
+    // job yet; queue them, to the Engineering Manager while they need
+    // technical shaping. Jobs already queued or running survive restarts in
+    // the job table and are claimed again by the dispatcher.
     if let Ok(ts) = SqliteTaskStorage::open(&config.database.url) {
         match ts.list_open_dispatchable_tasks().await {
             Ok(tasks) => {
@@ -111,14 +120,15 @@ async fn main() -> std::io::Result<()> {
                     log::info!("Reconciling {} open dispatchable task(s)", tasks.len());
                 }
                 for task in tasks {
-                    let _ = agent_state
-                        .dispatch_tx
-                        .send(agents_api::dispatcher::DispatchEvent {
-                            task_id: task.id.unwrap_or(0),
-                            project_id: task.project_id,
-                            assigned_to_agent: task.assigned_to_agent,
-                            source_prompt: task.source_prompt,
-                        });
+                    let event = agents_api::dispatcher::DispatchEvent {
+                        task_id: task.id.unwrap_or(0),
+                        project_id: task.project_id,
+                        assigned_to_agent: task_policy::dispatch_agent_for(&task).to_string(),
+                        source_prompt: task.source_prompt,
+                    };
+                    if let Err(e) = agent_state.jobs.enqueue(event).await {
+                        log::warn!("Startup reconciliation failed to queue task: {}", e);
+                    }
                 }
             }
             Err(e) => log::warn!("Startup reconciliation failed: {}", e),
@@ -184,6 +194,10 @@ async fn main() -> std::io::Result<()> {
             .configure(agents_api::project_notes::configure)
             .configure(agents_api::code_extractor::configure)
             .configure(agents_api::comments::configure)
+            .configure(agents_api::usage::configure)
+            .configure(agents_api::jobs::configure)
+            .configure(agents_api::dependencies::configure)
+            .configure(agents_api::approvals::configure)
             .configure(projects_api::configure)
             .configure(schema_api::configure)
     })
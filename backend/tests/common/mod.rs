@@ -0,0 +1,232 @@
+//! Helpers for end-to-end tests that run the real backend binary against the
+//! scripted `mock-llm` server.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Agent config prefixes the backend reads; each is pointed at the mock so a
+/// developer's project.toml / project.conf cannot route a test to a real provider.
+const AGENT_PREFIXES: &[&str] = &[
+    "AGENT",
+    "PM_AGENT",
+    "CONTEXT_AGENT",
+    "SCHEMA_AGENT",
+    "UI_AGENT",
+];
+
+/// A fresh directory under the system temp dir, removed on drop.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("nocodo-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("create temp dir");
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A child process that is killed on drop.
+struct ChildGuard(Child);
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// The `mock-llm` binary serving a scenario file.
+pub struct MockLlm {
+    _child: ChildGuard,
+    /// Root URL, e.g. `http://127.0.0.1:40123`.
+    pub url: String,
+}
+
+impl MockLlm {
+    pub fn start(scenario: &Path) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_mock-llm"))
+            .arg(scenario)
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("spawn mock-llm");
+        let stdout = child.stdout.take().expect("mock-llm stdout");
+        let mut lines = BufReader::new(stdout).lines();
+        let first = lines
+            .next()
+            .expect("mock-llm exited before printing its address")
+            .expect("read mock-llm stdout");
+        let url = first
+            .rsplit(' ')
+            .next()
+            .expect("mock-llm address line")
+            .to_string();
+        // Keep draining stdout so the mock never blocks on a full pipe.
+        std::thread::spawn(move || lines.for_each(drop));
+        Self {
+            _child: ChildGuard(child),
+            url,
+        }
+    }
+
+    /// Agent names of the completions served so far, in order.
+    pub fn called_agents(&self) -> Vec<String> {
+        let (_, calls) = http(&self.url, "GET", "/mock/calls", None);
+        calls
+            .as_array()
+            .expect("calls array")
+            .iter()
+            .filter_map(|c| c["agent"].as_str().map(String::from))
+            .collect()
+    }
+}
+
+/// The real backend binary on a free port, with every agent pointed at a mock.
+pub struct Backend {
+    _child: ChildGuard,
+    pub url: String,
+}
+
+impl Backend {
+    pub fn start(work_dir: &Path, db_path: &Path, llm_base_url: &str) -> Self {
+        Self::start_with_env(work_dir, db_path, llm_base_url, &[])
+    }
+
+    /// `start` with extra environment variables, e.g. `LLM_FIXTURE_MODE`.
+    pub fn start_with_env(
+        work_dir: &Path,
+        db_path: &Path,
+        llm_base_url: &str,
+        env: &[(&str, &str)],
+    ) -> Self {
+        let port = free_port();
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_nocodo-backend"));
+        cmd.current_dir(work_dir)
+            .env("BACKEND_HOST", "127.0.0.1")
+            .env("BACKEND_PORT", port.to_string())
+            .env("DATABASE_URL", db_path)
+            .env("DATABASE_KIND", "sqlite")
+            .env("MANDATORY_AUTHENTICATION", "false")
+            .env("OPENAI_API_KEY", "test")
+            .env_remove("LLM_FIXTURE_MODE")
+            .env(
+                "RUST_LOG",
+                std::env::var("RUST_LOG").unwrap_or_else(|_| "warn".to_string()),
+            )
+            .stdout(Stdio::null());
+        for prefix in AGENT_PREFIXES {
+            cmd.env(format!("{prefix}_PROVIDER"), "openai")
+                .env(format!("{prefix}_MODEL"), "mock")
+                .env(format!("{prefix}_BASE_URL"), llm_base_url);
+        }
+        cmd.envs(env.iter().copied());
+        let child = ChildGuard(cmd.spawn().expect("spawn nocodo-backend"));
+        let url = format!("http://127.0.0.1:{port}");
+
+        wait_until(Duration::from_secs(30), "backend heartbeat", || {
+            TcpStream::connect(("127.0.0.1", port)).is_ok()
+                && http(&url, "GET", "/api/heartbeat", None).0 == 200
+        });
+        Self { _child: child, url }
+    }
+
+    pub fn get(&self, path: &str) -> (u16, serde_json::Value) {
+        http(&self.url, "GET", path, None)
+    }
+
+    pub fn post(&self, path: &str, body: &serde_json::Value) -> (u16, serde_json::Value) {
+        http(&self.url, "POST", path, Some(body))
+    }
+}
+
+pub fn free_port() -> u16 {
+    TcpListener::bind(("127.0.0.1", 0))
+        .and_then(|l| l.local_addr())
+        .map(|a| a.port())
+        .expect("bind free port")
+}
+
+/// Poll `check` every 100ms until it returns true; panics after `timeout`.
+pub fn wait_until(timeout: Duration, what: &str, mut check: impl FnMut() -> bool) {
+    let deadline = Instant::now() + timeout;
+    while !check() {
+        assert!(Instant::now() < deadline, "timed out waiting for {what}");
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Minimal HTTP/1.1 JSON client. Returns the status code and the parsed body
+/// (`Null` when the body is empty or not JSON); connection errors give status 0.
+pub fn http(
+    base_url: &str,
+    method: &str,
+    path: &str,
+    body: Option<&serde_json::Value>,
+) -> (u16, serde_json::Value) {
+    let host = base_url.trim_start_matches("http://");
+    let Ok(mut stream) = TcpStream::connect(host) else {
+        return (0, serde_json::Value::Null);
+    };
+    let body = body.map(|b| b.to_string()).unwrap_or_default();
+    let request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: {host}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    if stream.write_all(request.as_bytes()).is_err() {
+        return (0, serde_json::Value::Null);
+    }
+    let mut response = Vec::new();
+    if stream.read_to_end(&mut response).is_err() {
+        return (0, serde_json::Value::Null);
+    }
+    let response = String::from_utf8_lossy(&response);
+    let Some((head, body)) = response.split_once("\r\n\r\n") else {
+        return (0, serde_json::Value::Null);
+    };
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+    let body = if head
+        .to_ascii_lowercase()
+        .contains("transfer-encoding: chunked")
+    {
+        dechunk(body)
+    } else {
+        body.to_string()
+    };
+    (
+        status,
+        serde_json::from_str(&body).unwrap_or(serde_json::Value::Null),
+    )
+}
+
+fn dechunk(mut body: &str) -> String {
+    let mut out = String::new();
+    while let Some((size, rest)) = body.split_once("\r\n") {
+        let Ok(size) = usize::from_str_radix(size.trim(), 16) else {
+            break;
+        };
+        if size == 0 || rest.len() < size {
+            break;
+        }
+        out.push_str(&rest[..size]);
+        body = rest[size..].trim_start_matches("\r\n");
+    }
+    out
+}
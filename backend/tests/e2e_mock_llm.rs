@@ -0,0 +1,244 @@
+//! End-to-end agent flow against the scripted `mock-llm` server: user chat →
+//! Product Owner → Project Manager → DB Engineer → UI Designer, all offline.
+//! The scenario lives in `tests/fixtures/po_pm_db_ui.json`; the same flow
+//! recorded through `FixtureClient` lives in `tests/fixtures/llm/po_pm_db_ui/`.
+//! Re-record it after changing a prompt with
+//! `RECORD_LLM_FIXTURES=1 cargo test --test e2e_mock_llm`.
+#![cfg(feature = "db-sqlite")]
+
+mod common;
+
+use std::path::Path;
+use std::time::Duration;
+
+use common::{wait_until, Backend, MockLlm, TempDir};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// Recorded LLM traffic of the flow, relative to the crate root.
+const RECORDED_FLOW: &str = "tests/fixtures/llm/po_pm_db_ui";
+
+fn query_one(db_path: &Path, sql: &str, project_id: i64) -> Option<String> {
+    let conn = rusqlite::Connection::open(db_path).ok()?;
+    conn.query_row(sql, rusqlite::params![project_id], |row| row.get(0))
+        .ok()
+}
+
+#[test]
+fn test_po_pm_db_ui_flow() {
+    let dir = TempDir::new("e2e-mock-llm");
+    let db_path = dir.path().join("app.db");
+    let mock = MockLlm::start(&scenario());
+    let llm_base_url = format!("{}/v1", mock.url);
+
+    let start = || Backend::start(dir.path(), &db_path, &llm_base_url);
+    po_pm_db_ui_flow(dir.path(), &db_path, &start);
+
+    let called = mock.called_agents();
+    for agent in [
+        "product_owner",
+        "product_owner_naming",
+        "project_manager",
+        "db_engineer",
+        "ui_designer",
+    ] {
+        assert!(
+            called.iter().any(|c| c == agent),
+            "{agent} never called: {called:?}"
+        );
+    }
+}
+
+/// The flow replays from the recorded fixtures with the mock gone and another
+/// model configured; `RECORD_LLM_FIXTURES=1` records them from the mock first.
+#[test]
+fn test_po_pm_db_ui_flow_replays_recorded_llm_traffic() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join(RECORDED_FLOW);
+    if std::env::var_os("RECORD_LLM_FIXTURES").is_some() {
+        let _ = std::fs::remove_dir_all(&fixtures);
+        let dir = TempDir::new("e2e-llm-record");
+        let db_path = dir.path().join("app.db");
+        let mock = MockLlm::start(&scenario());
+        let llm_base_url = format!("{}/v1", mock.url);
+        let env = [
+            ("LLM_FIXTURE_MODE", "record"),
+            ("LLM_FIXTURE_DIR", fixtures.to_str().expect("utf-8 path")),
+        ];
+        let start = || Backend::start_with_env(dir.path(), &db_path, &llm_base_url, &env);
+        po_pm_db_ui_flow(dir.path(), &db_path, &start);
+    }
+
+    let dir = TempDir::new("e2e-llm-replay");
+    let db_path = dir.path().join("app.db");
+    // Nothing listens here: every completion must come from a fixture.
+    let llm_base_url = format!("http://127.0.0.1:{}/v1", common::free_port());
+    let env = [
+        ("LLM_FIXTURE_MODE", "replay"),
+        ("LLM_FIXTURE_DIR", fixtures.to_str().expect("utf-8 path")),
+        ("AGENT_MODEL", "another-model"),
+        ("PM_AGENT_MODEL", "another-model"),
+    ];
+    let start = || Backend::start_with_env(dir.path(), &db_path, &llm_base_url, &env);
+    po_pm_db_ui_flow(dir.path(), &db_path, &start);
+}
+
+fn scenario() -> std::path::PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/po_pm_db_ui.json")
+}
+
+/// Chat with the PO until the PM plans a schema task, run it and the form
+/// task the workflow chains onto it, and check what they stored. `start`
+/// (re)starts the backend.
+fn po_pm_db_ui_flow(project_dir: &Path, db_path: &Path, start: &dyn Fn() -> Backend) {
+    let backend = start();
+
+    // Projects created through the API clone the template repo, so insert the
+    // row directly.
+    let project_id = {
+        let conn = rusqlite::Connection::open(db_path).expect("open db");
+        conn.execute(
+            "INSERT INTO project (name, path, created_at) VALUES ('Untitled', ?1, 0)",
+            rusqlite::params![project_dir.to_string_lossy()],
+        )
+        .expect("insert project");
+        conn.last_insert_rowid()
+    };
+
+    // PO gathers requirements and names the project; PM plans a db_engineer
+    // task which PO validation moves to Ready.
+    let (status, _) = backend.post(
+        "/api/user-chats",
+        &serde_json::json!({
+            "project_id": project_id,
+            "display_name": "Tester",
+            "message": "I need to keep track of my customer contacts.",
+        }),
+    );
+    assert_eq!(status, 200);
+
+    let task_status_sql =
+        "SELECT status FROM task WHERE project_id = ?1 AND assigned_to_agent = 'db_engineer'";
+    wait_until(TIMEOUT, "db_engineer task to be ready", || {
+        query_one(db_path, task_status_sql, project_id).as_deref() == Some("ready")
+    });
+    assert_eq!(
+        query_one(
+            db_path,
+            "SELECT name FROM project WHERE id = ?1",
+            project_id
+        )
+        .as_deref(),
+        Some("Contact Book")
+    );
+
+    // Ready tasks are dispatched by startup reconciliation, so restart.
+    drop(backend);
+    let backend = start();
+
+    wait_until(TIMEOUT, "db_engineer task to be done", || {
+        query_one(db_path, task_status_sql, project_id).as_deref() == Some("done")
+    });
+    let schema_json = query_one(
+        db_path,
+        "SELECT schema_json FROM project_schema WHERE project_id = ?1 ORDER BY id DESC",
+        project_id,
+    )
+    .expect("schema saved");
+    assert!(schema_json.contains("\"contact\""), "schema: {schema_json}");
+
+    // The built-in workflow chains a form task per table onto the finished
+    // schema task.
+    let follow_up_sql = "SELECT title FROM task
+         WHERE project_id = ?1 AND assigned_to_agent = 'ui_designer'
+           AND created_by_task_id =
+               (SELECT id FROM task WHERE project_id = ?1 AND assigned_to_agent = 'db_engineer')";
+    wait_until(TIMEOUT, "workflow form task", || {
+        query_one(db_path, follow_up_sql, project_id).as_deref() == Some("Design form for contact")
+    });
+
+    // UI designer builds a form for the new table.
+    let form_path = format!("/api/agents/ui-designer/form/{project_id}/contact");
+    wait_until(TIMEOUT, "contact form layout", || {
+        backend.get(&form_path).0 == 200
+    });
+    let (_, form) = backend.get(&form_path);
+    assert_eq!(form["layout"]["title"], "New Contact");
+
+    // Asking for the form again returns the stored layout without a new run.
+    let (status, form) = backend.post(
+        "/api/agents/ui-designer/form",
+        &serde_json::json!({ "project_id": project_id, "entity_name": "contact" }),
+    );
+    assert_eq!(status, 200);
+    assert_eq!(form["layout"]["title"], "New Contact");
+
+    let (status, board) = backend.get(&format!("/api/agents/board?project_id={project_id}"));
+    assert_eq!(status, 200);
+    let tasks = board["tasks"].as_array().expect("board tasks");
+    let agents: Vec<&str> = tasks
+        .iter()
+        .filter_map(|t| t["assigned_to_agent"].as_str())
+        .collect();
+    assert!(agents.contains(&"db_engineer"), "board: {board}");
+    assert!(agents.contains(&"ui_designer"), "board: {board}");
+    assert!(
+        board["queue"]["max_concurrent"].as_u64() > Some(0),
+        "board: {board}"
+    );
+
+    // Both runs went through the job queue and finished; a done task cannot
+    // be re-queued.
+    let jobs_path = format!("/api/agents/jobs?project_id={project_id}");
+    wait_until(
+        TIMEOUT,
+        "db_engineer and ui_designer jobs to be done",
+        || {
+            let (_, jobs) = backend.get(&jobs_path);
+            ["db_engineer", "ui_designer"].iter().all(|agent| {
+                jobs["jobs"].as_array().is_some_and(|jobs| {
+                    jobs.iter()
+                        .any(|j| j["agent_type"] == *agent && j["status"] == "done")
+                })
+            })
+        },
+    );
+    let (_, jobs) = backend.get(&jobs_path);
+    let jobs = jobs["jobs"].as_array().expect("jobs");
+    let db_task_id = jobs
+        .iter()
+        .find(|j| j["agent_type"] == "db_engineer")
+        .and_then(|j| j["task_id"].as_i64())
+        .expect("db_engineer job");
+    let (status, _) = backend.post(
+        &format!("/api/tasks/{db_task_id}/requeue"),
+        &serde_json::json!({}),
+    );
+    assert_eq!(status, 409);
+    // Nor cancelled: it has no queued or running run left.
+    let (status, _) = backend.post(
+        &format!("/api/tasks/{db_task_id}/cancel"),
+        &serde_json::json!({}),
+    );
+    assert_eq!(status, 409);
+
+    // Every completion was metered; the mock reports non-zero usage.
+    let (status, usage) = backend.get(&format!("/api/agents/usage?project_id={project_id}"));
+    assert_eq!(status, 200);
+    assert!(
+        usage["total"]["input_tokens"].as_i64() > Some(0),
+        "usage: {usage}"
+    );
+    let metered: Vec<&str> = usage["by_agent"]
+        .as_array()
+        .expect("usage by_agent")
+        .iter()
+        .filter_map(|g| g["agent_type"].as_str())
+        .collect();
+    for agent in [
+        "product_owner",
+        "project_manager",
+        "db_engineer",
+        "ui_designer",
+    ] {
+        assert!(metered.contains(&agent), "{agent} not metered: {usage}");
+    }
+}
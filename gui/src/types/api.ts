@@ -164,7 +164,16 @@ export type VerifyOtpRequest = { email: string, otp: string, };
 export type MeResponse = { email: string, };
 
 
-export type TaskItem = { id: number, project_id: number, epic_id: number | null, title: string, source_prompt: string, assigned_to_agent: string, status: string, created_at: number, updated_at: number, };
+export type TaskItem = { id: number, project_id: number, epic_id: number | null, title: string, source_prompt: string, assigned_to_agent: string, status: string, 
+/**
+ * Task this one was created on behalf of, e.g. by a workflow rule.
+ */
+created_by_task_id: number | null, 
+/**
+ * Tasks that must be done before this one runs: the edges of the
+ * project's dependency graph.
+ */
+depends_on: Array<number>, created_at: number, updated_at: number, };
 
 
 export type ListTasksResponse = { tasks: Array<TaskItem>, };
@@ -830,10 +830,558 @@ pub fn append_table_to_schema(
     Ok(rel_path.to_string())
 }
 
+/// Parse the name of the first function in `code`, e.g. `create_contact` from
+/// `pub async fn create_contact(...)`.
+pub fn parse_fn_name(code: &str) -> Option<String> {
+    for line in code.lines() {
+        let line = line.trim();
+        if line.starts_with("//") || line.starts_with("#[") {
+            continue;
+        }
+        if let Some(start) = line.find("fn ") {
+            let name = line[start + 3..]
+                .split(|c: char| !c.is_alphanumeric() && c != '_')
+                .next()?;
+            if !name.is_empty() {
+                return Some(name.to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Split a single-line `use` declaration into its path prefix and imported items:
+/// `use a::b::{C, d};` → `("a::b", ["C", "d"])`, `use a::b::C;` → `("a::b", ["C"])`.
+/// Returns `None` for nested groups, globs with groups, or anything unusual.
+fn parse_use_line(line: &str) -> Option<(String, Vec<String>)> {
+    let rest = line.trim().strip_prefix("use ")?.strip_suffix(';')?.trim();
+    if let Some(open) = rest.find("::{") {
+        let inner = rest[open + 3..].strip_suffix('}')?;
+        if inner.contains('{') {
+            return None;
+        }
+        let items = inner
+            .split(',')
+            .map(|i| i.trim().to_string())
+            .filter(|i| !i.is_empty())
+            .collect();
+        return Some((rest[..open].to_string(), items));
+    }
+    let (prefix, item) = rest.rsplit_once("::")?;
+    Some((prefix.to_string(), vec![item.to_string()]))
+}
+
+fn format_use_line(prefix: &str, items: &[String]) -> String {
+    match items {
+        [single] => format!("use {prefix}::{single};"),
+        _ => format!("use {prefix}::{{{}}};", items.join(", ")),
+    }
+}
+
+/// Merge `use` lines into a file's content. Items already imported are skipped;
+/// missing items are added to an existing grouped import with the same prefix,
+/// otherwise a new `use` line is inserted after the last top-level `use`.
+pub fn merge_use_lines(content: &str, uses: &[&str]) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+
+    for use_line in uses {
+        let use_line = use_line.trim();
+        if lines.iter().any(|l| l.trim() == use_line) {
+            continue;
+        }
+
+        let Some((prefix, items)) = parse_use_line(use_line) else {
+            insert_use_line(&mut lines, use_line.to_string());
+            continue;
+        };
+
+        // Items already imported by any single-line `use` with the same prefix.
+        let existing: Vec<(usize, Vec<String>)> = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| l.starts_with("use "))
+            .filter_map(|(i, l)| match parse_use_line(l) {
+                Some((p, its)) if p == prefix => Some((i, its)),
+                _ => None,
+            })
+            .collect();
+        let missing: Vec<String> = items
+            .into_iter()
+            .filter(|item| !existing.iter().any(|(_, its)| its.contains(item)))
+            .collect();
+        if missing.is_empty() {
+            continue;
+        }
+
+        match existing.first() {
+            Some((idx, its)) => {
+                let mut merged = its.clone();
+                merged.extend(missing);
+                lines[*idx] = format_use_line(&prefix, &merged);
+            }
+            None => insert_use_line(&mut lines, format_use_line(&prefix, &missing)),
+        }
+    }
+
+    let mut out = lines.join("\n");
+    if content.ends_with('\n') || content.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+fn insert_use_line(lines: &mut Vec<String>, line: String) {
+    let at = lines
+        .iter()
+        .rposition(|l| l.starts_with("use "))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    lines.insert(at, line);
+}
+
+/// Register `service` in the file's `configure(cfg: &mut web::ServiceConfig)`
+/// function by appending `cfg.service(service);` to its body. Returns `None` if
+/// the file has no `configure` fn; returns the content unchanged if the service
+/// is already registered.
+pub fn register_service_in_configure(content: &str, service: &str) -> Option<String> {
+    let fn_start = content.find("fn configure(")?;
+    let params_start = fn_start + "fn configure(".len();
+    let param = content[params_start..]
+        .split(':')
+        .next()?
+        .trim()
+        .trim_start_matches("mut ")
+        .trim()
+        .to_string();
+    if param.is_empty() || param.contains(')') {
+        return None;
+    }
+
+    let open = fn_start + content[fn_start..].find('{')?;
+    let close = matching_brace(content, open)?;
+
+    let body = &content[open + 1..close];
+    if body.contains(&format!(".service({service})")) {
+        return Some(content.to_string());
+    }
+
+    let line_start = content[..fn_start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let indent: String = content[line_start..fn_start]
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .collect();
+    let before = content[..close].trim_end();
+    Some(format!(
+        "{before}\n{indent}    {param}.service({service});\n{indent}{}",
+        &content[close..]
+    ))
+}
+
+/// Write an Actix route handler (generated by an LLM, imports included) into
+/// `handler_file` relative to `project_root`, and register it as a service.
+///
+/// Imports are merged into the existing file, the handler is appended before any
+/// `#[cfg(test)]` module, and the service is registered in the file's `configure`
+/// fn, or failing that in the sibling `mod.rs`'s `configure` fn. Fails without
+/// writing anything if neither exists (e.g. services are wired up in
+/// `main.rs`), since the handler would be unreachable.
+///
+/// Returns the relative file path that was written.
+pub fn write_handler(
+    project_root: &Path,
+    handler_file: &str,
+    handler_code: &str,
+) -> Result<String, String> {
+    let fn_name = parse_fn_name(handler_code).ok_or_else(|| {
+        format!(
+            "could not parse handler name from code. Code:\n{}",
+            &handler_code[..handler_code.len().min(500)]
+        )
+    })?;
+
+    let abs_path = project_root.join(handler_file);
+    let existing = if abs_path.exists() {
+        std::fs::read_to_string(&abs_path).map_err(|e| format!("read {}: {}", handler_file, e))?
+    } else {
+        String::new()
+    };
+
+    if existing.contains(&format!("fn {fn_name}(")) {
+        return Err(format!("handler `{fn_name}` already exists in {handler_file}"));
+    }
+
+    let uses: Vec<&str> = handler_code
+        .lines()
+        .filter(|line| line.trim_start().starts_with("use "))
+        .collect();
+    let body: String = handler_code
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("use "))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let content = merge_use_lines(&existing, &uses);
+    let mut content = match content.find("#[cfg(test)]") {
+        Some(idx) => format!(
+            "{}\n\n{}\n\n{}",
+            content[..idx].trim_end(),
+            body.trim(),
+            &content[idx..]
+        ),
+        None => format!("{}\n\n{}\n", content.trim_end(), body.trim()),
+    };
+
+    let mut mod_update = None;
+    let registered = match register_service_in_configure(&content, &fn_name) {
+        Some(updated) => {
+            content = updated;
+            true
+        }
+        None => false,
+    };
+    if !registered && abs_path.file_name().is_some_and(|n| n != "mod.rs") {
+        let mod_path = abs_path.with_file_name("mod.rs");
+        let stem = abs_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if mod_path.exists() {
+            let mod_content =
+                std::fs::read_to_string(&mod_path).map_err(|e| format!("read mod.rs: {}", e))?;
+            if let Some(updated) =
+                register_service_in_configure(&mod_content, &format!("{stem}::{fn_name}"))
+            {
+                mod_update = Some((mod_path, updated));
+            }
+        }
+    }
+    if !registered && mod_update.is_none() {
+        return Err(format!(
+            "no `configure(cfg: &mut web::ServiceConfig)` fn in {handler_file} or its mod.rs \
+             to register `{fn_name}` in"
+        ));
+    }
+
+    write_file_atomic(&abs_path, content.trim_start())?;
+    if let Some((mod_path, updated)) = mod_update {
+        write_file_atomic(&mod_path, &updated)?;
+    }
+
+    Ok(handler_file.to_string())
+}
+
+/// Add `{vis}mod {module};` after the last top-level `mod`/`pub mod` declaration
+/// (or at the top of the file). No-op if the module is already declared.
+pub fn declare_module(content: &str, module: &str, public: bool) -> String {
+    let declared = content.lines().any(|l| {
+        let l = l.trim();
+        l == format!("mod {module};") || l == format!("pub mod {module};")
+    });
+    if declared {
+        return content.to_string();
+    }
+
+    let vis = if public { "pub " } else { "" };
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let at = lines
+        .iter()
+        .rposition(|l| {
+            (l.starts_with("mod ") || l.starts_with("pub mod ")) && l.trim_end().ends_with(';')
+        })
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    lines.insert(at, format!("{vis}mod {module};"));
+
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+/// Insert `.wrap(expr)` directly after `App::new()` in an Actix app builder.
+/// Middleware registered first runs innermost, so existing wrappers such as CORS
+/// still see the request before it. Returns `None` if there is no `App::new()`;
+/// returns the content unchanged if `expr` is already wrapped.
+pub fn wrap_app_builder(content: &str, expr: &str) -> Option<String> {
+    let start = content.find("App::new()")?;
+    if content.contains(&format!(".wrap({expr})")) {
+        return Some(content.to_string());
+    }
+
+    let line_start = content[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let indent: String = content[line_start..start]
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .collect();
+    let end = start + "App::new()".len();
+    Some(format!(
+        "{}\n{indent}    .wrap({expr}){}",
+        &content[..end],
+        &content[end..]
+    ))
+}
+
+/// Write an Actix middleware or extractor module (generated by an LLM, imports
+/// included) to `backend/src/middleware/{stem}.rs`, declare it in
+/// `middleware/mod.rs` and `main.rs`, and when `wrap_app` is set wire
+/// `name` into the `App` builder in `main.rs`.
+///
+/// Returns the relative file path that was written.
+pub fn write_middleware_file(
+    project_root: &Path,
+    name: &str,
+    code: &str,
+    wrap_app: bool,
+) -> Result<String, String> {
+    let stem = to_snake_case(name);
+    let rel_path = format!("backend/src/middleware/{}.rs", stem);
+    let abs_path = project_root.join(&rel_path);
+
+    if abs_path.exists() {
+        return Err(format!("file already exists: {}", abs_path.display()));
+    }
+    if !code.contains(&format!("struct {name}")) {
+        return Err(format!("generated code does not define `{name}`"));
+    }
+
+    let main_path = project_root.join("backend/src/main.rs");
+    let main_update = if main_path.exists() {
+        let main = std::fs::read_to_string(&main_path).map_err(|e| format!("read main.rs: {}", e))?;
+        let updated = declare_module(&main, "middleware", false);
+        if wrap_app {
+            let wrapped = wrap_app_builder(&updated, &format!("middleware::{stem}::{name}"))
+                .ok_or_else(|| "could not find `App::new()` in backend/src/main.rs".to_string())?;
+            Some(wrapped)
+        } else {
+            Some(updated)
+        }
+    } else if wrap_app {
+        return Err("backend/src/main.rs not found; cannot wrap the App builder".to_string());
+    } else {
+        None
+    };
+
+    let mod_path = project_root.join("backend/src/middleware/mod.rs");
+    let mod_content = if mod_path.exists() {
+        std::fs::read_to_string(&mod_path).map_err(|e| format!("read mod.rs: {}", e))?
+    } else {
+        String::new()
+    };
+
+    write_file_atomic(&abs_path, &format!("{}\n", code.trim()))?;
+    write_file_atomic(&mod_path, &declare_module(&mod_content, &stem, true))?;
+    if let Some(main) = main_update {
+        write_file_atomic(&main_path, &main)?;
+    }
+
+    Ok(rel_path)
+}
+
+/// Replace `range` in `rel_file` (relative to `project_root`) with `replacement`.
+/// The bytes currently in `range` must equal `expected`, so a stale range from a
+/// file that has since changed is rejected instead of corrupting it.
+///
+/// Returns the relative file path that was written.
+pub fn replace_byte_range(
+    project_root: &Path,
+    rel_file: &str,
+    range: std::ops::Range<usize>,
+    expected: &str,
+    replacement: &str,
+) -> Result<String, String> {
+    let abs_path = project_root.join(rel_file);
+    let content =
+        std::fs::read_to_string(&abs_path).map_err(|e| format!("read {}: {}", rel_file, e))?;
+
+    if content.get(range.clone()) != Some(expected) {
+        return Err(format!(
+            "{} changed since the code was extracted (bytes {}..{} no longer match)",
+            rel_file, range.start, range.end
+        ));
+    }
+
+    let updated = format!(
+        "{}{}{}",
+        &content[..range.start],
+        replacement,
+        &content[range.end..]
+    );
+    write_file_atomic(&abs_path, &updated)?;
+    Ok(rel_file.to_string())
+}
+
+/// In-memory SQLite pool helper added to a model's test module alongside the
+/// first generated test. Migrations are embedded from the backend crate. Its
+/// imports are in `TEST_POOL_USES`.
+const TEST_POOL_HELPER: &str = r#"    const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+    fn test_pool() -> DbPool {
+        let manager = ConnectionManager::<SqliteConnection>::new(":memory:");
+        // One connection, so every checkout sees the same in-memory database.
+        let pool = Pool::builder()
+            .max_size(1)
+            .build(manager)
+            .expect("build test pool");
+        pool.get()
+            .expect("get test connection")
+            .run_pending_migrations(MIGRATIONS)
+            .expect("run migrations");
+        pool
+    }
+"#;
+
+/// What `TEST_POOL_HELPER` uses. Imported in the test module itself rather
+/// than relied on through `use super::*`, which only sees what the model file
+/// happens to import.
+const TEST_POOL_USES: &[&str] = &[
+    "use crate::db::DbPool;",
+    "use diesel::r2d2::{ConnectionManager, Pool};",
+    "use diesel::sqlite::SqliteConnection;",
+    "use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};",
+];
+
+/// The `use` lines a test module needs for the helper and a test that
+/// touches `table`, minus those `module` already has.
+fn missing_test_uses(module: &str, helper: bool, table: Option<&str>) -> Vec<String> {
+    let mut uses: Vec<String> = Vec::new();
+    if helper {
+        uses.extend(TEST_POOL_USES.iter().map(|u| u.to_string()));
+    }
+    if let Some(table) = table {
+        uses.push("use diesel::prelude::*;".to_string());
+        uses.push(format!("use crate::schema::{table};"));
+    }
+    uses.retain(|u| !module.lines().any(|line| line.trim() == u));
+    uses.dedup();
+    uses
+}
+
+/// The table of the first `#[diesel(table_name = ...)]` in `code`, quoted or
+/// not.
+fn diesel_table_name(code: &str) -> Option<String> {
+    let rest = &code[code.find("table_name")? + "table_name".len()..];
+    let name: String = rest
+        .trim_start_matches([' ', '=', '"'])
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Add a `#[test]` fn to the file's `#[cfg(test)] mod tests`, creating the module
+/// if it does not exist. The `test_pool()` helper is added when missing, and
+/// so are the `use` lines it and the test need: the pool types, and the
+/// model's schema table when the test refers to it.
+pub fn add_test_to_module(content: &str, test_code: &str) -> Result<String, String> {
+    let test_name = parse_fn_name(test_code)
+        .ok_or_else(|| "could not parse test name from code".to_string())?;
+    if content.contains(&format!("fn {test_name}(")) {
+        return Err(format!("test `{test_name}` already exists"));
+    }
+
+    let indented: String = test_code
+        .trim()
+        .lines()
+        .map(|l| {
+            if l.trim().is_empty() {
+                String::new()
+            } else {
+                format!("    {l}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let helper = if content.contains("fn test_pool(") {
+        ""
+    } else {
+        TEST_POOL_HELPER
+    };
+
+    let table = diesel_table_name(content).filter(|table| test_code.contains(&format!("{table}::")));
+    let uses_block = |module: &str| -> String {
+        missing_test_uses(module, !helper.is_empty(), table.as_deref())
+            .iter()
+            .map(|u| format!("    {u}\n"))
+            .collect()
+    };
+
+    let Some(mod_start) = content.find("mod tests {") else {
+        return Ok(format!(
+            "{}\n\n#[cfg(test)]\nmod tests {{\n    use super::*;\n{}{}{}\n\n{}\n}}\n",
+            content.trim_end(),
+            uses_block(""),
+            if helper.is_empty() { "" } else { "\n" },
+            helper.trim_end(),
+            indented
+        ));
+    };
+
+    let open = mod_start + "mod tests ".len();
+    let close = matching_brace(content, open)
+        .ok_or_else(|| "unbalanced braces in `mod tests`".to_string())?;
+    let uses = uses_block(&content[open..close]);
+    // After the module's own imports: its `use super::*;`, or its brace.
+    let uses_at = match content[open..close].find("use super::*;") {
+        Some(i) => open + i + "use super::*;\n".len(),
+        None => open + "{\n".len(),
+    };
+    let content = format!("{}{}{}", &content[..uses_at], uses, &content[uses_at..]);
+    let close = close + uses.len();
+    let mut body = content[..close].trim_end().to_string();
+    if !helper.is_empty() {
+        body.push_str("\n\n");
+        body.push_str(helper.trim_end());
+    }
+    body.push_str("\n\n");
+    body.push_str(&indented);
+    body.push('\n');
+    Ok(format!("{body}{}", &content[close..]))
+}
+
+/// Add a generated test to the `#[cfg(test)]` module of `model_file` (relative
+/// to `project_root`).
+///
+/// Returns the relative file path that was written.
+pub fn write_model_test(
+    project_root: &Path,
+    model_file: &str,
+    test_code: &str,
+) -> Result<String, String> {
+    let abs_path = project_root.join(model_file);
+    let content =
+        std::fs::read_to_string(&abs_path).map_err(|e| format!("read {}: {}", model_file, e))?;
+    let body: String = test_code
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("use "))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let updated = add_test_to_module(&content, &body)?;
+    write_file_atomic(&abs_path, &updated)?;
+    Ok(model_file.to_string())
+}
+
 // ---------------------------------------------------------------------------
 // Internal helpers
 // ---------------------------------------------------------------------------
 
+/// Byte index of the `}` matching the `{` at `open`. Does not understand braces
+/// inside string or char literals.
+fn matching_brace(content: &str, open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, c) in content[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
 fn data_type_to_rust(dt: &DataType, nullable: bool) -> String {
     let base = match dt {
         DataType::Text => "String",
@@ -1340,4 +1888,173 @@ mod tests {
                 .contains("diesel::joinable!(organization_users -> users (user_id));")
         );
     }
+
+    #[test]
+    fn test_parse_fn_name() {
+        let code = "#[get(\"/contacts\")]\npub async fn list_contacts() -> impl Responder {\n}";
+        assert_eq!(parse_fn_name(code), Some("list_contacts".to_string()));
+        assert_eq!(parse_fn_name("pub struct Foo;"), None);
+    }
+
+    #[test]
+    fn test_merge_use_lines() {
+        let content = "use actix_web::{get, web, HttpResponse};\nuse crate::db::DbPool;\n\nfn a() {}\n";
+        let merged = merge_use_lines(
+            content,
+            &[
+                "use actix_web::{post, web, HttpResponse, Responder};",
+                "use crate::db::DbPool;",
+                "use crate::models::Contact;",
+            ],
+        );
+        assert!(merged.contains("use actix_web::{get, web, HttpResponse, post, Responder};"));
+        assert_eq!(merged.matches("use crate::db::DbPool;").count(), 1);
+        assert!(merged.contains("use crate::db::DbPool;\nuse crate::models::Contact;\n\nfn a()"));
+    }
+
+    #[test]
+    fn test_register_service_in_configure() {
+        let content = "pub fn configure(cfg: &mut web::ServiceConfig) {\n    cfg.service(list_contacts);\n}\n";
+        let updated = register_service_in_configure(content, "create_contact").unwrap();
+        assert_eq!(
+            updated,
+            "pub fn configure(cfg: &mut web::ServiceConfig) {\n    cfg.service(list_contacts);\n    cfg.service(create_contact);\n}\n"
+        );
+        // Already registered — unchanged.
+        assert_eq!(
+            register_service_in_configure(&updated, "create_contact").unwrap(),
+            updated
+        );
+        assert!(register_service_in_configure("fn other() {}", "x").is_none());
+    }
+
+    #[test]
+    fn test_write_handler_registers_in_mod_configure() {
+        let root = std::env::temp_dir().join(format!("nocodo_write_handler_{}", std::process::id()));
+        let dir = root.join("backend/src/handlers");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("mod.rs"),
+            "pub mod contacts;\n\npub fn configure(cfg: &mut web::ServiceConfig) {\n    cfg.service(contacts::list_contacts);\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("contacts.rs"),
+            "use actix_web::{get, web, HttpResponse, Responder};\n\n#[get(\"/contacts\")]\npub async fn list_contacts() -> impl Responder {\n    HttpResponse::Ok().finish()\n}\n",
+        )
+        .unwrap();
+
+        let code = "use actix_web::{delete, web, HttpResponse, Responder};\n\n#[delete(\"/contacts/{id}\")]\npub async fn delete_contact(id: web::Path<i32>) -> impl Responder {\n    HttpResponse::NoContent().finish()\n}";
+        let rel = write_handler(&root, "backend/src/handlers/contacts.rs", code).unwrap();
+        assert_eq!(rel, "backend/src/handlers/contacts.rs");
+
+        let handlers = std::fs::read_to_string(dir.join("contacts.rs")).unwrap();
+        let mod_rs = std::fs::read_to_string(dir.join("mod.rs")).unwrap();
+        let second = write_handler(&root, "backend/src/handlers/contacts.rs", code);
+        std::fs::remove_dir_all(&root).ok();
+
+        assert!(handlers.starts_with("use actix_web::{get, web, HttpResponse, Responder, delete};\n"));
+        assert!(handlers.contains("pub async fn delete_contact("));
+        assert!(mod_rs.contains("    cfg.service(contacts::delete_contact);\n}"));
+        assert!(second.is_err());
+    }
+
+    #[test]
+    fn test_write_handler_without_configure_fails() {
+        let root = std::env::temp_dir().join(format!(
+            "nocodo_write_handler_unregistered_{}",
+            std::process::id()
+        ));
+        let dir = root.join("backend/src/handlers");
+        std::fs::create_dir_all(&dir).unwrap();
+        let original = "use actix_web::{get, HttpResponse, Responder};\n\n#[get(\"/contacts\")]\npub async fn list_contacts() -> impl Responder {\n    HttpResponse::Ok().finish()\n}\n";
+        std::fs::write(dir.join("contacts.rs"), original).unwrap();
+
+        let code = "#[delete(\"/contacts/{id}\")]\npub async fn delete_contact() -> impl Responder {\n    HttpResponse::NoContent().finish()\n}";
+        let result = write_handler(&root, "backend/src/handlers/contacts.rs", code);
+        let after = std::fs::read_to_string(dir.join("contacts.rs")).unwrap();
+        std::fs::remove_dir_all(&root).ok();
+
+        assert!(result.unwrap_err().contains("configure"));
+        assert_eq!(after, original);
+    }
+
+    #[test]
+    fn test_declare_module() {
+        let main = "mod auth;\nmod db;\n\nfn main() {}\n";
+        let updated = declare_module(main, "middleware", false);
+        assert_eq!(updated, "mod auth;\nmod db;\nmod middleware;\n\nfn main() {}\n");
+        assert_eq!(declare_module(&updated, "middleware", false), updated);
+        assert_eq!(declare_module("", "require_role", true), "pub mod require_role;\n");
+    }
+
+    #[test]
+    fn test_wrap_app_builder() {
+        let main = "    HttpServer::new(move || {\n        App::new()\n            .wrap(cors)\n            .service(index)\n    })\n";
+        let updated = wrap_app_builder(main, "middleware::require_role::RequireRole").unwrap();
+        assert_eq!(
+            updated,
+            "    HttpServer::new(move || {\n        App::new()\n            .wrap(middleware::require_role::RequireRole)\n            .wrap(cors)\n            .service(index)\n    })\n"
+        );
+        assert_eq!(
+            wrap_app_builder(&updated, "middleware::require_role::RequireRole").unwrap(),
+            updated
+        );
+        assert!(wrap_app_builder("fn main() {}", "X").is_none());
+    }
+
+    #[test]
+    fn test_replace_byte_range() {
+        let root = std::env::temp_dir().join(format!("nocodo_replace_range_{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a.rs"), "impl A {\n    fn x() {}\n}\n").unwrap();
+
+        let start = "impl A {\n    ".len();
+        let end = start + "fn x() {}".len();
+        let ok = replace_byte_range(&root, "a.rs", start..end, "fn x() {}", "fn x() -> i32 {\n        1\n    }");
+        let content = std::fs::read_to_string(root.join("a.rs")).unwrap();
+        let stale = replace_byte_range(&root, "a.rs", start..end, "fn x() {}", "fn x() {}");
+        std::fs::remove_dir_all(&root).ok();
+
+        assert_eq!(ok.unwrap(), "a.rs");
+        assert_eq!(content, "impl A {\n    fn x() -> i32 {\n        1\n    }\n}\n");
+        assert!(stale.is_err());
+    }
+
+    #[test]
+    fn test_add_test_to_module_creates_module() {
+        let content = "pub struct Contact;\n";
+        let test = "#[test]\nfn test_count() {\n    assert_eq!(1, 1);\n}";
+        let updated = add_test_to_module(content, test).unwrap();
+        assert!(updated.starts_with("pub struct Contact;\n\n#[cfg(test)]\nmod tests {\n    use super::*;\n"));
+        assert!(updated.contains("    fn test_pool() -> DbPool {"));
+        assert!(updated.ends_with("    #[test]\n    fn test_count() {\n        assert_eq!(1, 1);\n    }\n}\n"));
+        assert!(add_test_to_module(&updated, test).is_err());
+    }
+
+    #[test]
+    fn test_add_test_to_module_imports_what_the_test_uses() {
+        let content = "#[derive(Queryable)]\n#[diesel(table_name = contacts)]\npub struct Contact {\n    pub id: i32,\n}\n";
+        let test = "#[test]\nfn test_insert() {\n    diesel::insert_into(contacts::table);\n}";
+        let updated = add_test_to_module(content, test).unwrap();
+        for line in TEST_POOL_USES {
+            assert!(updated.contains(&format!("    {line}\n")), "{line}");
+        }
+        assert!(updated.contains("    use diesel::prelude::*;\n    use crate::schema::contacts;\n"));
+
+        // A module that already imports something does not import it twice.
+        let content = "#[diesel(table_name = contacts)]\npub struct Contact;\n\n#[cfg(test)]\nmod tests {\n    use super::*;\n    use crate::db::DbPool;\n}\n";
+        let updated = add_test_to_module(content, test).unwrap();
+        assert_eq!(updated.matches("use crate::db::DbPool;").count(), 1);
+        assert!(updated.contains("mod tests {\n    use super::*;\n    use diesel::r2d2::"));
+        assert!(updated.contains("    use crate::schema::contacts;\n    use crate::db::DbPool;\n"));
+    }
+
+    #[test]
+    fn test_add_test_to_module_existing_module() {
+        let content = "pub struct Contact;\n\n#[cfg(test)]\nmod tests {\n    use super::*;\n\n    fn test_pool() -> DbPool {\n        todo!()\n    }\n}\n";
+        let updated = add_test_to_module(content, "#[test]\nfn test_x() {}").unwrap();
+        assert_eq!(updated.matches("fn test_pool(").count(), 1);
+        assert!(updated.ends_with("    }\n\n    #[test]\n    fn test_x() {}\n}\n"));
+    }
 }
@@ -331,6 +331,13 @@ pub struct TaskItem {
     pub source_prompt: String,
     pub assigned_to_agent: String,
     pub status: String,
+    /// Task this one was created on behalf of, e.g. by a workflow rule.
+    #[ts(type = "number | null")]
+    pub created_by_task_id: Option<i64>,
+    /// Tasks that must be done before this one runs: the edges of the
+    /// project's dependency graph.
+    #[ts(type = "Array<number>")]
+    pub depends_on: Vec<i64>,
     #[ts(type = "number")]
     pub created_at: i64,
     #[ts(type = "number")]